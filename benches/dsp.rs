@@ -0,0 +1,172 @@
+//! DSP の中心部分のベンチマーク。`cargo bench` で実行する。
+//!
+//! バンド分割、コンプレッサーの内側のループ、1 バッファ分の処理（アップサンプリング、分割、圧縮、合成、
+//! ダウンサンプリング）を、いくつかのバッファサイズとチャンネル数で測る。プラグイン本体の `process()` は
+//! ホストのコンテキストなしには呼べないので、同じ順番で部品を組み合わせて測る。
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use multiband_compressor::compression::{CompressorSettings, DetectorMode, SingleBandCompressor};
+use multiband_compressor::crossover::{band_lanes, Crossover};
+use multiband_compressor::oversampling::{
+    Oversampler, OversamplingFactor, MAX_OVERSAMPLING_FACTOR,
+};
+
+const SAMPLE_RATE: f32 = 48000.0;
+const BUFFER_SIZES: [usize; 3] = [64, 256, 1024];
+const CHANNEL_COUNTS: [usize; 2] = [1, 2];
+
+/// 再現性のある、圧縮がかかる程度の大きさのノイズ
+fn test_signal(num_samples: usize) -> Vec<f32> {
+    let mut state = 0x1234_5678_u32;
+    (0..num_samples)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * 0.5
+        })
+        .collect()
+}
+
+fn crossover(sample_rate: f32) -> Crossover {
+    let mut crossover = Crossover::new(1);
+    crossover.set_frequencies(200.0, 2000.0, std::f32::consts::FRAC_1_SQRT_2, sample_rate);
+    crossover
+}
+
+fn settings(detector: DetectorMode, fast_math: bool, sample_rate: f32) -> CompressorSettings {
+    let sample_rate = f64::from(sample_rate);
+    CompressorSettings {
+        threshold_db: -24.0,
+        ratio: 4.0,
+        knee_db: 6.0,
+        range_db: 40.0,
+        attack_coef: (-1.0 / (0.01 * sample_rate)).exp(),
+        release_coef: (-1.0 / (0.1 * sample_rate)).exp(),
+        hold_samples: 0,
+        detector,
+        rms_coef: (-1.0 / (0.01 * sample_rate)).exp(),
+        fast_math,
+        makeup_db: 0.0,
+        mix: 1.0,
+    }
+}
+
+fn band_splitting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("band_splitting");
+    for num_samples in BUFFER_SIZES {
+        let input = test_signal(num_samples);
+        let mut crossover = crossover(SAMPLE_RATE);
+        group.throughput(Throughput::Elements(num_samples as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_samples),
+            &input,
+            |b, input| {
+                b.iter(|| {
+                    for &sample in input {
+                        black_box(crossover.split_lanes(0, sample));
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn compressor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compressor");
+    let input = test_signal(1024);
+    group.throughput(Throughput::Elements(input.len() as u64));
+    for (name, detector, fast_math) in [
+        ("peak", DetectorMode::Peak, false),
+        ("peak_fast", DetectorMode::Peak, true),
+        ("rms", DetectorMode::Rms, false),
+        ("rms_fast", DetectorMode::Rms, true),
+        ("hilbert", DetectorMode::Analytic, false),
+    ] {
+        let settings = settings(detector, fast_math, SAMPLE_RATE);
+        let mut compressor = SingleBandCompressor::new();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for &sample in &input {
+                    black_box(compressor.process_sample(sample, sample, &settings));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// 1 チャンネル分のプロセッサーの状態
+struct Channel {
+    oversampler: Oversampler,
+    crossover: Crossover,
+    compressors: [SingleBandCompressor; 3],
+}
+
+/// プラグインと同じ順番で、`buffer` の各チャンネルをアップサンプリング、分割、圧縮、合成、
+/// ダウンサンプリングする
+fn process_buffer(
+    channels: &mut [Channel],
+    buffer: &mut [Vec<f32>],
+    settings: &CompressorSettings,
+    factor: OversamplingFactor,
+) {
+    let mut scratch = [0.0; MAX_OVERSAMPLING_FACTOR];
+    for (channel, samples) in channels.iter_mut().zip(buffer.iter_mut()) {
+        for sample in samples.iter_mut() {
+            channel.oversampler.upsample(*sample, &mut scratch);
+            for oversampled in scratch.iter_mut().take(factor.factor()) {
+                let split = channel.crossover.split_lanes(0, *oversampled);
+                let [low, mid, high, _] = split.to_array();
+                let mut bands = [low, mid, high];
+                for (band, compressor) in bands.iter_mut().zip(channel.compressors.iter_mut()) {
+                    *band = compressor.process_sample(*band, *band, settings);
+                }
+                *oversampled = band_lanes(bands).sum();
+            }
+            *sample = channel.oversampler.downsample(&scratch);
+        }
+    }
+}
+
+fn full_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("full_buffer");
+    for factor in [OversamplingFactor::Off, OversamplingFactor::X4] {
+        let sample_rate = SAMPLE_RATE * factor.factor() as f32;
+        let settings = settings(DetectorMode::Peak, false, sample_rate);
+        for num_channels in CHANNEL_COUNTS {
+            for num_samples in BUFFER_SIZES {
+                let mut channels: Vec<Channel> = (0..num_channels)
+                    .map(|_| {
+                        let mut oversampler = Oversampler::new();
+                        oversampler.set_factor(factor);
+                        Channel {
+                            oversampler,
+                            crossover: crossover(sample_rate),
+                            compressors: Default::default(),
+                        }
+                    })
+                    .collect();
+                let input = vec![test_signal(num_samples); num_channels];
+                let mut buffer = input.clone();
+
+                group.throughput(Throughput::Elements((num_samples * num_channels) as u64));
+                group.bench_function(
+                    BenchmarkId::new(format!("{factor:?}/{num_channels}ch"), num_samples),
+                    |b| {
+                        b.iter(|| {
+                            buffer.clone_from(&input);
+                            process_buffer(&mut channels, &mut buffer, &settings, factor);
+                            black_box(&buffer);
+                        })
+                    },
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, band_splitting, compressor, full_buffer);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+use crate::sample::Sample;
+use crate::simd::{F32x4, LANES};
+
+/// The Q for a maximally flat 2nd-order section. Two cascaded sections with this Q form a
+/// Linkwitz-Riley crossover whose low and high outputs sum to a flat magnitude response.
+pub const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+/// Lower bound for the Q to keep `alpha` finite
+const MIN_Q: f32 = 0.1;
+
+/// The state of one [`Biquad`], for filters whose coefficients are shared between channels
+#[derive(Clone, Copy, Default)]
+pub struct BiquadState<T: Sample> {
+    z1: T,
+    z2: T,
+}
+
+impl<T: Sample> BiquadState<T> {
+    /// Clear the filter state
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// A 2nd-order filter with `f32` or `f64` coefficients and state
+#[derive(Clone, Copy)]
+pub struct Biquad<T: Sample = f32> {
+    b0: T,
+    b1: T,
+    b2: T,
+    a1: T,
+    a2: T,
+    state: BiquadState<T>,
+}
+
+impl<T: Sample> Biquad<T> {
+    pub fn new() -> Self {
+        Self {
+            b0: T::ONE,
+            b1: T::ZERO,
+            b2: T::ZERO,
+            a1: T::ZERO,
+            a2: T::ZERO,
+            state: BiquadState::default(),
+        }
+    }
+
+    /// Clear the filter state while keeping the coefficients
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+
+    pub fn process_sample(&mut self, x: T) -> T {
+        let mut state = self.state;
+        let y = self.process_with_state(&mut state, x);
+        self.state = state;
+        y
+    }
+
+    /// Filter `x` with these coefficients and an external `state`, ignoring the internal one
+    pub fn process_with_state(&self, state: &mut BiquadState<T>, x: T) -> T {
+        // Direct Form II Transposed to keep numerical stability
+        let y = self.b0 * x + state.z1;
+        state.z1 = self.b1 * x - self.a1 * y + state.z2;
+        state.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    /// Set normalized coefficients (`a0 == 1`) directly, keeping the filter state
+    pub fn set_coefficients(&mut self, b0: T, b1: T, b2: T, a1: T, a2: T) {
+        self.b0 = b0;
+        self.b1 = b1;
+        self.b2 = b2;
+        self.a1 = a1;
+        self.a2 = a2;
+    }
+
+    /// 2nd-order lowpass. `q` of [`BUTTERWORTH_Q`] gives a Butterworth response, lower values
+    /// give a gentler knee and higher values a sharper (slightly resonant) one.
+    pub fn set_lowpass(&mut self, freq: T, q: T, sr: T) {
+        let two = T::ONE + T::ONE;
+        let (sinw, cosw) = (two * T::PI * freq / sr).sin_cos();
+        let alpha = sinw / (two * q.max(T::from_f32(MIN_Q)));
+        let b0 = (T::ONE - cosw) / two;
+        let b1 = T::ONE - cosw;
+        let b2 = (T::ONE - cosw) / two;
+        let a0 = T::ONE + alpha;
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = -two * cosw / a0;
+        self.a2 = (T::ONE - alpha) / a0;
+        // reset states to avoid clicks on coefficient change
+        self.reset();
+    }
+
+    /// Magnitude (linear gain) of the current coefficients at `freq`, evaluated on the unit circle
+    pub fn magnitude_at(&self, freq: T, sr: T) -> T {
+        let two = T::ONE + T::ONE;
+        let omega = two * T::PI * freq / sr;
+        let (sin1, cos1) = omega.sin_cos();
+        let (sin2, cos2) = (two * omega).sin_cos();
+        let num_re = self.b0 + self.b1 * cos1 + self.b2 * cos2;
+        let num_im = -(self.b1 * sin1 + self.b2 * sin2);
+        let den_re = T::ONE + self.a1 * cos1 + self.a2 * cos2;
+        let den_im = -(self.a1 * sin1 + self.a2 * sin2);
+
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt()
+    }
+
+    /// 2nd-order highpass, the mirror image of [`Biquad::set_lowpass()`] for the same `q`
+    pub fn set_highpass(&mut self, freq: T, q: T, sr: T) {
+        let two = T::ONE + T::ONE;
+        let (sinw, cosw) = (two * T::PI * freq / sr).sin_cos();
+        let alpha = sinw / (two * q.max(T::from_f32(MIN_Q)));
+        let b0 = (T::ONE + cosw) / two;
+        let b1 = -(T::ONE + cosw);
+        let b2 = (T::ONE + cosw) / two;
+        let a0 = T::ONE + alpha;
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = -two * cosw / a0;
+        self.a2 = (T::ONE - alpha) / a0;
+        self.reset();
+    }
+}
+
+impl<T: Sample> Default for Biquad<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The coefficients of four independent biquads, one per lane of an [`F32x4`], processed
+/// together. Each lane has its own coefficients, so this can run several filter chains side by
+/// side. The filter state lives in a separate [`BiquadX4State`] so that several channels can
+/// share one set of coefficients.
+#[derive(Clone, Copy, Default)]
+pub struct BiquadX4 {
+    b0: F32x4,
+    b1: F32x4,
+    b2: F32x4,
+    a1: F32x4,
+    a2: F32x4,
+}
+
+/// The state of a [`BiquadX4`] for one channel
+#[derive(Clone, Copy, Default)]
+pub struct BiquadX4State {
+    z1: F32x4,
+    z2: F32x4,
+}
+
+impl BiquadX4State {
+    /// Clear the filter state of all lanes
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl BiquadX4 {
+    /// Every lane passes its input through unchanged until coefficients are set
+    pub fn new() -> Self {
+        Self {
+            b0: F32x4::splat(1.0),
+            ..Default::default()
+        }
+    }
+
+    /// Use the coefficients of `biquads[lane]` for each lane. `None` makes that lane pass its
+    /// input through unchanged. The states using these coefficients should be reset afterwards.
+    pub fn set_lanes(&mut self, biquads: [Option<&Biquad>; LANES]) {
+        let coefficient = |get: fn(&Biquad) -> f32, identity: f32| {
+            F32x4::from_array(biquads.map(|biquad| biquad.map_or(identity, get)))
+        };
+        self.b0 = coefficient(|biquad| biquad.b0, 1.0);
+        self.b1 = coefficient(|biquad| biquad.b1, 0.0);
+        self.b2 = coefficient(|biquad| biquad.b2, 0.0);
+        self.a1 = coefficient(|biquad| biquad.a1, 0.0);
+        self.a2 = coefficient(|biquad| biquad.a2, 0.0);
+    }
+
+    /// The same Direct Form II Transposed structure as [`Biquad::process_sample()`], per lane
+    pub fn process(&self, state: &mut BiquadX4State, x: F32x4) -> F32x4 {
+        let y = self.b0 * x + state.z1;
+        state.z1 = self.b1 * x - self.a1 * y + state.z2;
+        state.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
@@ -0,0 +1,255 @@
+use crate::detector::Detector;
+use crate::sample::Sample;
+
+/// コンプレッサーの検出器がレベルを測る方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "nih_plug", derive(nih_plug::prelude::Enum))]
+pub enum DetectorMode {
+    /// サンプルごとの絶対値。トランジェントに素早く反応する
+    #[cfg_attr(feature = "nih_plug", name = "Peak")]
+    Peak,
+    /// 短時間の二乗平均平方根。聴感上の音量に近く、穏やかに反応する
+    #[cfg_attr(feature = "nih_plug", name = "RMS")]
+    Rms,
+    /// 解析信号の振幅。ピークのように素早く反応しながら、低域でも波形の山と谷で揺れない
+    #[cfg_attr(feature = "nih_plug", name = "Hilbert")]
+    Analytic,
+}
+
+/// 少なくとも 1 バンド分のコンプレッション状態を保持するシンプルなコンプレッサー。
+/// 通常は f32 で、[`crate::precision::Precision::Double`] の経路では f64 で使う
+#[derive(Debug, Clone)]
+pub struct SingleBandCompressor<T: Sample = f32> {
+    envelope: T,
+    gain_reduction_db: T,
+    detector: Detector<T>,
+    /// リリースを始めるまでの残りサンプル数
+    hold_remaining: u32,
+}
+
+impl<T: Sample> SingleBandCompressor<T> {
+    pub fn new() -> Self {
+        Self {
+            envelope: T::MINUS_INFINITY_DB,
+            gain_reduction_db: T::ZERO,
+            detector: Detector::default(),
+            hold_remaining: 0,
+        }
+    }
+
+    /// 現在のゲインリダクション量 (dB、0 以下)
+    pub fn gain_reduction_db(&self) -> f32 {
+        self.gain_reduction_db.to_f32()
+    }
+
+    /// エンベロープとゲインリダクションを初期状態に戻す
+    pub fn reset(&mut self) {
+        self.envelope = T::MINUS_INFINITY_DB;
+        self.gain_reduction_db = T::ZERO;
+        self.detector.reset();
+        self.hold_remaining = 0;
+    }
+
+    /// `detector_input` のレベルでゲインリダクションを決め、`input` に適用する。
+    /// 通常は両方に同じ信号を渡し、検出器に K 特性などをかけるときだけ別の信号を渡す
+    pub fn process_sample(
+        &mut self,
+        input: T,
+        detector_input: T,
+        settings: &CompressorSettings,
+    ) -> T {
+        let detector_level = self.detect(detector_input, settings);
+        self.apply(input, detector_level, settings)
+    }
+
+    /// `detector_input` の検出器のレベル（振幅）。[`Self::apply()`] と分けて呼ぶと、リンクしたチャンネルの
+    /// レベルを揃えてから圧縮できる
+    pub fn detect(&mut self, detector_input: T, settings: &CompressorSettings) -> T {
+        self.detector.process(
+            detector_input,
+            settings.detector,
+            T::from_f64(settings.rms_coef),
+        )
+    }
+
+    /// 検出器のレベル `detector_level` でゲインリダクションを決め、`input` に適用する
+    pub fn apply(&mut self, input: T, detector_level: T, settings: &CompressorSettings) -> T {
+        let attack_coef = T::from_f64(settings.attack_coef);
+        let release_coef = T::from_f64(settings.release_coef);
+        let input_db = if detector_level > T::ZERO {
+            detector_level.gain_to_db(settings.fast_math)
+        } else {
+            T::MINUS_INFINITY_DB
+        };
+
+        // レベルが下がっても、ホールド時間が過ぎるまではエンベロープを保つ
+        if input_db > self.envelope {
+            self.envelope = self.envelope * attack_coef + input_db * (T::ONE - attack_coef);
+            self.hold_remaining = settings.hold_samples;
+        } else if self.hold_remaining > 0 {
+            self.hold_remaining -= 1;
+        } else {
+            self.envelope = self.envelope * release_coef + input_db * (T::ONE - release_coef);
+        }
+
+        let target_reduction_db = static_gain_reduction_db(settings, self.envelope);
+
+        let coef = if target_reduction_db < self.gain_reduction_db {
+            attack_coef
+        } else {
+            release_coef
+        };
+        self.gain_reduction_db =
+            self.gain_reduction_db * coef + target_reduction_db * (T::ONE - coef);
+
+        // Mix が 1 未満なら、圧縮していない信号と並列に混ぜる
+        let total_gain_db = self.gain_reduction_db + T::from_f32(settings.makeup_db);
+        let total_gain = total_gain_db.db_to_gain(settings.fast_math);
+        let output = input * (T::ONE + T::from_f32(settings.mix) * (total_gain - T::ONE));
+
+        // NaN や無限大はエンベロープに残り続けるので、出てきたら状態を戻して無音を返す
+        if !(output.is_finite() && self.envelope.is_finite()) {
+            self.reset();
+            return T::ZERO;
+        }
+
+        output
+    }
+}
+
+impl<T: Sample> Default for SingleBandCompressor<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompressorSettings {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    /// ソフトニーの幅 (dB)。0 でハードニー
+    pub knee_db: f32,
+    /// ゲインリダクションの上限 (dB、正の値)。`f32::INFINITY` で無制限
+    pub range_db: f32,
+    /// エンベロープの係数。1 に近い値になるので、倍精度の経路のために f64 で持つ
+    pub attack_coef: f64,
+    pub release_coef: f64,
+    /// レベルが下がってからリリースを始めるまでのサンプル数
+    pub hold_samples: u32,
+    pub detector: DetectorMode,
+    /// RMS 検出の平均化の係数
+    pub rms_coef: f64,
+    /// 検出器とゲインの dB 変換に [`crate::fast_math`] の近似を使うか
+    pub fast_math: bool,
+    pub makeup_db: f32,
+    /// 圧縮した信号の割合 (0..1)。1 で通常のコンプレッサー
+    pub mix: f32,
+}
+
+/// 圧縮の傾きを `amount` (0..1) 倍に弱めたレシオ。0 で 1:1 になる
+pub fn scaled_ratio(ratio: f32, amount: f32) -> f32 {
+    let slope = (1.0 - 1.0 / ratio.max(1.0)) * amount.clamp(0.0, 1.0);
+    1.0 / (1.0 - slope)
+}
+
+/// 入力レベル `input_db` に対する静的なゲインリダクション (dB、0 以下)。
+///
+/// ニーの中ではスレッショルドの前後 `knee_db / 2` にわたって二次曲線で傾きを変え、
+/// 結果を `range_db` で制限する。エンベロープを除けばコンプレッサーと同じ計算になる。
+pub fn static_gain_reduction_db<T: Sample>(settings: &CompressorSettings, input_db: T) -> T {
+    let two = T::ONE + T::ONE;
+    let slope = T::ONE / T::from_f32(settings.ratio.max(1.0)) - T::ONE;
+    let knee = T::from_f32(settings.knee_db.max(0.0));
+    let overshoot = input_db - T::from_f32(settings.threshold_db);
+
+    let reduction = if two * overshoot <= -knee {
+        T::ZERO
+    } else if two * overshoot < knee {
+        let knee_overshoot = overshoot + knee / two;
+        slope * knee_overshoot * knee_overshoot / (two * knee)
+    } else {
+        slope * overshoot
+    };
+
+    reduction.max(-T::from_f32(settings.range_db.max(0.0)))
+}
+
+/// 静的な入出力特性。入力レベル (dB) に対する出力レベル (dB) をメイクアップ込みで返す
+pub fn transfer_curve(settings: &CompressorSettings, input_db: f32) -> f32 {
+    input_db + static_gain_reduction_db(settings, input_db) + settings.makeup_db
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(detector: DetectorMode) -> CompressorSettings {
+        CompressorSettings {
+            threshold_db: -20.0,
+            ratio: 4.0,
+            knee_db: 6.0,
+            range_db: 40.0,
+            attack_coef: 0.99,
+            release_coef: 0.999,
+            hold_samples: 0,
+            detector,
+            rms_coef: 0.99,
+            fast_math: false,
+            makeup_db: 0.0,
+            mix: 1.0,
+        }
+    }
+
+    #[test]
+    fn static_curve_follows_knee_range_and_makeup() {
+        // -20 dB、4:1、ニー 6 dB なので、ニーは -23〜-17 dB
+        let mut settings = settings(DetectorMode::Peak);
+        let reduction = static_gain_reduction_db::<f32>;
+
+        // ニーより下は圧縮しない
+        assert_eq!(reduction(&settings, -30.0), 0.0);
+        assert_eq!(reduction(&settings, -23.0), 0.0);
+        // ニーの中は二次曲線で、スレッショルドでは (1/ratio - 1) * knee / 8 だけ下がる
+        assert!((reduction(&settings, -20.0) - -0.5625).abs() < 1e-6);
+        assert!(reduction(&settings, -21.0) > reduction(&settings, -20.0));
+        // ニーの上端で直線とつながり、その上は (1/ratio - 1) の傾き
+        assert!((reduction(&settings, -17.0) - -2.25).abs() < 1e-6);
+        assert!((reduction(&settings, -10.0) - -7.5).abs() < 1e-6);
+        assert!((static_gain_reduction_db(&settings, -10.0_f64) - -7.5).abs() < 1e-9);
+
+        // `range_db` で深さを制限する
+        settings.range_db = 6.0;
+        assert_eq!(reduction(&settings, 0.0), -6.0);
+        assert!((reduction(&settings, -10.0) - -6.0).abs() < 1e-6);
+
+        // 入出力特性はメイクアップを足す
+        settings.range_db = f32::INFINITY;
+        settings.makeup_db = 3.0;
+        assert_eq!(transfer_curve(&settings, -30.0), -27.0);
+        assert!((transfer_curve(&settings, -10.0) - -14.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn recovers_from_non_finite_input() {
+        for detector in [DetectorMode::Peak, DetectorMode::Rms, DetectorMode::Analytic] {
+            for poison in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+                let settings = settings(detector);
+                let mut compressor = SingleBandCompressor::new();
+                for _ in 0..100 {
+                    compressor.process_sample(0.5, 0.5, &settings);
+                }
+
+                assert_eq!(compressor.process_sample(poison, poison, &settings), 0.0);
+
+                // 状態が戻っているので、新しいコンプレッサーと全く同じ出力になる
+                let mut fresh = SingleBandCompressor::new();
+                for n in 0..1000 {
+                    let input = (n as f32 * 0.1).sin();
+                    let output = compressor.process_sample(input, input, &settings);
+                    assert!(output.is_finite());
+                    assert_eq!(output, fresh.process_sample(input, input, &settings));
+                }
+            }
+        }
+    }
+}
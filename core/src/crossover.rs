@@ -0,0 +1,222 @@
+use crate::util;
+
+use crate::biquad::{Biquad, BiquadX4, BiquadX4State};
+use crate::simd::F32x4;
+
+/// バンド数（low, mid, high）
+pub const NUM_BANDS: usize = 3;
+
+/// Mid のバンドの最小の幅 (オクターブ)。これより近いクロスオーバー点では Mid がほとんど残らない
+pub const MIN_MID_BAND_OCTAVES: f32 = 1.0 / 3.0;
+
+/// 実際に使うクロスオーバー周波数 (Low-Mid, Mid-High)。Mid のバンドが [`MIN_MID_BAND_OCTAVES`] より
+/// 狭くならないように Mid-High を持ち上げる
+pub fn limit_mid_band(lo_mid: f32, mid_hi: f32) -> [f32; 2] {
+    [lo_mid, mid_hi.max(lo_mid * MIN_MID_BAND_OCTAVES.exp2())]
+}
+
+/// クロスオーバーの直列のフィルターの段数。最も長い Mid の HP 2 段 + LP 2 段
+const STAGES: usize = 4;
+
+/// 3 バンドクロスオーバー。各クロスオーバー点で LP/HP を 2 段カスケードする
+///
+/// 処理は Low, Mid, High を [`F32x4`] のレーン 0, 1, 2 に割り当てて、4 段の [`BiquadX4`] でまとめて
+/// 行う。Low と High の 3, 4 段目と、使わないレーン 3 は入力をそのまま通す。係数は全チャンネルで共有し、
+/// チャンネルごとのフィルターの状態だけを連続した配列に並べて持つ
+#[derive(Clone)]
+pub struct Crossover {
+    low_lp: [Biquad; 2],
+    mid_hp: [Biquad; 2],
+    mid_lp: [Biquad; 2],
+    high_hp: [Biquad; 2],
+    /// 上の係数をレーンに並べた、実際に処理に使うフィルター
+    stages: [BiquadX4; STAGES],
+    /// per-channel filter states
+    states: Vec<[BiquadX4State; STAGES]>,
+}
+
+impl Crossover {
+    /// `num_channels` チャンネル分の状態を確保する。特性を計算するだけなら 0 でよい
+    pub fn new(num_channels: usize) -> Self {
+        Self {
+            low_lp: [Biquad::new(), Biquad::new()],
+            mid_hp: [Biquad::new(), Biquad::new()],
+            mid_lp: [Biquad::new(), Biquad::new()],
+            high_hp: [Biquad::new(), Biquad::new()],
+            stages: [BiquadX4::new(); STAGES],
+            states: vec![Default::default(); num_channels],
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.states.len()
+    }
+
+    /// 全チャンネルのフィルターの状態をクリアする
+    pub fn reset(&mut self) {
+        for state in self.states.iter_mut().flatten() {
+            state.reset();
+        }
+    }
+
+    /// クロスオーバー周波数と Q から係数を設計する。周波数はナイキストに収まるように制限する。
+    /// 再生中に周波数を動かしても音が途切れないよう、フィルターの状態は保つ。状態は `reset()` でクリアする
+    pub fn set_frequencies(&mut self, lo_mid: f32, mid_hi: f32, q: f32, sample_rate: f32) {
+        let nyquist = sample_rate * 0.5;
+        let low_freq = lo_mid.clamp(10.0, nyquist * 0.8);
+        let high_freq = mid_hi.clamp(low_freq + 10.0, nyquist * 0.99);
+
+        // 同じクロスオーバー点の LP/HP は同じ Q で設計し、2 段カスケードでペアの特性を揃える
+        for lp in self.low_lp.iter_mut() {
+            lp.set_lowpass(low_freq, q, sample_rate);
+        }
+        for hp in self.mid_hp.iter_mut() {
+            hp.set_highpass(low_freq, q, sample_rate);
+        }
+        for lp in self.mid_lp.iter_mut() {
+            lp.set_lowpass(high_freq, q, sample_rate);
+        }
+        for hp in self.high_hp.iter_mut() {
+            hp.set_highpass(high_freq, q, sample_rate);
+        }
+
+        for (index, stage) in self.stages.iter_mut().enumerate() {
+            let (low, mid, high) = if index < 2 {
+                (
+                    Some(&self.low_lp[index]),
+                    &self.mid_hp[index],
+                    Some(&self.high_hp[index]),
+                )
+            } else {
+                (None, &self.mid_lp[index - 2], None)
+            };
+            stage.set_lanes([low, Some(mid), high, None]);
+        }
+    }
+
+    /// チャンネル `channel` のフィルターの状態。`new()` で確保していないチャンネルなら `None`
+    pub fn channel_mut(&mut self, channel: usize) -> Option<CrossoverChannel<'_>> {
+        Some(CrossoverChannel {
+            stages: &self.stages,
+            states: self.states.get_mut(channel)?,
+        })
+    }
+
+    /// 全チャンネルのフィルターの状態をチャンネル順に借りる
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = CrossoverChannel<'_>> {
+        let stages = &self.stages;
+        self.states
+            .iter_mut()
+            .map(move |states| CrossoverChannel { stages, states })
+    }
+
+    /// チャンネル `channel` の 1 サンプルを [`CrossoverChannel::split_lanes()`] で分割する。
+    /// 状態を確保していないチャンネルでは全て 0 を返す
+    pub fn split_lanes(&mut self, channel: usize, input: f32) -> F32x4 {
+        self.channel_mut(channel)
+            .map_or(F32x4::default(), |mut channel| channel.split_lanes(input))
+    }
+
+    /// `frequency` における各バンドの振幅特性 (dB)。`split_lanes()` と同じフィルター構成で計算する
+    pub fn band_magnitudes_db(&self, frequency: f32, sample_rate: f32) -> [f32; NUM_BANDS] {
+        let magnitude = |biquads: &[Biquad]| -> f32 {
+            biquads
+                .iter()
+                .map(|biquad| biquad.magnitude_at(frequency, sample_rate))
+                .product()
+        };
+
+        [
+            magnitude(&self.low_lp),
+            magnitude(&self.mid_hp) * magnitude(&self.mid_lp),
+            magnitude(&self.high_hp),
+        ]
+        .map(util::gain_to_db)
+    }
+
+    /// `frequencies` の各点での各バンドの振幅特性 (dB) を `output` に書き込む
+    pub fn magnitude_response_db(
+        &self,
+        frequencies: &[f32],
+        sample_rate: f32,
+        output: &mut [Vec<f32>; NUM_BANDS],
+    ) {
+        for band in output.iter_mut() {
+            band.clear();
+        }
+        for &frequency in frequencies {
+            let magnitudes = self.band_magnitudes_db(frequency, sample_rate);
+            for (band, magnitude) in output.iter_mut().zip(magnitudes) {
+                band.push(magnitude);
+            }
+        }
+    }
+}
+
+/// 1 チャンネル分のフィルターの状態を [`Crossover`] から借りたもの。サンプルごとのループの外で借りておく
+pub struct CrossoverChannel<'a> {
+    stages: &'a [BiquadX4; STAGES],
+    states: &'a mut [BiquadX4State; STAGES],
+}
+
+impl CrossoverChannel<'_> {
+    /// 1 サンプルを 3 バンドに分割し、レーン 0, 1, 2 に (low, mid, high) を入れて返す。レーン 3 は 0
+    pub fn split_lanes(&mut self, input: f32) -> F32x4 {
+        let mut lanes = F32x4::from_array([input, input, input, 0.0]);
+        for (stage, state) in self.stages.iter().zip(self.states.iter_mut()) {
+            lanes = stage.process(state, lanes);
+        }
+
+        // NaN や無限大はフィルターの状態に残り続けるので、出てきたら状態をクリアして無音を返す
+        if !lanes.sum().is_finite() {
+            for state in self.states.iter_mut() {
+                state.reset();
+            }
+            return F32x4::default();
+        }
+
+        lanes
+    }
+}
+
+/// バンドごとの値を [`Crossover::split_lanes()`] と同じレーンに並べる。使わないレーン 3 は 0
+pub fn band_lanes(bands: [f32; NUM_BANDS]) -> F32x4 {
+    let [low, mid, high] = bands;
+    F32x4::from_array([low, mid, high, 0.0])
+}
+
+/// `min`..=`max` Hz を対数スケールで等間隔に分割した `num_points` 点の周波数
+pub fn log_frequency_grid(min: f32, max: f32, num_points: usize) -> Vec<f32> {
+    let steps = num_points.saturating_sub(1).max(1) as f32;
+    (0..num_points)
+        .map(|i| min * (max / min).powf(i as f32 / steps))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_from_non_finite_input() {
+        for poison in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let mut crossover = Crossover::new(2);
+            crossover.set_frequencies(200.0, 2000.0, crate::biquad::BUTTERWORTH_Q, 48000.0);
+            let mut fresh = crossover.clone();
+            for n in 0..100 {
+                crossover.split_lanes(0, (n as f32 * 0.3).sin());
+                crossover.split_lanes(1, (n as f32 * 0.3).sin());
+            }
+
+            assert_eq!(crossover.split_lanes(0, poison).to_array(), [0.0; 4]);
+
+            // そのチャンネルのフィルターの状態だけがクリアされ、係数は残っている
+            for n in 0..1000 {
+                let input = (n as f32 * 0.1).sin();
+                let bands = crossover.split_lanes(0, input).to_array();
+                assert!(bands.iter().all(|band| band.is_finite()));
+                assert_eq!(bands, fresh.split_lanes(0, input).to_array());
+            }
+        }
+    }
+}
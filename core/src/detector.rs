@@ -0,0 +1,199 @@
+//! コンプレッサーの検出器。入力の振幅から、ゲインリダクションを決めるためのレベルを測る。
+//!
+//! 測り方ごとに [`EnvelopeFollower`] を実装し、[`Detector`] が [`DetectorMode`] に従って使い分ける。
+//! アタックとリリースの時定数やホールドはコンプレッサーの側で扱うので、ここでは瞬間的なレベルだけを返す。
+//! どれも [`Sample`] について総称的で、倍精度の経路では f64 で動かす。
+
+use crate::compression::DetectorMode;
+use crate::sample::Sample;
+
+/// 1 サンプルずつ信号を受け取り、その時点のレベル（振幅）を返す検出器
+pub trait EnvelopeFollower<T: Sample> {
+    fn process(&mut self, input: T) -> T;
+
+    /// 溜まった状態をクリアする
+    fn reset(&mut self);
+}
+
+/// サンプルごとの絶対値。状態を持たない
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeakFollower;
+
+impl<T: Sample> EnvelopeFollower<T> for PeakFollower {
+    fn process(&mut self, input: T) -> T {
+        input.abs()
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// 1 次のローパスで平均した二乗の平方根
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RmsFollower<T: Sample> {
+    mean_square: T,
+    /// 平均化の係数。1 に近いほど長い時間で平均する
+    coef: T,
+}
+
+impl<T: Sample> RmsFollower<T> {
+    pub fn set_coef(&mut self, coef: T) {
+        self.coef = coef;
+    }
+}
+
+impl<T: Sample> EnvelopeFollower<T> for RmsFollower<T> {
+    fn process(&mut self, input: T) -> T {
+        self.mean_square = self.mean_square * self.coef + input * input * (T::ONE - self.coef);
+        self.mean_square.sqrt()
+    }
+
+    fn reset(&mut self) {
+        self.mean_square = T::ZERO;
+    }
+}
+
+/// ヒルベルト変換のオールパスの係数。2 系統の位相差がおよそ 0.001 fs から 0.499 fs まで
+/// 90 度 ±0.7 度に収まる (Olli Niemitalo による設計)。フィルターには 2 乗した値を使う
+const HILBERT_COEFS: [[f64; 4]; 2] = [
+    [0.692_387_8, 0.936_065_4, 0.988_229_5, 0.998_748_8],
+    [0.402_192_1, 0.856_171_1, 0.972_290_9, 0.995_288_5],
+];
+
+/// `y[n] = c (x[n] + y[n - 2]) - x[n - 2]` の 2 次オールパス
+#[derive(Debug, Clone, Copy, Default)]
+struct Allpass<T: Sample> {
+    coef: T,
+    x: [T; 2],
+    y: [T; 2],
+}
+
+impl<T: Sample> Allpass<T> {
+    fn process(&mut self, input: T) -> T {
+        let output = self.coef * (input + self.y[1]) - self.x[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// 解析信号の振幅。オールパスで 90 度ずれた 2 つの信号を作り、その二乗和の平方根を返す。
+/// 正弦波では波形の山と谷に関係なく一定の値になるので、低域でもリップルの少ないレベルが得られる
+#[derive(Debug, Clone, Copy)]
+pub struct AnalyticFollower<T: Sample> {
+    in_phase: [Allpass<T>; 4],
+    quadrature: [Allpass<T>; 4],
+    /// 同相側の 1 サンプルの遅延
+    in_phase_delay: T,
+}
+
+impl<T: Sample> AnalyticFollower<T> {
+    pub fn new() -> Self {
+        let allpasses = |coefs: [f64; 4]| {
+            coefs.map(|coef| Allpass {
+                coef: T::from_f64(coef * coef),
+                ..Default::default()
+            })
+        };
+        Self {
+            in_phase: allpasses(HILBERT_COEFS[0]),
+            quadrature: allpasses(HILBERT_COEFS[1]),
+            in_phase_delay: T::ZERO,
+        }
+    }
+}
+
+impl<T: Sample> Default for AnalyticFollower<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Sample> EnvelopeFollower<T> for AnalyticFollower<T> {
+    fn process(&mut self, input: T) -> T {
+        let in_phase = self
+            .in_phase
+            .iter_mut()
+            .fold(input, |x, allpass| allpass.process(x));
+        let quadrature = self
+            .quadrature
+            .iter_mut()
+            .fold(input, |x, allpass| allpass.process(x));
+        let in_phase = std::mem::replace(&mut self.in_phase_delay, in_phase);
+
+        (in_phase * in_phase + quadrature * quadrature).sqrt()
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// 1 バンド分の検出器。選んでいる [`DetectorMode`] の検出器だけを動かす。切り替えたときは、前に選んで
+/// いたときの古い状態が残らないよう、新しく選んだ検出器の状態をクリアしてから使う
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Detector<T: Sample> {
+    peak: PeakFollower,
+    rms: RmsFollower<T>,
+    analytic: AnalyticFollower<T>,
+    /// 前のサンプルで使った測り方。まだ使っていなければ `None`
+    mode: Option<DetectorMode>,
+}
+
+impl<T: Sample> Detector<T> {
+    /// `mode` で測った `input` のレベルを返す。`rms_coef` は RMS 検出の平均化の係数。
+    /// NaN や無限大は状態に残り続けるので、出てきたら状態をクリアして 0 を返す
+    pub fn process(&mut self, input: T, mode: DetectorMode, rms_coef: T) -> T {
+        if self.mode != Some(mode) {
+            self.mode = Some(mode);
+            match mode {
+                DetectorMode::Peak => EnvelopeFollower::<T>::reset(&mut self.peak),
+                DetectorMode::Rms => self.rms.reset(),
+                DetectorMode::Analytic => self.analytic.reset(),
+            }
+        }
+
+        let level = match mode {
+            DetectorMode::Peak => self.peak.process(input),
+            DetectorMode::Rms => {
+                self.rms.set_coef(rms_coef);
+                self.rms.process(input)
+            }
+            DetectorMode::Analytic => self.analytic.process(input),
+        };
+        if !level.is_finite() {
+            self.reset();
+            return T::ZERO;
+        }
+
+        level
+    }
+
+    pub fn reset(&mut self) {
+        EnvelopeFollower::<T>::reset(&mut self.peak);
+        self.rms.reset();
+        self.analytic.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switching_mode_clears_the_newly_selected_follower() {
+        let mut detector = Detector::<f32>::default();
+        let rms_coef = 0.99;
+        for _ in 0..2000 {
+            detector.process(1.0, DetectorMode::Rms, rms_coef);
+        }
+        assert!(detector.process(1.0, DetectorMode::Rms, rms_coef) > 0.99);
+
+        // 選んでいない間は RMS を動かさず、選び直したら無音から平均し直す
+        assert_eq!(detector.process(0.5, DetectorMode::Peak, rms_coef), 0.5);
+        let level = detector.process(1.0, DetectorMode::Rms, rms_coef);
+        assert!(
+            (level - (1.0 - rms_coef).sqrt()).abs() < 1e-6,
+            "level {level}"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! dB と振幅の変換の高速な近似。
+//!
+//! コンプレッサーの検出器はサンプルごと、バンドごとに振幅と dB を往復するので、`log10()` と `powf()` が
+//! 処理の大きな割合を占める。ここでは浮動小数点の指数部をそのまま使い、仮数部だけを多項式で近似する。
+//! 誤差は `gain_to_db()` で 1e-4 dB、`db_to_gain()` で相対 1e-6 程度で、聞き分けられる差にはならない。
+
+use crate::util;
+
+/// 1 dB あたりの log2 の値 (`log2(10) / 20`)
+const LOG2_PER_DB: f32 = std::f32::consts::LOG2_10 / 20.0;
+/// log2 の値 1 あたりの dB (`20 / log2(10)`)
+const DB_PER_LOG2: f32 = 20.0 / std::f32::consts::LOG2_10;
+
+/// `log2(x)` の近似。`x` は正の正規化数であること
+///
+/// 仮数部 `1 + t` について `log2(1 + t) = t + t (1 - t) q(t)` とし、3 次の `q` を最大誤差が最小になる
+/// ように合わせてある。`t` が 0 と 1 で厳密に一致するので、オクターブの境目で値が飛ばない
+pub fn fast_log2(x: f32) -> f32 {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127;
+    let t = f32::from_bits((bits & 0x007f_ffff) | 0x3f80_0000) - 1.0;
+    let q = 0.441_916_5 + t * (-0.267_175_14 + t * (0.148_417_17 + t * -0.045_142_926));
+
+    exponent as f32 + t + t * (1.0 - t) * q
+}
+
+/// `2^x` の近似。`x` は -126..=126 に制限する
+///
+/// 小数部 `f` について `2^f = 1 + f + f (1 - f) q(f)` とし、[`fast_log2()`] と同じように合わせてある
+pub fn fast_exp2(x: f32) -> f32 {
+    let x = x.clamp(-126.0, 126.0);
+    let integer = x.floor();
+    let f = x - integer;
+    let q = -0.306_847 + f * (-0.066_699_89 + f * (-0.010_844_609 + f * -0.001_896_841_5));
+    let fraction = 1.0 + f + f * (1.0 - f) * q;
+
+    f32::from_bits(((integer as i32 + 127) as u32) << 23) * fraction
+}
+
+/// [`util::gain_to_db()`] の近似。同じように [`util::MINUS_INFINITY_GAIN`] 未満の振幅は切り上げる
+pub fn gain_to_db(gain: f32) -> f32 {
+    fast_log2(gain.max(util::MINUS_INFINITY_GAIN)) * DB_PER_LOG2
+}
+
+/// [`util::db_to_gain()`] の近似。同じように [`util::MINUS_INFINITY_DB`] 以下は 0 を返す
+pub fn db_to_gain(dbs: f32) -> f32 {
+    if dbs > util::MINUS_INFINITY_DB {
+        fast_exp2(dbs * LOG2_PER_DB)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log2_is_accurate_and_continuous() {
+        let mut max_error = 0.0_f32;
+        let mut x = 1e-6_f32;
+        while x < 1e6 {
+            max_error = max_error.max((fast_log2(x) - x.log2()).abs());
+            x *= 1.001;
+        }
+        assert!(max_error < 2e-5, "max log2 error {max_error}");
+
+        // オクターブの境目の前後で値が飛ばない
+        for exponent in -20..20 {
+            let x = 2.0_f32.powi(exponent);
+            assert_eq!(fast_log2(x), exponent as f32);
+            assert!(fast_log2(x) - fast_log2(x.next_down()) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn exp2_is_accurate() {
+        let mut max_relative_error = 0.0_f32;
+        for i in -40_000..=40_000 {
+            let x = i as f32 * 0.001;
+            let expected = x.exp2();
+            max_relative_error = max_relative_error.max((fast_exp2(x) - expected).abs() / expected);
+        }
+        assert!(
+            max_relative_error < 1e-6,
+            "max exp2 error {max_relative_error}"
+        );
+        assert_eq!(fast_exp2(0.0), 1.0);
+        assert_eq!(fast_exp2(3.0), 8.0);
+    }
+
+    #[test]
+    fn db_conversions_match_util() {
+        let mut max_db_error = 0.0_f32;
+        let mut gain = 1e-5_f32;
+        while gain < 100.0 {
+            max_db_error = max_db_error.max((gain_to_db(gain) - util::gain_to_db(gain)).abs());
+            gain *= 1.0001;
+        }
+        assert!(
+            max_db_error < 2e-4,
+            "max gain_to_db error {max_db_error} dB"
+        );
+
+        let mut max_relative_error = 0.0_f32;
+        for i in -1_000..=400 {
+            let dbs = i as f32 * 0.1;
+            let expected = util::db_to_gain(dbs);
+            max_relative_error =
+                max_relative_error.max((db_to_gain(dbs) - expected).abs() / expected);
+        }
+        assert!(
+            max_relative_error < 2e-6,
+            "max db_to_gain error {max_relative_error}"
+        );
+
+        assert_eq!(gain_to_db(0.0), gain_to_db(util::MINUS_INFINITY_GAIN));
+        assert_eq!(db_to_gain(util::MINUS_INFINITY_DB), 0.0);
+    }
+}
@@ -0,0 +1,19 @@
+//! マルチバンドコンプレッサーの DSP の部品。
+//!
+//! バンド分割 ([`crossover`])、コンプレッサー ([`compression`])、オーバーサンプリング ([`oversampling`])
+//! と、それらが使うフィルターや検出器をまとめたもの。プラグインのフレームワークに依存しないので、
+//! ほかの Rust のオーディオのプロジェクトからもそのまま使える。
+//!
+//! `nih_plug` フィーチャーを有効にすると、[`compression::DetectorMode`] などの設定の列挙型に nih_plug の
+//! `Enum` を実装し、`EnumParam` として使えるようにする。
+
+pub mod biquad;
+pub mod compression;
+pub mod crossover;
+pub mod detector;
+pub mod fast_math;
+pub mod oversampling;
+pub mod precision;
+pub mod sample;
+pub mod simd;
+pub mod util;
@@ -0,0 +1,261 @@
+/// 2x ステージ 1 段あたりのハーフバンド FIR のタップ数。
+/// (TAPS - 1) を 8 の倍数にしておくと、8x まで遅延が整数サンプルになる。
+const HALFBAND_TAPS: usize = 33;
+/// 1 サンプルあたりの最大オーバーサンプリング倍率
+pub const MAX_OVERSAMPLING_FACTOR: usize = 8;
+/// 最大倍率に必要な 2x ステージ数
+const MAX_STAGES: usize = 3;
+/// 最大のレイテンシー（元のサンプルレート換算）。ステージごとの遅延は半分ずつ小さくなるので、
+/// 合計は (TAPS - 1) を超えない
+const MAX_LATENCY_SAMPLES: usize = HALFBAND_TAPS - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "nih_plug", derive(nih_plug::prelude::Enum))]
+pub enum OversamplingFactor {
+    #[cfg_attr(feature = "nih_plug", id = "off", name = "Off")]
+    Off,
+    #[cfg_attr(feature = "nih_plug", id = "2x", name = "2x")]
+    X2,
+    #[cfg_attr(feature = "nih_plug", id = "4x", name = "4x")]
+    X4,
+    #[cfg_attr(feature = "nih_plug", id = "8x", name = "8x")]
+    X8,
+}
+
+impl OversamplingFactor {
+    /// 2x ステージの段数
+    pub fn stages(self) -> usize {
+        match self {
+            OversamplingFactor::Off => 0,
+            OversamplingFactor::X2 => 1,
+            OversamplingFactor::X4 => 2,
+            OversamplingFactor::X8 => 3,
+        }
+    }
+
+    pub fn factor(self) -> usize {
+        1 << self.stages()
+    }
+
+    /// アップ + ダウンサンプリングで生じる遅延（元のサンプルレート換算）
+    pub fn latency_samples(self) -> u32 {
+        // 各ステージの FIR は (TAPS - 1) / 2 サンプルの群遅延を持ち、アップとダウンで 2 回通る。
+        // ステージ i はベースレートの 2^(i+1) 倍で動くので、ベースレート換算では半分ずつ小さくなる。
+        (0..self.stages())
+            .map(|stage| ((HALFBAND_TAPS - 1) >> (stage + 1)) as u32)
+            .sum()
+    }
+}
+
+/// 窓関数付き sinc で設計したハーフバンドローパスの係数
+fn halfband_coefficients() -> [f32; HALFBAND_TAPS] {
+    let mut coefs = [0.0; HALFBAND_TAPS];
+    let center = (HALFBAND_TAPS - 1) as f32 / 2.0;
+    for (n, coef) in coefs.iter_mut().enumerate() {
+        let t = n as f32 - center;
+        let sinc = if t == 0.0 {
+            0.5
+        } else {
+            (std::f32::consts::FRAC_PI_2 * t).sin() / (std::f32::consts::PI * t)
+        };
+        // Blackman window
+        let phase = 2.0 * std::f32::consts::PI * n as f32 / (HALFBAND_TAPS - 1) as f32;
+        let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+        *coef = sinc * window;
+    }
+
+    // DC ゲインを 1 に正規化
+    let sum: f32 = coefs.iter().sum();
+    for coef in coefs.iter_mut() {
+        *coef /= sum;
+    }
+
+    coefs
+}
+
+/// 1 段分の 2x アップ/ダウンサンプラー。ポリフェーズ形式で、ゼロ挿入したサンプルの乗算を省く。
+#[derive(Clone)]
+struct HalfbandStage {
+    coefs: [f32; HALFBAND_TAPS],
+    /// アップサンプリング側の入力履歴（新しいものが先頭）
+    up_history: [f32; HALFBAND_TAPS],
+    /// ダウンサンプリング側の入力履歴（新しいものが先頭）
+    down_history: [f32; HALFBAND_TAPS],
+}
+
+impl HalfbandStage {
+    fn new() -> Self {
+        Self {
+            coefs: halfband_coefficients(),
+            up_history: [0.0; HALFBAND_TAPS],
+            down_history: [0.0; HALFBAND_TAPS],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.up_history = [0.0; HALFBAND_TAPS];
+        self.down_history = [0.0; HALFBAND_TAPS];
+    }
+
+    /// 1 サンプルを 2 サンプルに補間する
+    fn upsample(&mut self, input: f32) -> [f32; 2] {
+        self.up_history.copy_within(0..HALFBAND_TAPS - 1, 1);
+        self.up_history[0] = input;
+
+        // ゼロ挿入後の信号に対する畳み込みは、偶数タップと奇数タップの 2 つの位相に分かれる。
+        // ゼロ挿入で失われるエネルギーを補うためにゲインを 2 倍する。
+        let mut even = 0.0;
+        let mut odd = 0.0;
+        for (k, x) in self.up_history.iter().enumerate() {
+            if let Some(coef) = self.coefs.get(2 * k) {
+                even += coef * x;
+            }
+            if let Some(coef) = self.coefs.get(2 * k + 1) {
+                odd += coef * x;
+            }
+        }
+
+        [even * 2.0, odd * 2.0]
+    }
+
+    /// 2 サンプルをフィルタリングして 1 サンプルに間引く
+    fn downsample(&mut self, input: [f32; 2]) -> f32 {
+        // 偶数番目のサンプルの位置で出力を計算して、遅延が整数サンプルになるようにする
+        self.down_history.copy_within(0..HALFBAND_TAPS - 1, 1);
+        self.down_history[0] = input[0];
+        let output = self
+            .coefs
+            .iter()
+            .zip(self.down_history.iter())
+            .map(|(coef, x)| coef * x)
+            .sum();
+
+        self.down_history.copy_within(0..HALFBAND_TAPS - 1, 1);
+        self.down_history[0] = input[1];
+
+        output
+    }
+}
+
+/// 1 チャンネル分のオーバーサンプラー。2x ステージを最大 3 段カスケードする。
+///
+/// ステージは常に最大段数ぶん確保しておき、倍率の切り替えでアロケーションが起きないようにしている。
+#[derive(Clone)]
+pub struct Oversampler {
+    stages: [HalfbandStage; MAX_STAGES],
+    factor: OversamplingFactor,
+    /// ステージ間の受け渡しに使うスクラッチバッファ
+    scratch: [f32; MAX_OVERSAMPLING_FACTOR],
+}
+
+impl Oversampler {
+    pub fn new() -> Self {
+        Self {
+            stages: [HalfbandStage::new(), HalfbandStage::new(), HalfbandStage::new()],
+            factor: OversamplingFactor::Off,
+            scratch: [0.0; MAX_OVERSAMPLING_FACTOR],
+        }
+    }
+
+    pub fn factor(&self) -> OversamplingFactor {
+        self.factor
+    }
+
+    /// 倍率を変更する。フィルター履歴は古い倍率のものなのでリセットする。
+    pub fn set_factor(&mut self, factor: OversamplingFactor) {
+        if factor != self.factor {
+            self.factor = factor;
+            self.reset();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.reset();
+        }
+        self.scratch = [0.0; MAX_OVERSAMPLING_FACTOR];
+    }
+
+    /// `input` をアップサンプリングし、`factor()` 個のサンプルを `output` の先頭に書き込む
+    pub fn upsample(&mut self, input: f32, output: &mut [f32; MAX_OVERSAMPLING_FACTOR]) {
+        output[0] = input;
+        let mut len = 1;
+        for stage in self.stages.iter_mut().take(self.factor.stages()) {
+            self.scratch[..len].copy_from_slice(&output[..len]);
+            for (i, sample) in self.scratch[..len].iter().enumerate() {
+                let [a, b] = stage.upsample(*sample);
+                output[2 * i] = a;
+                output[2 * i + 1] = b;
+            }
+            len *= 2;
+        }
+    }
+
+    /// `input` の先頭 `factor()` 個のサンプルを 1 サンプルにダウンサンプリングする
+    pub fn downsample(&mut self, input: &[f32; MAX_OVERSAMPLING_FACTOR]) -> f32 {
+        let mut len = self.factor.factor();
+        self.scratch[..len].copy_from_slice(&input[..len]);
+        for stage in self.stages.iter_mut().take(self.factor.stages()).rev() {
+            len /= 2;
+            for i in 0..len {
+                self.scratch[i] = stage.downsample([self.scratch[2 * i], self.scratch[2 * i + 1]]);
+            }
+        }
+
+        self.scratch[0]
+    }
+}
+
+impl Default for Oversampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// オーバーサンプリングと同じだけ遅らせる遅延線。処理した信号と混ぜるドライ信号の位置を揃える
+#[derive(Clone)]
+pub struct LatencyDelay {
+    buffer: [f32; MAX_LATENCY_SAMPLES + 1],
+    write_pos: usize,
+    delay: usize,
+}
+
+impl LatencyDelay {
+    pub fn new() -> Self {
+        Self {
+            buffer: [0.0; MAX_LATENCY_SAMPLES + 1],
+            write_pos: 0,
+            delay: 0,
+        }
+    }
+
+    /// 遅延を `factor` のレイテンシーに合わせる。古い倍率の信号が混ざらないようにリセットする
+    pub fn set_factor(&mut self, factor: OversamplingFactor) {
+        let delay = (factor.latency_samples() as usize).min(MAX_LATENCY_SAMPLES);
+        if delay != self.delay {
+            self.delay = delay;
+            self.reset();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer = [0.0; MAX_LATENCY_SAMPLES + 1];
+        self.write_pos = 0;
+    }
+
+    /// `input` を書き込み、遅延した 1 サンプルを返す
+    pub fn process(&mut self, input: f32) -> f32 {
+        let len = self.buffer.len();
+        self.buffer[self.write_pos] = input;
+        let read_pos = (self.write_pos + len - self.delay) % len;
+        self.write_pos = (self.write_pos + 1) % len;
+
+        self.buffer[read_pos]
+    }
+}
+
+impl Default for LatencyDelay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,121 @@
+//! 倍精度 (f64) のバンド分割と圧縮。
+//!
+//! 高いサンプルレートやオーバーサンプリングでは、クロスオーバーの低い周波数の極が単位円に近づき、
+//! エンベロープの係数も 1 に近づくので、f32 では係数と状態の丸め誤差が目立ってくる。
+//! [`Precision::Double`] のときはバンド分割から圧縮、バンドの合成までを f64 で行い、f32 との変換は
+//! この経路の入口と出口でだけ行う。オーバーサンプリングの FIR は再帰しないので f32 のままにする。
+//!
+//! フィルターとコンプレッサーは [`crate::sample::Sample`] について総称的なものを f64 で使う。
+//! クロスオーバーだけは f32 の経路で全バンドを SIMD のレーンに並べているので、ここに f64 版を置く。
+
+use crate::biquad::{Biquad, BiquadState};
+use crate::crossover::NUM_BANDS;
+
+/// バンド分割と圧縮の演算精度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "nih_plug", derive(nih_plug::prelude::Enum))]
+pub enum Precision {
+    #[cfg_attr(feature = "nih_plug", id = "32", name = "32-bit")]
+    Single,
+    #[cfg_attr(feature = "nih_plug", id = "64", name = "64-bit")]
+    Double,
+}
+
+/// [`Crossover64`] のフィルターの数と、各バンドが使うフィルターの範囲
+const NUM_BIQUADS: usize = 8;
+const LOW_LP: std::ops::Range<usize> = 0..2;
+const MID: std::ops::Range<usize> = 2..6;
+const HIGH_HP: std::ops::Range<usize> = 6..8;
+
+/// f64 の 3 バンドクロスオーバー。[`crate::crossover::Crossover`] と同じ周波数の制限とフィルター構成を
+/// 使い、係数は全チャンネルで共有してチャンネルごとの状態だけを連続した配列に並べて持つ
+#[derive(Clone)]
+pub struct Crossover64 {
+    /// Low の LP 2 段、Mid の HP 2 段と LP 2 段、High の HP 2 段の順。係数だけを使う
+    biquads: [Biquad<f64>; NUM_BIQUADS],
+    /// per-channel filter states, in the same order as `biquads`
+    states: Vec<[BiquadState<f64>; NUM_BIQUADS]>,
+}
+
+impl Crossover64 {
+    /// `num_channels` チャンネル分の状態を確保する
+    pub fn new(num_channels: usize) -> Self {
+        Self {
+            biquads: [Biquad::new(); NUM_BIQUADS],
+            states: vec![Default::default(); num_channels],
+        }
+    }
+
+    /// 全チャンネルのフィルターの状態をクリアする
+    pub fn reset(&mut self) {
+        for state in self.states.iter_mut().flatten() {
+            state.reset();
+        }
+    }
+
+    /// [`crate::crossover::Crossover::set_frequencies()`] と同じ構成の係数を f64 で設計する。フィルターの状態は保つ
+    pub fn set_frequencies(&mut self, lo_mid: f32, mid_hi: f32, q: f32, sample_rate: f32) {
+        let sample_rate = sample_rate as f64;
+        let q = q as f64;
+        let nyquist = sample_rate * 0.5;
+        let low_freq = (lo_mid as f64).clamp(10.0, nyquist * 0.8);
+        let high_freq = (mid_hi as f64).clamp(low_freq + 10.0, nyquist * 0.99);
+
+        // Low の LP、Mid の HP、Mid の LP、High の HP の順に 2 段ずつ
+        let passes = [
+            (false, low_freq),
+            (true, low_freq),
+            (false, high_freq),
+            (true, high_freq),
+        ];
+        for (biquads, (highpass, freq)) in self.biquads.chunks_exact_mut(2).zip(passes) {
+            for biquad in biquads {
+                if highpass {
+                    biquad.set_highpass(freq, q, sample_rate);
+                } else {
+                    biquad.set_lowpass(freq, q, sample_rate);
+                }
+            }
+        }
+    }
+
+    /// 全チャンネルのフィルターの状態をチャンネル順に借りる
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = Crossover64Channel<'_>> {
+        let biquads = &self.biquads;
+        self.states
+            .iter_mut()
+            .map(move |states| Crossover64Channel { biquads, states })
+    }
+}
+
+/// 1 チャンネル分のフィルターの状態を [`Crossover64`] から借りたもの
+pub struct Crossover64Channel<'a> {
+    biquads: &'a [Biquad<f64>; NUM_BIQUADS],
+    states: &'a mut [BiquadState<f64>; NUM_BIQUADS],
+}
+
+impl Crossover64Channel<'_> {
+    /// 1 サンプルを (low, mid, high) に分割する。NaN や無限大が出たら状態をクリアして無音を返す
+    pub fn split(&mut self, input: f64) -> [f64; NUM_BANDS] {
+        let biquads = self.biquads;
+        let states = &mut *self.states;
+        let mut cascade = |range: std::ops::Range<usize>, x: f64| {
+            biquads[range.clone()]
+                .iter()
+                .zip(states[range].iter_mut())
+                .fold(x, |x, (biquad, state)| biquad.process_with_state(state, x))
+        };
+        let low = cascade(LOW_LP, input);
+        let mid = cascade(MID, input);
+        let high = cascade(HIGH_HP, input);
+
+        if !(low + mid + high).is_finite() {
+            for state in self.states.iter_mut() {
+                state.reset();
+            }
+            return [0.0; NUM_BANDS];
+        }
+
+        [low, mid, high]
+    }
+}
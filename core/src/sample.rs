@@ -0,0 +1,155 @@
+//! DSP の中心部分を f32 と f64 の両方で書くための、サンプルの型。
+//!
+//! フィルター、検出器、ゲインコンピューターは [`Sample`] について総称的に書き、通常の経路では f32、
+//! [`crate::precision::Precision::Double`] の経路では f64 で使う。係数の設計や dB の変換のように
+//! 型ごとに計算の仕方が変わるところだけを、このトレイトのメソッドにする。
+
+use std::fmt::Debug;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::util;
+
+use crate::fast_math;
+
+/// f32 か f64 の 1 サンプル
+pub trait Sample:
+    Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const PI: Self;
+    /// [`util::MINUS_INFINITY_DB`] をこの型にしたもの
+    const MINUS_INFINITY_DB: Self;
+
+    fn from_f32(value: f32) -> Self;
+    /// f32 より精度の高い定数や係数を、f64 の経路で丸めずに使うためのもの
+    fn from_f64(value: f64) -> Self;
+    fn to_f32(self) -> f32;
+
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin_cos(self) -> (Self, Self);
+    fn max(self, other: Self) -> Self;
+    fn is_finite(self) -> bool;
+
+    /// 振幅を dB にする。[`util::MINUS_INFINITY_GAIN`] 未満の振幅は切り上げる。`fast` なら
+    /// [`fast_math`] の近似を使う（f32 だけで、f64 では常に正確に計算する）
+    fn gain_to_db(self, fast: bool) -> Self;
+    /// dB を振幅にする。[`util::MINUS_INFINITY_DB`] 以下は 0。`fast` の扱いは
+    /// [`Self::gain_to_db()`] と同じ
+    fn db_to_gain(self, fast: bool) -> Self;
+}
+
+impl Sample for f32 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+    const PI: Self = std::f32::consts::PI;
+    const MINUS_INFINITY_DB: Self = util::MINUS_INFINITY_DB;
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn abs(self) -> Self {
+        self.abs()
+    }
+
+    fn sqrt(self) -> Self {
+        self.sqrt()
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        self.sin_cos()
+    }
+
+    fn max(self, other: Self) -> Self {
+        self.max(other)
+    }
+
+    fn is_finite(self) -> bool {
+        self.is_finite()
+    }
+
+    fn gain_to_db(self, fast: bool) -> Self {
+        if fast {
+            fast_math::gain_to_db(self)
+        } else {
+            util::gain_to_db(self)
+        }
+    }
+
+    fn db_to_gain(self, fast: bool) -> Self {
+        if fast {
+            fast_math::db_to_gain(self)
+        } else {
+            util::db_to_gain(self)
+        }
+    }
+}
+
+impl Sample for f64 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+    const PI: Self = std::f64::consts::PI;
+    const MINUS_INFINITY_DB: Self = util::MINUS_INFINITY_DB as f64;
+
+    fn from_f32(value: f32) -> Self {
+        f64::from(value)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn abs(self) -> Self {
+        self.abs()
+    }
+
+    fn sqrt(self) -> Self {
+        self.sqrt()
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        self.sin_cos()
+    }
+
+    fn max(self, other: Self) -> Self {
+        self.max(other)
+    }
+
+    fn is_finite(self) -> bool {
+        self.is_finite()
+    }
+
+    fn gain_to_db(self, _fast: bool) -> Self {
+        20.0 * self.max(f64::from(util::MINUS_INFINITY_GAIN)).log10()
+    }
+
+    fn db_to_gain(self, _fast: bool) -> Self {
+        if self > Self::MINUS_INFINITY_DB {
+            10.0_f64.powf(self * 0.05)
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! 4 レーンの f32 ベクトル。クロスオーバーのバンドを 1 レーンずつに割り当てて、まとめて処理するのに使う。
+//!
+//! x86_64 では SSE、aarch64 では NEON を使う。どちらもそのアーキテクチャのターゲットで必ず使える命令
+//! なので、実行時の判定はしない。それ以外のアーキテクチャでは配列で同じ計算をする。
+
+use std::ops::{Add, Mul, Sub};
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+/// レーンの数
+pub const LANES: usize = 4;
+
+#[cfg(target_arch = "x86_64")]
+#[derive(Clone, Copy)]
+pub struct F32x4(__m128);
+
+#[cfg(target_arch = "aarch64")]
+#[derive(Clone, Copy)]
+pub struct F32x4(float32x4_t);
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[derive(Clone, Copy)]
+pub struct F32x4([f32; LANES]);
+
+// SAFETY: 以下の SSE と NEON の命令は、それぞれのターゲットで常に使える
+#[cfg(target_arch = "x86_64")]
+impl F32x4 {
+    pub fn splat(value: f32) -> Self {
+        Self(unsafe { _mm_set1_ps(value) })
+    }
+
+    pub fn from_array(values: [f32; LANES]) -> Self {
+        Self(unsafe { _mm_loadu_ps(values.as_ptr()) })
+    }
+
+    pub fn to_array(self) -> [f32; LANES] {
+        let mut values = [0.0; LANES];
+        unsafe { _mm_storeu_ps(values.as_mut_ptr(), self.0) };
+        values
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Add for F32x4 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(unsafe { _mm_add_ps(self.0, rhs.0) })
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Sub for F32x4 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(unsafe { _mm_sub_ps(self.0, rhs.0) })
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Mul for F32x4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(unsafe { _mm_mul_ps(self.0, rhs.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl F32x4 {
+    pub fn splat(value: f32) -> Self {
+        Self(unsafe { vdupq_n_f32(value) })
+    }
+
+    pub fn from_array(values: [f32; LANES]) -> Self {
+        Self(unsafe { vld1q_f32(values.as_ptr()) })
+    }
+
+    pub fn to_array(self) -> [f32; LANES] {
+        let mut values = [0.0; LANES];
+        unsafe { vst1q_f32(values.as_mut_ptr(), self.0) };
+        values
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Add for F32x4 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(unsafe { vaddq_f32(self.0, rhs.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Sub for F32x4 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(unsafe { vsubq_f32(self.0, rhs.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Mul for F32x4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(unsafe { vmulq_f32(self.0, rhs.0) })
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl F32x4 {
+    pub fn splat(value: f32) -> Self {
+        Self([value; LANES])
+    }
+
+    pub fn from_array(values: [f32; LANES]) -> Self {
+        Self(values)
+    }
+
+    pub fn to_array(self) -> [f32; LANES] {
+        self.0
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl Add for F32x4 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|lane| self.0[lane] + rhs.0[lane]))
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl Sub for F32x4 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|lane| self.0[lane] - rhs.0[lane]))
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl Mul for F32x4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|lane| self.0[lane] * rhs.0[lane]))
+    }
+}
+
+impl F32x4 {
+    /// 全レーンの和
+    pub fn sum(self) -> f32 {
+        self.to_array().iter().sum()
+    }
+}
+
+impl Default for F32x4 {
+    fn default() -> Self {
+        Self::splat(0.0)
+    }
+}
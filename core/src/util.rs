@@ -0,0 +1,22 @@
+//! dB と振幅の変換。nih_plug の `util` と同じ定義にして、プラグインのメーターやパラメーターと値を揃える。
+
+/// これ以下の dB は無音として扱う
+pub const MINUS_INFINITY_DB: f32 = -100.0;
+/// [`MINUS_INFINITY_DB`] を振幅にしたもの
+pub const MINUS_INFINITY_GAIN: f32 = 1e-5;
+
+/// dB を振幅にする。[`MINUS_INFINITY_DB`] 以下は 0
+#[inline]
+pub fn db_to_gain(dbs: f32) -> f32 {
+    if dbs > MINUS_INFINITY_DB {
+        10.0f32.powf(dbs * 0.05)
+    } else {
+        0.0
+    }
+}
+
+/// 振幅を dB にする。[`MINUS_INFINITY_GAIN`] 未満の振幅は切り上げる
+#[inline]
+pub fn gain_to_db(gain: f32) -> f32 {
+    f32::max(gain, MINUS_INFINITY_GAIN).log10() * 20.0
+}
@@ -0,0 +1,84 @@
+/*
+ * multiband_compressor の DSP のコアの C インターフェース。
+ *
+ * 3 バンドに分割して各バンドを圧縮し、合成する。ライブラリは `cargo build --release -p
+ * multiband_compressor_ffi` で共有ライブラリと静的ライブラリとしてビルドする。
+ *
+ * どの関数もスレッドセーフではない。mbc_set_param() と mbc_process() は同じスレッドから呼ぶか、
+ * 呼び出し側で排他する。mbc_process() と mbc_set_param() はメモリを確保しないので、オーディオ
+ * スレッドから呼べる。
+ */
+
+#ifndef MULTIBAND_COMPRESSOR_H
+#define MULTIBAND_COMPRESSOR_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MbcCompressor MbcCompressor;
+
+/* パラメーター。値はプレーンな値で設定し、範囲外の値は範囲に収める */
+enum {
+    /* クロスオーバー周波数 (Hz、20〜20000) */
+    MBC_PARAM_XOVER_LO_MID = 0,
+    MBC_PARAM_XOVER_MID_HI = 1,
+    /* バンドのパラメーターの番号は
+     * MBC_PARAM_FIRST_BAND + バンド * MBC_BAND_PARAM_COUNT + 種類。
+     * バンドは 0 が Low、1 が Mid、2 が High */
+    MBC_PARAM_FIRST_BAND = 2,
+    MBC_PARAM_COUNT = 23,
+};
+
+/* バンドのパラメーターの種類 */
+enum {
+    /* スレッショルド (dB、-60〜0) */
+    MBC_BAND_THRESHOLD = 0,
+    /* レシオ (1〜20) */
+    MBC_BAND_RATIO = 1,
+    /* アタック (ms、0.1〜100) */
+    MBC_BAND_ATTACK = 2,
+    /* リリース (ms、10〜1000) */
+    MBC_BAND_RELEASE = 3,
+    /* メイクアップ (dB、0〜24) */
+    MBC_BAND_MAKEUP = 4,
+    /* ニー (dB、0〜24) */
+    MBC_BAND_KNEE = 5,
+    /* ゲインリダクションの上限 (dB、0〜60) */
+    MBC_BAND_RANGE = 6,
+    MBC_BAND_PARAM_COUNT = 7,
+};
+
+/* バンド band のパラメーター kind の番号 */
+#define MBC_BAND_PARAM(band, kind) \
+    (MBC_PARAM_FIRST_BAND + (band) * MBC_BAND_PARAM_COUNT + (kind))
+
+/* sample_rate (Hz) で num_channels チャンネルを処理するコンプレッサーを作る。パラメーターは
+ * プラグインのデフォルト値になる。引数が不正なら NULL を返す */
+MbcCompressor *mbc_create(float sample_rate, uint32_t num_channels);
+
+/* mbc_create() で作ったコンプレッサーを解放する。NULL なら何もしない */
+void mbc_destroy(MbcCompressor *compressor);
+
+/* パラメーター param をプレーンな値 value にする。成功すれば 0、param が不正か value が
+ * 有限でなければ -1 を返す。クロスオーバー周波数を変えてもフィルターの状態は保つので、処理の途中で
+ * 動かしても音は途切れない */
+int32_t mbc_set_param(MbcCompressor *compressor, uint32_t param, float value);
+
+/* num_channels 個のチャンネルのバッファ channels を、それぞれ num_samples サンプル分その場で
+ * 処理する。mbc_create() で指定したより多いチャンネルはそのまま残す */
+void mbc_process(MbcCompressor *compressor,
+                 float *const *channels,
+                 uint32_t num_channels,
+                 uint32_t num_samples);
+
+/* フィルターとエンベロープの状態をクリアする。再生位置を変えたときなどに呼ぶ */
+void mbc_reset(MbcCompressor *compressor);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MULTIBAND_COMPRESSOR_H */
@@ -0,0 +1,326 @@
+//! DSP のコアを C から使うためのインターフェース。
+//!
+//! 3 バンドに分割して各バンドを圧縮し、合成するだけの最小限のプロセッサーで、プラグインのスムージングや
+//! オーバーサンプリング、メーターは含まない。ゲームエンジンや JUCE のプロジェクトのような、Rust 以外の
+//! ホストに組み込むためのもので、C のヘッダーは `include/multiband_compressor.h` にある。
+//!
+//! どの関数もスレッドセーフではない。`mbc_set_param()` と `mbc_process()` は同じスレッドから呼ぶか、
+//! 呼び出し側で排他する。`mbc_process()` と `mbc_set_param()` はメモリを確保しないので、オーディオ
+//! スレッドから呼べる。
+
+use std::ptr;
+
+use multiband_compressor_core::biquad::BUTTERWORTH_Q;
+use multiband_compressor_core::compression::{
+    CompressorSettings, DetectorMode, SingleBandCompressor,
+};
+use multiband_compressor_core::crossover::{self, band_lanes, Crossover, NUM_BANDS};
+
+/// Low-Mid と Mid-High のクロスオーバー周波数 (Hz) のパラメーター
+pub const MBC_PARAM_XOVER_LO_MID: u32 = 0;
+pub const MBC_PARAM_XOVER_MID_HI: u32 = 1;
+/// バンドのパラメーターの番号は `MBC_PARAM_FIRST_BAND + バンド * MBC_BAND_PARAM_COUNT + 種類`。
+/// バンドは 0 が Low、1 が Mid、2 が High
+pub const MBC_PARAM_FIRST_BAND: u32 = 2;
+/// バンドのパラメーターの種類。スレッショルド (dB)、レシオ、アタック (ms)、リリース (ms)、
+/// メイクアップ (dB)、ニー (dB)、レンジ (dB)
+pub const MBC_BAND_THRESHOLD: u32 = 0;
+pub const MBC_BAND_RATIO: u32 = 1;
+pub const MBC_BAND_ATTACK: u32 = 2;
+pub const MBC_BAND_RELEASE: u32 = 3;
+pub const MBC_BAND_MAKEUP: u32 = 4;
+pub const MBC_BAND_KNEE: u32 = 5;
+pub const MBC_BAND_RANGE: u32 = 6;
+pub const MBC_BAND_PARAM_COUNT: u32 = 7;
+/// パラメーターの数
+pub const MBC_PARAM_COUNT: u32 = MBC_PARAM_FIRST_BAND + NUM_BANDS as u32 * MBC_BAND_PARAM_COUNT;
+
+/// クロスオーバー周波数の範囲 (Hz)
+const CROSSOVER_RANGE: (f32, f32) = (20.0, 20_000.0);
+/// バンドのパラメーターの種類ごとの範囲。プラグインのパラメーターと同じにする
+const BAND_PARAM_RANGES: [(f32, f32); MBC_BAND_PARAM_COUNT as usize] = [
+    (-60.0, 0.0),
+    (1.0, 20.0),
+    (0.1, 100.0),
+    (10.0, 1000.0),
+    (0.0, 24.0),
+    (0.0, 24.0),
+    (0.0, 60.0),
+];
+/// バンドごとのデフォルト値。プラグインのパラメーターと同じにする
+const BAND_DEFAULTS: [[f32; MBC_BAND_PARAM_COUNT as usize]; NUM_BANDS] = [
+    [-12.0, 2.0, 20.0, 150.0, 0.0, 0.0, 60.0],
+    [-10.0, 3.0, 10.0, 100.0, 0.0, 0.0, 60.0],
+    [-8.0, 4.0, 5.0, 80.0, 0.0, 0.0, 60.0],
+];
+/// RMS 検出の平均化の時間 (ms)。検出器はピークなので使わないが、プラグインと同じ値にしておく
+const RMS_DETECTOR_WINDOW_MS: f32 = 10.0;
+
+/// C から使うコンプレッサー。C には中身を見せず、ポインターとしてだけ渡す
+pub struct MbcCompressor {
+    sample_rate: f32,
+    /// クロスオーバー周波数 (Low-Mid, Mid-High)
+    crossover_frequencies: [f32; 2],
+    /// バンドごとの、種類の順に並べたパラメーターの値
+    band_values: [[f32; MBC_BAND_PARAM_COUNT as usize]; NUM_BANDS],
+    crossover: Crossover,
+    settings: [CompressorSettings; NUM_BANDS],
+    /// チャンネルごと、バンドごとのコンプレッサー
+    compressors: Vec<[SingleBandCompressor; NUM_BANDS]>,
+}
+
+impl MbcCompressor {
+    fn new(sample_rate: f32, num_channels: usize) -> Self {
+        let mut compressor = Self {
+            sample_rate,
+            crossover_frequencies: [200.0, 2000.0],
+            band_values: BAND_DEFAULTS,
+            crossover: Crossover::new(num_channels),
+            settings: std::array::from_fn(|band| band_settings(&BAND_DEFAULTS[band], sample_rate)),
+            compressors: vec![Default::default(); num_channels],
+        };
+        compressor.update_crossover();
+        compressor
+    }
+
+    /// `param` をプレーンな値 `value` にする。範囲外の値は範囲に収める。`param` が不正か `value` が有限で
+    /// なければ何もせずに `false` を返す
+    fn set_param(&mut self, param: u32, value: f32) -> bool {
+        if !value.is_finite() || param >= MBC_PARAM_COUNT {
+            return false;
+        }
+
+        match param {
+            MBC_PARAM_XOVER_LO_MID | MBC_PARAM_XOVER_MID_HI => {
+                let (min, max) = CROSSOVER_RANGE;
+                self.crossover_frequencies[param as usize] = value.clamp(min, max);
+                self.update_crossover();
+            }
+            _ => {
+                let band = ((param - MBC_PARAM_FIRST_BAND) / MBC_BAND_PARAM_COUNT) as usize;
+                let kind = ((param - MBC_PARAM_FIRST_BAND) % MBC_BAND_PARAM_COUNT) as usize;
+                let (min, max) = BAND_PARAM_RANGES[kind];
+                self.band_values[band][kind] = value.clamp(min, max);
+                self.settings[band] = band_settings(&self.band_values[band], self.sample_rate);
+            }
+        }
+
+        true
+    }
+
+    /// クロスオーバーを設計し直す。フィルターの状態は保つ
+    fn update_crossover(&mut self) {
+        let [lo_mid, mid_hi] =
+            crossover::limit_mid_band(self.crossover_frequencies[0], self.crossover_frequencies[1]);
+        self.crossover
+            .set_frequencies(lo_mid, mid_hi, BUTTERWORTH_Q, self.sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.crossover.reset();
+        for compressor in self.compressors.iter_mut().flatten() {
+            compressor.reset();
+        }
+    }
+
+    /// チャンネル `channel` の `samples` を、その場で分割、圧縮、合成する。`new()` で確保していない
+    /// チャンネルならそのまま残す
+    fn process_channel(&mut self, channel: usize, samples: &mut [f32]) {
+        let (Some(mut crossover), Some(compressors)) = (
+            self.crossover.channel_mut(channel),
+            self.compressors.get_mut(channel),
+        ) else {
+            return;
+        };
+
+        for sample in samples.iter_mut() {
+            let [low, mid, high, _] = crossover.split_lanes(*sample).to_array();
+            let mut bands = [low, mid, high];
+            for ((band, compressor), settings) in bands
+                .iter_mut()
+                .zip(compressors.iter_mut())
+                .zip(&self.settings)
+            {
+                *band = compressor.process_sample(*band, *band, settings);
+            }
+            *sample = band_lanes(bands).sum();
+        }
+    }
+}
+
+/// 1 バンドの、種類の順に並べたパラメーターの値 `values` から、`sample_rate` での設定を作る
+fn band_settings(
+    values: &[f32; MBC_BAND_PARAM_COUNT as usize],
+    sample_rate: f32,
+) -> CompressorSettings {
+    let time_coef = |ms: f32| (-1.0 / (f64::from(ms / 1000.0) * f64::from(sample_rate))).exp();
+
+    CompressorSettings {
+        threshold_db: values[MBC_BAND_THRESHOLD as usize],
+        ratio: values[MBC_BAND_RATIO as usize],
+        knee_db: values[MBC_BAND_KNEE as usize],
+        range_db: values[MBC_BAND_RANGE as usize],
+        attack_coef: time_coef(values[MBC_BAND_ATTACK as usize]),
+        release_coef: time_coef(values[MBC_BAND_RELEASE as usize]),
+        hold_samples: 0,
+        detector: DetectorMode::Peak,
+        rms_coef: time_coef(RMS_DETECTOR_WINDOW_MS),
+        fast_math: false,
+        makeup_db: values[MBC_BAND_MAKEUP as usize],
+        mix: 1.0,
+    }
+}
+
+/// サンプルレート `sample_rate` (Hz) で `num_channels` チャンネルを処理するコンプレッサーを作る。
+/// パラメーターはプラグインのデフォルト値になる。引数が不正なら NULL を返す。使い終わったら
+/// [`mbc_destroy()`] で解放する
+#[no_mangle]
+pub extern "C" fn mbc_create(sample_rate: f32, num_channels: u32) -> *mut MbcCompressor {
+    if !(sample_rate.is_finite() && sample_rate > 0.0) || num_channels == 0 {
+        return ptr::null_mut();
+    }
+
+    Box::into_raw(Box::new(MbcCompressor::new(
+        sample_rate,
+        num_channels as usize,
+    )))
+}
+
+/// [`mbc_create()`] で作ったコンプレッサーを解放する。NULL なら何もしない
+///
+/// # Safety
+///
+/// `compressor` は NULL か、[`mbc_create()`] が返してまだ解放していないポインター
+#[no_mangle]
+pub unsafe extern "C" fn mbc_destroy(compressor: *mut MbcCompressor) {
+    if !compressor.is_null() {
+        drop(unsafe { Box::from_raw(compressor) });
+    }
+}
+
+/// パラメーター `param` (`MBC_PARAM_*`) をプレーンな値 `value` にする。範囲外の値は範囲に収める。
+/// 成功すれば 0、`compressor` が NULL か `param` が不正か `value` が有限でなければ -1 を返す。
+/// クロスオーバー周波数を変えてもフィルターの状態は保つので、処理の途中で動かしても音は途切れない
+///
+/// # Safety
+///
+/// `compressor` は NULL か、[`mbc_create()`] が返してまだ解放していないポインター
+#[no_mangle]
+pub unsafe extern "C" fn mbc_set_param(
+    compressor: *mut MbcCompressor,
+    param: u32,
+    value: f32,
+) -> i32 {
+    let Some(compressor) = (unsafe { compressor.as_mut() }) else {
+        return -1;
+    };
+
+    if compressor.set_param(param, value) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// `num_channels` 個のチャンネルのバッファ `channels` を、それぞれ `num_samples` サンプル分その場で
+/// 処理する。[`mbc_create()`] で指定したより多いチャンネルはそのまま残す
+///
+/// # Safety
+///
+/// `compressor` は NULL か、[`mbc_create()`] が返してまだ解放していないポインター。`channels` は NULL
+/// か、`num_samples` 個の `float` を読み書きできる互いに重ならないバッファへのポインターを
+/// `num_channels` 個並べた配列
+#[no_mangle]
+pub unsafe extern "C" fn mbc_process(
+    compressor: *mut MbcCompressor,
+    channels: *const *mut f32,
+    num_channels: u32,
+    num_samples: u32,
+) {
+    let Some(compressor) = (unsafe { compressor.as_mut() }) else {
+        return;
+    };
+    if channels.is_null() || num_samples == 0 {
+        return;
+    }
+
+    let num_channels = (num_channels as usize).min(compressor.compressors.len());
+    for channel in 0..num_channels {
+        // SAFETY: the caller guarantees `num_channels` pointers to disjoint buffers of
+        // `num_samples` samples
+        let samples = unsafe { *channels.add(channel) };
+        if !samples.is_null() {
+            let samples = unsafe { std::slice::from_raw_parts_mut(samples, num_samples as usize) };
+            compressor.process_channel(channel, samples);
+        }
+    }
+}
+
+/// フィルターとエンベロープの状態をクリアする。再生位置を変えたときなどに呼ぶ
+///
+/// # Safety
+///
+/// `compressor` は NULL か、[`mbc_create()`] が返してまだ解放していないポインター
+#[no_mangle]
+pub unsafe extern "C" fn mbc_reset(compressor: *mut MbcCompressor) {
+    if let Some(compressor) = unsafe { compressor.as_mut() } {
+        compressor.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        assert!(mbc_create(0.0, 2).is_null());
+        assert!(mbc_create(f32::NAN, 2).is_null());
+        assert!(mbc_create(48000.0, 0).is_null());
+
+        let compressor = mbc_create(48000.0, 2);
+        assert!(!compressor.is_null());
+        unsafe {
+            assert_eq!(mbc_set_param(compressor, MBC_PARAM_XOVER_LO_MID, 150.0), 0);
+            assert_eq!(mbc_set_param(compressor, MBC_PARAM_COUNT, 0.0), -1);
+            assert_eq!(
+                mbc_set_param(compressor, MBC_PARAM_FIRST_BAND, f32::NAN),
+                -1
+            );
+            assert_eq!(
+                mbc_set_param(ptr::null_mut(), MBC_PARAM_FIRST_BAND, 0.0),
+                -1
+            );
+            mbc_process(compressor, ptr::null(), 2, 64);
+            mbc_destroy(compressor);
+            mbc_destroy(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn compresses_loud_input() {
+        const SAMPLE_RATE: f32 = 48000.0;
+        let sine: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * 1000.0 * i as f32 / SAMPLE_RATE).sin())
+            .collect();
+        let mut left = sine.clone();
+        let mut right = sine;
+
+        let compressor = mbc_create(SAMPLE_RATE, 2);
+        unsafe {
+            let mid_ratio = MBC_PARAM_FIRST_BAND + MBC_BAND_PARAM_COUNT + MBC_BAND_RATIO;
+            assert_eq!(mbc_set_param(compressor, mid_ratio, 10.0), 0);
+            let channels = [left.as_mut_ptr(), right.as_mut_ptr()];
+            mbc_process(compressor, channels.as_ptr(), 2, left.len() as u32);
+            mbc_destroy(compressor);
+        }
+
+        // 1 kHz は Mid のバンドに入り、-10 dB のスレッショルドから 10:1 で圧縮される
+        let settled = left.len() / 2;
+        let peak = left[settled..]
+            .iter()
+            .fold(0.0f32, |peak, x| peak.max(x.abs()));
+        assert!(peak < 0.5, "peak {peak}");
+        assert_eq!(left, right);
+    }
+}
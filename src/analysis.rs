@@ -0,0 +1,213 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::util;
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+
+use crate::compression::DetectorMode;
+use crate::metering::{GainReductionStats, GONIOMETER_POINTS};
+use crate::processor::NUM_BANDS;
+use crate::spectrum::SPECTRUM_BINS;
+use crate::triple_buffer::{triple_buffer, TripleBufferInput, TripleBufferOutput};
+
+/// ピークメーターのチャンネル数（L/R）。モノラルでは両方に同じ値を入れ、3 チャンネル目以降は R に含める
+pub const METER_CHANNELS: usize = 2;
+
+/// `process()` から GUI に送るメーターと解析結果の 1 フレーム分
+#[derive(Debug, Clone)]
+pub struct AnalysisFrame {
+    /// バンド分割前の L/R の入力レベル（振幅、メーターの応答特性を適用済み）
+    pub input_peak: [f32; METER_CHANNELS],
+    /// 合成後の L/R の出力レベル（振幅、メーターの応答特性を適用済み）
+    pub output_peak: [f32; METER_CHANNELS],
+    /// 4x オーバーサンプリングで検出した出力のトゥルーピーク（振幅）
+    pub output_true_peak: f32,
+    /// 入力、出力、トゥルーピークそれぞれのピークホールド（振幅）
+    pub input_peak_hold: f32,
+    pub output_peak_hold: f32,
+    pub output_true_peak_hold: f32,
+    /// 出力の RMS（振幅）
+    pub output_rms: f32,
+
+    /// 最後にリセットしてから 0 dBFS を超えたサンプル数。0 より大きければクリップ表示を点灯する
+    pub input_clipped_samples: u64,
+    pub output_clipped_samples: u64,
+
+    /// 出力の L/R 相関係数 (-1..1)。モノラルのときは常に 1
+    pub output_correlation: f32,
+    /// ゴニオメーターに表示する出力の L/R（間引き済み、古い順）。モノラルでは L と R が同じ値になる
+    pub output_goniometer: [[f32; 2]; GONIOMETER_POINTS],
+
+    /// 各バンドのコンプレッサーに入る信号のピーク（振幅、減衰処理済み）
+    pub band_input_peak: [f32; NUM_BANDS],
+    /// 入力全体のエネルギーのうち各バンドに入っている割合 (%)。無音のときは全て 0
+    pub band_energy_percent: [f32; NUM_BANDS],
+    /// バンドごとのゲインリダクション (dB、0 以下)
+    pub gain_reduction_db: [f32; NUM_BANDS],
+    /// バンドごとのゲインリダクションのピークホールド (dB、0 以下)。ホールド時間は出力のピークホールドと共通
+    pub gain_reduction_hold_db: [f32; NUM_BANDS],
+    /// バンドごとのゲインリダクション統計（直近 10 秒）
+    pub gain_reduction_stats: [GainReductionStats; NUM_BANDS],
+
+    /// Gain Match で出力に掛けているトリム (dB)。無効なときは 0
+    pub gain_match_trim_db: f32,
+    /// Auto Makeup で出力に掛けているゲイン (dB)。無効なときは 0
+    pub auto_makeup_db: f32,
+
+    /// BS.1770 ラウドネス (LUFS)
+    pub momentary_lufs: f32,
+    pub short_term_lufs: f32,
+    pub integrated_lufs: f32,
+
+    /// プロセッサーのサンプルレート（オーバーサンプリング前）
+    pub sample_rate: f32,
+
+    /// `process()` の DSP 負荷 (%)
+    pub dsp_load_percent: f32,
+}
+
+impl Default for AnalysisFrame {
+    fn default() -> Self {
+        Self {
+            input_peak: [0.0; METER_CHANNELS],
+            output_peak: [0.0; METER_CHANNELS],
+            output_true_peak: 0.0,
+            input_peak_hold: 0.0,
+            output_peak_hold: 0.0,
+            output_true_peak_hold: 0.0,
+            output_rms: 0.0,
+
+            input_clipped_samples: 0,
+            output_clipped_samples: 0,
+
+            output_correlation: 0.0,
+            output_goniometer: [[0.0; 2]; GONIOMETER_POINTS],
+
+            band_input_peak: [0.0; NUM_BANDS],
+            band_energy_percent: [0.0; NUM_BANDS],
+            gain_reduction_db: [0.0; NUM_BANDS],
+            gain_reduction_hold_db: [0.0; NUM_BANDS],
+            gain_reduction_stats: [GainReductionStats::default(); NUM_BANDS],
+
+            gain_match_trim_db: 0.0,
+            auto_makeup_db: 0.0,
+
+            momentary_lufs: util::MINUS_INFINITY_DB,
+            short_term_lufs: util::MINUS_INFINITY_DB,
+            integrated_lufs: util::MINUS_INFINITY_DB,
+
+            sample_rate: 44100.0,
+
+            dsp_load_percent: 0.0,
+        }
+    }
+}
+
+/// バックグラウンドスレッドから GUI に送る入力と出力のスペクトル。
+///
+/// `Vec` は生成時に確保したものを使い回すので、フレームの書き込みでアロケーションは起きない。
+#[derive(Debug, Clone)]
+pub struct SpectrumFrame {
+    /// スペクトル (dB)。インデックスは FFT のビン
+    pub input_db: Vec<f32>,
+    pub output_db: Vec<f32>,
+    /// ビンを周波数に変換するためのサンプルレート
+    pub sample_rate: f32,
+}
+
+impl Default for SpectrumFrame {
+    fn default() -> Self {
+        Self {
+            input_db: vec![util::MINUS_INFINITY_DB; SPECTRUM_BINS],
+            output_db: vec![util::MINUS_INFINITY_DB; SPECTRUM_BINS],
+            sample_rate: 44100.0,
+        }
+    }
+}
+
+/// GUI からプロセッサーへのリセット要求。GUI がフラグを立て、次の `process()` がフラグを下ろしてリセットする
+#[derive(Debug, Default)]
+pub struct ResetRequests {
+    /// インテグレーテッドラウドネスの積算
+    pub loudness: AtomicBool,
+    /// クリップ表示とクリップしたサンプル数
+    pub clip_indicators: AtomicBool,
+    /// ピークホールド
+    pub peak_holds: AtomicBool,
+}
+
+/// オートスレッショルドの学習で測った、1 バンドのコンプレッサーに入る信号のレベル
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandLevels {
+    /// 学習した区間全体の RMS (dB)
+    pub rms_db: f32,
+    /// 学習した区間の最大ピーク (dB)
+    pub peak_db: f32,
+}
+
+impl BandLevels {
+    /// これより RMS が小さいバンドは無音とみなし、スレッショルドを変えない
+    const SILENCE_DB: f32 = -80.0;
+
+    /// ピークと RMS の差 (dB)
+    pub fn crest_db(&self) -> f32 {
+        self.peak_db - self.rms_db
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.rms_db < Self::SILENCE_DB
+    }
+
+    /// `detector` で検出したときのおおよそのレベル (dB)。ピーク検出はトランジェントで RMS より高い
+    /// レベルを見るので、クレストファクターの半分だけ上に置く
+    pub fn detected_level_db(&self, detector: DetectorMode) -> f32 {
+        match detector {
+            DetectorMode::Peak | DetectorMode::Analytic => self.rms_db + self.crest_db() / 2.0,
+            DetectorMode::Rms => self.rms_db,
+        }
+    }
+}
+
+/// GUI、プロセッサー、バックグラウンドタスクで共有する、オートスレッショルドの学習の状態
+#[derive(Debug, Default)]
+pub struct ThresholdLearn {
+    /// GUI が立て、次の `process()` が下ろして学習を始める
+    pub start: AtomicBool,
+    /// 学習中か。プロセッサーが立て、集計を終えたバックグラウンドタスクが下ろす
+    pub active: AtomicBool,
+    /// 最後に学習したバンドごとのレベル。エディターが取り出してスレッショルドに反映する
+    pub result: Mutex<Option<[BandLevels; NUM_BANDS]>>,
+}
+
+/// バックグラウンドタスクが計算した出力のラウドネス (LUFS)。プロセッサーが解析フレームに写す
+#[derive(Debug)]
+pub struct LoudnessReadings {
+    pub momentary: AtomicF32,
+    pub short_term: AtomicF32,
+    pub integrated: AtomicF32,
+}
+
+impl Default for LoudnessReadings {
+    fn default() -> Self {
+        Self {
+            momentary: AtomicF32::new(util::MINUS_INFINITY_DB),
+            short_term: AtomicF32::new(util::MINUS_INFINITY_DB),
+            integrated: AtomicF32::new(util::MINUS_INFINITY_DB),
+        }
+    }
+}
+
+pub type AnalysisInput = TripleBufferInput<AnalysisFrame>;
+pub type AnalysisOutput = TripleBufferOutput<AnalysisFrame>;
+
+pub type SpectrumInput = TripleBufferInput<SpectrumFrame>;
+pub type SpectrumOutput = TripleBufferOutput<SpectrumFrame>;
+
+/// 解析チャンネルを作る。書き込み側はプロセッサー、読み出し側はエディターが持つ
+pub fn analysis_channel() -> (AnalysisInput, AnalysisOutput) {
+    triple_buffer(&AnalysisFrame::default())
+}
+
+/// スペクトルのチャンネルを作る。書き込み側はバックグラウンドの解析、読み出し側はエディターが持つ
+pub fn spectrum_channel() -> (SpectrumInput, SpectrumOutput) {
+    triple_buffer(&SpectrumFrame::default())
+}
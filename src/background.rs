@@ -0,0 +1,265 @@
+//! `process()` から切り離して、nih-plug のバックグラウンドスレッドで行う解析処理。
+//!
+//! オーディオスレッドはサンプルを [`SampleProducer`] に積んでタスクを投げるだけで、
+//! FFT やラウドネスのゲーティング、オートスレッショルドの集計などの重い処理はここで行う。
+
+use nih_plug::prelude::util;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::analysis::{BandLevels, LoudnessReadings, SpectrumInput, ThresholdLearn};
+use crate::loudness::LoudnessIntegrator;
+use crate::processor::NUM_BANDS;
+use crate::sample_queue::{sample_queue, SampleConsumer, SampleProducer};
+use crate::spectrum::SpectrumAnalyzer;
+
+/// スペクトル解析用のキューの容量。48 kHz で約 0.7 秒分
+const SPECTRUM_QUEUE_CAPACITY: usize = 1 << 15;
+/// オートスレッショルドの学習用のキューの容量。タスクはバッファごとに投げるので、48 kHz で約 0.3 秒分あれば
+/// 足りる
+const BAND_LEVEL_QUEUE_CAPACITY: usize = 1 << 14;
+/// ラウドネスの 100 ms ステップのキューの容量。約 6 秒分
+const LOUDNESS_QUEUE_CAPACITY: usize = 1 << 6;
+
+/// `Plugin::BackgroundTask` として使うタスク
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundTask {
+    /// キューに溜まったサンプルでスペクトルを更新し、GUI に送る
+    AnalyzeSpectrum { sample_rate: f32 },
+    /// スペクトルの平滑化状態と、キューに溜まったサンプルを捨てる
+    ResetSpectrum,
+    /// オートスレッショルドの集計をやり直す
+    StartThresholdLearn,
+    /// キューに溜まったバンドのレベルを集計する。`finish` が true なら結果を GUI に渡して学習を終える
+    AnalyzeBandLevels { finish: bool },
+    /// キューに溜まったラウドネスのステップを積算し、ラウドネス値を更新する
+    AnalyzeLoudness,
+    /// インテグレーテッドラウドネスの積算をやり直す
+    ResetIntegratedLoudness,
+}
+
+/// スペクトル解析用に送る 1 サンプルフレーム分の値（全チャンネルの平均）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpectrumSample {
+    /// バンド分割前の入力
+    pub input: f32,
+    /// 合成後の出力
+    pub output: f32,
+}
+
+/// オートスレッショルドの学習用に送る 1 サンプルフレーム分の、各バンドのコンプレッサーに入る信号のレベル
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BandLevelSample {
+    /// 全チャンネル、全オーバーサンプルの二乗平均
+    pub mean_square: [f32; NUM_BANDS],
+    /// 全チャンネル、全オーバーサンプルのピーク（振幅）
+    pub peak: [f32; NUM_BANDS],
+}
+
+/// スペクトル解析用のキューを作る。書き込み側はプロセッサー、読み出し側は [`SpectrumWorker`] が持つ
+pub fn spectrum_queue() -> (SampleProducer<SpectrumSample>, SampleConsumer<SpectrumSample>) {
+    sample_queue(SPECTRUM_QUEUE_CAPACITY)
+}
+
+/// バックグラウンドスレッドで入力と出力のスペクトルを計算する
+pub struct SpectrumWorker {
+    samples: SampleConsumer<SpectrumSample>,
+    input: SpectrumAnalyzer,
+    output: SpectrumAnalyzer,
+    /// 計算したスペクトルを GUI に送るトリプルバッファ
+    frames: SpectrumInput,
+}
+
+impl SpectrumWorker {
+    pub fn new(samples: SampleConsumer<SpectrumSample>, frames: SpectrumInput) -> Self {
+        Self {
+            samples,
+            input: SpectrumAnalyzer::new(),
+            output: SpectrumAnalyzer::new(),
+            frames,
+        }
+    }
+
+    pub fn run(&mut self, task: BackgroundTask) {
+        match task {
+            BackgroundTask::AnalyzeSpectrum { sample_rate } => self.analyze(sample_rate),
+            BackgroundTask::ResetSpectrum => self.reset(),
+            // ほかのワーカーのタスク
+            BackgroundTask::StartThresholdLearn
+            | BackgroundTask::AnalyzeBandLevels { .. }
+            | BackgroundTask::AnalyzeLoudness
+            | BackgroundTask::ResetIntegratedLoudness => (),
+        }
+    }
+
+    fn analyze(&mut self, sample_rate: f32) {
+        let mut received = false;
+        while let Some(sample) = self.samples.pop() {
+            self.input.push(sample.input);
+            self.output.push(sample.output);
+            received = true;
+        }
+        if !received {
+            return;
+        }
+
+        let frame = self.frames.input_buffer();
+        frame.input_db.copy_from_slice(self.input.magnitudes_db());
+        frame.output_db.copy_from_slice(self.output.magnitudes_db());
+        frame.sample_rate = sample_rate;
+        self.frames.publish();
+    }
+
+    fn reset(&mut self) {
+        while self.samples.pop().is_some() {}
+        self.input.reset();
+        self.output.reset();
+    }
+}
+
+/// オートスレッショルドの学習用のキューを作る。書き込み側はプロセッサー、読み出し側は [`BandLevelWorker`]
+/// が持つ
+pub fn band_level_queue() -> (SampleProducer<BandLevelSample>, SampleConsumer<BandLevelSample>) {
+    sample_queue(BAND_LEVEL_QUEUE_CAPACITY)
+}
+
+/// バックグラウンドスレッドで、学習中に積まれたバンドのレベルを集計する
+pub struct BandLevelWorker {
+    samples: SampleConsumer<BandLevelSample>,
+    /// 二乗平均の合計。数秒分を足すので f64 で持つ
+    square_sum: [f64; NUM_BANDS],
+    peak: [f32; NUM_BANDS],
+    count: u64,
+    /// 集計した結果を置く、GUI との共有状態
+    learn: Arc<ThresholdLearn>,
+}
+
+impl BandLevelWorker {
+    pub fn new(samples: SampleConsumer<BandLevelSample>, learn: Arc<ThresholdLearn>) -> Self {
+        Self {
+            samples,
+            square_sum: [0.0; NUM_BANDS],
+            peak: [0.0; NUM_BANDS],
+            count: 0,
+            learn,
+        }
+    }
+
+    pub fn run(&mut self, task: BackgroundTask) {
+        match task {
+            BackgroundTask::StartThresholdLearn => self.reset(),
+            BackgroundTask::AnalyzeBandLevels { finish } => {
+                self.accumulate();
+                if finish {
+                    self.finish();
+                }
+            }
+            // ほかのワーカーのタスク
+            BackgroundTask::AnalyzeSpectrum { .. }
+            | BackgroundTask::ResetSpectrum
+            | BackgroundTask::AnalyzeLoudness
+            | BackgroundTask::ResetIntegratedLoudness => (),
+        }
+    }
+
+    fn accumulate(&mut self) {
+        while let Some(sample) = self.samples.pop() {
+            for ((sum, peak), (mean_square, sample_peak)) in self
+                .square_sum
+                .iter_mut()
+                .zip(self.peak.iter_mut())
+                .zip(sample.mean_square.into_iter().zip(sample.peak))
+            {
+                *sum += mean_square as f64;
+                *peak = peak.max(sample_peak);
+            }
+            self.count += 1;
+        }
+    }
+
+    fn finish(&mut self) {
+        let count = self.count.max(1) as f64;
+        let levels: [BandLevels; NUM_BANDS] = std::array::from_fn(|band| BandLevels {
+            rms_db: util::gain_to_db((self.square_sum[band] / count).sqrt() as f32),
+            peak_db: util::gain_to_db(self.peak[band]),
+        });
+        if let Ok(mut result) = self.learn.result.lock() {
+            *result = Some(levels);
+        }
+        self.learn.active.store(false, Ordering::Relaxed);
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.square_sum = [0.0; NUM_BANDS];
+        self.peak = [0.0; NUM_BANDS];
+        self.count = 0;
+    }
+}
+
+/// ラウドネスのキューを作る。書き込み側はプロセッサー、読み出し側は [`LoudnessWorker`] が持つ。
+/// 値は 100 ms ステップごとの K 特性の平均二乗値（全チャンネル合計）
+pub fn loudness_queue() -> (SampleProducer<f64>, SampleConsumer<f64>) {
+    sample_queue(LOUDNESS_QUEUE_CAPACITY)
+}
+
+/// バックグラウンドスレッドで BS.1770 のゲーティングと積算を行い、ラウドネス値をプロセッサーに返す
+pub struct LoudnessWorker {
+    steps: SampleConsumer<f64>,
+    integrator: LoudnessIntegrator,
+    /// 計算したラウドネス値を置く、プロセッサーとの共有状態
+    readings: Arc<LoudnessReadings>,
+}
+
+impl LoudnessWorker {
+    pub fn new(steps: SampleConsumer<f64>, readings: Arc<LoudnessReadings>) -> Self {
+        Self {
+            steps,
+            integrator: LoudnessIntegrator::new(),
+            readings,
+        }
+    }
+
+    pub fn run(&mut self, task: BackgroundTask) {
+        match task {
+            BackgroundTask::AnalyzeLoudness => self.accumulate(),
+            // リセットより前に積まれたステップはモーメンタリーとショートタームには使う
+            BackgroundTask::ResetIntegratedLoudness => {
+                self.accumulate();
+                self.integrator.reset_integrated();
+            }
+            // ほかのワーカーのタスク
+            BackgroundTask::AnalyzeSpectrum { .. }
+            | BackgroundTask::ResetSpectrum
+            | BackgroundTask::StartThresholdLearn
+            | BackgroundTask::AnalyzeBandLevels { .. } => return,
+        }
+        self.publish();
+    }
+
+    /// キューに溜まったステップと全ての測定値を捨てる
+    pub fn reset(&mut self) {
+        while self.steps.pop().is_some() {}
+        self.integrator.reset();
+        self.publish();
+    }
+
+    fn accumulate(&mut self) {
+        while let Some(mean_square) = self.steps.pop() {
+            self.integrator.push_step(mean_square);
+        }
+    }
+
+    fn publish(&self) {
+        let readings = &self.readings;
+        readings
+            .momentary
+            .store(self.integrator.momentary(), Ordering::Relaxed);
+        readings
+            .short_term
+            .store(self.integrator.short_term(), Ordering::Relaxed);
+        readings
+            .integrated
+            .store(self.integrator.integrated(), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,288 @@
+//! 1 バンド分の処理。
+//!
+//! クロスオーバーで分割した後の 1 バンドについて、全チャンネルのコンプレッサー（検出器とゲインコンピューター）
+//! と検出器の K 特性フィルター、コンプレッサーの設定、ミュートやバイパスのフェード、バンドごとのメーターを
+//! [`BandProcessor`] にまとめる。チャンネルごとの状態は種類ごとにチャンネル順の配列に並べ、内側のループで
+//! [`BandChannel`] として借りる。バンド分割のフィルターは全バンドを SIMD のレーンに並べて一度に処理するので、
+//! [`crate::crossover::Crossover`] に残す。
+
+use nih_plug::prelude::{Smoother, SmoothingStyle};
+
+use crate::compression::{scaled_ratio, CompressorSettings, DetectorMode, SingleBandCompressor};
+use crate::loudness::KWeightingFilter;
+use crate::metering::{PeakHold, RmsMeter};
+use crate::morph::{BandSnapshot, SmoothedValues, Snapshot};
+use crate::params::BandParams;
+use crate::precision::Precision;
+
+/// バンドのミュート、ソロ、バイパスを切り替えるときのフェードの長さ
+const BAND_FADE_MS: f32 = 20.0;
+
+/// 1 バンド分のアタック、リリース、ホールドの係数と、それを計算したときのパラメーターの値。
+/// `exp()` を毎バッファ計算しないように、値が変わったときだけ計算し直す
+#[derive(Clone, Copy, Default)]
+struct EnvelopeCoefficients {
+    /// 係数の計算に使ったアタック、リリース、ホールド (ms)、全バンド共通の倍率、サンプルレート
+    params: [f32; 6],
+    attack_coef: f64,
+    release_coef: f64,
+    hold_samples: u32,
+}
+
+impl EnvelopeCoefficients {
+    /// `band` と全体の `values` の値が前回から変わっていれば、`sample_rate` で係数を計算し直す
+    fn update(&mut self, band: &BandSnapshot, values: &Snapshot, sample_rate: f32) {
+        let params = [
+            band.attack,
+            band.release,
+            band.hold,
+            values.attack_scale,
+            values.release_scale,
+            sample_rate,
+        ];
+        if params == self.params {
+            return;
+        }
+
+        // 全バンド共通の倍率をかけてから係数にする
+        let attack = (band.attack * values.attack_scale / 1000.0).max(0.0001);
+        let release = (band.release * values.release_scale / 1000.0).max(0.0001);
+        let hold = band.hold / 1000.0;
+
+        self.params = params;
+        self.attack_coef = (-1.0 / (f64::from(attack) * f64::from(sample_rate))).exp();
+        self.release_coef = (-1.0 / (f64::from(release) * f64::from(sample_rate))).exp();
+        self.hold_samples = (hold * sample_rate).round() as u32;
+    }
+}
+
+/// 1 バンドの 1 チャンネル分の状態を [`BandProcessor`] から借りたもの
+pub struct BandChannel<'a> {
+    compressor: &'a mut SingleBandCompressor,
+    /// [`Precision::Double`] のときに使うコンプレッサー
+    compressor64: &'a mut SingleBandCompressor<f64>,
+    /// K-Weighted Detection が有効なときに検出器の前段で使う K 特性フィルター
+    detector_k_filter: &'a mut KWeightingFilter,
+}
+
+impl BandChannel<'_> {
+    /// `input` の検出器のレベル。`k_weighted` のときは K 特性をかけた信号を測る
+    pub fn detect(&mut self, input: f32, settings: &CompressorSettings, k_weighted: bool) -> f32 {
+        let detector = if k_weighted {
+            self.detector_k_filter.process_sample(input)
+        } else {
+            input
+        };
+        self.compressor.detect(detector, settings)
+    }
+
+    /// [`Self::detect()`] の倍精度版。K 特性はレベルを測るだけなので f32 でかける
+    pub fn detect64(&mut self, input: f64, settings: &CompressorSettings, k_weighted: bool) -> f64 {
+        let detector = if k_weighted {
+            f64::from(self.detector_k_filter.process_sample(input as f32))
+        } else {
+            input
+        };
+        self.compressor64.detect(detector, settings)
+    }
+
+    /// 検出器のレベル `level` で `input` を圧縮する
+    pub fn compress(&mut self, input: f32, level: f32, settings: &CompressorSettings) -> f32 {
+        self.compressor.apply(input, level, settings)
+    }
+
+    /// [`Self::compress()`] の倍精度版
+    pub fn compress64(&mut self, input: f64, level: f64, settings: &CompressorSettings) -> f64 {
+        self.compressor64.apply(input, level, settings)
+    }
+
+    /// `precision` の経路のコンプレッサーの、現在のゲインリダクション量 (dB、0 以下)
+    pub fn gain_reduction_db(&self, precision: Precision) -> f32 {
+        match precision {
+            Precision::Single => self.compressor.gain_reduction_db(),
+            Precision::Double => self.compressor64.gain_reduction_db(),
+        }
+    }
+}
+
+/// 1 バンド分の処理の状態
+pub struct BandProcessor {
+    /// このバッファのコンプレッサーの設定。スムージングする値はサンプルフレームごとに
+    /// [`Self::frame_settings()`] で差し替える
+    settings: CompressorSettings,
+    envelope_coefficients: EnvelopeCoefficients,
+    // per-channel compressors and detector filters, indexed by channel. actual sizes are set
+    // in `initialize`
+    compressors: Vec<SingleBandCompressor>,
+    compressors64: Vec<SingleBandCompressor<f64>>,
+    detector_k_filters: Vec<KWeightingFilter>,
+    // ミュートとソロを反映した音量と、コンプレッサーをかける割合（0 でバイパス）
+    gain: Smoother<f32>,
+    compression: Smoother<f32>,
+
+    /// コンプレッサーに入る信号（分割後、ゲイン適用前）のピークメーター
+    pub input_peak_meter: f32,
+    /// 入力の短時間平均エネルギー。全体に対する割合を GUI に表示する
+    pub energy: RmsMeter,
+    /// ゲインリダクションのピークホールド（深さを正の dB で保持する）
+    pub gain_reduction_hold: PeakHold,
+}
+
+impl BandProcessor {
+    /// [`Self::update_settings()`] を呼ぶまでは圧縮しない
+    pub fn new() -> Self {
+        Self {
+            settings: CompressorSettings {
+                threshold_db: 0.0,
+                ratio: 1.0,
+                knee_db: 0.0,
+                range_db: 0.0,
+                attack_coef: 0.0,
+                release_coef: 0.0,
+                hold_samples: 0,
+                detector: DetectorMode::Peak,
+                rms_coef: 0.0,
+                fast_math: false,
+                makeup_db: 0.0,
+                mix: 0.0,
+            },
+            envelope_coefficients: EnvelopeCoefficients::default(),
+            compressors: Vec::new(),
+            compressors64: Vec::new(),
+            detector_k_filters: Vec::new(),
+            gain: Smoother::new(SmoothingStyle::Linear(BAND_FADE_MS)),
+            compression: Smoother::new(SmoothingStyle::Linear(BAND_FADE_MS)),
+            input_peak_meter: 0.0,
+            energy: RmsMeter::new(),
+            gain_reduction_hold: PeakHold::new(),
+        }
+    }
+
+    /// チャンネルごとの状態を `num_channels` 分作り直す。確保するので `initialize()` から呼ぶこと
+    pub fn initialize(&mut self, num_channels: usize, processing_rate: f32) {
+        self.compressors.clear();
+        self.compressors
+            .resize(num_channels, SingleBandCompressor::new());
+        self.compressors64.clear();
+        self.compressors64
+            .resize(num_channels, SingleBandCompressor::new());
+        self.detector_k_filters.clear();
+        self.detector_k_filters
+            .resize(num_channels, KWeightingFilter::new(processing_rate));
+    }
+
+    /// 内部のサンプルレートが変わったときに、検出器の K 特性フィルターを設計し直す
+    pub fn set_processing_rate(&mut self, processing_rate: f32) {
+        for filter in self.detector_k_filters.iter_mut() {
+            filter.set_sample_rate(processing_rate);
+            filter.reset();
+        }
+    }
+
+    /// `precision` の経路のコンプレッサーの状態をクリアする
+    pub fn reset_compressors(&mut self, precision: Precision) {
+        match precision {
+            Precision::Single => self.compressors.iter_mut().for_each(|c| c.reset()),
+            Precision::Double => self.compressors64.iter_mut().for_each(|c| c.reset()),
+        }
+    }
+
+    /// 係数はそのままで、コンプレッサーとフィルター、メーターに溜まった状態をクリアし、フェードを
+    /// `fade_target` に合わせる
+    pub fn reset(&mut self, fade_target: (f32, f32)) {
+        self.compressors.iter_mut().for_each(|c| c.reset());
+        self.compressors64.iter_mut().for_each(|c| c.reset());
+        self.detector_k_filters.iter_mut().for_each(|f| f.reset());
+        self.reset_fades(fade_target);
+        self.input_peak_meter = 0.0;
+        self.energy.reset();
+        self.gain_reduction_hold.reset();
+    }
+
+    /// 音量とコンプレッサーをかける割合のフェードを、フェードせずに `(gain, compression)` にする
+    pub fn reset_fades(&mut self, (gain, compression): (f32, f32)) {
+        self.gain.reset(gain);
+        self.compression.reset(compression);
+    }
+
+    /// 音量とコンプレッサーをかける割合を `(gain, compression)` に向けてフェードさせる
+    pub fn set_fade_target(&mut self, sample_rate: f32, (gain, compression): (f32, f32)) {
+        self.gain.set_target(sample_rate, gain);
+        self.compression.set_target(sample_rate, compression);
+    }
+
+    /// 次のサンプルフレームの音量とコンプレッサーをかける割合
+    pub fn next_fade(&mut self) -> (f32, f32) {
+        (self.gain.next(), self.compression.next())
+    }
+
+    /// このバッファのコンプレッサーの設定
+    pub fn settings(&self) -> &CompressorSettings {
+        &self.settings
+    }
+
+    /// このバッファのパラメーターの値 `band` と `values` からコンプレッサーの設定を作る。
+    /// エンベロープの係数はパラメーターかサンプルレートが変わったときだけ計算し直す
+    pub fn update_settings(
+        &mut self,
+        params: &BandParams,
+        band: &BandSnapshot,
+        values: &Snapshot,
+        sample_rate: f32,
+        rms_coef: f64,
+        fast_math: bool,
+    ) {
+        self.envelope_coefficients.update(band, values, sample_rate);
+        let envelope = &self.envelope_coefficients;
+        self.settings = CompressorSettings {
+            threshold_db: band.threshold,
+            ratio: band.ratio.max(1.0),
+            knee_db: band.knee,
+            range_db: band.range,
+            attack_coef: envelope.attack_coef,
+            release_coef: envelope.release_coef,
+            hold_samples: envelope.hold_samples,
+            detector: params.detector.value(),
+            rms_coef,
+            fast_math,
+            makeup_db: band.makeup,
+            mix: band.mix / 100.0,
+        };
+    }
+
+    /// このバッファの設定に、スムージングした値 `smoothed` のうちバンド `band` の値を反映した設定。
+    /// Amount はレシオの傾きとレンジを弱める
+    pub fn frame_settings(&self, band: usize, smoothed: &SmoothedValues) -> CompressorSettings {
+        let amount = smoothed.amount / 100.0;
+        CompressorSettings {
+            threshold_db: smoothed.threshold[band],
+            makeup_db: smoothed.makeup[band],
+            mix: smoothed.mix[band] / 100.0,
+            ratio: scaled_ratio(smoothed.ratio[band].max(1.0), amount),
+            knee_db: smoothed.knee[band],
+            range_db: smoothed.range[band] * amount,
+            ..self.settings
+        }
+    }
+
+    /// 全チャンネルの状態をチャンネル順に借りる。長さは `initialize()` で確保したチャンネル数
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = BandChannel<'_>> {
+        self.compressors
+            .iter_mut()
+            .zip(self.compressors64.iter_mut())
+            .zip(self.detector_k_filters.iter_mut())
+            .map(
+                |((compressor, compressor64), detector_k_filter)| BandChannel {
+                    compressor,
+                    compressor64,
+                    detector_k_filter,
+                },
+            )
+    }
+}
+
+impl Default for BandProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
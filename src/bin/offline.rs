@@ -0,0 +1,510 @@
+//! WAV ファイルをプラグインに通して書き出すオフライン処理のコマンド。`offline` フィーチャーでビルドする。
+//!
+//! ```shell
+//! multiband_compressor_offline [--preset <preset.json>] <input.wav> <output.wav>
+//! ```
+//!
+//! DAW で使うのと全く同じ処理にするため、プラグインはライブラリの CLAP のエントリーポイントからこの
+//! プロセスの中に読み込み、オフラインレンダリングとして処理する。プリセットはエディターから書き出した
+//! JSON のファイルで、省略したパラメーターは既定値のまま。出力はレイテンシーの分だけ前にずらして入力と
+//! 揃え、入力と同じサンプルレートとチャンネル数の 32 ビット浮動小数点の WAV で書き出す。
+
+use clap_sys::audio_buffer::clap_audio_buffer;
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::events::{
+    clap_event_header, clap_event_param_value, clap_input_events, clap_output_events,
+    CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_PARAM_VALUE,
+};
+use clap_sys::ext::audio_ports_config::{
+    clap_audio_ports_config, clap_plugin_audio_ports_config, CLAP_EXT_AUDIO_PORTS_CONFIG,
+};
+use clap_sys::ext::latency::{clap_plugin_latency, CLAP_EXT_LATENCY};
+use clap_sys::ext::params::{clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS};
+use clap_sys::ext::render::{clap_plugin_render, CLAP_EXT_RENDER, CLAP_RENDER_OFFLINE};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::host::clap_host;
+use clap_sys::plugin::clap_plugin;
+use clap_sys::process::{clap_process, CLAP_PROCESS_ERROR};
+use clap_sys::version::CLAP_VERSION;
+use std::ffi::{c_char, c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::{fs, mem, process, ptr};
+
+/// 1 回の `process()` で処理するサンプル数
+const BLOCK_SIZE: usize = 1024;
+
+const USAGE: &str = "usage: multiband_compressor_offline [--preset <preset.json>] <input.wav> \
+                     <output.wav>";
+
+struct Args {
+    preset: Option<PathBuf>,
+    input: PathBuf,
+    output: PathBuf,
+}
+
+fn main() {
+    if let Err(err) = parse_args().and_then(|args| run(&args)) {
+        eprintln!("error: {err}");
+        process::exit(1);
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut preset = None;
+    let mut paths = Vec::new();
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--preset") => {
+                preset = Some(PathBuf::from(args.next().ok_or(USAGE)?));
+            }
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    match <[PathBuf; 2]>::try_from(paths) {
+        Ok([input, output]) => Ok(Args {
+            preset,
+            input,
+            output,
+        }),
+        Err(_) => Err(USAGE.to_owned()),
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let param_values = match &args.preset {
+        Some(path) => {
+            let json = fs::read_to_string(path)
+                .map_err(|err| format!("could not read '{}': {err}", path.display()))?;
+            multiband_compressor::param_map_values(&json)
+                .map_err(|err| format!("could not load '{}': {err}", path.display()))?
+        }
+        None => Vec::new(),
+    };
+
+    let (sample_rate, inputs) = read_wav(&args.input)?;
+    let num_frames = inputs.first().map_or(0, Vec::len);
+
+    let mut plugin = HostedPlugin::new(inputs.len(), sample_rate, &param_values)?;
+    // レイテンシーの分だけ無音を足して処理し、出力の先頭をその分だけ捨てる
+    let latency = plugin.latency();
+    let mut outputs = vec![Vec::with_capacity(num_frames + latency); inputs.len()];
+    let mut input_block = vec![[0.0; BLOCK_SIZE]; inputs.len()];
+    let mut output_block = vec![[0.0; BLOCK_SIZE]; inputs.len()];
+    for start in (0..num_frames + latency).step_by(BLOCK_SIZE) {
+        let len = BLOCK_SIZE.min(num_frames + latency - start);
+        for (block, input) in input_block.iter_mut().zip(&inputs) {
+            let available = input.get(start..).unwrap_or_default();
+            let copied = available.len().min(len);
+            block[..copied].copy_from_slice(&available[..copied]);
+            block[copied..len].fill(0.0);
+        }
+
+        plugin.process(&mut input_block, &mut output_block, len)?;
+        for (output, block) in outputs.iter_mut().zip(&output_block) {
+            output.extend_from_slice(&block[..len]);
+        }
+    }
+    for output in &mut outputs {
+        output.drain(..latency);
+    }
+
+    write_wav(&args.output, sample_rate, &outputs)
+}
+
+/// `path` の WAV ファイルを読み、サンプルレートとチャンネルごとに分けたサンプルを返す
+fn read_wav(path: &Path) -> Result<(u32, Vec<Vec<f32>>), String> {
+    let error = |err: hound::Error| format!("could not read '{}': {err}", path.display());
+    let mut reader = hound::WavReader::open(path).map_err(error)?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .map_err(error)?;
+
+    let num_channels = usize::from(spec.channels);
+    let channels = (0..num_channels)
+        .map(|channel| {
+            samples
+                .iter()
+                .skip(channel)
+                .step_by(num_channels)
+                .copied()
+                .collect()
+        })
+        .collect();
+    Ok((spec.sample_rate, channels))
+}
+
+/// チャンネルごとのサンプルを 32 ビット浮動小数点の WAV ファイルとして `path` に書き出す
+fn write_wav(path: &Path, sample_rate: u32, channels: &[Vec<f32>]) -> Result<(), String> {
+    let error = |err: hound::Error| format!("could not write '{}': {err}", path.display());
+    let spec = hound::WavSpec {
+        channels: channels.len() as u16,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(error)?;
+    let num_frames = channels.first().map_or(0, Vec::len);
+    for frame in 0..num_frames {
+        for channel in channels {
+            writer.write_sample(channel[frame]).map_err(error)?;
+        }
+    }
+    writer.finalize().map_err(error)
+}
+
+/// このプロセスに読み込んで、処理中の状態にしたプラグイン
+struct HostedPlugin {
+    entry: &'static clap_plugin_entry,
+    plugin: *const clap_plugin,
+    /// プラグインが `host` を参照し続けるので、プラグインより長く保持する
+    _host: Box<clap_host>,
+    active: bool,
+    processing: bool,
+    steady_time: i64,
+}
+
+impl HostedPlugin {
+    /// `num_channels` チャンネルの入出力で、`param_values` のパラメーターを設定したプラグインを作る。
+    /// `param_values` は [`multiband_compressor::param_map_values()`] の値で、空なら全て既定値
+    fn new(
+        num_channels: usize,
+        sample_rate: u32,
+        param_values: &[(String, Option<f32>)],
+    ) -> Result<Self, String> {
+        // SAFETY: `nih_export_clap!()` defines `clap_entry` with clap-sys's `clap_plugin_entry`
+        //         layout, and it is never written to
+        let entry = unsafe {
+            &*ptr::addr_of!(multiband_compressor::clap_entry).cast::<clap_plugin_entry>()
+        };
+        let host = Box::new(clap_host {
+            clap_version: CLAP_VERSION,
+            host_data: ptr::null_mut(),
+            name: c"multiband_compressor_offline".as_ptr(),
+            vendor: c"".as_ptr(),
+            url: c"".as_ptr(),
+            version: c"".as_ptr(),
+            get_extension: Some(host_get_extension),
+            request_restart: Some(host_request),
+            request_process: Some(host_request),
+            request_callback: Some(host_request),
+        });
+
+        // SAFETY: the entry point and the plugin are used from this thread only, in the order the
+        //         CLAP specification requires
+        unsafe {
+            if !(entry.init.ok_or("missing entry init")?)(c"".as_ptr()) {
+                return Err("could not initialize the plugin library".to_owned());
+            }
+            let factory =
+                (entry.get_factory.ok_or("missing get_factory")?)(CLAP_PLUGIN_FACTORY_ID.as_ptr())
+                    .cast::<clap_plugin_factory>();
+            let Some(factory) = factory.as_ref() else {
+                (entry.deinit.ok_or("missing entry deinit")?)();
+                return Err("the plugin library has no plugin factory".to_owned());
+            };
+            let descriptor =
+                (factory.get_plugin_descriptor.ok_or("missing descriptor")?)(factory, 0);
+            let plugin = match descriptor.as_ref() {
+                Some(descriptor) => (factory.create_plugin.ok_or("missing create_plugin")?)(
+                    factory,
+                    &*host,
+                    descriptor.id,
+                ),
+                None => ptr::null(),
+            };
+            if plugin.is_null() {
+                (entry.deinit.ok_or("missing entry deinit")?)();
+                return Err("could not create the plugin".to_owned());
+            }
+
+            let mut hosted = Self {
+                entry,
+                plugin,
+                _host: host,
+                active: false,
+                processing: false,
+                steady_time: 0,
+            };
+            if !(hosted.vtable().init.ok_or("missing init")?)(plugin) {
+                return Err("could not initialize the plugin".to_owned());
+            }
+            hosted.select_audio_ports_config(num_channels)?;
+            if let Some(render) = hosted.extension::<clap_plugin_render>(CLAP_EXT_RENDER) {
+                (render.set.ok_or("missing render set")?)(plugin, CLAP_RENDER_OFFLINE);
+            }
+            hosted.set_params(param_values)?;
+
+            if !(hosted.vtable().activate.ok_or("missing activate")?)(
+                plugin,
+                f64::from(sample_rate),
+                1,
+                BLOCK_SIZE as u32,
+            ) {
+                return Err(format!("the plugin does not support {sample_rate} Hz"));
+            }
+            hosted.active = true;
+            if !(hosted
+                .vtable()
+                .start_processing
+                .ok_or("missing start_processing")?)(plugin)
+            {
+                return Err("could not start processing".to_owned());
+            }
+            hosted.processing = true;
+
+            Ok(hosted)
+        }
+    }
+
+    /// プラグインが報告するレイテンシー（サンプル）
+    fn latency(&self) -> usize {
+        // SAFETY: the plugin is active, and the extension is called from the main thread
+        unsafe {
+            self.extension::<clap_plugin_latency>(CLAP_EXT_LATENCY)
+                .and_then(|latency| latency.get)
+                .map_or(0, |get| get(self.plugin) as usize)
+        }
+    }
+
+    /// `inputs` の先頭の `len` サンプルを処理して `outputs` に書き込む
+    fn process(
+        &mut self,
+        inputs: &mut [[f32; BLOCK_SIZE]],
+        outputs: &mut [[f32; BLOCK_SIZE]],
+        len: usize,
+    ) -> Result<(), String> {
+        let mut input_ptrs: Vec<*mut f32> = inputs.iter_mut().map(|c| c.as_mut_ptr()).collect();
+        let mut output_ptrs: Vec<*mut f32> = outputs.iter_mut().map(|c| c.as_mut_ptr()).collect();
+        let input_buffer = audio_buffer(&mut input_ptrs);
+        let mut output_buffer = audio_buffer(&mut output_ptrs);
+        let in_events = ParamEvents::default();
+        let in_events = in_events.input_events();
+        let out_events = clap_output_events {
+            ctx: ptr::null_mut(),
+            try_push: Some(ignore_output_event),
+        };
+        let process = clap_process {
+            steady_time: self.steady_time,
+            frames_count: len as u32,
+            transport: ptr::null(),
+            audio_inputs: &input_buffer,
+            audio_outputs: &mut output_buffer,
+            audio_inputs_count: 1,
+            audio_outputs_count: 1,
+            in_events: &in_events,
+            out_events: &out_events,
+        };
+        self.steady_time += len as i64;
+
+        // SAFETY: the buffers hold `len` samples for every channel of the selected configuration,
+        //         and they outlive the call
+        let status =
+            unsafe { (self.vtable().process.ok_or("missing process")?)(self.plugin, &process) };
+        if status == CLAP_PROCESS_ERROR {
+            return Err("the plugin failed to process the audio".to_owned());
+        }
+
+        Ok(())
+    }
+
+    fn vtable(&self) -> &clap_plugin {
+        // SAFETY: `plugin` stays valid until it is destroyed in `drop()`
+        unsafe { &*self.plugin }
+    }
+
+    /// プラグインの拡張。なければ `None`
+    unsafe fn extension<T>(&self, id: &CStr) -> Option<&T> {
+        let get_extension = self.vtable().get_extension?;
+        get_extension(self.plugin, id.as_ptr()).cast::<T>().as_ref()
+    }
+
+    /// メイン入出力が `num_channels` チャンネルで、バンド出力のない構成を選ぶ
+    unsafe fn select_audio_ports_config(&self, num_channels: usize) -> Result<(), String> {
+        let configs = self
+            .extension::<clap_plugin_audio_ports_config>(CLAP_EXT_AUDIO_PORTS_CONFIG)
+            .ok_or("the plugin has no audio port configurations")?;
+        let count = (configs.count.ok_or("missing audio ports config count")?)(self.plugin);
+        for index in 0..count {
+            let mut config: clap_audio_ports_config = mem::zeroed();
+            if !(configs.get.ok_or("missing audio ports config get")?)(
+                self.plugin,
+                index,
+                &mut config,
+            ) {
+                continue;
+            }
+            if config.has_main_input
+                && config.main_input_channel_count as usize == num_channels
+                && config.output_port_count == 1
+            {
+                if (configs.select.ok_or("missing audio ports config select")?)(
+                    self.plugin,
+                    config.id,
+                ) {
+                    return Ok(());
+                }
+                break;
+            }
+        }
+
+        Err(format!(
+            "the plugin does not support {num_channels} channel audio"
+        ))
+    }
+
+    /// `param_values` のパラメーターを設定する。CLAP のパラメーターは [`Params::param_map()`] と
+    /// 同じ順に並ぶので、名前が一致することを確かめてから同じ位置の値を送る
+    ///
+    /// [`Params::param_map()`]: nih_plug::prelude::Params::param_map
+    unsafe fn set_params(&self, param_values: &[(String, Option<f32>)]) -> Result<(), String> {
+        if param_values.is_empty() {
+            return Ok(());
+        }
+
+        let params = self
+            .extension::<clap_plugin_params>(CLAP_EXT_PARAMS)
+            .ok_or("the plugin has no parameters")?;
+        let count = (params.count.ok_or("missing params count")?)(self.plugin);
+        if count as usize != param_values.len() {
+            return Err("the plugin's parameters do not match the preset".to_owned());
+        }
+
+        let mut events = ParamEvents::default();
+        for (index, (name, normalized)) in (0..count).zip(param_values) {
+            let mut info: clap_param_info = mem::zeroed();
+            if !(params.get_info.ok_or("missing params get_info")?)(self.plugin, index, &mut info)
+                || CStr::from_ptr(info.name.as_ptr()).to_string_lossy() != name.as_str()
+            {
+                return Err("the plugin's parameters do not match the preset".to_owned());
+            }
+            if let Some(normalized) = normalized {
+                // 段階のあるパラメーターは 0 から段階数までの値で送る
+                let value =
+                    info.min_value + f64::from(*normalized) * (info.max_value - info.min_value);
+                events.push(info.id, value);
+            }
+        }
+
+        let in_events = events.input_events();
+        let out_events = clap_output_events {
+            ctx: ptr::null_mut(),
+            try_push: Some(ignore_output_event),
+        };
+        (params.flush.ok_or("missing params flush")?)(self.plugin, &in_events, &out_events);
+
+        Ok(())
+    }
+}
+
+impl Drop for HostedPlugin {
+    fn drop(&mut self) {
+        // SAFETY: the plugin is torn down in the reverse order it was set up, and nothing uses it
+        //         after this
+        unsafe {
+            let vtable = *self.vtable();
+            if let (true, Some(stop_processing)) = (self.processing, vtable.stop_processing) {
+                stop_processing(self.plugin);
+            }
+            if let (true, Some(deactivate)) = (self.active, vtable.deactivate) {
+                deactivate(self.plugin);
+            }
+            if let Some(destroy) = vtable.destroy {
+                destroy(self.plugin);
+            }
+            if let Some(deinit) = self.entry.deinit {
+                deinit();
+            }
+        }
+    }
+}
+
+fn audio_buffer(channels: &mut [*mut f32]) -> clap_audio_buffer {
+    clap_audio_buffer {
+        data32: channels.as_mut_ptr(),
+        data64: ptr::null_mut(),
+        channel_count: channels.len() as u32,
+        latency: 0,
+        constant_mask: 0,
+    }
+}
+
+/// プラグインに送るパラメーターの変更
+#[derive(Default)]
+struct ParamEvents(Vec<clap_event_param_value>);
+
+impl ParamEvents {
+    fn push(&mut self, param_id: u32, value: f64) {
+        self.0.push(clap_event_param_value {
+            header: clap_event_header {
+                size: mem::size_of::<clap_event_param_value>() as u32,
+                time: 0,
+                space_id: CLAP_CORE_EVENT_SPACE_ID,
+                type_: CLAP_EVENT_PARAM_VALUE,
+                flags: 0,
+            },
+            param_id,
+            cookie: ptr::null_mut(),
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            value,
+        });
+    }
+
+    /// プラグインに渡すイベントのリスト。`self` より長く使ってはいけない
+    fn input_events(&self) -> clap_input_events {
+        clap_input_events {
+            ctx: ptr::from_ref(self).cast_mut().cast(),
+            size: Some(param_events_size),
+            get: Some(param_events_get),
+        }
+    }
+}
+
+unsafe extern "C" fn param_events_size(list: *const clap_input_events) -> u32 {
+    let events = &*(*list).ctx.cast::<ParamEvents>();
+    events.0.len() as u32
+}
+
+unsafe extern "C" fn param_events_get(
+    list: *const clap_input_events,
+    index: u32,
+) -> *const clap_event_header {
+    let events = &*(*list).ctx.cast::<ParamEvents>();
+    events
+        .0
+        .get(index as usize)
+        .map_or(ptr::null(), |event| &event.header)
+}
+
+unsafe extern "C" fn ignore_output_event(
+    _list: *const clap_output_events,
+    _event: *const clap_event_header,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const clap_host,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    ptr::null()
+}
+
+unsafe extern "C" fn host_request(_host: *const clap_host) {}
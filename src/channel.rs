@@ -0,0 +1,366 @@
+//! 1 チャンネル分のブロックの処理。
+//!
+//! アップサンプリングからバンド分割、圧縮、合成、ダウンサンプリングまではチャンネルごとに独立しているので、
+//! 1 チャンネル分の状態を [`ChannelBlock`] として借りてまとめて処理する。通常はチャンネル順に処理し、
+//! `parallel` フィーチャーを有効にしたビルドのオフラインレンダリングでは [`process_parallel()`] で
+//! チャンネルを並列に処理する。チャンネルをリンクしたときは検出器のレベルを揃えるために
+//! [`process_linked()`] で全チャンネルをサンプルごとに揃えて処理する。ゲインマッチやメーターのように
+//! 全チャンネルをまとめて扱う出力段は [`crate::processor`] に残す。
+
+use crate::band::BandChannel;
+use crate::compression::CompressorSettings;
+use crate::crossover::{band_lanes, CrossoverChannel};
+use crate::oversampling::{Oversampler, MAX_OVERSAMPLING_FACTOR};
+use crate::precision::{Crossover64Channel, Precision};
+use crate::processor::{MAX_BLOCK_SIZE, NUM_BANDS};
+use crate::simd::F32x4;
+use crate::surround::MAX_CHANNELS;
+
+/// 1 サンプルフレーム分の、フレームごとに変わる値。ブロックの始めにまとめて計算する
+#[derive(Clone, Copy)]
+pub struct FrameValues {
+    /// 各バンドのコンプレッサーの設定
+    pub band_settings: [CompressorSettings; NUM_BANDS],
+    /// ミュートとソロを反映した各バンドの音量と、コンプレッサーをかける割合。
+    /// [`band_lanes()`] でレーンに並べる
+    pub band_gains: F32x4,
+    pub band_compression: F32x4,
+    /// 入力トリムと出力ゲイン（振幅）
+    pub input_gain: f32,
+    pub output_gain: f32,
+    /// バイパスの混合比
+    pub bypass_mix: f32,
+    /// MIDI のダッキングで各バンドを下げている量 (dB)。`band_gains` にはかけてあり、
+    /// ゲインリダクションの表示に加える
+    pub duck_db: [f32; NUM_BANDS],
+}
+
+impl FrameValues {
+    /// `band_settings` のほかは 0 の値
+    pub fn new(band_settings: [CompressorSettings; NUM_BANDS]) -> Self {
+        Self {
+            band_settings,
+            band_gains: F32x4::default(),
+            band_compression: F32x4::default(),
+            input_gain: 0.0,
+            output_gain: 0.0,
+            bypass_mix: 0.0,
+            duck_db: [0.0; NUM_BANDS],
+        }
+    }
+}
+
+/// 1 サンプルフレーム分の、全チャンネルをまとめた測定値
+#[derive(Clone, Copy, Default)]
+pub struct FrameStats {
+    /// 入力の合計
+    pub input_sum: f32,
+    /// 最も深かったゲインリダクション
+    pub gain_reduction: [f32; NUM_BANDS],
+    /// 各バンドの二乗和とピーク（全オーバーサンプル）
+    pub band_energy: [f32; NUM_BANDS],
+    pub band_peak: [f32; NUM_BANDS],
+}
+
+impl FrameStats {
+    /// 別に測った `other` をこの測定値に加える
+    #[cfg(feature = "parallel")]
+    pub fn merge(&mut self, other: &Self) {
+        self.input_sum += other.input_sum;
+        for band in 0..NUM_BANDS {
+            self.gain_reduction[band] = self.gain_reduction[band].min(other.gain_reduction[band]);
+            self.band_energy[band] += other.band_energy[band];
+            self.band_peak[band] = self.band_peak[band].max(other.band_peak[band]);
+        }
+    }
+}
+
+/// 全チャンネルで共通の、ブロックの処理の設定
+#[derive(Clone, Copy)]
+pub struct ChannelOptions {
+    pub oversampling_factor: usize,
+    pub precision: Precision,
+    pub k_weighted_detection: bool,
+}
+
+/// バンド出力があるときに、1 チャンネル分の各バンドを個別にダウンサンプリングする状態と書き込み先
+pub struct BandOutputs<'a> {
+    pub downsamplers: &'a mut [Oversampler; NUM_BANDS],
+    pub scratch: &'a mut [[f32; MAX_OVERSAMPLING_FACTOR]; NUM_BANDS],
+    pub outputs: &'a mut [[f32; MAX_BLOCK_SIZE]; NUM_BANDS],
+}
+
+/// 1 チャンネル分の、ブロックの処理に使う状態を借りたもの
+pub struct ChannelBlock<'a> {
+    /// ホストからの入力（NaN や無限大は 0 にしたもの）と、出力ゲインなどをかける前の出力の書き込み先
+    pub inputs: &'a [f32; MAX_BLOCK_SIZE],
+    pub outputs: &'a mut [f32; MAX_BLOCK_SIZE],
+    pub oversampler: &'a mut Oversampler,
+    pub scratch: &'a mut [f32; MAX_OVERSAMPLING_FACTOR],
+    pub crossover: CrossoverChannel<'a>,
+    pub crossover64: Crossover64Channel<'a>,
+    pub bands: [BandChannel<'a>; NUM_BANDS],
+    pub band_outputs: Option<BandOutputs<'a>>,
+}
+
+/// 1 サンプル分のバンド分割の結果
+#[derive(Clone, Copy, Default)]
+struct Split {
+    /// 各バンドを [`band_lanes()`] でレーンに並べたもの
+    lanes: F32x4,
+    /// 倍精度のときの、f64 のままの各バンド
+    bands64: [f64; NUM_BANDS],
+}
+
+impl ChannelBlock<'_> {
+    /// `frames` の長さの分だけ、入力をバンド分割、圧縮、合成して `outputs` に書き込み、`stats` に
+    /// バンドのレベルとゲインリダクションを加える
+    pub fn process(
+        &mut self,
+        frames: &[FrameValues],
+        stats: &mut [FrameStats],
+        options: &ChannelOptions,
+    ) {
+        for (i, (frame, frame_stats)) in frames.iter().zip(stats.iter_mut()).enumerate() {
+            self.upsample(i, frame);
+            for os_idx in 0..options.oversampling_factor {
+                let split = self.split(os_idx, frame_stats, options);
+                let levels = self.detect(&split, frame, options);
+                self.compress(os_idx, &split, &levels, frame, frame_stats, options);
+            }
+            self.downsample(i);
+        }
+    }
+
+    /// `i` 番目の入力に入力トリムをかけて `scratch` にアップサンプリングする（Off のときは 1 サンプルが
+    /// そのまま入る）。入力のメーターとゲインマッチは、トリム前のホストからの入力を基準にする
+    fn upsample(&mut self, i: usize, frame: &FrameValues) {
+        self.oversampler
+            .upsample(self.inputs[i] * frame.input_gain, self.scratch);
+    }
+
+    /// `scratch` の `os_idx` 番目のサンプルをバンドに分割し、`stats` にバンドのレベルを加える。
+    /// 倍精度のときは f64 のまま圧縮に渡し、メーターには f32 に丸めた値を使う
+    fn split(&mut self, os_idx: usize, stats: &mut FrameStats, options: &ChannelOptions) -> Split {
+        let input = self.scratch[os_idx];
+        let split = if options.precision == Precision::Double {
+            let bands64 = self.crossover64.split(f64::from(input));
+            Split {
+                lanes: band_lanes(bands64.map(|band| band as f32)),
+                bands64,
+            }
+        } else {
+            Split {
+                lanes: self.crossover.split_lanes(input),
+                bands64: [0.0; NUM_BANDS],
+            }
+        };
+
+        let [low, mid, high, _] = split.lanes.to_array();
+        for ((peak, energy), band) in stats
+            .band_peak
+            .iter_mut()
+            .zip(stats.band_energy.iter_mut())
+            .zip([low, mid, high])
+        {
+            *peak = peak.max(band.abs());
+            *energy += band * band;
+        }
+
+        split
+    }
+
+    /// 分割した各バンドの、コンプレッサーの検出器のレベル。倍精度でなければ f32 の値をそのまま広げる
+    fn detect(
+        &mut self,
+        split: &Split,
+        frame: &FrameValues,
+        options: &ChannelOptions,
+    ) -> [f64; NUM_BANDS] {
+        let mut levels = [0.0; NUM_BANDS];
+        let [low, mid, high, _] = split.lanes.to_array();
+        let bands = self
+            .bands
+            .iter_mut()
+            .zip(&frame.band_settings)
+            .zip([low, mid, high].into_iter().zip(split.bands64));
+        for (level, ((channel, settings), (band, band64))) in levels.iter_mut().zip(bands) {
+            *level = if options.precision == Precision::Double {
+                channel.detect64(band64, settings, options.k_weighted_detection)
+            } else {
+                f64::from(channel.detect(band, settings, options.k_weighted_detection))
+            };
+        }
+
+        levels
+    }
+
+    /// 各バンドを検出器のレベル `levels` で圧縮して合成し、`scratch` の `os_idx` 番目に書き戻す。
+    /// バイパスしたバンドは分割しただけの信号を使い、ミュートしたバンドは出力に含めない。切り替えはクリックが
+    /// 出ないようにフェードする。バイパス中もコンプレッサーは動かしておき、ゲインリダクションの表示と解除後の
+    /// 動作を保つ
+    fn compress(
+        &mut self,
+        os_idx: usize,
+        split: &Split,
+        levels: &[f64; NUM_BANDS],
+        frame: &FrameValues,
+        stats: &mut FrameStats,
+        options: &ChannelOptions,
+    ) {
+        // バンドの合成までは f64 で行う
+        let (band_out, band_sum) = if options.precision == Precision::Double {
+            let mut band_out = [0.0_f64; NUM_BANDS];
+            let bands = split
+                .bands64
+                .iter()
+                .zip(levels)
+                .zip(self.bands.iter_mut())
+                .zip(&frame.band_settings)
+                .zip(frame.band_gains.to_array())
+                .zip(frame.band_compression.to_array());
+            for (output, (((((&input, &level), channel), settings), gain), compression)) in
+                band_out.iter_mut().zip(bands)
+            {
+                let compressed = channel.compress64(input, level, settings);
+                *output = f64::from(gain) * (input + f64::from(compression) * (compressed - input));
+            }
+
+            let band_sum: f64 = band_out.iter().sum();
+            (
+                band_lanes(band_out.map(|band| band as f32)),
+                band_sum as f32,
+            )
+        } else {
+            let [low, mid, high, _] = split.lanes.to_array();
+            let mut compressed = [low, mid, high];
+            for (((output, &level), channel), settings) in compressed
+                .iter_mut()
+                .zip(levels)
+                .zip(self.bands.iter_mut())
+                .zip(&frame.band_settings)
+            {
+                *output = channel.compress(*output, level as f32, settings);
+            }
+            let compressed = band_lanes(compressed);
+            let band_out = frame.band_gains
+                * (split.lanes + frame.band_compression * (compressed - split.lanes));
+            (band_out, band_out.sum())
+        };
+        for (reduction, channel) in stats.gain_reduction.iter_mut().zip(self.bands.iter()) {
+            *reduction = reduction.min(channel.gain_reduction_db(options.precision));
+        }
+
+        if let Some(band_outputs) = self.band_outputs.as_mut() {
+            for (band_scratch, output) in band_outputs.scratch.iter_mut().zip(band_out.to_array()) {
+                band_scratch[os_idx] = output;
+            }
+        }
+
+        self.scratch[os_idx] = band_sum;
+    }
+
+    /// `scratch` をダウンサンプリングして `outputs` の `i` 番目に書き込む。
+    /// バンド出力がある場合は各バンドを個別にダウンサンプリングし、その和をメイン出力にする。
+    /// こうするとメイン出力とバンド出力のレイテンシーが必ず一致する。
+    fn downsample(&mut self, i: usize) {
+        self.outputs[i] = match self.band_outputs.as_mut() {
+            Some(band_outputs) => {
+                let mut out = 0.0;
+                for ((downsampler, band), band_output) in band_outputs
+                    .downsamplers
+                    .iter_mut()
+                    .zip(band_outputs.scratch.iter())
+                    .zip(band_outputs.outputs.iter_mut())
+                {
+                    let band_out = downsampler.downsample(band);
+                    band_output[i] = band_out;
+                    out += band_out;
+                }
+                out
+            }
+            None => self.oversampler.downsample(self.scratch),
+        };
+    }
+}
+
+/// `channels` を、`groups` が同じチャンネル同士で各バンドの検出器のレベルを揃えながら、サンプルごとに
+/// 足並みを揃えて処理する。`groups` は [`crate::surround::ChannelLayout::link_groups()`] の値で、
+/// `channels` のうち `None` のチャンネルは処理しない
+pub fn process_linked(
+    channels: &mut [Option<ChannelBlock<'_>>; MAX_CHANNELS],
+    groups: &[usize; MAX_CHANNELS],
+    frames: &[FrameValues],
+    stats: &mut [FrameStats],
+    options: &ChannelOptions,
+) {
+    let mut splits = [Split::default(); MAX_CHANNELS];
+    let mut levels = [[0.0; NUM_BANDS]; MAX_CHANNELS];
+    for (i, (frame, frame_stats)) in frames.iter().zip(stats.iter_mut()).enumerate() {
+        for channel in channels.iter_mut().flatten() {
+            channel.upsample(i, frame);
+        }
+
+        for os_idx in 0..options.oversampling_factor {
+            for ((channel, split), levels) in channels
+                .iter_mut()
+                .flatten()
+                .zip(splits.iter_mut())
+                .zip(levels.iter_mut())
+            {
+                *split = channel.split(os_idx, frame_stats, options);
+                *levels = channel.detect(split, frame, options);
+            }
+
+            // 各グループのレベルは、グループの中で最も大きいチャンネルのレベル
+            let mut group_levels = [[0.0_f64; NUM_BANDS]; MAX_CHANNELS];
+            for (levels, &group) in levels.iter().zip(groups) {
+                if let Some(group_levels) = group_levels.get_mut(group) {
+                    for (group_level, &level) in group_levels.iter_mut().zip(levels) {
+                        *group_level = group_level.max(level);
+                    }
+                }
+            }
+
+            for ((channel, split), &group) in
+                channels.iter_mut().flatten().zip(splits.iter()).zip(groups)
+            {
+                let Some(levels) = group_levels.get(group) else {
+                    continue;
+                };
+                channel.compress(os_idx, split, levels, frame, frame_stats, options);
+            }
+        }
+
+        for channel in channels.iter_mut().flatten() {
+            channel.downsample(i);
+        }
+    }
+}
+
+/// `channels` を並列に処理する。各チャンネルの測定値は `channel_stats` に分けて測ってから、チャンネル順に
+/// `stats` に加える。`channel_stats` は `channels` 以上の長さが必要で、足りない分のチャンネルは処理しない。
+/// スレッドプールへのジョブの受け渡しでメモリを確保するので、オフラインレンダリングでだけ使うこと
+#[cfg(feature = "parallel")]
+pub fn process_parallel(
+    channels: &mut [ChannelBlock<'_>],
+    frames: &[FrameValues],
+    stats: &mut [FrameStats],
+    channel_stats: &mut [[FrameStats; MAX_BLOCK_SIZE]],
+    options: &ChannelOptions,
+) {
+    use rayon::prelude::*;
+
+    channels
+        .par_iter_mut()
+        .zip(channel_stats.par_iter_mut())
+        .for_each(|(channel, channel_stats)| {
+            *channel_stats = [FrameStats::default(); MAX_BLOCK_SIZE];
+            channel.process(frames, channel_stats, options);
+        });
+    for channel_stats in channel_stats.iter().take(channels.len()) {
+        for (frame_stats, channel_frame_stats) in stats.iter_mut().zip(channel_stats) {
+            frame_stats.merge(channel_frame_stats);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! 非正規化数の抑制。
+//!
+//! 無音が続くと、フィルターの状態や RMS の平均が 0 に向かって減衰して非正規化数になる。x86 では非正規化数の
+//! 演算が極端に遅いので、処理のあいだだけ CPU の FTZ (flush-to-zero) と DAZ (denormals-are-zero) を
+//! 有効にして、非正規化数を 0 として扱わせる。
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use std::arch::asm;
+
+/// MXCSR の DAZ と FTZ のビット
+#[cfg(target_arch = "x86_64")]
+const MXCSR_DAZ_FTZ: u32 = (1 << 6) | (1 << 15);
+/// FPCR の FZ のビット。aarch64 では入力と出力の両方の非正規化数が 0 になる
+#[cfg(target_arch = "aarch64")]
+const FPCR_FZ: u64 = 1 << 24;
+
+/// 作ってから drop するまでのあいだ、このスレッドで FTZ と DAZ を有効にする。drop すると元の設定に戻す
+pub struct DenormalGuard {
+    #[cfg(target_arch = "x86_64")]
+    previous_mxcsr: u32,
+    #[cfg(target_arch = "aarch64")]
+    previous_fpcr: u64,
+}
+
+impl DenormalGuard {
+    #[cfg(target_arch = "x86_64")]
+    pub fn enable() -> Self {
+        let mut previous_mxcsr = 0_u32;
+        // SAFETY: MXCSR の読み書きは SSE のある x86_64 では常にできる。変えるのは丸めの扱いだけ
+        unsafe {
+            asm!(
+                "stmxcsr [{}]",
+                in(reg) std::ptr::addr_of_mut!(previous_mxcsr),
+                options(nostack, preserves_flags)
+            );
+            let mxcsr = previous_mxcsr | MXCSR_DAZ_FTZ;
+            asm!(
+                "ldmxcsr [{}]",
+                in(reg) std::ptr::addr_of!(mxcsr),
+                options(nostack, readonly, preserves_flags)
+            );
+        }
+
+        Self { previous_mxcsr }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    pub fn enable() -> Self {
+        let previous_fpcr: u64;
+        // SAFETY: FPCR はユーザーモードから読み書きできる
+        unsafe {
+            asm!("mrs {}, fpcr", out(reg) previous_fpcr, options(nomem, nostack, preserves_flags));
+            asm!(
+                "msr fpcr, {}",
+                in(reg) previous_fpcr | FPCR_FZ,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+
+        Self { previous_fpcr }
+    }
+
+    /// それ以外のアーキテクチャでは何もしない
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn enable() -> Self {
+        Self {}
+    }
+}
+
+impl Drop for DenormalGuard {
+    fn drop(&mut self) {
+        // SAFETY: enable() で読み出した値をそのまま書き戻す
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            asm!(
+                "ldmxcsr [{}]",
+                in(reg) std::ptr::addr_of!(self.previous_mxcsr),
+                options(nostack, readonly, preserves_flags)
+            );
+        }
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            asm!(
+                "msr fpcr, {}",
+                in(reg) self.previous_fpcr,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+    }
+}
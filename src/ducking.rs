@@ -0,0 +1,124 @@
+//! MIDI のノートで各バンドの音量を下げるダッキング。
+//!
+//! ノートを押すとエンベロープがアタックの時間でベロシティの値まで上がり、全てのノートを離すとリリースの
+//! 時間で 0 に戻る。短いノートでもアタックは最後まで進めるので、ドラムパッドで叩いても深さが揃う。
+//! 各バンドはエンベロープにそのバンドの Duck の量をかけた分だけ下げる。
+
+/// MIDI のチャンネル数
+const MIDI_CHANNELS: usize = 16;
+/// これより小さくなったエンベロープは 0 にして、ダッキングしていない状態に戻す
+const SILENT_ENVELOPE: f32 = 1e-6;
+
+/// ノートで動かすダッキングのエンベロープ
+#[derive(Debug, Clone, Default)]
+pub struct Ducker {
+    /// チャンネルごとの、押しているノートのビット
+    held_notes: [u128; MIDI_CHANNELS],
+    /// 最後に押したノートのベロシティ (0..1)。押している間、エンベロープはこの値に向かう
+    velocity: f32,
+    /// ノートを押してから、エンベロープがまだ `velocity` に届いていないか
+    attacking: bool,
+    envelope: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    /// 係数を計算したときのアタック、リリース (ms) とサンプルレート
+    times: [f32; 3],
+}
+
+impl Ducker {
+    /// アタックとリリース (ms) が前回から変わっていれば、`sample_rate` で係数を計算し直す
+    pub fn set_times(&mut self, attack_ms: f32, release_ms: f32, sample_rate: f32) {
+        let times = [attack_ms, release_ms, sample_rate];
+        if times == self.times {
+            return;
+        }
+
+        self.times = times;
+        self.attack_coef = time_coef(attack_ms, sample_rate);
+        self.release_coef = time_coef(release_ms, sample_rate);
+    }
+
+    /// ベロシティ 0 のノートオンはノートオフとして扱う
+    pub fn note_on(&mut self, channel: u8, note: u8, velocity: f32) {
+        if velocity <= 0.0 {
+            self.note_off(channel, note);
+            return;
+        }
+
+        if let Some(held) = self.held_notes.get_mut(usize::from(channel)) {
+            *held |= 1 << (note & 0x7f);
+        }
+        self.velocity = velocity.min(1.0);
+        self.attacking = true;
+    }
+
+    pub fn note_off(&mut self, channel: u8, note: u8) {
+        if let Some(held) = self.held_notes.get_mut(usize::from(channel)) {
+            *held &= !(1 << (note & 0x7f));
+        }
+    }
+
+    /// 全てのノートを離し、エンベロープを 0 に戻す
+    pub fn reset(&mut self) {
+        self.held_notes = [0; MIDI_CHANNELS];
+        self.attacking = false;
+        self.envelope = 0.0;
+    }
+
+    /// エンベロープを 1 サンプル進めた値 (0..1)。ダッキングしていなければちょうど 0
+    pub fn next_envelope(&mut self) -> f32 {
+        let held = self.held_notes.iter().any(|&notes| notes != 0);
+        let (target, coef) = if held || self.attacking {
+            (self.velocity, self.attack_coef)
+        } else {
+            (0.0, self.release_coef)
+        };
+
+        self.envelope = target + coef * (self.envelope - target);
+        // 指数的に近づくので、残り 1% でアタックを終えたとみなす
+        if self.attacking && self.envelope >= target * 0.99 {
+            self.attacking = false;
+        }
+        if target == 0.0 && self.envelope < SILENT_ENVELOPE {
+            self.envelope = 0.0;
+        }
+
+        self.envelope
+    }
+}
+
+/// 時定数 `ms` の 1 次のエンベロープの係数
+fn time_coef(ms: f32, sample_rate: f32) -> f32 {
+    (-1.0 / (ms / 1000.0 * sample_rate).max(1.0)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_note_completes_attack_and_release_returns_to_zero() {
+        let mut ducker = Ducker::default();
+        ducker.set_times(5.0, 50.0, 48000.0);
+
+        // ドラムパッドのように、押してすぐ離してもベロシティの深さまで下げる
+        ducker.note_on(9, 36, 0.5);
+        ducker.next_envelope();
+        ducker.note_off(9, 36);
+        let peak = (0..4800)
+            .map(|_| ducker.next_envelope())
+            .fold(0.0_f32, f32::max);
+        assert!(peak > 0.49 && peak <= 0.5, "peak {peak}");
+
+        // 離したあとはちょうど 0 に戻る
+        for _ in 0..48000 {
+            ducker.next_envelope();
+        }
+        assert_eq!(ducker.next_envelope(), 0.0);
+
+        // ベロシティ 0 のノートオンはノートオフ
+        ducker.note_on(0, 60, 1.0);
+        ducker.note_on(0, 60, 0.0);
+        assert!(ducker.held_notes.iter().all(|&notes| notes == 0));
+    }
+}
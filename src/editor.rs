@@ -1,52 +1,757 @@
-use atomic_float::AtomicF32;
-use nih_plug::prelude::{util, Editor, GuiContext};
+use nih_plug::prelude::{util, Editor, Enum, GuiContext};
+use nih_plug::prelude::{BoolParam, Param, ParamPtr};
 use nih_plug_iced::widgets as nih_widgets;
 use nih_plug_iced::*;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::params::MultibandCompressorParams;
+use crate::analysis::{
+    AnalysisFrame, AnalysisOutput, ResetRequests, SpectrumFrame, SpectrumOutput, ThresholdLearn,
+    METER_CHANNELS,
+};
+use crate::background::BackgroundTask;
+use crate::crossover::{log_frequency_grid, Crossover};
+use crate::gui::EditorShared;
+use crate::metering::{
+    GainReductionHistory, GainReductionStats, MeterBallistics, GAIN_REDUCTION_ACTIVE_DB,
+};
+use crate::midi::MidiCcEvent;
+use crate::morph::Snapshot;
+use crate::params::{BandParams, MultibandCompressorParams};
+use crate::preset_file;
+use crate::presets::{self, FACTORY_PRESETS, INIT_PRESET};
+use crate::processor::NUM_BANDS;
+use crate::sample_queue::SampleConsumer;
 
-pub(crate) fn create(
-    params: Arc<MultibandCompressorParams>,
-    peak_meter: Arc<AtomicF32>,
-    editor_state: Arc<IcedState>,
-) -> Option<Box<dyn Editor>> {
-    create_iced_editor::<MultibandCompressorEditor>(editor_state, (params, peak_meter))
+mod correlation_meter;
+mod focus;
+mod gain_reduction_history_view;
+mod gain_reduction_meter;
+mod goniometer;
+mod knob;
+mod level_meter;
+mod midi_learn;
+mod scale;
+mod slider;
+mod spectrum_view;
+mod text_entry;
+mod theme;
+mod tooltips;
+mod transfer_curve;
+mod undo;
+
+/// メーターと解析結果を読み出す間隔 (30 Hz)。描画のたびではなく、この間隔でだけ読み出す
+const METER_POLL_INTERVAL: Duration = Duration::from_micros(1_000_000 / 30);
+
+/// パラメーターに保存するエディターの状態
+pub(crate) type EditorState = IcedState;
+
+pub(crate) fn default_state() -> Arc<IcedState> {
+    IcedState::from_size(680, 500)
+}
+
+pub(crate) fn create(shared: EditorShared) -> Option<Box<dyn Editor>> {
+    let ui_scale = shared.params.ui_scale.clone();
+    let editor = create_iced_editor::<MultibandCompressorEditor>(
+        shared.params.editor_state.clone(),
+        shared,
+    )?;
+
+    Some(Box::new(scale::ScaledEditor::new(editor, ui_scale)))
+}
+
+/// バンドのゲインリダクションの現在値とピークホールドのテキスト
+fn gain_reduction_text(gain_reduction_db: f32, gain_reduction_hold_db: f32) -> Text {
+    Text::new(format!(
+        "GR {:.1} dB, Peak {:.1} dB",
+        gain_reduction_db, gain_reduction_hold_db
+    ))
+    .size(14)
+    .width(Length::Fill)
+    .horizontal_alignment(alignment::Horizontal::Center)
+}
+
+/// 周波数の表示。1 kHz 以上は kHz で表示する
+fn format_frequency(frequency: f32) -> String {
+    if frequency >= 1000.0 {
+        format!("{:.1} kHz", frequency / 1000.0)
+    } else {
+        format!("{:.0} Hz", frequency)
+    }
+}
+
+/// バンドが受け持つ周波数範囲のテキスト。クロスオーバーを動かすとそのまま追従する
+fn band_range_text(display: BandDisplay) -> Text {
+    let [low, high] = display.frequency_range;
+    Text::new(format!(
+        "{}: {}–{}",
+        display.name,
+        format_frequency(low),
+        format_frequency(high)
+    ))
+    .size(14)
+    .color(display.color)
+}
+
+/// バンドのゲインリダクション統計（直近 10 秒）のテキスト
+fn gain_reduction_stats_text(stats: GainReductionStats) -> Text {
+    Text::new(format!(
+        "Avg {:.1} / Max {:.1} dB, {:.0}% > {:.0} dB",
+        stats.average_db, stats.max_db, stats.active_percent, GAIN_REDUCTION_ACTIVE_DB
+    ))
+    .size(12)
+    .width(Length::Fill)
+    .horizontal_alignment(alignment::Horizontal::Center)
+}
+
+/// ラウドネス表示用のテキスト
+fn loudness_text(label: &str, lufs: f32) -> Text {
+    let value = if lufs <= util::MINUS_INFINITY_DB {
+        String::from("-inf")
+    } else {
+        format!("{:.1}", lufs)
+    };
+
+    Text::new(format!("{} {} LUFS", label, value)).size(14)
+}
+
+/// クリップ表示。クリップしたサンプル数を赤で表示し、クリックでリセットする
+fn clip_indicator(state: &mut button::State, clipped_samples: u64) -> Button<'_, Message> {
+    let text = if clipped_samples > 0 {
+        Text::new(format!("CLIP {}", clipped_samples)).color(Color::from_rgb(0.85, 0.1, 0.1))
+    } else {
+        Text::new("No Clip")
+    };
+
+    Button::new(state, text.size(14)).on_press(Message::ResetClipIndicators)
+}
+
+/// バンドの Solo/Mute/Bypass やプラグイン全体の Bypass のボタン。オンのときは `color` で塗りつぶす
+fn band_toggle_button<'a>(
+    state: &'a mut button::State,
+    label: &str,
+    param: &BoolParam,
+    description: &str,
+    color: Color,
+) -> Element<'a, Message> {
+    tooltips::with_tooltip(
+        Button::new(state, Text::new(label).size(12))
+            .style(theme::ToggleStyle {
+                active: param.value(),
+                color,
+            })
+            .on_press(Message::ToggleParam(param.as_ptr())),
+        param,
+        description,
+    )
+}
+
+/// Solo, Mute, Bypass のボタンを並べた行
+fn band_toggle_row<'a>(
+    states: &'a mut [button::State; 3],
+    [solo, mute, bypass]: [&BoolParam; 3],
+    color: Color,
+) -> Row<'a, Message> {
+    let [solo_state, mute_state, bypass_state] = states;
+
+    Row::new()
+        .spacing(4)
+        .push(band_toggle_button(solo_state, "S", solo, tooltips::SOLO, color))
+        .push(band_toggle_button(mute_state, "M", mute, tooltips::MUTE, color))
+        .push(band_toggle_button(
+            bypass_state,
+            "Bypass",
+            bypass,
+            tooltips::BYPASS,
+            color,
+        ))
+}
+
+/// L/R のメーターを上下に並べたもの
+fn stereo_level_meter(
+    states: &mut [level_meter::State; METER_CHANNELS],
+    levels: [f32; METER_CHANNELS],
+    scale: level_meter::MeterScale,
+) -> Column<'_, Message> {
+    states.iter_mut().zip(["L", "R"]).zip(levels).fold(
+        Column::new().spacing(4),
+        |column, ((state, label), level)| {
+            column.push(
+                Row::new()
+                    .spacing(6)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(label).size(12))
+                    .push(
+                        level_meter::LevelMeter::new(state, util::gain_to_db(level), scale)
+                            .hold_time(Duration::from_millis(600)),
+                    ),
+            )
+        },
+    )
+}
+
+/// ピークホールドの表示。クリックで全てのピークホールドをリセットする
+fn peak_hold_button<'a>(
+    state: &'a mut button::State,
+    label: &str,
+    peak_hold: f32,
+) -> Button<'a, Message> {
+    let value = if peak_hold > 0.0 {
+        format!("{:.1}", util::gain_to_db(peak_hold))
+    } else {
+        String::from("-inf")
+    };
+
+    Button::new(state, Text::new(format!("{} {}", label, value)).size(14))
+        .on_press(Message::ResetPeakHolds)
+}
+
+/// バンドの表示名
+const BAND_NAMES: [&str; NUM_BANDS] = ["Low", "Mid", "High"];
+
+/// 1 バンド分のウィジェットの状態
+#[derive(Default)]
+struct BandWidgetStates {
+    /// Band input meter (post-split, pre-gain)
+    input_meter: level_meter::State,
+    threshold_knob: knob::State,
+    ratio_knob: knob::State,
+    attack_knob: knob::State,
+    release_knob: knob::State,
+    makeup_knob: knob::State,
+    /// Solo, Mute, Bypass
+    toggle_buttons: [button::State; 3],
+    transfer_curve: transfer_curve::State,
+    advanced: AdvancedWidgetStates,
+}
+
+impl BandWidgetStates {
+    /// Tab キーでフォーカスを移す順に並べたノブのフォーカスと、そのパラメーター
+    fn focus_chain(
+        &mut self,
+        band: BandControls<'_>,
+        focused_band_view: bool,
+    ) -> Vec<(&mut focus::Focus, ParamPtr)> {
+        let BandControls { params, advanced } = band;
+        let mut chain = vec![
+            (self.threshold_knob.focus_mut(), params.threshold.as_ptr()),
+            (self.ratio_knob.focus_mut(), params.ratio.as_ptr()),
+        ];
+        // 全バンド表示ではメイクアップがレシオの右に、1 バンド表示ではリリースの右に並ぶ
+        if focused_band_view {
+            chain.push((self.attack_knob.focus_mut(), params.attack.as_ptr()));
+            chain.push((self.release_knob.focus_mut(), params.release.as_ptr()));
+            chain.push((self.makeup_knob.focus_mut(), params.makeup.as_ptr()));
+        } else {
+            chain.push((self.makeup_knob.focus_mut(), params.makeup.as_ptr()));
+            chain.push((self.attack_knob.focus_mut(), params.attack.as_ptr()));
+            chain.push((self.release_knob.focus_mut(), params.release.as_ptr()));
+        }
+        if advanced.load(Ordering::Relaxed) {
+            let advanced = &mut self.advanced;
+            chain.push((advanced.knee_knob.focus_mut(), params.knee.as_ptr()));
+            chain.push((advanced.range_knob.focus_mut(), params.range.as_ptr()));
+            chain.push((advanced.hold_knob.focus_mut(), params.hold.as_ptr()));
+            chain.push((advanced.detector_knob.focus_mut(), params.detector.as_ptr()));
+            chain.push((advanced.mix_knob.focus_mut(), params.mix.as_ptr()));
+            chain.push((advanced.duck_knob.focus_mut(), params.duck.as_ptr()));
+        }
+
+        chain
+    }
+}
+
+/// バンドの詳細設定のウィジェットの状態
+#[derive(Default)]
+struct AdvancedWidgetStates {
+    toggle_button: button::State,
+    knee_knob: knob::State,
+    range_knob: knob::State,
+    hold_knob: knob::State,
+    detector_knob: knob::State,
+    mix_knob: knob::State,
+    duck_knob: knob::State,
+}
+
+/// 1 バンド分のパラメーターと、詳細設定を開いているか
+#[derive(Clone, Copy)]
+struct BandControls<'a> {
+    params: &'a BandParams,
+    advanced: &'a AtomicBool,
+}
+
+impl<'a> BandControls<'a> {
+    /// low, mid, high の順に並べたパラメーター
+    fn all(params: &'a MultibandCompressorParams) -> [Self; NUM_BANDS] {
+        let bands = params.bands();
+        let advanced = params.advanced_bands();
+        std::array::from_fn(|band| BandControls {
+            params: bands[band],
+            advanced: advanced[band],
+        })
+    }
+}
+
+/// バンドの表示に使う測定値と色
+#[derive(Clone, Copy)]
+struct BandDisplay {
+    index: usize,
+    name: &'static str,
+    /// 下端と上端の周波数。両端のバンドはスペクトルの表示範囲で区切る
+    frequency_range: [f32; 2],
+    input_peak: f32,
+    energy_percent: f32,
+    gain_reduction_db: f32,
+    gain_reduction_hold_db: f32,
+    gain_reduction_stats: GainReductionStats,
+    meter_scale: level_meter::MeterScale,
+    /// Solo/Mute/Bypass ボタンの色
+    accent: Color,
+    /// ノブやメーターの色。出力に影響していないバンドでは薄くなる
+    color: Color,
+    title_color: Color,
+}
+
+fn band_knob<'a, P: Param>(
+    state: &'a mut knob::State,
+    param: &'a P,
+    label: &'a str,
+    description: &str,
+    color: Color,
+) -> Element<'a, Message> {
+    tooltips::with_tooltip(
+        knob::Knob::new(state, param)
+            .label(label)
+            .color(color)
+            .map(Message::ParamUpdate),
+        param,
+        description,
+    )
+}
+
+/// バンドの詳細設定。ボタンで開閉し、開いているときだけノブを表示する
+fn advanced_section<'a>(
+    states: &'a mut AdvancedWidgetStates,
+    band: BandControls<'a>,
+    display: BandDisplay,
+) -> Column<'a, Message> {
+    let BandControls { params, advanced } = band;
+    let AdvancedWidgetStates {
+        toggle_button,
+        knee_knob,
+        range_knob,
+        hold_knob,
+        detector_knob,
+        mix_knob,
+        duck_knob,
+    } = states;
+    let expanded = advanced.load(Ordering::Relaxed);
+
+    let column = Column::new().align_items(Alignment::Center).spacing(10).push(
+        Button::new(
+            toggle_button,
+            Text::new(if expanded {
+                "Hide Advanced"
+            } else {
+                "Show Advanced"
+            })
+            .size(12),
+        )
+        .on_press(Message::ToggleAdvanced(display.index)),
+    );
+    if !expanded {
+        return column;
+    }
+
+    column
+        .push(
+            Row::new()
+                .spacing(4)
+                .push(band_knob(
+                    knee_knob,
+                    &params.knee,
+                    "Knee",
+                    tooltips::KNEE,
+                    display.color,
+                ))
+                .push(band_knob(
+                    range_knob,
+                    &params.range,
+                    "Range",
+                    tooltips::RANGE,
+                    display.color,
+                ))
+                .push(band_knob(
+                    hold_knob,
+                    &params.hold,
+                    "Hold",
+                    tooltips::HOLD,
+                    display.color,
+                )),
+        )
+        .push(
+            Row::new()
+                .spacing(4)
+                .push(band_knob(
+                    detector_knob,
+                    &params.detector,
+                    "Detector",
+                    tooltips::DETECTOR,
+                    display.color,
+                ))
+                .push(band_knob(
+                    mix_knob,
+                    &params.mix,
+                    "Mix",
+                    tooltips::MIX,
+                    display.color,
+                ))
+                .push(band_knob(
+                    duck_knob,
+                    &params.duck,
+                    "Duck",
+                    tooltips::DUCK,
+                    display.color,
+                )),
+        )
+}
+
+/// 全バンドを並べて表示するときの 1 バンド分の列
+fn band_column<'a>(
+    states: &'a mut BandWidgetStates,
+    band: BandControls<'a>,
+    display: BandDisplay,
+) -> Column<'a, Message> {
+    let params = band.params;
+    let BandWidgetStates {
+        input_meter,
+        threshold_knob,
+        ratio_knob,
+        attack_knob,
+        release_knob,
+        makeup_knob,
+        toggle_buttons,
+        transfer_curve,
+        advanced,
+    } = states;
+
+    Column::new()
+        .align_items(Alignment::Center)
+        .spacing(10)
+        .width(Length::Fill)
+        .push(
+            Text::new(format!("{} Band", display.name))
+                .font(assets::NOTO_SANS_LIGHT)
+                .size(18)
+                .color(display.title_color)
+                .width(Length::Fill)
+                .horizontal_alignment(alignment::Horizontal::Center),
+        )
+        .push(band_range_text(display))
+        .push(band_toggle_row(
+            toggle_buttons,
+            [&params.solo, &params.mute, &params.bypass],
+            display.accent,
+        ))
+        .push(
+            level_meter::LevelMeter::new(
+                input_meter,
+                util::gain_to_db(display.input_peak),
+                display.meter_scale,
+            )
+            .hold_time(Duration::from_millis(600)),
+        )
+        .push(Text::new(format!("Energy {:.0}%", display.energy_percent)).size(14))
+        .push(
+            Row::new()
+                .spacing(4)
+                .push(band_knob(
+                    threshold_knob,
+                    &params.threshold,
+                    "Threshold",
+                    tooltips::THRESHOLD,
+                    display.color,
+                ))
+                .push(band_knob(
+                    ratio_knob,
+                    &params.ratio,
+                    "Ratio",
+                    tooltips::RATIO,
+                    display.color,
+                ))
+                .push(band_knob(
+                    makeup_knob,
+                    &params.makeup,
+                    "Makeup",
+                    tooltips::MAKEUP,
+                    display.color,
+                )),
+        )
+        .push(
+            Row::new()
+                .spacing(4)
+                .push(band_knob(
+                    attack_knob,
+                    &params.attack,
+                    "Attack",
+                    tooltips::ATTACK,
+                    display.color,
+                ))
+                .push(band_knob(
+                    release_knob,
+                    &params.release,
+                    "Release",
+                    tooltips::RELEASE,
+                    display.color,
+                )),
+        )
+        .push(advanced_section(advanced, band, display))
+        .push(
+            transfer_curve::TransferCurve::new(
+                transfer_curve,
+                &params.threshold,
+                params.ratio,
+                params.makeup.value(),
+                display.color,
+            )
+            .knee(params.knee.value())
+            .range(params.range.value())
+            .map(Message::ParamUpdate),
+        )
+        .push(gain_reduction_meter::GainReductionMeter::new(
+            display.gain_reduction_db,
+            display.gain_reduction_hold_db,
+            display.color,
+        ))
+        .push(gain_reduction_text(
+            display.gain_reduction_db,
+            display.gain_reduction_hold_db,
+        ))
+        .push(gain_reduction_stats_text(display.gain_reduction_stats))
+}
+
+/// 1 バンドだけを表示するときの行。コントロール、特性カーブ、メーターを横に並べて大きく表示する
+fn focused_band_row<'a>(
+    states: &'a mut BandWidgetStates,
+    band: BandControls<'a>,
+    display: BandDisplay,
+) -> Row<'a, Message> {
+    let params = band.params;
+    let BandWidgetStates {
+        input_meter,
+        threshold_knob,
+        ratio_knob,
+        attack_knob,
+        release_knob,
+        makeup_knob,
+        toggle_buttons,
+        transfer_curve,
+        advanced,
+    } = states;
+
+    Row::new()
+        .spacing(20)
+        .align_items(Alignment::Center)
+        .push(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(10)
+                .push(
+                    Text::new(format!("{} Band", display.name))
+                        .font(assets::NOTO_SANS_LIGHT)
+                        .size(18)
+                        .color(display.title_color),
+                )
+                .push(band_range_text(display))
+                .push(band_toggle_row(
+                    toggle_buttons,
+                    [&params.solo, &params.mute, &params.bypass],
+                    display.accent,
+                ))
+                .push(
+                    level_meter::LevelMeter::new(
+                        input_meter,
+                        util::gain_to_db(display.input_peak),
+                        display.meter_scale,
+                    )
+                    .hold_time(Duration::from_millis(600)),
+                )
+                .push(Text::new(format!("Energy {:.0}%", display.energy_percent)).size(14))
+                .push(
+                    Row::new()
+                        .spacing(4)
+                        .push(band_knob(
+                            threshold_knob,
+                            &params.threshold,
+                            "Threshold",
+                            tooltips::THRESHOLD,
+                            display.color,
+                        ))
+                        .push(band_knob(
+                            ratio_knob,
+                            &params.ratio,
+                            "Ratio",
+                            tooltips::RATIO,
+                            display.color,
+                        ))
+                        .push(band_knob(
+                            attack_knob,
+                            &params.attack,
+                            "Attack",
+                            tooltips::ATTACK,
+                            display.color,
+                        ))
+                        .push(band_knob(
+                            release_knob,
+                            &params.release,
+                            "Release",
+                            tooltips::RELEASE,
+                            display.color,
+                        ))
+                        .push(band_knob(
+                            makeup_knob,
+                            &params.makeup,
+                            "Makeup",
+                            tooltips::MAKEUP,
+                            display.color,
+                        )),
+                )
+                .push(advanced_section(advanced, band, display)),
+        )
+        .push(
+            transfer_curve::TransferCurve::new(
+                transfer_curve,
+                &params.threshold,
+                params.ratio,
+                params.makeup.value(),
+                display.color,
+            )
+            .knee(params.knee.value())
+            .range(params.range.value())
+            .size(200)
+            .map(Message::ParamUpdate),
+        )
+        .push(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(10)
+                .push(gain_reduction_meter::GainReductionMeter::new(
+                    display.gain_reduction_db,
+                    display.gain_reduction_hold_db,
+                    display.color,
+                ))
+                .push(gain_reduction_text(
+                    display.gain_reduction_db,
+                    display.gain_reduction_hold_db,
+                ))
+                .push(gain_reduction_stats_text(display.gain_reduction_stats)),
+        )
+}
+
+/// バンドのリンクが有効なときに、1 つのパラメーターの操作から全バンドに広げているジェスチャー
+#[derive(Debug, Clone, Copy)]
+struct LinkedGesture {
+    /// 同じ種類の全バンドのパラメーター
+    params: [ParamPtr; NUM_BANDS],
+    /// ジェスチャーを始めたときの各パラメーターの値
+    start_values: [f32; NUM_BANDS],
+    /// 操作されているパラメーターのインデックス
+    source: usize,
 }
 
 struct MultibandCompressorEditor {
     params: Arc<MultibandCompressorParams>,
     context: Arc<dyn GuiContext>,
 
-    peak_meter: Arc<AtomicF32>,
-
-    // Low band sliders
-    threshold_low_slider_state: nih_widgets::param_slider::State,
-    ratio_low_slider_state: nih_widgets::param_slider::State,
-    attack_low_slider_state: nih_widgets::param_slider::State,
-    release_low_slider_state: nih_widgets::param_slider::State,
-    makeup_low_slider_state: nih_widgets::param_slider::State,
-
-    // Mid band sliders
-    threshold_mid_slider_state: nih_widgets::param_slider::State,
-    ratio_mid_slider_state: nih_widgets::param_slider::State,
-    attack_mid_slider_state: nih_widgets::param_slider::State,
-    release_mid_slider_state: nih_widgets::param_slider::State,
-    makeup_mid_slider_state: nih_widgets::param_slider::State,
-
-    // High band sliders
-    threshold_high_slider_state: nih_widgets::param_slider::State,
-    ratio_high_slider_state: nih_widgets::param_slider::State,
-    attack_high_slider_state: nih_widgets::param_slider::State,
-    release_high_slider_state: nih_widgets::param_slider::State,
-    makeup_high_slider_state: nih_widgets::param_slider::State,
+    /// プロセッサーから送られてくるフレームの読み出し側
+    analysis_output: Arc<Mutex<AnalysisOutput>>,
+    /// 最後に受け取ったフレーム。[`METER_POLL_INTERVAL`] ごとに更新する
+    analysis: AnalysisFrame,
+    /// バックグラウンドの解析から送られてくるスペクトルの読み出し側と、最後に受け取ったスペクトル
+    spectrum_output: Arc<Mutex<SpectrumOutput>>,
+    spectrum: SpectrumFrame,
+    /// 最後にメーターを読み出した時刻
+    last_meter_poll: Instant,
+    reset_requests: Arc<ResetRequests>,
+    gain_reduction_history: Arc<GainReductionHistory>,
+    /// プロセッサーが受け取った CC の読み出し側と、MIDI ラーンの状態
+    midi_cc_events: Arc<Mutex<SampleConsumer<MidiCcEvent>>>,
+    midi_learn: midi_learn::MidiLearn,
+    /// オートスレッショルドの学習の状態と、最後に学習した結果
+    threshold_learn: Arc<ThresholdLearn>,
+    threshold_learn_status: Option<String>,
+    linked_gesture: Option<LinkedGesture>,
+    undo_history: undo::UndoHistory,
+    /// エディターを開いたときの拡大率。変更は次に開いたときに反映される
+    opened_ui_scale: scale::UiScale,
+    /// クロスオーバー特性を計算する周波数グリッドと、その結果 (dB)
+    crossover_response_frequencies: Vec<f32>,
+    crossover_response_db: [Vec<f32>; NUM_BANDS],
+    spectrum_view_state: spectrum_view::State,
+    band_widget_states: [BandWidgetStates; NUM_BANDS],
 
     // Crossover sliders
-    xover_lo_mid_state: nih_widgets::param_slider::State,
-    xover_mid_hi_state: nih_widgets::param_slider::State,
+    xover_lo_mid_state: slider::State,
+    xover_mid_hi_state: slider::State,
+    xover_q_state: slider::State,
+
+    // Global sliders
+    amount_state: slider::State,
+    attack_scale_state: slider::State,
+    release_scale_state: slider::State,
+    morph_state: slider::State,
+    input_gain_state: slider::State,
+    output_gain_state: slider::State,
+    oversampling_state: slider::State,
+    precision_state: slider::State,
+    channel_link_state: slider::State,
+    exclude_lfe_state: slider::State,
+    rms_time_state: slider::State,
+    peak_hold_state: slider::State,
+    gain_match_state: slider::State,
+    auto_makeup_state: slider::State,
+    loudness_target_state: slider::State,
+    k_weighted_meters_state: slider::State,
+    k_weighted_detection_state: slider::State,
+    fast_detector_state: slider::State,
+    offline_quality_state: slider::State,
+    midi_ducking_state: slider::State,
+    duck_attack_state: slider::State,
+    duck_release_state: slider::State,
+    learn_offset_state: slider::State,
 
-    peak_meter_state: nih_widgets::peak_meter::State,
+    input_level_meter_states: [level_meter::State; METER_CHANNELS],
+    output_level_meter_states: [level_meter::State; METER_CHANNELS],
+    output_rms_meter_state: level_meter::State,
+    meter_scale_button_state: button::State,
+    meter_ballistics_button_state: button::State,
+    loudness_reset_button_state: button::State,
+    input_clip_button_state: button::State,
+    input_peak_hold_button_state: button::State,
+    output_peak_hold_button_state: button::State,
+    true_peak_hold_button_state: button::State,
+    output_clip_button_state: button::State,
+    dark_theme_button_state: button::State,
+    band_view_button_state: button::State,
+    link_bands_button_state: button::State,
+    bypass_button_state: button::State,
+    band_tab_button_states: [button::State; NUM_BANDS],
+    band_accents_button_state: button::State,
+    ui_scale_button_state: button::State,
+    midi_learn_button_state: button::State,
+    clear_midi_button_state: button::State,
+    preset_button_states: [button::State; FACTORY_PRESETS.len()],
+    init_button_state: button::State,
+    randomize_button_state: button::State,
+    export_preset_button_state: button::State,
+    import_preset_button_state: button::State,
+    store_morph_a_button_state: button::State,
+    store_morph_b_button_state: button::State,
+    clear_morph_button_state: button::State,
+    learn_thresholds_button_state: button::State,
+    /// 最後にプリセットファイルを書き出した、または読み込んだ結果
+    preset_file_status: Option<String>,
     scrollable_state: scrollable::State,
 }
 
@@ -54,49 +759,180 @@ struct MultibandCompressorEditor {
 enum Message {
     /// Update a parameter's value.
     ParamUpdate(nih_widgets::ParamMessage),
+    /// Restart the integrated loudness measurement.
+    ResetLoudness,
+    /// Clear the latched input and output clip indicators.
+    ResetClipIndicators,
+    /// Clear the held peak values.
+    ResetPeakHolds,
+    /// Flip a `BoolParam` with a full begin/set/end gesture.
+    ToggleParam(ParamPtr),
+    /// Switch between the light and the dark theme.
+    ToggleDarkTheme,
+    /// Switch to the next set of band accent colors.
+    CycleBandAccents,
+    /// Switch to the next GUI scale. Takes effect when the editor is opened again.
+    CycleUiScale,
+    /// Start or stop assigning a MIDI CC to the next control that is moved.
+    ToggleMidiLearn,
+    /// Remove all MIDI CC assignments.
+    ClearMidiMappings,
+    /// Load one of the `FACTORY_PRESETS`.
+    LoadPreset(usize),
+    /// Reset all parameters to a neutral pass-through setting.
+    InitParams,
+    /// Set the main band and crossover parameters to random values within musical ranges.
+    RandomizeParams,
+    /// Write all parameters to a JSON preset file.
+    ExportPresetFile,
+    /// Load a JSON preset file.
+    ImportPresetFile,
+    /// Store the current parameter values as the A snapshot for morphing.
+    StoreMorphA,
+    /// Store the current parameter values as the B snapshot for morphing.
+    StoreMorphB,
+    /// Remove both morph snapshots so the parameters apply directly again.
+    ClearMorph,
+    /// Measure the input for a few seconds and set every band's threshold below its level.
+    LearnThresholds,
+    /// Switch to the next meter range.
+    CycleMeterScale,
+    /// Switch to the next meter ballistics.
+    CycleMeterBallistics,
+    /// Switch between showing all bands side by side and showing a single band.
+    ToggleFocusedBandView,
+    /// Show this band in the single band view.
+    FocusBand(usize),
+    /// Enable or disable moving the same parameter of all bands together.
+    ToggleLinkBands,
+    /// Show or hide the advanced settings of a band.
+    ToggleAdvanced(usize),
+    /// Sent every frame to poll the meters at a fixed rate.
+    Frame,
+    /// Revert the last parameter gesture.
+    Undo,
+    /// Reapply the last reverted parameter gesture.
+    Redo,
+    /// Move the keyboard focus to the next control.
+    FocusNext,
+    /// Move the keyboard focus to the previous control.
+    FocusPrevious,
 }
 
 impl IcedEditor for MultibandCompressorEditor {
     type Executor = executor::Default;
     type Message = Message;
-    type InitializationFlags = (Arc<MultibandCompressorParams>, Arc<AtomicF32>);
+    type InitializationFlags = EditorShared;
 
     fn new(
-        (params, peak_meter): Self::InitializationFlags,
+        EditorShared {
+            params,
+            async_executor,
+            analysis: analysis_output,
+            spectrum: spectrum_output,
+            reset_requests,
+            gain_reduction_history,
+            midi_cc_events,
+            threshold_learn,
+        }: Self::InitializationFlags,
         context: Arc<dyn GuiContext>,
     ) -> (Self, Command<Self::Message>) {
+        // 前回 GUI を閉じたときの古いスペクトルが一瞬表示されないように、解析の状態を捨てておく
+        async_executor.execute_background(BackgroundTask::ResetSpectrum);
+        let opened_ui_scale = scale::UiScale::load(&params.ui_scale);
+        // 閉じている間に溜まった CC で、開いた瞬間にパラメーターが飛ばないように捨てておく
+        if let Ok(mut events) = midi_cc_events.lock() {
+            while events.pop().is_some() {}
+        }
+        let midi_learn = midi_learn::MidiLearn::new(params.as_ref());
+
         let editor = MultibandCompressorEditor {
             params,
             context,
 
-            peak_meter,
-
-            // Low band
-            threshold_low_slider_state: Default::default(),
-            ratio_low_slider_state: Default::default(),
-            attack_low_slider_state: Default::default(),
-            release_low_slider_state: Default::default(),
-            makeup_low_slider_state: Default::default(),
-
-            // Mid band
-            threshold_mid_slider_state: Default::default(),
-            ratio_mid_slider_state: Default::default(),
-            attack_mid_slider_state: Default::default(),
-            release_mid_slider_state: Default::default(),
-            makeup_mid_slider_state: Default::default(),
-
-            // High band
-            threshold_high_slider_state: Default::default(),
-            ratio_high_slider_state: Default::default(),
-            attack_high_slider_state: Default::default(),
-            release_high_slider_state: Default::default(),
-            makeup_high_slider_state: Default::default(),
+            analysis_output,
+            analysis: AnalysisFrame::default(),
+            spectrum_output,
+            spectrum: SpectrumFrame::default(),
+            last_meter_poll: Instant::now(),
+            reset_requests,
+            gain_reduction_history,
+            midi_cc_events,
+            midi_learn,
+            threshold_learn,
+            threshold_learn_status: None,
+            linked_gesture: None,
+            undo_history: Default::default(),
+            opened_ui_scale,
+            crossover_response_frequencies: log_frequency_grid(
+                spectrum_view::MIN_FREQUENCY,
+                spectrum_view::MAX_FREQUENCY,
+                spectrum_view::RESPONSE_POINTS,
+            ),
+            crossover_response_db: Default::default(),
+            spectrum_view_state: Default::default(),
+            band_widget_states: Default::default(),
 
             // Crossovers
             xover_lo_mid_state: Default::default(),
             xover_mid_hi_state: Default::default(),
+            xover_q_state: Default::default(),
+
+            // Global
+            amount_state: Default::default(),
+            attack_scale_state: Default::default(),
+            release_scale_state: Default::default(),
+            morph_state: Default::default(),
+            input_gain_state: Default::default(),
+            output_gain_state: Default::default(),
+            oversampling_state: Default::default(),
+            precision_state: Default::default(),
+            channel_link_state: Default::default(),
+            exclude_lfe_state: Default::default(),
+            rms_time_state: Default::default(),
+            peak_hold_state: Default::default(),
+            gain_match_state: Default::default(),
+            auto_makeup_state: Default::default(),
+            loudness_target_state: Default::default(),
+            k_weighted_meters_state: Default::default(),
+            k_weighted_detection_state: Default::default(),
+            fast_detector_state: Default::default(),
+            offline_quality_state: Default::default(),
+            midi_ducking_state: Default::default(),
+            duck_attack_state: Default::default(),
+            duck_release_state: Default::default(),
+            learn_offset_state: Default::default(),
 
-            peak_meter_state: Default::default(),
+            input_level_meter_states: Default::default(),
+            output_level_meter_states: Default::default(),
+            output_rms_meter_state: Default::default(),
+            meter_scale_button_state: Default::default(),
+            meter_ballistics_button_state: Default::default(),
+            loudness_reset_button_state: Default::default(),
+            input_clip_button_state: Default::default(),
+            input_peak_hold_button_state: Default::default(),
+            output_peak_hold_button_state: Default::default(),
+            true_peak_hold_button_state: Default::default(),
+            output_clip_button_state: Default::default(),
+            dark_theme_button_state: Default::default(),
+            band_view_button_state: Default::default(),
+            link_bands_button_state: Default::default(),
+            bypass_button_state: Default::default(),
+            band_tab_button_states: Default::default(),
+            band_accents_button_state: Default::default(),
+            ui_scale_button_state: Default::default(),
+            midi_learn_button_state: Default::default(),
+            clear_midi_button_state: Default::default(),
+            preset_button_states: Default::default(),
+            init_button_state: Default::default(),
+            randomize_button_state: Default::default(),
+            export_preset_button_state: Default::default(),
+            import_preset_button_state: Default::default(),
+            store_morph_a_button_state: Default::default(),
+            store_morph_b_button_state: Default::default(),
+            clear_morph_button_state: Default::default(),
+            learn_thresholds_button_state: Default::default(),
+            preset_file_status: None,
             scrollable_state: Default::default(),
         };
 
@@ -113,14 +949,262 @@ impl IcedEditor for MultibandCompressorEditor {
         message: Self::Message,
     ) -> Command<Self::Message> {
         match message {
-            Message::ParamUpdate(message) => self.handle_param_message(message),
+            Message::ParamUpdate(message) => self.update_param(message),
+            Message::ResetLoudness => self.reset_requests.loudness.store(true, Ordering::Relaxed),
+            Message::ResetClipIndicators => self
+                .reset_requests
+                .clip_indicators
+                .store(true, Ordering::Relaxed),
+            Message::ResetPeakHolds => self
+                .reset_requests
+                .peak_holds
+                .store(true, Ordering::Relaxed),
+            Message::ToggleParam(param) => {
+                self.midi_learn.touch(param);
+                // SAFETY: the pointer comes from one of our own parameters, which outlive the editor
+                let value = unsafe { param.unmodulated_normalized_value() };
+                self.send_param_message(nih_widgets::ParamMessage::BeginSetParameter(param));
+                self.send_param_message(nih_widgets::ParamMessage::SetParameterNormalized(
+                    param,
+                    if value >= 0.5 { 0.0 } else { 1.0 },
+                ));
+                self.send_param_message(nih_widgets::ParamMessage::EndSetParameter(param));
+            }
+            Message::ToggleDarkTheme => {
+                self.params.dark_theme.fetch_xor(true, Ordering::Relaxed);
+            }
+            Message::CycleBandAccents => {
+                let band_accents = self.band_accents().next();
+                self.params
+                    .band_accents
+                    .store(band_accents.to_index(), Ordering::Relaxed);
+            }
+            Message::CycleUiScale => {
+                let ui_scale = scale::UiScale::load(&self.params.ui_scale).next();
+                self.params
+                    .ui_scale
+                    .store(ui_scale.to_index(), Ordering::Relaxed);
+            }
+            Message::LoadPreset(index) => {
+                let values = FACTORY_PRESETS[index].normalized_values(self.params.as_ref());
+                self.set_params(&values);
+            }
+            Message::InitParams => {
+                let values = INIT_PRESET.normalized_values(self.params.as_ref());
+                self.set_params(&values);
+            }
+            Message::RandomizeParams => {
+                let values =
+                    presets::random_values(self.params.as_ref(), &mut fastrand::Rng::new());
+                self.set_params(&values);
+            }
+            Message::ExportPresetFile => self.export_preset_file(),
+            Message::ImportPresetFile => self.import_preset_file(),
+            Message::StoreMorphA | Message::StoreMorphB | Message::ClearMorph => {
+                if let Ok(mut snapshots) = self.params.morph_snapshots.write() {
+                    match message {
+                        Message::StoreMorphA => snapshots.a = Some(Snapshot::capture(&self.params)),
+                        Message::StoreMorphB => snapshots.b = Some(Snapshot::capture(&self.params)),
+                        _ => *snapshots = Default::default(),
+                    }
+                }
+            }
+            Message::LearnThresholds => {
+                self.threshold_learn.start.store(true, Ordering::Relaxed);
+                self.threshold_learn_status = None;
+            }
+            Message::CycleMeterScale => {
+                let meter_scale = level_meter::MeterScale::load(&self.params.meter_scale).next();
+                self.params
+                    .meter_scale
+                    .store(meter_scale.to_index(), Ordering::Relaxed);
+            }
+            Message::CycleMeterBallistics => {
+                let meter_ballistics = MeterBallistics::load(&self.params.meter_ballistics).next();
+                self.params
+                    .meter_ballistics
+                    .store(meter_ballistics.to_index(), Ordering::Relaxed);
+            }
+            Message::ToggleFocusedBandView => {
+                self.params
+                    .focused_band_view
+                    .fetch_xor(true, Ordering::Relaxed);
+            }
+            Message::FocusBand(band) => self.params.focused_band.store(band, Ordering::Relaxed),
+            Message::ToggleLinkBands => {
+                self.params.link_bands.fetch_xor(true, Ordering::Relaxed);
+            }
+            Message::ToggleAdvanced(band) => {
+                BandControls::all(&self.params)[band]
+                    .advanced
+                    .fetch_xor(true, Ordering::Relaxed);
+            }
+            Message::ToggleMidiLearn => self.midi_learn.toggle_learn(),
+            Message::ClearMidiMappings => self.midi_learn.clear(&self.params.midi_cc_mappings),
+            Message::Frame => {
+                self.poll_meters();
+                self.poll_midi();
+                self.poll_threshold_learn();
+            }
+            Message::Undo => {
+                let values = self.undo_history.undo();
+                self.apply_history(&values);
+            }
+            Message::Redo => {
+                let values = self.undo_history.redo();
+                self.apply_history(&values);
+            }
+            Message::FocusNext => self.move_focus(true),
+            Message::FocusPrevious => self.move_focus(false),
         }
 
         Command::none()
     }
 
+    fn subscription(
+        &self,
+        window_subscription: &mut WindowSubscription<Self::Message>,
+    ) -> Subscription<Self::Message> {
+        window_subscription.on_frame(Message::Frame);
+
+        // どのウィジェットも使わなかった Tab キーでフォーカスを移し、Ctrl（macOS では Cmd）+ Z で元に戻す
+        subscription::events_with(|event, status| {
+            if status != event::Status::Ignored {
+                return None;
+            }
+            let Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }) = event else {
+                return None;
+            };
+
+            match key_code {
+                keyboard::KeyCode::Tab if modifiers.shift() => Some(Message::FocusPrevious),
+                keyboard::KeyCode::Tab => Some(Message::FocusNext),
+                keyboard::KeyCode::Z if modifiers.command() && modifiers.shift() => {
+                    Some(Message::Redo)
+                }
+                keyboard::KeyCode::Z if modifiers.command() => Some(Message::Undo),
+                keyboard::KeyCode::Y if modifiers.command() => Some(Message::Redo),
+                _ => None,
+            }
+        })
+    }
+
     fn view(&mut self) -> Element<'_, Self::Message> {
-        Scrollable::new(&mut self.scrollable_state)
+        // 現在のパラメーターでクロスオーバーを設計し、プロセッサーと同じ内部レートで特性を計算する
+        let processing_rate =
+            self.analysis.sample_rate * self.params.global.oversampling.value().factor() as f32;
+        let [lo_mid, mid_hi] = self.params.crossovers.frequencies();
+        // 特性を計算するだけなので、チャンネルごとの状態は確保しない
+        let mut crossover = Crossover::new(0);
+        crossover.set_frequencies(
+            lo_mid,
+            mid_hi,
+            self.params.crossovers.q.value(),
+            processing_rate,
+        );
+        crossover.magnitude_response_db(
+            &self.crossover_response_frequencies,
+            processing_rate,
+            &mut self.crossover_response_db,
+        );
+
+        let focus_label = self.midi_learn.prompt().or_else(|| self.focus_label());
+        let midi_learning = self.midi_learn.is_learning();
+        let morph_status = match self.params.morph_snapshots.read().map(|snapshots| *snapshots) {
+            Ok(snapshots) if snapshots.pair().is_some() => {
+                "Morphing between A and B, the knobs for continuous parameters have no effect"
+            }
+            Ok(snapshots) if snapshots.a.is_some() => "A stored, store B to start morphing",
+            Ok(snapshots) if snapshots.b.is_some() => "B stored, store A to start morphing",
+            _ => "Store A and B to morph between them",
+        };
+        let threshold_learning = self.threshold_learn.active.load(Ordering::Relaxed);
+        let theme = self.theme();
+        let band_colors = theme.band_colors;
+        let band_accents = self.band_accents();
+        let dark_theme = self.params.dark_theme.load(Ordering::Relaxed);
+        let focused_band_view = self.params.focused_band_view.load(Ordering::Relaxed);
+        let focused_band = self.focused_band();
+        let link_bands = self.params.link_bands.load(Ordering::Relaxed);
+        let ui_scale = scale::UiScale::load(&self.params.ui_scale);
+        let meter_scale = level_meter::MeterScale::load(&self.params.meter_scale);
+        let meter_ballistics = MeterBallistics::load(&self.params.meter_ballistics);
+        // 出力に影響していないバンドの列は薄く表示する
+        let audible_bands = self.params.audible_bands();
+        let bypassed_bands = self.params.bypassed_bands();
+        let band_edges = [
+            spectrum_view::MIN_FREQUENCY,
+            lo_mid,
+            mid_hi,
+            spectrum_view::MAX_FREQUENCY,
+        ];
+        let band_displays: [BandDisplay; NUM_BANDS] = std::array::from_fn(|band| {
+            let dimmed = !audible_bands[band] || bypassed_bands[band];
+            BandDisplay {
+                index: band,
+                name: BAND_NAMES[band],
+                frequency_range: [band_edges[band], band_edges[band + 1]],
+                input_peak: self.analysis.band_input_peak[band],
+                energy_percent: self.analysis.band_energy_percent[band],
+                gain_reduction_db: self.analysis.gain_reduction_db[band],
+                gain_reduction_hold_db: self.analysis.gain_reduction_hold_db[band],
+                gain_reduction_stats: self.analysis.gain_reduction_stats[band],
+                meter_scale,
+                accent: band_colors[band],
+                color: if dimmed {
+                    theme::dimmed(band_colors[band])
+                } else {
+                    band_colors[band]
+                },
+                title_color: if dimmed {
+                    theme::dimmed(theme.text)
+                } else {
+                    theme.text
+                },
+            }
+        });
+
+        let bands: Element<'_, Message> = if focused_band_view {
+            let tabs = self.band_tab_button_states.iter_mut().enumerate().fold(
+                Row::new().spacing(4),
+                |row, (band, state)| {
+                    row.push(
+                        Button::new(state, Text::new(BAND_NAMES[band]).size(14))
+                            .style(theme::ToggleStyle {
+                                active: band == focused_band,
+                                color: band_colors[band],
+                            })
+                            .on_press(Message::FocusBand(band)),
+                    )
+                },
+            );
+
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(10)
+                .width(Length::Fill)
+                .push(tabs)
+                .push(focused_band_row(
+                    &mut self.band_widget_states[focused_band],
+                    BandControls::all(&self.params)[focused_band],
+                    band_displays[focused_band],
+                ))
+                .into()
+        } else {
+            self.band_widget_states
+                .iter_mut()
+                .zip(BandControls::all(&self.params))
+                .zip(band_displays)
+                .fold(
+                    Row::new().spacing(20).width(Length::Fill),
+                    |row, ((states, params), display)| {
+                        row.push(band_column(states, params, display))
+                    },
+                )
+                .into()
+        };
+
+        let content = Scrollable::new(&mut self.scrollable_state)
             .push(
                 Column::new()
                     .align_items(Alignment::Center)
@@ -135,223 +1219,988 @@ impl IcedEditor for MultibandCompressorEditor {
                             .horizontal_alignment(alignment::Horizontal::Center)
                             .vertical_alignment(alignment::Vertical::Bottom),
                     )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(band_toggle_button(
+                                &mut self.bypass_button_state,
+                                "Bypass",
+                                &self.params.global.bypass,
+                                tooltips::PLUGIN_BYPASS,
+                                theme.text,
+                            ))
+                            .push(
+                                Button::new(
+                                    &mut self.dark_theme_button_state,
+                                    Text::new(if dark_theme { "Light" } else { "Dark" }).size(14),
+                                )
+                                .on_press(Message::ToggleDarkTheme),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.band_accents_button_state,
+                                    Text::new(format!("Colors: {}", band_accents.name()))
+                                        .size(14),
+                                )
+                                .on_press(Message::CycleBandAccents),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.band_view_button_state,
+                                    Text::new(if focused_band_view {
+                                        "All Bands"
+                                    } else {
+                                        "Single Band"
+                                    })
+                                    .size(14),
+                                )
+                                .on_press(Message::ToggleFocusedBandView),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.link_bands_button_state,
+                                    Text::new("Link Bands").size(14),
+                                )
+                                .style(theme::ToggleStyle {
+                                    active: link_bands,
+                                    color: theme.text,
+                                })
+                                .on_press(Message::ToggleLinkBands),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.ui_scale_button_state,
+                                    Text::new(if ui_scale == self.opened_ui_scale {
+                                        format!("Scale: {}", ui_scale.name())
+                                    } else {
+                                        format!("Scale: {} (reopen to apply)", ui_scale.name())
+                                    })
+                                    .size(14),
+                                )
+                                .on_press(Message::CycleUiScale),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.midi_learn_button_state,
+                                    Text::new("MIDI Learn").size(14),
+                                )
+                                .style(theme::ToggleStyle {
+                                    active: midi_learning,
+                                    color: theme.text,
+                                })
+                                .on_press(Message::ToggleMidiLearn),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.clear_midi_button_state,
+                                    Text::new("Clear MIDI").size(14),
+                                )
+                                .on_press(Message::ClearMidiMappings),
+                            ),
+                    )
+                    .push(
+                        self.preset_button_states
+                            .iter_mut()
+                            .zip(&FACTORY_PRESETS)
+                            .enumerate()
+                            .fold(
+                                Row::new()
+                                    .spacing(10)
+                                    .align_items(Alignment::Center)
+                                    .push(Text::new("Presets").size(14)),
+                                |row, (index, (state, preset))| {
+                                    row.push(
+                                        Button::new(state, Text::new(preset.name).size(14))
+                                            .on_press(Message::LoadPreset(index)),
+                                    )
+                                },
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.init_button_state,
+                                    Text::new(INIT_PRESET.name).size(14),
+                                )
+                                .on_press(Message::InitParams),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.randomize_button_state,
+                                    Text::new("Randomize").size(14),
+                                )
+                                .on_press(Message::RandomizeParams),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.export_preset_button_state,
+                                    Text::new("Export...").size(14),
+                                )
+                                .on_press(Message::ExportPresetFile),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.import_preset_button_state,
+                                    Text::new("Import...").size(14),
+                                )
+                                .on_press(Message::ImportPresetFile),
+                            )
+                            .push(
+                                Text::new(self.preset_file_status.as_deref().unwrap_or(""))
+                                    .size(13),
+                            ),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(Text::new("Morph").size(14))
+                            .push(
+                                Button::new(
+                                    &mut self.store_morph_a_button_state,
+                                    Text::new("Store A").size(14),
+                                )
+                                .on_press(Message::StoreMorphA),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.store_morph_b_button_state,
+                                    Text::new("Store B").size(14),
+                                )
+                                .on_press(Message::StoreMorphB),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.clear_morph_button_state,
+                                    Text::new("Clear").size(14),
+                                )
+                                .on_press(Message::ClearMorph),
+                            )
+                            .push(Text::new(morph_status).size(13)),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(Text::new("Auto Threshold").size(14))
+                            .push(
+                                Button::new(
+                                    &mut self.learn_thresholds_button_state,
+                                    Text::new(if threshold_learning {
+                                        "Learning..."
+                                    } else {
+                                        "Learn"
+                                    })
+                                    .size(14),
+                                )
+                                .style(theme::ToggleStyle {
+                                    active: threshold_learning,
+                                    color: theme.text,
+                                })
+                                .on_press(Message::LearnThresholds),
+                            )
+                            .push(tooltips::with_tooltip(
+                                slider::Slider::new(
+                                    &mut self.learn_offset_state,
+                                    &self.params.global.learn_offset,
+                                )
+                                .map(Message::ParamUpdate),
+                                &self.params.global.learn_offset,
+                                tooltips::LEARN_OFFSET,
+                            ))
+                            .push(
+                                Text::new(self.threshold_learn_status.as_deref().unwrap_or(
+                                    "Play typical material and press Learn to set the thresholds",
+                                ))
+                                .size(13),
+                            ),
+                    )
+                    .push(
+                        Text::new(focus_label.unwrap_or_else(|| {
+                            String::from(
+                                "Tab: next control, arrows: adjust, Enter: type a value, \
+                                 Delete: default, Ctrl+Z: undo",
+                            )
+                        }))
+                        .size(13),
+                    )
+                    .push(
+                        spectrum_view::SpectrumView::new(
+                            &mut self.spectrum_view_state,
+                            &self.spectrum.input_db,
+                            &self.spectrum.output_db,
+                            self.spectrum.sample_rate,
+                            [&self.params.crossovers.lo_mid, &self.params.crossovers.mid_hi],
+                            &self.crossover_response_db,
+                            band_colors,
+                        )
+                        .map(Message::ParamUpdate),
+                    )
+                    .push(Space::with_height(10.into()))
+                    .push(bands)
+                    .push(gain_reduction_history_view::GainReductionHistoryView::new(
+                        &self.gain_reduction_history,
+                        band_colors,
+                    ))
                     .push(Space::with_height(10.into()))
                     .push(
                         Row::new()
                             .spacing(20)
                             .width(Length::Fill)
+                            .align_items(Alignment::Start)
                             .push(
                                 Column::new()
                                     .align_items(Alignment::Center)
                                     .spacing(10)
                                     .width(Length::Fill)
                                     .push(
-                                        Text::new("Low Band")
+                                        Text::new("Crossovers")
                                             .font(assets::NOTO_SANS_LIGHT)
                                             .size(18)
                                             .width(Length::Fill)
                                             .horizontal_alignment(alignment::Horizontal::Center),
                                     )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.threshold_low_slider_state,
-                                            &self.params.threshold_low,
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.xover_lo_mid_state,
+                                            &self.params.crossovers.lo_mid,
                                         )
+                                        .logarithmic()
                                         .map(Message::ParamUpdate),
-                                    )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.ratio_low_slider_state,
-                                            &self.params.ratio_low,
+                                        &self.params.crossovers.lo_mid,
+                                        tooltips::CROSSOVER,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.xover_mid_hi_state,
+                                            &self.params.crossovers.mid_hi,
                                         )
+                                        .logarithmic()
                                         .map(Message::ParamUpdate),
-                                    )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.attack_low_slider_state,
-                                            &self.params.attack_low,
+                                        &self.params.crossovers.mid_hi,
+                                        tooltips::CROSSOVER,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.xover_q_state,
+                                            &self.params.crossovers.q,
                                         )
                                         .map(Message::ParamUpdate),
-                                    )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.release_low_slider_state,
-                                            &self.params.release_low,
+                                        &self.params.crossovers.q,
+                                        tooltips::CROSSOVER_Q,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.amount_state,
+                                            &self.params.global.amount,
                                         )
                                         .map(Message::ParamUpdate),
-                                    )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.makeup_low_slider_state,
-                                            &self.params.makeup_low,
+                                        &self.params.global.amount,
+                                        tooltips::AMOUNT,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.attack_scale_state,
+                                            &self.params.global.attack_scale,
                                         )
                                         .map(Message::ParamUpdate),
-                                    ),
-                            )
-                            .push(
-                                Column::new()
-                                    .align_items(Alignment::Center)
-                                    .spacing(10)
-                                    .width(Length::Fill)
+                                        &self.params.global.attack_scale,
+                                        tooltips::ATTACK_SCALE,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.release_scale_state,
+                                            &self.params.global.release_scale,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.release_scale,
+                                        tooltips::RELEASE_SCALE,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.morph_state,
+                                            &self.params.global.morph,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.morph,
+                                        tooltips::MORPH,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.input_gain_state,
+                                            &self.params.global.input_gain,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.input_gain,
+                                        tooltips::INPUT_GAIN,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.output_gain_state,
+                                            &self.params.global.output_gain,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.output_gain,
+                                        tooltips::OUTPUT_GAIN,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.oversampling_state,
+                                            &self.params.global.oversampling,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.oversampling,
+                                        tooltips::OVERSAMPLING,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.precision_state,
+                                            &self.params.global.precision,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.precision,
+                                        tooltips::PRECISION,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.channel_link_state,
+                                            &self.params.global.channel_link,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.channel_link,
+                                        tooltips::CHANNEL_LINK,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.exclude_lfe_state,
+                                            &self.params.global.exclude_lfe,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.exclude_lfe,
+                                        tooltips::EXCLUDE_LFE,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.rms_time_state,
+                                            &self.params.global.rms_time,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.rms_time,
+                                        tooltips::RMS_TIME,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.peak_hold_state,
+                                            &self.params.global.peak_hold,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.peak_hold,
+                                        tooltips::PEAK_HOLD,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.gain_match_state,
+                                            &self.params.global.gain_match,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.gain_match,
+                                        tooltips::GAIN_MATCH,
+                                    ))
                                     .push(
-                                        Text::new("Mid Band")
-                                            .font(assets::NOTO_SANS_LIGHT)
-                                            .size(18)
-                                            .width(Length::Fill)
-                                            .horizontal_alignment(alignment::Horizontal::Center),
+                                        Text::new(format!(
+                                            "Trim {:+.1} dB",
+                                            self.analysis.gain_match_trim_db
+                                        ))
+                                        .size(14),
                                     )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.threshold_mid_slider_state,
-                                            &self.params.threshold_mid,
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.auto_makeup_state,
+                                            &self.params.global.auto_makeup,
                                         )
                                         .map(Message::ParamUpdate),
-                                    )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.ratio_mid_slider_state,
-                                            &self.params.ratio_mid,
+                                        &self.params.global.auto_makeup,
+                                        tooltips::AUTO_MAKEUP,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.loudness_target_state,
+                                            &self.params.global.loudness_target,
                                         )
                                         .map(Message::ParamUpdate),
-                                    )
+                                        &self.params.global.loudness_target,
+                                        tooltips::LOUDNESS_TARGET,
+                                    ))
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.attack_mid_slider_state,
-                                            &self.params.attack_mid,
+                                        Text::new(format!(
+                                            "Auto Makeup {:+.1} dB",
+                                            self.analysis.auto_makeup_db
+                                        ))
+                                        .size(14),
+                                    )
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.k_weighted_meters_state,
+                                            &self.params.global.k_weighted_meters,
                                         )
                                         .map(Message::ParamUpdate),
-                                    )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.release_mid_slider_state,
-                                            &self.params.release_mid,
+                                        &self.params.global.k_weighted_meters,
+                                        tooltips::K_WEIGHTED_METERS,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.k_weighted_detection_state,
+                                            &self.params.global.k_weighted_detection,
                                         )
                                         .map(Message::ParamUpdate),
-                                    )
-                                    .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.makeup_mid_slider_state,
-                                            &self.params.makeup_mid,
+                                        &self.params.global.k_weighted_detection,
+                                        tooltips::K_WEIGHTED_DETECTION,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.fast_detector_state,
+                                            &self.params.global.fast_detector,
                                         )
                                         .map(Message::ParamUpdate),
-                                    ),
+                                        &self.params.global.fast_detector,
+                                        tooltips::FAST_DETECTOR,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.offline_quality_state,
+                                            &self.params.global.offline_quality,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.offline_quality,
+                                        tooltips::OFFLINE_QUALITY,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.midi_ducking_state,
+                                            &self.params.global.midi_ducking,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.midi_ducking,
+                                        tooltips::MIDI_DUCKING,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.duck_attack_state,
+                                            &self.params.global.duck_attack,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.duck_attack,
+                                        tooltips::DUCK_ATTACK,
+                                    ))
+                                    .push(tooltips::with_tooltip(
+                                        slider::Slider::new(
+                                            &mut self.duck_release_state,
+                                            &self.params.global.duck_release,
+                                        )
+                                        .map(Message::ParamUpdate),
+                                        &self.params.global.duck_release,
+                                        tooltips::DUCK_RELEASE,
+                                    )),
                             )
                             .push(
                                 Column::new()
                                     .align_items(Alignment::Center)
                                     .spacing(10)
-                                    .width(Length::Fill)
-                                    .push(
-                                        Text::new("High Band")
-                                            .font(assets::NOTO_SANS_LIGHT)
-                                            .size(18)
-                                            .width(Length::Fill)
-                                            .horizontal_alignment(alignment::Horizontal::Center),
-                                    )
+                                    .width(Length::Shrink)
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.threshold_high_slider_state,
-                                            &self.params.threshold_high,
+                                        Button::new(
+                                            &mut self.meter_scale_button_state,
+                                            Text::new(format!("Meter: {}", meter_scale.name()))
+                                                .size(14),
                                         )
-                                        .map(Message::ParamUpdate),
+                                        .on_press(Message::CycleMeterScale),
                                     )
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.ratio_high_slider_state,
-                                            &self.params.ratio_high,
+                                        Button::new(
+                                            &mut self.meter_ballistics_button_state,
+                                            Text::new(format!(
+                                                "Ballistics: {}",
+                                                meter_ballistics.name()
+                                            ))
+                                            .size(14),
                                         )
-                                        .map(Message::ParamUpdate),
+                                        .on_press(Message::CycleMeterBallistics),
                                     )
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.attack_high_slider_state,
-                                            &self.params.attack_high,
-                                        )
-                                        .map(Message::ParamUpdate),
+                                        Text::new("Input")
+                                            .font(assets::NOTO_SANS_LIGHT)
+                                            .size(18)
+                                            .horizontal_alignment(alignment::Horizontal::Center),
                                     )
+                                    .push(stereo_level_meter(
+                                        &mut self.input_level_meter_states,
+                                        self.analysis.input_peak,
+                                        meter_scale,
+                                    ))
+                                    .push(peak_hold_button(
+                                        &mut self.input_peak_hold_button_state,
+                                        "Hold",
+                                        self.analysis.input_peak_hold,
+                                    ))
+                                    .push(clip_indicator(
+                                        &mut self.input_clip_button_state,
+                                        self.analysis.input_clipped_samples,
+                                    ))
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.release_high_slider_state,
-                                            &self.params.release_high,
-                                        )
-                                        .map(Message::ParamUpdate),
+                                        Text::new("Output")
+                                            .font(assets::NOTO_SANS_LIGHT)
+                                            .size(18)
+                                            .horizontal_alignment(alignment::Horizontal::Center),
                                     )
+                                    .push(stereo_level_meter(
+                                        &mut self.output_level_meter_states,
+                                        self.analysis.output_peak,
+                                        meter_scale,
+                                    ))
+                                    .push(peak_hold_button(
+                                        &mut self.output_peak_hold_button_state,
+                                        "Hold",
+                                        self.analysis.output_peak_hold,
+                                    ))
+                                    .push(clip_indicator(
+                                        &mut self.output_clip_button_state,
+                                        self.analysis.output_clipped_samples,
+                                    ))
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.makeup_high_slider_state,
-                                            &self.params.makeup_high,
-                                        )
-                                        .map(Message::ParamUpdate),
-                                    ),
-                            ),
-                    )
-                    .push(Space::with_height(10.into()))
-                    .push(
-                        Row::new()
-                            .spacing(20)
-                            .width(Length::Fill)
-                            .align_items(Alignment::Start)
-                            .push(
-                                Column::new()
-                                    .align_items(Alignment::Center)
-                                    .spacing(10)
-                                    .width(Length::Fill)
+                                        Text::new(format!(
+                                            "True Peak {:.1} dBTP",
+                                            util::gain_to_db(self.analysis.output_true_peak)
+                                        ))
+                                        .size(14),
+                                    )
+                                    .push(peak_hold_button(
+                                        &mut self.true_peak_hold_button_state,
+                                        "Max dBTP",
+                                        self.analysis.output_true_peak_hold,
+                                    ))
                                     .push(
-                                        Text::new("Crossovers")
+                                        Text::new("Output RMS")
                                             .font(assets::NOTO_SANS_LIGHT)
                                             .size(18)
-                                            .width(Length::Fill)
                                             .horizontal_alignment(alignment::Horizontal::Center),
                                     )
+                                    .push(level_meter::LevelMeter::new(
+                                        &mut self.output_rms_meter_state,
+                                        util::gain_to_db(self.analysis.output_rms),
+                                        meter_scale,
+                                    ))
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.xover_lo_mid_state,
-                                            &self.params.xover_lo_mid,
-                                        )
-                                        .map(Message::ParamUpdate),
+                                        Text::new("Correlation")
+                                            .font(assets::NOTO_SANS_LIGHT)
+                                            .size(18)
+                                            .horizontal_alignment(alignment::Horizontal::Center),
                                     )
+                                    .push(correlation_meter::CorrelationMeter::new(
+                                        self.analysis.output_correlation,
+                                    ))
                                     .push(
-                                        nih_widgets::ParamSlider::new(
-                                            &mut self.xover_mid_hi_state,
-                                            &self.params.xover_mid_hi,
-                                        )
-                                        .map(Message::ParamUpdate),
-                                    ),
-                            )
-                            .push(
-                                Column::new()
-                                    .align_items(Alignment::Center)
-                                    .spacing(10)
-                                    .width(Length::Shrink)
+                                        Text::new(format!(
+                                            "{:+.2}",
+                                            self.analysis.output_correlation
+                                        ))
+                                        .size(14),
+                                    )
+                                    .push(goniometer::Goniometer::new(
+                                        &self.analysis.output_goniometer,
+                                        Color::from_rgb(0.4, 0.8, 0.4),
+                                    ))
                                     .push(
-                                        Text::new("Peak Meter")
+                                        Text::new("Loudness")
                                             .font(assets::NOTO_SANS_LIGHT)
                                             .size(18)
                                             .horizontal_alignment(alignment::Horizontal::Center),
                                     )
+                                    .push(loudness_text("M", self.analysis.momentary_lufs))
+                                    .push(loudness_text("S", self.analysis.short_term_lufs))
+                                    .push(loudness_text("I", self.analysis.integrated_lufs))
                                     .push(
-                                        nih_widgets::PeakMeter::new(
-                                            &mut self.peak_meter_state,
-                                            util::gain_to_db(
-                                                self.peak_meter
-                                                    .load(std::sync::atomic::Ordering::Relaxed),
-                                            ),
+                                        Button::new(
+                                            &mut self.loudness_reset_button_state,
+                                            Text::new("Reset").size(14),
                                         )
-                                        .hold_time(Duration::from_millis(600)),
+                                        .on_press(Message::ResetLoudness),
+                                    )
+                                    .push(
+                                        Text::new(format!(
+                                            "DSP {:.1}%",
+                                            self.analysis.dsp_load_percent
+                                        ))
+                                        .size(14),
                                     ),
                             ),
                     )
                     .push(Space::with_height(20.into())),
-            )
+            );
+
+        Container::new(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(theme::RootStyle(theme))
             .into()
     }
 
     fn background_color(&self) -> nih_plug_iced::Color {
-        nih_plug_iced::Color {
-            r: 0.98,
-            g: 0.98,
-            b: 0.98,
-            a: 1.0,
+        self.theme().background
+    }
+}
+
+impl MultibandCompressorEditor {
+    /// 保存されているアクセントカラーの組み合わせ
+    fn band_accents(&self) -> theme::BandAccents {
+        let index = self.params.band_accents.load(Ordering::Relaxed);
+        theme::BandAccents::from_index(index.min(theme::BandAccents::variants().len() - 1))
+    }
+
+    /// パラメーターの変更を反映する。バンドのリンクが有効なら、スレッショルド、レシオ、メイクアップの
+    /// 操作を全バンドの同じパラメーターに広げ、ジェスチャーを始めたときのバンド間の差を保つ
+    fn update_param(&mut self, message: nih_widgets::ParamMessage) {
+        use nih_widgets::ParamMessage;
+
+        if let ParamMessage::BeginSetParameter(param) = message {
+            self.midi_learn.touch(param);
+        }
+
+        match message {
+            ParamMessage::BeginSetParameter(param)
+                if self.params.link_bands.load(Ordering::Relaxed) =>
+            {
+                if let Some((params, source)) = self.linked_params(param) {
+                    // SAFETY: the pointers come from our own parameters, which outlive the editor
+                    let start_values =
+                        params.map(|param| unsafe { param.unmodulated_plain_value() });
+                    for param in params {
+                        self.send_param_message(ParamMessage::BeginSetParameter(param));
+                    }
+                    self.linked_gesture = Some(LinkedGesture {
+                        params,
+                        start_values,
+                        source,
+                    });
+
+                    return;
+                }
+            }
+            ParamMessage::SetParameterNormalized(param, normalized_value) => {
+                if let Some(gesture) = self
+                    .linked_gesture
+                    .filter(|gesture| gesture.params[gesture.source] == param)
+                {
+                    // SAFETY: see above
+                    let delta = unsafe { param.preview_plain(normalized_value) }
+                        - gesture.start_values[gesture.source];
+                    for (param, start_value) in gesture.params.into_iter().zip(gesture.start_values)
+                    {
+                        let normalized_value =
+                            unsafe { param.preview_normalized(start_value + delta) };
+                        self.send_param_message(ParamMessage::SetParameterNormalized(
+                            param,
+                            normalized_value,
+                        ));
+                    }
+
+                    return;
+                }
+            }
+            ParamMessage::EndSetParameter(param) => {
+                if let Some(gesture) = self
+                    .linked_gesture
+                    .filter(|gesture| gesture.params[gesture.source] == param)
+                {
+                    for param in gesture.params {
+                        self.send_param_message(ParamMessage::EndSetParameter(param));
+                    }
+                    self.linked_gesture = None;
+
+                    return;
+                }
+            }
+            _ => {}
+        }
+
+        self.send_param_message(message);
+    }
+
+    /// パラメーターの変更をホストに送り、Undo の履歴に記録する
+    fn send_param_message(&mut self, message: nih_widgets::ParamMessage) {
+        use nih_widgets::ParamMessage;
+
+        match message {
+            ParamMessage::BeginSetParameter(param) => {
+                // SAFETY: the pointer comes from our own parameters, which outlive the editor
+                let value = unsafe { param.unmodulated_normalized_value() };
+                self.undo_history.begin(param, value);
+            }
+            ParamMessage::SetParameterNormalized(param, value) => {
+                self.undo_history.set(param, value)
+            }
+            ParamMessage::EndSetParameter(_) => self.undo_history.end(),
+        }
+
+        self.handle_param_message(message);
+    }
+
+    /// 複数のパラメーターを 1 回のジェスチャーでまとめて設定し、1 つの操作として Undo の履歴に記録する
+    fn set_params(&mut self, values: &[(ParamPtr, f32)]) {
+        use nih_widgets::ParamMessage;
+
+        for &(param, _) in values {
+            self.send_param_message(ParamMessage::BeginSetParameter(param));
+        }
+        for &(param, value) in values {
+            self.send_param_message(ParamMessage::SetParameterNormalized(param, value));
+        }
+        for &(param, _) in values {
+            self.send_param_message(ParamMessage::EndSetParameter(param));
+        }
+    }
+
+    /// 全パラメーターを、ダイアログで選んだ JSON のプリセットファイルに書き出す
+    fn export_preset_file(&mut self) {
+        let Some(path) = preset_file::save_dialog() else {
+            return;
+        };
+
+        self.preset_file_status = Some(match preset_file::write(self.params.as_ref(), &path) {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(error) => format!("Could not save {}: {}", path.display(), error),
+        });
+    }
+
+    /// ダイアログで選んだ JSON のプリセットファイルを読み込む。1 つの操作として Undo できる
+    fn import_preset_file(&mut self) {
+        let Some(path) = preset_file::open_dialog() else {
+            return;
+        };
+
+        self.preset_file_status = Some(match preset_file::read(self.params.as_ref(), &path) {
+            Ok(values) => {
+                self.set_params(&values);
+                format!("Loaded {}", path.display())
+            }
+            Err(error) => format!("Could not load {}: {}", path.display(), error),
+        });
+    }
+
+    /// Undo/Redo で戻す値を、履歴に記録せずに 1 回のジェスチャーでまとめて設定する
+    fn apply_history(&self, values: &[(ParamPtr, f32)]) {
+        use nih_widgets::ParamMessage;
+
+        for &(param, _) in values {
+            self.handle_param_message(ParamMessage::BeginSetParameter(param));
+        }
+        for &(param, value) in values {
+            self.handle_param_message(ParamMessage::SetParameterNormalized(param, value));
+        }
+        for &(param, _) in values {
+            self.handle_param_message(ParamMessage::EndSetParameter(param));
+        }
+    }
+
+    /// `param` がリンクの対象なら、同じ種類の全バンドのパラメーターと、その中での `param` の位置を返す
+    fn linked_params(&self, param: ParamPtr) -> Option<([ParamPtr; NUM_BANDS], usize)> {
+        let bands = self.params.bands();
+        [
+            bands.map(|band| band.threshold.as_ptr()),
+            bands.map(|band| band.ratio.as_ptr()),
+            bands.map(|band| band.makeup.as_ptr()),
+        ]
+        .into_iter()
+        .find_map(|params| {
+            params
+                .iter()
+                .position(|&linked| linked == param)
+                .map(|source| (params, source))
+        })
+    }
+
+    /// プロセッサーから届いた CC を、割り当てたパラメーターに反映する。Undo の履歴には記録しない
+    fn poll_midi(&mut self) {
+        use nih_widgets::ParamMessage;
+
+        let Ok(mut events) = self.midi_cc_events.lock() else {
+            return;
+        };
+        while let Some(event) = events.pop() {
+            if let Some((param, value)) =
+                self.midi_learn.handle(event, &self.params.midi_cc_mappings)
+            {
+                self.handle_param_message(ParamMessage::BeginSetParameter(param));
+                self.handle_param_message(ParamMessage::SetParameterNormalized(param, value));
+                self.handle_param_message(ParamMessage::EndSetParameter(param));
+            }
+        }
+    }
+
+    /// オートスレッショルドの学習が終わっていれば、測ったレベルから Learn Offset だけ下に各バンドの
+    /// スレッショルドを設定する。全バンドの変更を 1 つの操作として Undo できる
+    fn poll_threshold_learn(&mut self) {
+        let Some(levels) = self
+            .threshold_learn
+            .result
+            .lock()
+            .ok()
+            .and_then(|mut result| result.take())
+        else {
+            return;
+        };
+
+        let offset = self.params.global.learn_offset.value();
+        let mut values = Vec::new();
+        let mut status = Vec::new();
+        for ((band, levels), name) in self.params.bands().into_iter().zip(levels).zip(BAND_NAMES) {
+            // 無音のバンドはスレッショルドを変えない
+            if levels.is_silent() {
+                status.push(format!("{name} silent"));
+                continue;
+            }
+
+            let threshold = levels.detected_level_db(band.detector.value()) - offset;
+            values.push((
+                band.threshold.as_ptr(),
+                band.threshold.preview_normalized(threshold),
+            ));
+            status.push(format!(
+                "{name} {:.1} dB RMS, {:.1} dB crest",
+                levels.rms_db,
+                levels.crest_db()
+            ));
+        }
+        self.set_params(&values);
+        self.threshold_learn_status = Some(status.join(", "));
+    }
+
+    /// 前回から [`METER_POLL_INTERVAL`] 以上経っていれば、新しいフレームを受け取る。新しいフレームが
+    /// なければ前回のものがそのまま残り、複製もしない
+    fn poll_meters(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.last_meter_poll) < METER_POLL_INTERVAL {
+            return;
+        }
+        self.last_meter_poll = now;
+
+        if let Ok(mut analysis_output) = self.analysis_output.lock() {
+            if analysis_output.update() {
+                self.analysis.clone_from(analysis_output.latest());
+            }
         }
+        if let Ok(mut spectrum_output) = self.spectrum_output.lock() {
+            if spectrum_output.update() {
+                self.spectrum.clone_from(spectrum_output.latest());
+            }
+        }
+    }
+
+    /// Tab キーでフォーカスを移す順に並べた、表示されているノブとスライダーのフォーカスと、その
+    /// パラメーター
+    fn focus_chain(&mut self) -> Vec<(&mut focus::Focus, ParamPtr)> {
+        let focused_band_view = self.params.focused_band_view.load(Ordering::Relaxed);
+        let focused_band = self.focused_band();
+
+        let mut chain = Vec::new();
+        for (band, (states, params)) in self
+            .band_widget_states
+            .iter_mut()
+            .zip(BandControls::all(&self.params))
+            .enumerate()
+        {
+            if !focused_band_view || band == focused_band {
+                chain.extend(states.focus_chain(params, focused_band_view));
+            }
+        }
+        let crossovers = &self.params.crossovers;
+        let global = &self.params.global;
+        for (state, param) in [
+            (&mut self.xover_lo_mid_state, crossovers.lo_mid.as_ptr()),
+            (&mut self.xover_mid_hi_state, crossovers.mid_hi.as_ptr()),
+            (&mut self.xover_q_state, crossovers.q.as_ptr()),
+            (&mut self.amount_state, global.amount.as_ptr()),
+            (&mut self.attack_scale_state, global.attack_scale.as_ptr()),
+            (&mut self.release_scale_state, global.release_scale.as_ptr()),
+            (&mut self.morph_state, global.morph.as_ptr()),
+            (&mut self.input_gain_state, global.input_gain.as_ptr()),
+            (&mut self.output_gain_state, global.output_gain.as_ptr()),
+            (&mut self.oversampling_state, global.oversampling.as_ptr()),
+            (&mut self.precision_state, global.precision.as_ptr()),
+            (&mut self.channel_link_state, global.channel_link.as_ptr()),
+            (&mut self.exclude_lfe_state, global.exclude_lfe.as_ptr()),
+            (&mut self.rms_time_state, global.rms_time.as_ptr()),
+            (&mut self.peak_hold_state, global.peak_hold.as_ptr()),
+            (&mut self.gain_match_state, global.gain_match.as_ptr()),
+            (&mut self.auto_makeup_state, global.auto_makeup.as_ptr()),
+            (&mut self.loudness_target_state, global.loudness_target.as_ptr()),
+            (&mut self.k_weighted_meters_state, global.k_weighted_meters.as_ptr()),
+            (
+                &mut self.k_weighted_detection_state,
+                global.k_weighted_detection.as_ptr(),
+            ),
+            (&mut self.fast_detector_state, global.fast_detector.as_ptr()),
+            (&mut self.offline_quality_state, global.offline_quality.as_ptr()),
+            (&mut self.midi_ducking_state, global.midi_ducking.as_ptr()),
+            (&mut self.duck_attack_state, global.duck_attack.as_ptr()),
+            (&mut self.duck_release_state, global.duck_release.as_ptr()),
+            (&mut self.learn_offset_state, global.learn_offset.as_ptr()),
+        ] {
+            chain.push((state.focus_mut(), param));
+        }
+
+        chain
+    }
+
+    /// フォーカスを表示順で次（`forward` が `false` なら前）のコントロールに移す。どこにもフォーカスが
+    /// なければ最初（最後）のコントロールにする
+    fn move_focus(&mut self, forward: bool) {
+        let mut chain = self.focus_chain();
+        let len = chain.len();
+        if len == 0 {
+            return;
+        }
+
+        let current = chain.iter().position(|(focus, _)| focus.is_focused());
+        let next = match (current, forward) {
+            (Some(index), true) => (index + 1) % len,
+            (Some(index), false) => (index + len - 1) % len,
+            (None, true) => 0,
+            (None, false) => len - 1,
+        };
+        for (index, (focus, _)) in chain.iter_mut().enumerate() {
+            focus.set(index == next);
+        }
+    }
+
+    /// フォーカスしているコントロールの名前と値。スクリーンリーダーの代わりに、画面の上部に読める形で
+    /// 表示する
+    fn focus_label(&mut self) -> Option<String> {
+        let (_, param) = self
+            .focus_chain()
+            .into_iter()
+            .find(|(focus, _)| focus.is_focused())?;
+
+        // SAFETY: the pointer comes from one of our own parameters, which outlive the editor
+        Some(unsafe {
+            format!(
+                "{}: {}",
+                param.name(),
+                param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+            )
+        })
+    }
+
+    /// 1 バンド表示で表示するバンド。保存されている値が範囲外なら最後のバンドにする
+    fn focused_band(&self) -> usize {
+        self.params
+            .focused_band
+            .load(Ordering::Relaxed)
+            .min(NUM_BANDS - 1)
+    }
+
+    /// 保存されているテーマの設定から作った配色
+    fn theme(&self) -> theme::Theme {
+        theme::Theme::new(
+            self.params.dark_theme.load(Ordering::Relaxed),
+            self.band_accents(),
+        )
     }
 }
@@ -0,0 +1,126 @@
+//! 出力の L/R 相関係数を表示する横長のメーター。
+
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer,
+    Size, Widget,
+};
+use std::marker::PhantomData;
+
+const BORDER_WIDTH: f32 = 1.0;
+
+/// 中央を 0 として、左端が -1（逆相）、右端が +1（モノラル）
+pub struct CorrelationMeter<Message> {
+    correlation: f32,
+
+    width: Length,
+    height: Length,
+
+    /// We don't emit any messages, but iced requires us to define some message type anyways.
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> CorrelationMeter<Message> {
+    pub fn new(correlation: f32) -> Self {
+        Self {
+            correlation: correlation.clamp(-1.0, 1.0),
+
+            width: Length::Units(180),
+            height: Length::Units(14),
+
+            _phantom: PhantomData,
+        }
+    }
+}
+
+fn fill_rect(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+impl<Message> Widget<Message, Renderer> for CorrelationMeter<Message>
+where
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::from_rgb(0.12, 0.12, 0.14)),
+        );
+
+        // 中央から現在値までをバーで描く。負の相関はモノラルにしたときに打ち消し合うので赤にする
+        let center_x = bounds.x + bounds.width / 2.0;
+        let value_x = center_x + self.correlation * bounds.width / 2.0;
+        let color = if self.correlation < 0.0 {
+            Color::from_rgb(0.9, 0.3, 0.25)
+        } else {
+            Color::from_rgb(0.4, 0.8, 0.4)
+        };
+        fill_rect(
+            renderer,
+            Rectangle {
+                x: center_x.min(value_x),
+                y: bounds.y + 2.0,
+                width: (value_x - center_x).abs().max(1.0),
+                height: (bounds.height - 4.0).max(1.0),
+            },
+            color,
+        );
+
+        fill_rect(
+            renderer,
+            Rectangle {
+                x: center_x - 0.5,
+                y: bounds.y,
+                width: 1.0,
+                height: bounds.height,
+            },
+            Color::from_rgb(0.7, 0.7, 0.7),
+        );
+    }
+}
+
+impl<'a, Message> From<CorrelationMeter<Message>> for Element<'a, Message>
+where
+    Message: 'a + Clone,
+{
+    fn from(widget: CorrelationMeter<Message>) -> Self {
+        Element::new(widget)
+    }
+}
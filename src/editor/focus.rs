@@ -0,0 +1,87 @@
+//! キーボードでのフォーカスの移動と、フォーカスしているノブやスライダーの操作。
+//!
+//! iced にはカスタムウィジェットのフォーカスの仕組みがないので、ウィジェットの状態がそれぞれ [`Focus`] を持つ。
+//! クリックしたウィジェットにフォーカスが移り、Tab キーではエディターが表示順に並べた状態をたどって移す。
+
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::{keyboard, renderer, Background, Color, Point, Rectangle, Renderer};
+
+/// フォーカスの枠の色と太さ
+const RING_COLOR: Color = Color::from_rgb(0.3, 0.6, 1.0);
+const RING_WIDTH: f32 = 2.0;
+
+/// ウィジェットの状態に持たせるフォーカス
+#[derive(Debug, Default)]
+pub struct Focus {
+    focused: bool,
+}
+
+impl Focus {
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn set(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// クリックされたウィジェットにフォーカスを移す。どのウィジェットにもマウスのイベントが届くので、
+    /// 他の場所がクリックされたときはフォーカスを外す
+    pub fn on_mouse_press(&mut self, bounds: Rectangle, cursor_position: Point) {
+        self.focused = bounds.contains(cursor_position);
+    }
+
+    /// フォーカスしているときだけ `bounds` の周りに枠を描く
+    pub fn draw(&self, renderer: &mut Renderer, bounds: Rectangle) {
+        if !self.focused {
+            return;
+        }
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: Rectangle {
+                    x: bounds.x - RING_WIDTH,
+                    y: bounds.y - RING_WIDTH,
+                    width: bounds.width + RING_WIDTH * 2.0,
+                    height: bounds.height + RING_WIDTH * 2.0,
+                },
+                border_radius: 2.0,
+                border_width: RING_WIDTH,
+                border_color: RING_COLOR,
+            },
+            Background::Color(Color::TRANSPARENT),
+        );
+    }
+}
+
+/// フォーカスしているウィジェットへのキー操作
+#[derive(Debug, Clone, Copy)]
+pub enum KeyAction {
+    /// 値を `steps` 段階動かす。1 段階の大きさはウィジェットが決める
+    Nudge(f32),
+    /// 値を直接入力する
+    StartTextEntry,
+    /// デフォルト値に戻す
+    ResetToDefault,
+    /// フォーカスを外す
+    Blur,
+}
+
+impl KeyAction {
+    /// 上と右の矢印で増やし、下と左の矢印で減らす。Page Up/Down は 10 段階ずつ動かす。
+    /// Enter で値を入力し、Delete でデフォルト値に戻す
+    pub fn from_key_code(key_code: keyboard::KeyCode) -> Option<Self> {
+        use keyboard::KeyCode;
+
+        match key_code {
+            KeyCode::Up | KeyCode::Right => Some(KeyAction::Nudge(1.0)),
+            KeyCode::Down | KeyCode::Left => Some(KeyAction::Nudge(-1.0)),
+            KeyCode::PageUp => Some(KeyAction::Nudge(10.0)),
+            KeyCode::PageDown => Some(KeyAction::Nudge(-10.0)),
+            KeyCode::Enter | KeyCode::NumpadEnter => Some(KeyAction::StartTextEntry),
+            KeyCode::Delete => Some(KeyAction::ResetToDefault),
+            KeyCode::Escape => Some(KeyAction::Blur),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! バンドごとのゲインリダクション履歴を、右から左へ流れるタイムラインとして表示するウィジェット。
+
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer,
+    Size, Widget,
+};
+use std::marker::PhantomData;
+
+use crate::metering::{
+    GainReductionHistory, GAIN_REDUCTION_HISTORY_LEN, GAIN_REDUCTION_HISTORY_RATE_HZ,
+};
+use crate::processor::NUM_BANDS;
+
+/// 表示するゲインリダクションの最大値 (dB)
+const MAX_GAIN_REDUCTION_DB: f32 = 24.0;
+/// 横方向の目盛りの間隔 (dB)
+const DB_GRID_INTERVAL: f32 = 6.0;
+/// 縦方向の目盛りの間隔 (秒)
+const TIME_GRID_INTERVAL_SECONDS: f32 = 1.0;
+const GRID_COLOR: Color = Color::from_rgb(0.25, 0.25, 0.28);
+
+const BORDER_WIDTH: f32 = 1.0;
+
+/// 上端を 0 dB として、ゲインリダクションを下向きに描く。右端が最新で、左端が約 10 秒前になる。
+///
+/// 1 ピクセルの列に複数のエントリが入るときは、その中で最も深い値を使う。列の間は縦線でつなぐので、
+/// 速いリリースのポンピングも途切れずに見える。
+pub struct GainReductionHistoryView<'a, Message> {
+    history: &'a GainReductionHistory,
+    band_colors: [Color; NUM_BANDS],
+
+    width: Length,
+    height: Length,
+
+    /// We don't emit any messages, but iced requires us to define some message type anyways.
+    _phantom: PhantomData<Message>,
+}
+
+impl<'a, Message> GainReductionHistoryView<'a, Message> {
+    pub fn new(history: &'a GainReductionHistory, band_colors: [Color; NUM_BANDS]) -> Self {
+        Self {
+            history,
+            band_colors,
+
+            width: Length::Fill,
+            height: Length::Units(100),
+
+            _phantom: PhantomData,
+        }
+    }
+}
+
+fn fill_rect(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+impl<'a, Message> Widget<Message, Renderer> for GainReductionHistoryView<'a, Message>
+where
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::from_rgb(0.12, 0.12, 0.14)),
+        );
+
+        // 1 秒ごとの縦線と、6 dB ごとの横線
+        let seconds = GAIN_REDUCTION_HISTORY_LEN as f32 / GAIN_REDUCTION_HISTORY_RATE_HZ;
+        let mut time = TIME_GRID_INTERVAL_SECONDS;
+        while time < seconds {
+            let x = bounds.x + bounds.width * (1.0 - time / seconds);
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x,
+                    width: 1.0,
+                    ..bounds
+                },
+                GRID_COLOR,
+            );
+            time += TIME_GRID_INTERVAL_SECONDS;
+        }
+        let mut db = DB_GRID_INTERVAL;
+        while db < MAX_GAIN_REDUCTION_DB {
+            fill_rect(
+                renderer,
+                Rectangle {
+                    y: bounds.y + db / MAX_GAIN_REDUCTION_DB * bounds.height,
+                    height: 1.0,
+                    ..bounds
+                },
+                GRID_COLOR,
+            );
+            db += DB_GRID_INTERVAL;
+        }
+
+        // 古いエントリから順に、ピクセルの列ごとに最も深い値を集める。幅がエントリ数より広いときは
+        // 空の列ができないように、1 列を 1 エントリにして引き伸ばす
+        let columns = (bounds.width.floor() as usize).clamp(1, GAIN_REDUCTION_HISTORY_LEN);
+        let column_width = bounds.width / columns as f32;
+        let mut deepest = vec![[0.0f32; NUM_BANDS]; columns];
+        self.history.for_each(|index, gain_reduction_db| {
+            let column = index * columns / GAIN_REDUCTION_HISTORY_LEN;
+            for (deepest, value) in deepest[column].iter_mut().zip(gain_reduction_db) {
+                *deepest = deepest.min(value);
+            }
+        });
+
+        let depth_to_y = |reduction: f32| {
+            bounds.y + (-reduction / MAX_GAIN_REDUCTION_DB).clamp(0.0, 1.0) * bounds.height
+        };
+        for (band, color) in self.band_colors.iter().enumerate() {
+            let mut previous_y = None;
+            for (column, gain_reduction_db) in deepest.iter().enumerate() {
+                let y = depth_to_y(gain_reduction_db[band]);
+                let (top, bottom) = match previous_y {
+                    Some(previous_y) => (y.min(previous_y), y.max(previous_y)),
+                    None => (y, y),
+                };
+                fill_rect(
+                    renderer,
+                    Rectangle {
+                        x: bounds.x + column as f32 * column_width,
+                        y: top - 1.0,
+                        width: column_width.max(1.0),
+                        height: bottom - top + 2.0,
+                    },
+                    *color,
+                );
+                previous_y = Some(y);
+            }
+        }
+    }
+}
+
+impl<'a, Message> From<GainReductionHistoryView<'a, Message>> for Element<'a, Message>
+where
+    Message: 'a + Clone,
+{
+    fn from(widget: GainReductionHistoryView<'a, Message>) -> Self {
+        Element::new(widget)
+    }
+}
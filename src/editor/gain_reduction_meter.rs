@@ -0,0 +1,161 @@
+//! バンドごとのゲインリダクションを表示する縦長のメーター。
+
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer,
+    Size, Widget,
+};
+use std::marker::PhantomData;
+
+/// 表示するゲインリダクションの最大値 (dB)
+const MAX_GAIN_REDUCTION_DB: f32 = 24.0;
+/// 目盛りの間隔 (dB)
+const TICK_INTERVAL_DB: f32 = 6.0;
+
+const BORDER_WIDTH: f32 = 1.0;
+
+/// 上端を 0 dB として、ゲインリダクションを下向きのバーで描く。ピークホールドは横線で示す
+pub struct GainReductionMeter<Message> {
+    gain_reduction_db: f32,
+    hold_db: f32,
+    color: Color,
+
+    width: Length,
+    height: Length,
+
+    /// We don't emit any messages, but iced requires us to define some message type anyways.
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> GainReductionMeter<Message> {
+    /// `gain_reduction_db` と `hold_db` は 0 以下の dB
+    pub fn new(gain_reduction_db: f32, hold_db: f32, color: Color) -> Self {
+        Self {
+            gain_reduction_db,
+            hold_db,
+            color,
+
+            width: Length::Units(24),
+            height: Length::Units(120),
+
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// ゲインリダクション (dB) を上端からの深さ (0..1) に変換する
+fn depth(gain_reduction_db: f32) -> f32 {
+    (-gain_reduction_db / MAX_GAIN_REDUCTION_DB).clamp(0.0, 1.0)
+}
+
+fn fill_rect(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+impl<Message> Widget<Message, Renderer> for GainReductionMeter<Message>
+where
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::from_rgb(0.12, 0.12, 0.14)),
+        );
+
+        let inner = Rectangle {
+            x: bounds.x + BORDER_WIDTH,
+            y: bounds.y + BORDER_WIDTH,
+            width: bounds.width - BORDER_WIDTH * 2.0,
+            height: bounds.height - BORDER_WIDTH * 2.0,
+        };
+
+        // 6 dB ごとの目盛り
+        let num_ticks = (MAX_GAIN_REDUCTION_DB / TICK_INTERVAL_DB) as usize;
+        for tick in 1..num_ticks {
+            let y = inner.y + depth(-(tick as f32) * TICK_INTERVAL_DB) * inner.height;
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: inner.x,
+                    y,
+                    width: inner.width * 0.25,
+                    height: 1.0,
+                },
+                Color::from_rgb(0.4, 0.4, 0.45),
+            );
+        }
+
+        let bar_height = depth(self.gain_reduction_db) * inner.height;
+        if bar_height > 0.0 {
+            fill_rect(
+                renderer,
+                Rectangle {
+                    height: bar_height,
+                    ..inner
+                },
+                self.color,
+            );
+        }
+
+        // ピークホールドの位置に横線を引く
+        if self.hold_db < 0.0 {
+            let y = inner.y + depth(self.hold_db) * inner.height;
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: inner.x,
+                    y: (y - 1.0).max(inner.y),
+                    width: inner.width,
+                    height: 2.0,
+                },
+                Color::WHITE,
+            );
+        }
+    }
+}
+
+impl<'a, Message> From<GainReductionMeter<Message>> for Element<'a, Message>
+where
+    Message: 'a + Clone,
+{
+    fn from(widget: GainReductionMeter<Message>) -> Self {
+        Element::new(widget)
+    }
+}
@@ -0,0 +1,164 @@
+//! 出力の L/R を 45 度回転して点で描くゴニオメーター（ベクトルスコープ）。
+
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::text::Renderer as _;
+use nih_plug_iced::{
+    alignment, layout, renderer, text, Background, Color, Element, Font, Layout, Length, Point,
+    Rectangle, Renderer, Size, Widget,
+};
+use std::marker::PhantomData;
+
+use crate::metering::GONIOMETER_POINTS;
+
+const SIZE: u16 = 140;
+const BORDER_WIDTH: f32 = 1.0;
+const DOT_SIZE: f32 = 2.0;
+const TEXT_SIZE: u16 = 12;
+const AXIS_COLOR: Color = Color::from_rgb(0.35, 0.35, 0.4);
+
+/// 縦軸がミッド (L+R)、横軸がサイド (R-L)。モノラルの信号は縦の線に、L だけの信号は左上がりの斜線になる
+pub struct Goniometer<'a, Message> {
+    points: &'a [[f32; 2]; GONIOMETER_POINTS],
+    color: Color,
+
+    /// We don't emit any messages, but iced requires us to define some message type anyways.
+    _phantom: PhantomData<Message>,
+}
+
+impl<'a, Message> Goniometer<'a, Message> {
+    /// `points` は古い順に並んだ L/R の組
+    pub fn new(points: &'a [[f32; 2]; GONIOMETER_POINTS], color: Color) -> Self {
+        Self {
+            points,
+            color,
+
+            _phantom: PhantomData,
+        }
+    }
+}
+
+fn fill_rect(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+impl<'a, Message> Widget<Message, Renderer> for Goniometer<'a, Message>
+where
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        Length::Units(SIZE)
+    }
+
+    fn height(&self) -> Length {
+        Length::Units(SIZE)
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width()).height(self.height());
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::from_rgb(0.12, 0.12, 0.14)),
+        );
+
+        // ミッドとサイドの軸
+        let center = bounds.center();
+        fill_rect(
+            renderer,
+            Rectangle {
+                x: center.x - 0.5,
+                y: bounds.y,
+                width: 1.0,
+                height: bounds.height,
+            },
+            AXIS_COLOR,
+        );
+        fill_rect(
+            renderer,
+            Rectangle {
+                x: bounds.x,
+                y: center.y - 0.5,
+                width: bounds.width,
+                height: 1.0,
+            },
+            AXIS_COLOR,
+        );
+        for (label, x, horizontal_alignment) in [
+            ("L", bounds.x + 4.0, alignment::Horizontal::Left),
+            ("R", bounds.x + bounds.width - 4.0, alignment::Horizontal::Right),
+        ] {
+            renderer.fill_text(text::Text {
+                content: label,
+                font: Font::Default,
+                size: TEXT_SIZE as f32,
+                bounds: Rectangle {
+                    x,
+                    y: bounds.y + 2.0,
+                    ..bounds
+                },
+                color: AXIS_COLOR,
+                horizontal_alignment,
+                vertical_alignment: alignment::Vertical::Top,
+            });
+        }
+
+        // 0 dBFS の片チャンネルの信号が枠の角に届く大きさで描く。新しい点ほど濃くする
+        let radius = bounds.width.min(bounds.height) / 2.0 - DOT_SIZE;
+        for (index, [left, right]) in self.points.iter().enumerate() {
+            let side = (right - left) * std::f32::consts::FRAC_1_SQRT_2;
+            let mid = (left + right) * std::f32::consts::FRAC_1_SQRT_2;
+            let x = center.x + side.clamp(-1.0, 1.0) * radius;
+            let y = center.y - mid.clamp(-1.0, 1.0) * radius;
+            let alpha = 0.15 + 0.85 * (index + 1) as f32 / GONIOMETER_POINTS as f32;
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: x - DOT_SIZE / 2.0,
+                    y: y - DOT_SIZE / 2.0,
+                    width: DOT_SIZE,
+                    height: DOT_SIZE,
+                },
+                Color {
+                    a: alpha,
+                    ..self.color
+                },
+            );
+        }
+    }
+}
+
+impl<'a, Message> From<Goniometer<'a, Message>> for Element<'a, Message>
+where
+    Message: 'a + Clone,
+{
+    fn from(widget: Goniometer<'a, Message>) -> Self {
+        Element::new(widget)
+    }
+}
@@ -0,0 +1,412 @@
+//! パラメーターをドラッグで操作するロータリーノブ。
+
+use nih_plug::prelude::Param;
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::text::Renderer as _;
+use nih_plug_iced::widgets::ParamMessage;
+use nih_plug_iced::{
+    alignment, event, keyboard, layout, mouse, renderer, text, Background, Clipboard, Color,
+    Element, Event, Font, Layout, Length, Point, Rectangle, Renderer, Shell, Size, Widget,
+};
+
+use super::focus::{Focus, KeyAction};
+use super::text_entry::TextEntry;
+
+/// ノブ全体の幅と高さ
+const WIDTH: u16 = 64;
+const HEIGHT: u16 = 92;
+/// ノブの直径
+const DIAMETER: f32 = 44.0;
+/// ラベルと値の文字サイズ
+const TEXT_SIZE: u16 = 13;
+/// 円弧の開始角と終了角（ラジアン、真下から時計回りに 30 度ずつ空ける）
+const ARC_START: f32 = std::f32::consts::PI * (2.0 / 3.0);
+const ARC_END: f32 = std::f32::consts::PI * (7.0 / 3.0);
+/// 円弧を描くときの点の数
+const ARC_SEGMENTS: usize = 48;
+/// 上下方向に何ピクセルドラッグすると、正規化された値が 0 から 1 まで動くか
+const DRAG_RANGE_PX: f32 = 200.0;
+/// Shift を押しながらドラッグしたときの感度
+const FINE_DRAG_FACTOR: f32 = 0.1;
+/// 矢印キー 1 回で動かす量（正規化された値）。ステップのあるパラメーターでは 1 ステップ動かす
+const KEY_STEP: f32 = 0.01;
+
+/// ノブのドラッグ状態。エディターがパラメーターごとに保持する
+#[derive(Debug, Default)]
+pub struct State {
+    keyboard_modifiers: keyboard::Modifiers,
+    /// ドラッグを始めた、または Shift を押した／離したときの y 座標と正規化された値
+    drag_start: Option<(f32, f32)>,
+    /// ダブルクリックの判定用
+    last_click: Option<mouse::Click>,
+    /// 値を直接入力するための入力欄
+    text_entry: TextEntry,
+    focus: Focus,
+}
+
+impl State {
+    /// Tab キーでフォーカスを移すときにエディターが使う
+    pub fn focus_mut(&mut self) -> &mut Focus {
+        &mut self.focus
+    }
+}
+
+/// 円弧のインジケーター、パラメーター名、現在値を表示するノブ。
+///
+/// 上下のドラッグで値を変え、Shift で細かく調整できる。Ctrl（macOS では Cmd）+ クリックでデフォルト値に戻す。
+/// ダブルクリックか Alt + クリックで値を直接入力でき、入力した文字列はパラメーターの変換関数で解釈する。
+/// フォーカスしているときは [`KeyAction`] のキーでも操作できる。
+pub struct Knob<'a, P: Param> {
+    state: &'a mut State,
+    param: &'a P,
+    /// ノブの上に表示する名前。デフォルトはパラメーター名
+    label: &'a str,
+    color: Color,
+}
+
+impl<'a, P: Param> Knob<'a, P> {
+    pub fn new(state: &'a mut State, param: &'a P) -> Self {
+        Self {
+            state,
+            param,
+            label: param.name(),
+            color: Color::from_rgb(1.0, 0.8, 0.2),
+        }
+    }
+
+    /// パラメーター名の代わりに表示する名前。バンドの列の中ではバンド名を省いた短い名前にする
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// 円弧の色
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// 1 回のジェスチャーでデフォルト値に戻す
+    fn reset_to_default(&self, shell: &mut Shell<'_, ParamMessage>) {
+        shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+        self.set_normalized_value(shell, self.param.default_normalized_value());
+        shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+    }
+
+    fn set_normalized_value(&self, shell: &mut Shell<'_, ParamMessage>, normalized_value: f32) {
+        // ステップのあるパラメーターで同じ値を何度も送らないように、丸めた値が変わったときだけ送る
+        let plain_value = self.param.preview_plain(normalized_value.clamp(0.0, 1.0));
+        let normalized_value = self.param.preview_normalized(plain_value);
+        if normalized_value != self.param.unmodulated_normalized_value() {
+            shell.publish(ParamMessage::SetParameterNormalized(
+                self.param.as_ptr(),
+                normalized_value,
+            ));
+        }
+    }
+
+    /// 矢印キー 1 回分の変化量（正規化された値）。Shift を押すと細かくなる
+    fn key_step(&self) -> f32 {
+        match self.param.step_count() {
+            Some(step_count) => 1.0 / step_count as f32,
+            None if self.state.keyboard_modifiers.shift() => KEY_STEP * FINE_DRAG_FACTOR,
+            None => KEY_STEP,
+        }
+    }
+
+    /// 値の表示の位置。入力中はここに入力欄を置く
+    fn value_bounds(bounds: Rectangle) -> Rectangle {
+        let height = TEXT_SIZE as f32 + 2.0;
+        Rectangle {
+            y: bounds.y + bounds.height - height,
+            height,
+            ..bounds
+        }
+    }
+
+    /// ノブの円の中心
+    fn knob_center(bounds: Rectangle) -> Point {
+        Point::new(
+            bounds.center_x(),
+            bounds.y + TEXT_SIZE as f32 + 4.0 + DIAMETER / 2.0,
+        )
+    }
+}
+
+fn fill_dot(renderer: &mut Renderer, center: Point, size: f32, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds: Rectangle {
+                x: center.x - size / 2.0,
+                y: center.y - size / 2.0,
+                width: size,
+                height: size,
+            },
+            border_radius: size / 2.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+/// 中心 `center`、半径 `radius` の円弧を `from`..`to` の角度（ラジアン）で点を並べて描く
+fn draw_arc(
+    renderer: &mut Renderer,
+    center: Point,
+    radius: f32,
+    from: f32,
+    to: f32,
+    color: Color,
+) {
+    let segments = ((to - from) / (ARC_END - ARC_START) * ARC_SEGMENTS as f32).ceil() as usize;
+    for segment in 0..=segments {
+        let angle = from + (to - from) * segment as f32 / segments.max(1) as f32;
+        let point = Point::new(
+            center.x + radius * angle.cos(),
+            center.y + radius * angle.sin(),
+        );
+        fill_dot(renderer, point, 4.0, color);
+    }
+}
+
+impl<'a, P: Param> Widget<ParamMessage, Renderer> for Knob<'a, P> {
+    fn width(&self) -> Length {
+        Length::Units(WIDTH)
+    }
+
+    fn height(&self) -> Length {
+        Length::Units(HEIGHT)
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width()).height(self.height());
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, ParamMessage>,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+
+        if let Event::Mouse(mouse::Event::ButtonPressed(_)) = event {
+            self.state.focus.on_mouse_press(bounds, cursor_position);
+        }
+        if let Some(status) = self.state.text_entry.on_event(
+            self.param,
+            &event,
+            Self::value_bounds(bounds),
+            TEXT_SIZE,
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        ) {
+            return status;
+        }
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if !bounds.contains(cursor_position) {
+                    return event::Status::Ignored;
+                }
+
+                let click = mouse::Click::new(cursor_position, self.state.last_click);
+                self.state.last_click = Some(click);
+                if self.state.keyboard_modifiers.alt()
+                    || matches!(click.kind(), mouse::click::Kind::Double)
+                {
+                    self.state.text_entry.start(self.param);
+                } else if self.state.keyboard_modifiers.command() {
+                    self.reset_to_default(shell);
+                } else {
+                    shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+                    self.state.drag_start = Some((
+                        cursor_position.y,
+                        self.param.unmodulated_normalized_value(),
+                    ));
+                }
+
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                if self.state.drag_start.take().is_some() {
+                    shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if let Some((start_y, start_value)) = self.state.drag_start {
+                    let sensitivity = if self.state.keyboard_modifiers.shift() {
+                        FINE_DRAG_FACTOR
+                    } else {
+                        1.0
+                    };
+                    let delta = (start_y - cursor_position.y) / DRAG_RANGE_PX * sensitivity;
+                    self.set_normalized_value(shell, start_value + delta);
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code, .. })
+                if self.state.focus.is_focused() && self.state.drag_start.is_none() =>
+            {
+                let Some(action) = KeyAction::from_key_code(key_code) else {
+                    return event::Status::Ignored;
+                };
+                match action {
+                    KeyAction::Nudge(steps) => {
+                        let value = self.param.unmodulated_normalized_value();
+                        shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+                        self.set_normalized_value(shell, value + steps * self.key_step());
+                        shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+                    }
+                    KeyAction::StartTextEntry => self.state.text_entry.start(self.param),
+                    KeyAction::ResetToDefault => self.reset_to_default(shell),
+                    KeyAction::Blur => self.state.focus.set(false),
+                }
+
+                return event::Status::Captured;
+            }
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                // 感度が変わったところから続けてドラッグできるように、基準点を取り直す
+                if self.state.drag_start.is_some()
+                    && modifiers.shift() != self.state.keyboard_modifiers.shift()
+                {
+                    self.state.drag_start = Some((
+                        cursor_position.y,
+                        self.param.unmodulated_normalized_value(),
+                    ));
+                }
+                self.state.keyboard_modifiers = modifiers;
+            }
+            _ => {}
+        }
+
+        event::Status::Ignored
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if self.state.text_entry.is_active() {
+            mouse::Interaction::Text
+        } else if self.state.drag_start.is_some() || layout.bounds().contains(cursor_position) {
+            mouse::Interaction::ResizingVertically
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        self.state.focus.draw(renderer, bounds);
+        let center = Self::knob_center(bounds);
+        let radius = DIAMETER / 2.0;
+
+        renderer.fill_text(text::Text {
+            content: self.label,
+            font: Font::Default,
+            size: TEXT_SIZE as f32,
+            bounds: Rectangle {
+                x: bounds.center_x(),
+                y: bounds.y,
+                ..bounds
+            },
+            color: style.text_color,
+            horizontal_alignment: alignment::Horizontal::Center,
+            vertical_alignment: alignment::Vertical::Top,
+        });
+
+        // 背景の円と、値の範囲全体の円弧
+        fill_dot(
+            renderer,
+            center,
+            DIAMETER - 8.0,
+            Color::from_rgb(0.2, 0.2, 0.23),
+        );
+        draw_arc(
+            renderer,
+            center,
+            radius,
+            ARC_START,
+            ARC_END,
+            Color::from_rgb(0.35, 0.35, 0.4),
+        );
+
+        // 現在値までの円弧と、値を指す針
+        let value = self.param.unmodulated_normalized_value();
+        let angle = ARC_START + (ARC_END - ARC_START) * value;
+        draw_arc(renderer, center, radius, ARC_START, angle, self.color);
+        for step in 0..4 {
+            let distance = (radius - 6.0) * (step + 1) as f32 / 4.0;
+            let point = Point::new(
+                center.x + distance * angle.cos(),
+                center.y + distance * angle.sin(),
+            );
+            fill_dot(renderer, point, 3.0, style.text_color);
+        }
+
+        // 入力中は値の表示の代わりに入力欄を描く
+        if self.state.text_entry.draw(
+            renderer,
+            Self::value_bounds(bounds),
+            TEXT_SIZE,
+            cursor_position,
+        ) {
+            return;
+        }
+
+        renderer.fill_text(text::Text {
+            content: &self.param.to_string(),
+            font: Font::Default,
+            size: TEXT_SIZE as f32,
+            bounds: Rectangle {
+                x: bounds.center_x(),
+                y: bounds.y + bounds.height,
+                ..bounds
+            },
+            color: style.text_color,
+            horizontal_alignment: alignment::Horizontal::Center,
+            vertical_alignment: alignment::Vertical::Bottom,
+        });
+    }
+}
+
+impl<'a, P: Param> Knob<'a, P> {
+    /// エディターのメッセージ型に変換した [`Element`] を返す。`f` は [`ParamMessage`] を包む
+    /// バリアントで、エディターはそれを `handle_param_message()` に渡す
+    pub fn map<Message, F>(self, f: F) -> Element<'a, Message>
+    where
+        Message: 'static,
+        F: Fn(ParamMessage) -> Message + 'static,
+    {
+        Element::from(self).map(f)
+    }
+}
+
+impl<'a, P: Param> From<Knob<'a, P>> for Element<'a, ParamMessage> {
+    fn from(widget: Knob<'a, P>) -> Self {
+        Element::new(widget)
+    }
+}
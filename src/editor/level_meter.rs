@@ -0,0 +1,267 @@
+//! 表示範囲を選べる横長のレベルメーター。nih_plug_iced の `PeakMeter` の代わりに使う。
+
+use nih_plug::prelude::Enum;
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::text::Renderer as _;
+use nih_plug_iced::{
+    alignment, layout, renderer, text, Background, Color, Element, Font, Layout, Length, Point,
+    Rectangle, Renderer, Size, Widget,
+};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const WIDTH: u16 = 180;
+const BAR_HEIGHT: f32 = 12.0;
+const TEXT_SIZE: u16 = 10;
+const BORDER_WIDTH: f32 = 1.0;
+/// これより上のレベルは色を変える (dB)
+const WARNING_DB: f32 = -6.0;
+const NORMAL_COLOR: Color = Color::from_rgb(0.4, 0.8, 0.4);
+const WARNING_COLOR: Color = Color::from_rgb(0.95, 0.7, 0.25);
+const TICK_COLOR: Color = Color::from_rgb(0.5, 0.5, 0.55);
+
+/// メーターの表示範囲。上端は常に 0 dBFS。エディターの設定として保存する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum MeterScale {
+    #[name = "-60 dB"]
+    Range60,
+    #[name = "-30 dB"]
+    Range30,
+    #[name = "-90 dB"]
+    Range90,
+}
+
+impl MeterScale {
+    /// 保存されているインデックスから読み出す。範囲外なら -60 dB にする
+    pub fn load(index: &AtomicUsize) -> Self {
+        let index = index.load(Ordering::Relaxed);
+        if index < Self::variants().len() {
+            Self::from_index(index)
+        } else {
+            MeterScale::Range60
+        }
+    }
+
+    /// 表示名
+    pub fn name(self) -> &'static str {
+        Self::variants()[self.to_index()]
+    }
+
+    /// ボタンで切り替えるときの次の表示範囲
+    pub fn next(self) -> Self {
+        Self::from_index((self.to_index() + 1) % Self::variants().len())
+    }
+
+    /// 左端のレベル (dB)
+    fn min_db(self) -> f32 {
+        match self {
+            MeterScale::Range60 => -60.0,
+            MeterScale::Range30 => -30.0,
+            MeterScale::Range90 => -90.0,
+        }
+    }
+
+    /// 目盛りの間隔 (dB)
+    fn tick_step_db(self) -> f32 {
+        match self {
+            MeterScale::Range60 => 10.0,
+            MeterScale::Range30 => 6.0,
+            MeterScale::Range90 => 15.0,
+        }
+    }
+}
+
+/// ピークホールドの状態。エディターがメーターごとに保持する
+#[derive(Debug, Default)]
+pub struct State {
+    /// 保持しているレベル (dB) と、それを記録した時刻
+    held: Option<(f32, Instant)>,
+}
+
+/// 左端から現在のレベルまでを塗りつぶし、下に目盛りを表示するメーター
+pub struct LevelMeter<'a, Message> {
+    state: &'a mut State,
+    level_db: f32,
+    scale: MeterScale,
+
+    /// We don't emit any messages, but iced requires us to define some message type anyways.
+    _phantom: PhantomData<Message>,
+}
+
+impl<'a, Message> LevelMeter<'a, Message> {
+    pub fn new(state: &'a mut State, level_db: f32, scale: MeterScale) -> Self {
+        Self {
+            state,
+            level_db,
+            scale,
+
+            _phantom: PhantomData,
+        }
+    }
+
+    /// 最大値を `hold_time` の間、線で表示する
+    pub fn hold_time(self, hold_time: Duration) -> Self {
+        let now = Instant::now();
+        let expired = match self.state.held {
+            Some((held_db, held_at)) => {
+                self.level_db >= held_db || now.duration_since(held_at) > hold_time
+            }
+            None => true,
+        };
+        if expired {
+            self.state.held = Some((self.level_db, now));
+        }
+
+        self
+    }
+
+    /// `db` を表示範囲の中の 0〜1 の位置にする
+    fn position(&self, db: f32) -> f32 {
+        let min_db = self.scale.min_db();
+        ((db - min_db) / -min_db).clamp(0.0, 1.0)
+    }
+}
+
+fn fill_rect(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+impl<'a, Message> Widget<Message, Renderer> for LevelMeter<'a, Message>
+where
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        Length::Units(WIDTH)
+    }
+
+    fn height(&self) -> Length {
+        Length::Units(BAR_HEIGHT as u16 + TEXT_SIZE + 2)
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width()).height(self.height());
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let bar = Rectangle {
+            height: BAR_HEIGHT,
+            ..bounds
+        };
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: bar,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::from_rgb(0.12, 0.12, 0.14)),
+        );
+
+        // 警告レベルまでと、それより上を色分けして塗る
+        let inner_x = bar.x + BORDER_WIDTH;
+        let inner_width = bar.width - BORDER_WIDTH * 2.0;
+        let level = self.position(self.level_db);
+        let warning = self.position(WARNING_DB);
+        for (start, end, color) in [
+            (0.0, level.min(warning), NORMAL_COLOR),
+            (warning, level, WARNING_COLOR),
+        ] {
+            if end > start {
+                fill_rect(
+                    renderer,
+                    Rectangle {
+                        x: inner_x + start * inner_width,
+                        y: bar.y + BORDER_WIDTH,
+                        width: (end - start) * inner_width,
+                        height: bar.height - BORDER_WIDTH * 2.0,
+                    },
+                    color,
+                );
+            }
+        }
+
+        if let Some((held_db, _)) = self.state.held {
+            if held_db > self.scale.min_db() {
+                fill_rect(
+                    renderer,
+                    Rectangle {
+                        x: inner_x + self.position(held_db) * inner_width - 1.0,
+                        y: bar.y,
+                        width: 1.0,
+                        height: bar.height,
+                    },
+                    style.text_color,
+                );
+            }
+        }
+
+        // 目盛りと、その下の数値
+        let min_db = self.scale.min_db();
+        let step_db = self.scale.tick_step_db();
+        let ticks = (-min_db / step_db).round() as usize;
+        for tick in 0..=ticks {
+            let db = min_db + tick as f32 * step_db;
+            let x = inner_x + self.position(db) * inner_width;
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: x - 0.5,
+                    y: bar.y + bar.height - 3.0,
+                    width: 1.0,
+                    height: 3.0,
+                },
+                TICK_COLOR,
+            );
+            let horizontal_alignment = if tick == 0 {
+                alignment::Horizontal::Left
+            } else if tick == ticks {
+                alignment::Horizontal::Right
+            } else {
+                alignment::Horizontal::Center
+            };
+            renderer.fill_text(text::Text {
+                content: &format!("{:.0}", db),
+                font: Font::Default,
+                size: TEXT_SIZE as f32,
+                bounds: Rectangle {
+                    x,
+                    y: bar.y + bar.height + 2.0,
+                    ..bounds
+                },
+                color: TICK_COLOR,
+                horizontal_alignment,
+                vertical_alignment: alignment::Vertical::Top,
+            });
+        }
+    }
+}
+
+impl<'a, Message> From<LevelMeter<'a, Message>> for Element<'a, Message>
+where
+    Message: 'a + Clone,
+{
+    fn from(widget: LevelMeter<'a, Message>) -> Self {
+        Element::new(widget)
+    }
+}
@@ -0,0 +1,165 @@
+//! MIDI ラーンと、CC で動かすパラメーターのソフトテイクオーバー。
+//!
+//! MIDI ラーンを始めてからエディターのコントロールを動かし、続けてコントローラーのノブを回すと、その CC に
+//! パラメーターを割り当てる。割り当てた CC は、ノブの位置がパラメーターの今の値に追いつくまで（ソフト
+//! テイクオーバー）パラメーターを動かさないので、値が急に飛ばない。
+
+use nih_plug::prelude::{ParamPtr, Params};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use crate::midi::{MidiCcEvent, MidiCcMappings};
+
+/// CC の値とパラメーターの値（どちらも正規化した値）がこれより近ければ、ノブが追いついたとみなす
+const PICKUP_DISTANCE: f32 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LearnState {
+    Off,
+    /// 割り当てるパラメーターを動かすのを待っている
+    WaitingForParam,
+    /// 割り当てる CC を待っている
+    WaitingForCc(ParamPtr),
+}
+
+/// ソフトテイクオーバーのための CC ごとの状態
+#[derive(Debug, Clone, Copy, Default)]
+struct Takeover {
+    /// 前回受け取った CC の値
+    last_cc_value: Option<f32>,
+    /// 前回この CC で設定した後のパラメーターの値。パラメーターがほかの操作で動いたら追いつき直す
+    last_set_value: Option<f32>,
+}
+
+/// MIDI ラーンの状態と、CC ごとのソフトテイクオーバーの状態
+pub struct MidiLearn {
+    /// パラメーター ID とポインターの組
+    params: Vec<(String, ParamPtr)>,
+    learn: LearnState,
+    takeover: BTreeMap<u8, Takeover>,
+}
+
+impl MidiLearn {
+    pub fn new(params: &dyn Params) -> Self {
+        Self {
+            params: params
+                .param_map()
+                .into_iter()
+                .map(|(id, param, _)| (id, param))
+                .collect(),
+            learn: LearnState::Off,
+            takeover: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learn != LearnState::Off
+    }
+
+    /// MIDI ラーンを始める、またはやめる
+    pub fn toggle_learn(&mut self) {
+        self.learn = if self.is_learning() {
+            LearnState::Off
+        } else {
+            LearnState::WaitingForParam
+        };
+    }
+
+    /// エディターでパラメーターの操作が始まったときに呼ぶ。MIDI ラーン中ならそのパラメーターを割り当てる
+    pub fn touch(&mut self, param: ParamPtr) {
+        if self.is_learning() {
+            self.learn = LearnState::WaitingForCc(param);
+        }
+    }
+
+    /// MIDI ラーン中に表示する案内
+    pub fn prompt(&self) -> Option<String> {
+        match self.learn {
+            LearnState::Off => None,
+            LearnState::WaitingForParam => {
+                Some(String::from("MIDI Learn: move the control to assign"))
+            }
+            LearnState::WaitingForCc(param) => {
+                // SAFETY: the pointer comes from our own parameters, which outlive the editor
+                let name = unsafe { param.name() };
+                Some(format!("MIDI Learn: move a MIDI controller to assign it to {name}"))
+            }
+        }
+    }
+
+    /// 全ての割り当てを消す
+    pub fn clear(&mut self, mappings: &RwLock<MidiCcMappings>) {
+        if let Ok(mut mappings) = mappings.write() {
+            mappings.clear();
+        }
+        self.takeover.clear();
+    }
+
+    /// 受け取った CC を処理し、設定するパラメーターと正規化された値を返す。MIDI ラーン中なら CC を
+    /// 割り当て、ノブがまだパラメーターの値に追いついていなければ何もしない
+    pub fn handle(
+        &mut self,
+        event: MidiCcEvent,
+        mappings: &RwLock<MidiCcMappings>,
+    ) -> Option<(ParamPtr, f32)> {
+        if let LearnState::WaitingForCc(param) = self.learn {
+            self.learn = LearnState::Off;
+            let id = self.id(param)?;
+            if let Ok(mut mappings) = mappings.write() {
+                // 1 つのパラメーターには 1 つの CC だけを割り当てる
+                mappings.retain(|_, mapped| mapped.as_str() != id);
+                mappings.insert(event.cc, id.to_owned());
+            }
+            // 割り当てた直後も、ノブが追いつくまではパラメーターを動かさない
+            self.takeover.insert(
+                event.cc,
+                Takeover {
+                    last_cc_value: Some(event.value),
+                    last_set_value: None,
+                },
+            );
+
+            return None;
+        }
+
+        let param = {
+            let mappings = mappings.read().ok()?;
+            self.param(mappings.get(&event.cc)?)?
+        };
+
+        // SAFETY: the pointer comes from our own parameters, which outlive the editor
+        let current = unsafe { param.unmodulated_normalized_value() };
+        let takeover = self.takeover.entry(event.cc).or_default();
+        let picked_up = takeover.last_set_value == Some(current)
+            || (event.value - current).abs() <= PICKUP_DISTANCE
+            // 前回の CC からの間にパラメーターの値をまたいだ
+            || takeover
+                .last_cc_value
+                .is_some_and(|last| (last - current) * (event.value - current) <= 0.0);
+        takeover.last_cc_value = Some(event.value);
+        if !picked_up {
+            return None;
+        }
+
+        // 段階的なパラメーターは設定した値が丸められるので、丸めた後の値を覚えておく
+        // SAFETY: see above
+        takeover.last_set_value =
+            Some(unsafe { param.preview_normalized(param.preview_plain(event.value)) });
+
+        Some((param, event.value))
+    }
+
+    fn id(&self, param: ParamPtr) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(_, candidate)| *candidate == param)
+            .map(|(id, _)| id.as_str())
+    }
+
+    fn param(&self, id: &str) -> Option<ParamPtr> {
+        self.params
+            .iter()
+            .find(|(candidate, _)| candidate == id)
+            .map(|&(_, param)| param)
+    }
+}
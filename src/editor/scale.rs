@@ -0,0 +1,129 @@
+//! ユーザーが選ぶ GUI の拡大率。ホストから指定されたスケールに掛け合わせてエディターを開く。
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::{Editor, Enum, GuiContext, ParentWindowHandle};
+use std::any::Any;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// GUI の拡大率
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum UiScale {
+    #[name = "75%"]
+    Percent75,
+    #[name = "100%"]
+    Percent100,
+    #[name = "125%"]
+    Percent125,
+    #[name = "150%"]
+    Percent150,
+    #[name = "175%"]
+    Percent175,
+    #[name = "200%"]
+    Percent200,
+}
+
+impl UiScale {
+    /// 保存されているインデックスから読み出す。範囲外なら 100% にする
+    pub fn load(index: &AtomicUsize) -> Self {
+        let index = index.load(Ordering::Relaxed);
+        if index < Self::variants().len() {
+            Self::from_index(index)
+        } else {
+            UiScale::Percent100
+        }
+    }
+
+    pub fn factor(self) -> f32 {
+        match self {
+            UiScale::Percent75 => 0.75,
+            UiScale::Percent100 => 1.0,
+            UiScale::Percent125 => 1.25,
+            UiScale::Percent150 => 1.5,
+            UiScale::Percent175 => 1.75,
+            UiScale::Percent200 => 2.0,
+        }
+    }
+
+    /// 表示名
+    pub fn name(self) -> &'static str {
+        Self::variants()[self.to_index()]
+    }
+
+    /// ボタンで切り替えるときの次の拡大率。200% の次は 75% に戻る
+    pub fn next(self) -> Self {
+        Self::from_index((self.to_index() + 1) % Self::variants().len())
+    }
+}
+
+/// iced のエディターを包み、ウィンドウの大きさとスケールにユーザーの拡大率を掛ける。
+///
+/// iced のウィンドウは開いた後にスケールを変えられないので、拡大率の変更は次にエディターを開いたときに
+/// 反映される。
+pub struct ScaledEditor {
+    inner: Box<dyn Editor>,
+    /// 保存されている拡大率（`UiScale` のインデックス）
+    ui_scale: Arc<AtomicUsize>,
+    /// ホストから指定されたスケール。指定されていなければ 0
+    host_scale_factor: AtomicF32,
+}
+
+impl ScaledEditor {
+    pub fn new(inner: Box<dyn Editor>, ui_scale: Arc<AtomicUsize>) -> Self {
+        Self {
+            inner,
+            ui_scale,
+            host_scale_factor: AtomicF32::new(0.0),
+        }
+    }
+
+    fn ui_scale(&self) -> UiScale {
+        UiScale::load(&self.ui_scale)
+    }
+}
+
+impl Editor for ScaledEditor {
+    fn spawn(
+        &self,
+        parent: ParentWindowHandle,
+        context: Arc<dyn GuiContext>,
+    ) -> Box<dyn Any + Send> {
+        // 100% でホストからの指定もなければ、システムのスケールに任せる
+        let ui_scale = self.ui_scale().factor();
+        let host_scale_factor = self.host_scale_factor.load(Ordering::Relaxed);
+        if host_scale_factor > 0.0 {
+            self.inner.set_scale_factor(host_scale_factor * ui_scale);
+        } else if ui_scale != 1.0 {
+            self.inner.set_scale_factor(ui_scale);
+        }
+
+        self.inner.spawn(parent, context)
+    }
+
+    fn size(&self) -> (u32, u32) {
+        let (width, height) = self.inner.size();
+        let ui_scale = self.ui_scale().factor();
+
+        (
+            (width as f32 * ui_scale).round() as u32,
+            (height as f32 * ui_scale).round() as u32,
+        )
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        self.host_scale_factor.store(factor, Ordering::Relaxed);
+        self.inner.set_scale_factor(factor * self.ui_scale().factor())
+    }
+
+    fn param_value_changed(&self, id: &str, normalized_value: f32) {
+        self.inner.param_value_changed(id, normalized_value)
+    }
+
+    fn param_modulation_changed(&self, id: &str, modulation_offset: f32) {
+        self.inner.param_modulation_changed(id, modulation_offset)
+    }
+
+    fn param_values_changed(&self) {
+        self.inner.param_values_changed()
+    }
+}
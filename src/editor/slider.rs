@@ -0,0 +1,403 @@
+//! パラメーターを操作する横長のスライダー。nih_plug_iced の `ParamSlider` の代わりに使う。
+
+use nih_plug::prelude::{FloatParam, Param};
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::text::Renderer as _;
+use nih_plug_iced::widgets::ParamMessage;
+use nih_plug_iced::{
+    alignment, event, keyboard, layout, mouse, renderer, text, Background, Clipboard, Color,
+    Element, Event, Font, Layout, Length, Point, Rectangle, Renderer, Shell, Size, Widget,
+};
+
+use super::focus::{Focus, KeyAction};
+use super::text_entry::TextEntry;
+
+const WIDTH: u16 = 200;
+const HEIGHT: u16 = 24;
+const TEXT_SIZE: u16 = 13;
+const BORDER_WIDTH: f32 = 1.0;
+/// 値の位置まで塗りつぶす色
+const FILL_COLOR: Color = Color::from_rgba(1.0, 0.8, 0.2, 0.5);
+/// 名前と値の左右の余白
+const TEXT_PADDING: f32 = 6.0;
+/// Shift を押しながらドラッグしたときの感度
+const FINE_DRAG_FACTOR: f32 = 0.1;
+/// マウスホイール 1 行分で動かす量（正規化された値）。ステップのあるパラメーターでは 1 ステップ動かす
+const WHEEL_STEP: f32 = 0.02;
+/// トラックパッドなどのピクセル単位のスクロールを行数に換算する
+const WHEEL_PIXELS_PER_LINE: f32 = 20.0;
+
+/// スライダーのドラッグ状態。エディターがパラメーターごとに保持する
+#[derive(Debug, Default)]
+pub struct State {
+    keyboard_modifiers: keyboard::Modifiers,
+    /// ドラッグを始めた、または Shift を押した／離したときの x 座標とスライダーの位置
+    drag_start: Option<(f32, f32)>,
+    /// ダブルクリックの判定用
+    last_click: Option<mouse::Click>,
+    /// 値を直接入力するための入力欄
+    text_entry: TextEntry,
+    focus: Focus,
+}
+
+impl State {
+    /// Tab キーでフォーカスを移すときにエディターが使う
+    pub fn focus_mut(&mut self) -> &mut Focus {
+        &mut self.focus
+    }
+}
+
+/// 左にパラメーター名、右に値を表示し、値の位置まで塗りつぶすスライダー。
+///
+/// ドラッグはクリックした位置に値が飛ばない相対的な動きで、Shift で細かく調整できる。マウスホイールでも
+/// 値を増減でき、Shift を押すと細かくなる。Ctrl（macOS では Cmd）+ クリックでデフォルト値に戻し、
+/// ダブルクリックか Alt + クリックで値を直接入力する。フォーカスしているときは [`KeyAction`] のキーでも
+/// 操作できる。
+pub struct Slider<'a, P: Param> {
+    state: &'a mut State,
+    param: &'a P,
+    /// 対数の目盛りで操作するときは `param` と同じパラメーターを指す
+    log_param: Option<&'a FloatParam>,
+}
+
+impl<'a> Slider<'a, FloatParam> {
+    /// 周波数のパラメーターを対数（オクターブ）の目盛りで操作する。パラメーター自体の範囲が線形でも、
+    /// スライダーの位置は 1 オクターブあたり同じ幅になる
+    pub fn logarithmic(mut self) -> Self {
+        self.log_param = Some(self.param);
+        self
+    }
+}
+
+impl<'a, P: Param> Slider<'a, P> {
+    pub fn new(state: &'a mut State, param: &'a P) -> Self {
+        Self {
+            state,
+            param,
+            log_param: None,
+        }
+    }
+
+    /// 塗りつぶしとドラッグに使う 0〜1 の位置。線形の目盛りでは正規化された値そのもの
+    fn position(&self) -> f32 {
+        match self.log_param {
+            Some(param) => {
+                let (min, max) = log_bounds(param);
+                ((param.unmodulated_plain_value() / min).ln() / (max / min).ln()).clamp(0.0, 1.0)
+            }
+            None => self.param.unmodulated_normalized_value(),
+        }
+    }
+
+    /// スライダーの位置を `position` にする
+    fn set_position(&self, shell: &mut Shell<'_, ParamMessage>, position: f32) {
+        let position = position.clamp(0.0, 1.0);
+        let normalized_value = match self.log_param {
+            Some(param) => {
+                let (min, max) = log_bounds(param);
+                param.preview_normalized(min * (max / min).powf(position))
+            }
+            None => position,
+        };
+        self.set_normalized_value(shell, normalized_value);
+    }
+
+    /// 1 回のジェスチャーでデフォルト値に戻す
+    fn reset_to_default(&self, shell: &mut Shell<'_, ParamMessage>) {
+        shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+        self.set_normalized_value(shell, self.param.default_normalized_value());
+        shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+    }
+
+    fn set_normalized_value(&self, shell: &mut Shell<'_, ParamMessage>, normalized_value: f32) {
+        // ステップのあるパラメーターで同じ値を何度も送らないように、丸めた値が変わったときだけ送る
+        let plain_value = self.param.preview_plain(normalized_value.clamp(0.0, 1.0));
+        let normalized_value = self.param.preview_normalized(plain_value);
+        if normalized_value != self.param.unmodulated_normalized_value() {
+            shell.publish(ParamMessage::SetParameterNormalized(
+                self.param.as_ptr(),
+                normalized_value,
+            ));
+        }
+    }
+
+    /// マウスホイール 1 行分と矢印キー 1 回分の変化量（スライダーの位置）
+    fn wheel_step(&self) -> f32 {
+        let step = match self.param.step_count() {
+            Some(step_count) => 1.0 / step_count as f32,
+            None => WHEEL_STEP,
+        };
+        if self.state.keyboard_modifiers.shift() && self.param.step_count().is_none() {
+            step * FINE_DRAG_FACTOR
+        } else {
+            step
+        }
+    }
+
+    /// 入力中に入力欄を置く位置。枠の内側いっぱいに置く
+    fn text_entry_bounds(bounds: Rectangle) -> Rectangle {
+        let height = TEXT_SIZE as f32 + 2.0;
+        Rectangle {
+            x: bounds.x + TEXT_PADDING,
+            y: bounds.center_y() - height / 2.0,
+            width: bounds.width - TEXT_PADDING * 2.0,
+            height,
+        }
+    }
+}
+
+impl<'a, P: Param> Widget<ParamMessage, Renderer> for Slider<'a, P> {
+    fn width(&self) -> Length {
+        Length::Units(WIDTH)
+    }
+
+    fn height(&self) -> Length {
+        Length::Units(HEIGHT)
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width()).height(self.height());
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, ParamMessage>,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+
+        if let Event::Mouse(mouse::Event::ButtonPressed(_)) = event {
+            self.state.focus.on_mouse_press(bounds, cursor_position);
+        }
+        if let Some(status) = self.state.text_entry.on_event(
+            self.param,
+            &event,
+            Self::text_entry_bounds(bounds),
+            TEXT_SIZE,
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        ) {
+            return status;
+        }
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if !bounds.contains(cursor_position) {
+                    return event::Status::Ignored;
+                }
+
+                let click = mouse::Click::new(cursor_position, self.state.last_click);
+                self.state.last_click = Some(click);
+                if self.state.keyboard_modifiers.alt()
+                    || matches!(click.kind(), mouse::click::Kind::Double)
+                {
+                    self.state.text_entry.start(self.param);
+                } else if self.state.keyboard_modifiers.command() {
+                    self.reset_to_default(shell);
+                } else {
+                    shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+                    self.state.drag_start = Some((cursor_position.x, self.position()));
+                }
+
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                if self.state.drag_start.take().is_some() {
+                    shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if let Some((start_x, start_value)) = self.state.drag_start {
+                    let sensitivity = if self.state.keyboard_modifiers.shift() {
+                        FINE_DRAG_FACTOR
+                    } else {
+                        1.0
+                    };
+                    let delta = (cursor_position.x - start_x) / bounds.width.max(1.0) * sensitivity;
+                    self.set_position(shell, start_value + delta);
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                if self.state.drag_start.is_some() || !bounds.contains(cursor_position) {
+                    return event::Status::Ignored;
+                }
+
+                let lines = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / WHEEL_PIXELS_PER_LINE,
+                };
+                if lines != 0.0 {
+                    let position = self.position();
+                    shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+                    self.set_position(shell, position + lines * self.wheel_step());
+                    shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+                }
+
+                return event::Status::Captured;
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code, .. })
+                if self.state.focus.is_focused() && self.state.drag_start.is_none() =>
+            {
+                let Some(action) = KeyAction::from_key_code(key_code) else {
+                    return event::Status::Ignored;
+                };
+                match action {
+                    KeyAction::Nudge(steps) => {
+                        let position = self.position();
+                        shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+                        self.set_position(shell, position + steps * self.wheel_step());
+                        shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+                    }
+                    KeyAction::StartTextEntry => self.state.text_entry.start(self.param),
+                    KeyAction::ResetToDefault => self.reset_to_default(shell),
+                    KeyAction::Blur => self.state.focus.set(false),
+                }
+
+                return event::Status::Captured;
+            }
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                // 感度が変わったところから続けてドラッグできるように、基準点を取り直す
+                if self.state.drag_start.is_some()
+                    && modifiers.shift() != self.state.keyboard_modifiers.shift()
+                {
+                    self.state.drag_start = Some((cursor_position.x, self.position()));
+                }
+                self.state.keyboard_modifiers = modifiers;
+            }
+            _ => {}
+        }
+
+        event::Status::Ignored
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if self.state.text_entry.is_active() {
+            mouse::Interaction::Text
+        } else if self.state.drag_start.is_some() || layout.bounds().contains(cursor_position) {
+            mouse::Interaction::ResizingHorizontally
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        self.state.focus.draw(renderer, bounds);
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: style.text_color,
+            },
+            Background::Color(Color::TRANSPARENT),
+        );
+
+        let fill_width = (bounds.width - BORDER_WIDTH * 2.0) * self.position();
+        if fill_width > 0.0 {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: bounds.x + BORDER_WIDTH,
+                        y: bounds.y + BORDER_WIDTH,
+                        width: fill_width,
+                        height: bounds.height - BORDER_WIDTH * 2.0,
+                    },
+                    border_radius: 0.0,
+                    border_width: 0.0,
+                    border_color: Color::TRANSPARENT,
+                },
+                Background::Color(FILL_COLOR),
+            );
+        }
+
+        // 入力中は名前と値の代わりに入力欄を描く
+        if self.state.text_entry.draw(
+            renderer,
+            Self::text_entry_bounds(bounds),
+            TEXT_SIZE,
+            cursor_position,
+        ) {
+            return;
+        }
+
+        for (content, x, horizontal_alignment) in [
+            (
+                self.param.name().to_string(),
+                bounds.x + TEXT_PADDING,
+                alignment::Horizontal::Left,
+            ),
+            (
+                self.param.to_string(),
+                bounds.x + bounds.width - TEXT_PADDING,
+                alignment::Horizontal::Right,
+            ),
+        ] {
+            renderer.fill_text(text::Text {
+                content: &content,
+                font: Font::Default,
+                size: TEXT_SIZE as f32,
+                bounds: Rectangle {
+                    x,
+                    y: bounds.center_y(),
+                    ..bounds
+                },
+                color: style.text_color,
+                horizontal_alignment,
+                vertical_alignment: alignment::Vertical::Center,
+            });
+        }
+    }
+}
+
+impl<'a, P: Param> Slider<'a, P> {
+    /// エディターのメッセージ型に変換した [`Element`] を返す。`f` は [`ParamMessage`] を包む
+    /// バリアントで、エディターはそれを `handle_param_message()` に渡す
+    pub fn map<Message, F>(self, f: F) -> Element<'a, Message>
+    where
+        Message: 'static,
+        F: Fn(ParamMessage) -> Message + 'static,
+    {
+        Element::from(self).map(f)
+    }
+}
+
+impl<'a, P: Param> From<Slider<'a, P>> for Element<'a, ParamMessage> {
+    fn from(widget: Slider<'a, P>) -> Self {
+        Element::new(widget)
+    }
+}
+
+/// 対数の目盛りの両端の周波数。0 Hz は対数にできないので 1 Hz 以上にする
+fn log_bounds(param: &FloatParam) -> (f32, f32) {
+    let min = param.preview_plain(0.0).max(1.0);
+    let max = param.preview_plain(1.0).max(min * 2.0);
+    (min, max)
+}
@@ -0,0 +1,398 @@
+//! 入力と出力のスペクトルを重ねて表示するウィジェット。クロスオーバー周波数はドラッグで変更できる。
+
+use nih_plug::prelude::{FloatParam, Param};
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::widgets::ParamMessage;
+use nih_plug_iced::{
+    event, layout, mouse, renderer, Background, Clipboard, Color, Element, Event, Layout,
+    Length, Point, Rectangle, Renderer, Shell, Size, Widget,
+};
+
+use crate::processor::NUM_BANDS;
+use crate::spectrum::FFT_SIZE;
+
+/// 表示する周波数の範囲 (Hz)
+pub const MIN_FREQUENCY: f32 = 20.0;
+pub const MAX_FREQUENCY: f32 = 20_000.0;
+/// 表示する dB の範囲
+const MIN_DB: f32 = -90.0;
+const MAX_DB: f32 = 0.0;
+/// クロスオーバー特性を表示する dB の範囲。スペクトルとは別のスケールで重ねて描く
+const RESPONSE_MIN_DB: f32 = -36.0;
+const RESPONSE_MAX_DB: f32 = 6.0;
+/// クロスオーバー特性を計算する周波数の点数（[`MIN_FREQUENCY`]..=[`MAX_FREQUENCY`] の対数スケール）
+pub const RESPONSE_POINTS: usize = 512;
+
+const BORDER_WIDTH: f32 = 1.0;
+/// クロスオーバーのハンドルを掴める、線からの距離 (px)
+const HANDLE_GRAB_DISTANCE: f32 = 6.0;
+const HANDLE_WIDTH: f32 = 8.0;
+const HANDLE_HEIGHT: f32 = 12.0;
+/// 各バンドの範囲を塗る色の不透明度
+const BAND_SHADE_ALPHA: f32 = 0.08;
+
+/// ドラッグ中のハンドル。エディターが保持する
+#[derive(Debug, Default)]
+pub struct State {
+    /// ドラッグしているクロスオーバーのインデックス
+    drag_active: Option<usize>,
+}
+
+/// 周波数を対数スケールで 0..1 に写す
+pub fn frequency_to_normalized(frequency: f32) -> f32 {
+    (frequency.max(MIN_FREQUENCY) / MIN_FREQUENCY).log2() / (MAX_FREQUENCY / MIN_FREQUENCY).log2()
+}
+
+/// [`frequency_to_normalized()`] の逆変換
+pub fn normalized_to_frequency(normalized: f32) -> f32 {
+    MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(normalized.clamp(0.0, 1.0))
+}
+
+/// 入力（グレー）と出力（青）のスペクトル、クロスオーバー位置を表示する。
+///
+/// クロスオーバーの線をドラッグすると、対応するパラメーターの変更を [`ParamMessage`] で送る。
+pub struct SpectrumView<'a> {
+    state: &'a mut State,
+    /// ビンごとのスペクトル (dB)
+    input: &'a [f32],
+    output: &'a [f32],
+    /// ビンを周波数に変換するためのサンプルレート
+    sample_rate: f32,
+    /// クロスオーバー周波数のパラメーター（低い方から順に）
+    crossovers: [&'a FloatParam; 2],
+    /// 各バンドのクロスオーバー特性 (dB)。[`RESPONSE_POINTS`] 点の対数グリッドで計算したもの
+    band_responses_db: &'a [Vec<f32>; NUM_BANDS],
+    band_colors: [Color; NUM_BANDS],
+
+    width: Length,
+    height: Length,
+}
+
+impl<'a> SpectrumView<'a> {
+    pub fn new(
+        state: &'a mut State,
+        input: &'a [f32],
+        output: &'a [f32],
+        sample_rate: f32,
+        crossovers: [&'a FloatParam; 2],
+        band_responses_db: &'a [Vec<f32>; NUM_BANDS],
+        band_colors: [Color; NUM_BANDS],
+    ) -> Self {
+        Self {
+            state,
+            input,
+            output,
+            sample_rate,
+            crossovers,
+            band_responses_db,
+            band_colors,
+
+            width: Length::Fill,
+            height: Length::Units(140),
+        }
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// クロスオーバーの位置 (Hz)。ドラッグ中もパラメーターの値をそのまま使う
+    fn crossover_frequencies(&self) -> [f32; 2] {
+        self.crossovers.map(|param| param.value())
+    }
+
+    /// `x` に最も近いハンドルのインデックス。[`HANDLE_GRAB_DISTANCE`] より離れていれば `None`
+    fn handle_at(&self, bounds: Rectangle, x: f32) -> Option<usize> {
+        self.crossover_frequencies()
+            .iter()
+            .map(|&frequency| {
+                (bounds.x + frequency_to_normalized(frequency) * bounds.width - x).abs()
+            })
+            .enumerate()
+            .filter(|&(_, distance)| distance <= HANDLE_GRAB_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// ドラッグ中のハンドルを `x` の周波数に動かす
+    fn set_frequency_from_x(
+        &self,
+        shell: &mut Shell<'_, ParamMessage>,
+        bounds: Rectangle,
+        index: usize,
+        x: f32,
+    ) {
+        let param = self.crossovers[index];
+        let frequency = normalized_to_frequency((x - bounds.x) / bounds.width);
+        shell.publish(ParamMessage::SetParameterNormalized(
+            param.as_ptr(),
+            param.preview_normalized(frequency),
+        ));
+    }
+}
+
+/// x 座標（0..1）に対応するビンの値 (dB)。隣り合うビンは周波数が高いほど 1 ピクセルに多く入るので、
+/// その範囲の最大値を使う
+fn magnitude_at(magnitudes_db: &[f32], sample_rate: f32, start: f32, end: f32) -> f32 {
+    let Some(last_index) = magnitudes_db.len().checked_sub(1) else {
+        return MIN_DB;
+    };
+    let bin_width = sample_rate / FFT_SIZE as f32;
+    let first_bin = ((normalized_to_frequency(start) / bin_width).round() as usize).min(last_index);
+    let last_bin =
+        ((normalized_to_frequency(end) / bin_width).round() as usize).clamp(first_bin, last_index);
+
+    magnitudes_db[first_bin..=last_bin]
+        .iter()
+        .copied()
+        .fold(MIN_DB, f32::max)
+}
+
+fn fill_rect(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+impl<'a> Widget<ParamMessage, Renderer> for SpectrumView<'a> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, ParamMessage>,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if !bounds.contains(cursor_position) {
+                    return event::Status::Ignored;
+                }
+                if let Some(index) = self.handle_at(bounds, cursor_position.x) {
+                    shell.publish(ParamMessage::BeginSetParameter(
+                        self.crossovers[index].as_ptr(),
+                    ));
+                    self.state.drag_active = Some(index);
+                    self.set_frequency_from_x(shell, bounds, index, cursor_position.x);
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                if let Some(index) = self.state.drag_active.take() {
+                    shell.publish(ParamMessage::EndSetParameter(
+                        self.crossovers[index].as_ptr(),
+                    ));
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if let Some(index) = self.state.drag_active {
+                    self.set_frequency_from_x(shell, bounds, index, cursor_position.x);
+
+                    return event::Status::Captured;
+                }
+            }
+            _ => {}
+        }
+
+        event::Status::Ignored
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let bounds = layout.bounds();
+        if self.state.drag_active.is_some()
+            || (bounds.contains(cursor_position)
+                && self.handle_at(bounds, cursor_position.x).is_some())
+        {
+            mouse::Interaction::ResizingHorizontally
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::from_rgb(0.12, 0.12, 0.14)),
+        );
+
+        // クロスオーバーで区切った各バンドの範囲を薄く塗る
+        let crossovers = self.crossover_frequencies();
+        let mut edges = [bounds.x; NUM_BANDS + 1];
+        for (edge, frequency) in edges[1..NUM_BANDS].iter_mut().zip(crossovers) {
+            *edge = bounds.x + frequency_to_normalized(frequency) * bounds.width;
+        }
+        edges[NUM_BANDS] = bounds.x + bounds.width;
+        for (band, color) in self.band_colors.iter().enumerate() {
+            let start = edges[band];
+            let end = edges[band + 1].max(start);
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: start,
+                    y: bounds.y,
+                    width: end - start,
+                    height: bounds.height,
+                },
+                Color {
+                    a: BAND_SHADE_ALPHA,
+                    ..*color
+                },
+            );
+        }
+
+        let columns = bounds.width.floor().max(1.0) as usize;
+        let db_to_height =
+            |db: f32| ((db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0) * bounds.height;
+        for column in 0..columns {
+            let start = column as f32 / columns as f32;
+            let end = (column + 1) as f32 / columns as f32;
+            let x = bounds.x + column as f32;
+
+            for (data, color) in [
+                (self.input, Color::from_rgba(0.7, 0.7, 0.7, 0.5)),
+                (self.output, Color::from_rgba(0.3, 0.6, 1.0, 0.7)),
+            ] {
+                let height = db_to_height(magnitude_at(data, self.sample_rate, start, end));
+                if height > 0.0 {
+                    fill_rect(
+                        renderer,
+                        Rectangle {
+                            x,
+                            y: bounds.y + bounds.height - height,
+                            width: 1.0,
+                            height,
+                        },
+                        color,
+                    );
+                }
+            }
+        }
+
+        // クロスオーバー特性。列の中心に最も近いグリッド点の値を使う
+        let response_to_y = |db: f32| {
+            let normalized =
+                ((db - RESPONSE_MIN_DB) / (RESPONSE_MAX_DB - RESPONSE_MIN_DB)).clamp(0.0, 1.0);
+            bounds.y + bounds.height - normalized * bounds.height
+        };
+        for (response, color) in self.band_responses_db.iter().zip(self.band_colors) {
+            let Some(last_point) = response.len().checked_sub(1) else {
+                continue;
+            };
+            for column in 0..columns {
+                let center = (column as f32 + 0.5) / columns as f32;
+                let point = ((center * last_point as f32).round() as usize).min(last_point);
+                fill_rect(
+                    renderer,
+                    Rectangle {
+                        x: bounds.x + column as f32,
+                        y: response_to_y(response[point]) - 1.0,
+                        width: 1.0,
+                        height: 2.0,
+                    },
+                    color,
+                );
+            }
+        }
+
+        for (index, frequency) in crossovers.into_iter().enumerate() {
+            let x = bounds.x + frequency_to_normalized(frequency) * bounds.width;
+            let color = if self.state.drag_active == Some(index) {
+                Color::WHITE
+            } else {
+                Color::from_rgb(1.0, 0.8, 0.2)
+            };
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: x - 0.5,
+                    y: bounds.y,
+                    width: 1.0,
+                    height: bounds.height,
+                },
+                color,
+            );
+            // 掴む位置がわかるように、上端につまみを描く
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: x - HANDLE_WIDTH / 2.0,
+                    y: bounds.y,
+                    width: HANDLE_WIDTH,
+                    height: HANDLE_HEIGHT,
+                },
+                color,
+            );
+        }
+    }
+}
+
+impl<'a> SpectrumView<'a> {
+    /// エディターのメッセージ型に変換した [`Element`] を返す。`f` は [`ParamMessage`] を包む
+    /// バリアントで、エディターはそれを `handle_param_message()` に渡す
+    pub fn map<Message, F>(self, f: F) -> Element<'a, Message>
+    where
+        Message: 'static,
+        F: Fn(ParamMessage) -> Message + 'static,
+    {
+        Element::from(self).map(f)
+    }
+}
+
+impl<'a> From<SpectrumView<'a>> for Element<'a, ParamMessage> {
+    fn from(widget: SpectrumView<'a>) -> Self {
+        Element::new(widget)
+    }
+}
@@ -0,0 +1,146 @@
+//! ノブやスライダーの中で、パラメーターの値を直接入力するための入力欄。
+
+use nih_plug::prelude::Param;
+use nih_plug_iced::widgets::ParamMessage;
+use nih_plug_iced::{
+    event, layout, text_input, Clipboard, Event, Font, Layout, Length, Point, Rectangle, Renderer,
+    Shell, TextInput, Vector, Widget,
+};
+
+/// 入力欄から受け取るメッセージ。ウィジェットの中だけで処理する
+#[derive(Debug, Clone)]
+enum TextInputMessage {
+    Value(String),
+    Submit,
+}
+
+/// 入力中の文字列と入力欄の状態。ウィジェットの状態の一部として保持する
+#[derive(Debug, Default)]
+pub struct TextEntry {
+    /// 入力している間だけ `Some` になる
+    value: Option<String>,
+    state: text_input::State,
+}
+
+impl TextEntry {
+    pub fn is_active(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// 入力を始める。現在の値を全て選択した状態で入力欄にフォーカスする
+    pub fn start(&mut self, param: &impl Param) {
+        let mut state = text_input::State::focused();
+        state.select_all();
+        self.state = state;
+        self.value = Some(param.to_string());
+    }
+
+    /// 入力中ならイベントを入力欄に渡して `Some` を返す。確定した文字列はパラメーターの変換関数で
+    /// 解釈し、解釈できたときだけ値を変更する。Esc や入力欄の外のクリックでは値を変えずに入力を終える
+    #[allow(clippy::too_many_arguments)]
+    pub fn on_event(
+        &mut self,
+        param: &impl Param,
+        event: &Event,
+        bounds: Rectangle,
+        text_size: u16,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, ParamMessage>,
+    ) -> Option<event::Status> {
+        let current_value = self.value.clone()?;
+
+        let mut messages = Vec::new();
+        let mut text_input_shell = Shell::new(&mut messages);
+        let status = with_text_input(
+            &mut self.state,
+            bounds,
+            text_size,
+            &current_value,
+            |mut text_input, layout| {
+                text_input.on_event(
+                    event.clone(),
+                    layout,
+                    cursor_position,
+                    renderer,
+                    clipboard,
+                    &mut text_input_shell,
+                )
+            },
+        );
+
+        if !self.state.is_focused() {
+            self.value = None;
+            return Some(status);
+        }
+        for message in messages {
+            match message {
+                TextInputMessage::Value(value) => self.value = Some(value),
+                TextInputMessage::Submit => {
+                    if let Some(normalized_value) = self
+                        .value
+                        .as_deref()
+                        .and_then(|value| param.string_to_normalized_value(value))
+                    {
+                        shell.publish(ParamMessage::BeginSetParameter(param.as_ptr()));
+                        shell.publish(ParamMessage::SetParameterNormalized(
+                            param.as_ptr(),
+                            normalized_value,
+                        ));
+                        shell.publish(ParamMessage::EndSetParameter(param.as_ptr()));
+                    }
+                    self.value = None;
+                }
+            }
+        }
+
+        Some(event::Status::Captured)
+    }
+
+    /// 入力中なら `bounds` に入力欄を描いて `true` を返す
+    pub fn draw(
+        &self,
+        renderer: &mut Renderer,
+        bounds: Rectangle,
+        text_size: u16,
+        cursor_position: Point,
+    ) -> bool {
+        let Some(current_value) = &self.value else {
+            return false;
+        };
+
+        // 入力欄は状態の可変参照を要求するが、描画では変更しないので複製を渡す
+        let mut state = self.state.clone();
+        with_text_input(
+            &mut state,
+            bounds,
+            text_size,
+            current_value,
+            |text_input, layout| text_input.draw(renderer, layout, cursor_position, None),
+        );
+
+        true
+    }
+}
+
+/// `bounds` に置く入力欄と、そのレイアウトを作って `f` に渡す
+fn with_text_input<T>(
+    state: &mut text_input::State,
+    bounds: Rectangle,
+    text_size: u16,
+    current_value: &str,
+    f: impl FnOnce(TextInput<'_, TextInputMessage>, Layout<'_>) -> T,
+) -> T {
+    let text_input = TextInput::new(state, "", current_value, TextInputMessage::Value)
+        .font(Font::Default)
+        .size(text_size)
+        .padding(0)
+        .width(Length::Units(bounds.width as u16))
+        .on_submit(TextInputMessage::Submit);
+
+    let node = layout::Node::with_children(bounds.size(), vec![layout::Node::new(bounds.size())]);
+    let layout = Layout::with_offset(Vector::new(bounds.x, bounds.y), &node);
+
+    f(text_input, layout)
+}
@@ -0,0 +1,144 @@
+//! エディターの配色。ダークテーマの切り替えとバンドごとのアクセントカラー。
+
+use nih_plug::prelude::Enum;
+use nih_plug_iced::{button, container, Background, Color, Vector};
+
+use crate::processor::NUM_BANDS;
+
+/// バンドごとのアクセントカラーの組み合わせ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum BandAccents {
+    #[name = "Classic"]
+    Classic,
+    #[name = "Pastel"]
+    Pastel,
+    /// 色覚の違いがあっても区別しやすい組み合わせ（Okabe-Ito のパレットから選んだもの）
+    #[name = "Colorblind Safe"]
+    ColorblindSafe,
+}
+
+impl BandAccents {
+    /// low, mid, high の色
+    pub fn colors(self) -> [Color; NUM_BANDS] {
+        match self {
+            BandAccents::Classic => [
+                Color::from_rgb(0.9, 0.4, 0.3),
+                Color::from_rgb(0.4, 0.8, 0.4),
+                Color::from_rgb(0.3, 0.6, 1.0),
+            ],
+            BandAccents::Pastel => [
+                Color::from_rgb(0.96, 0.66, 0.6),
+                Color::from_rgb(0.66, 0.88, 0.66),
+                Color::from_rgb(0.62, 0.76, 0.98),
+            ],
+            BandAccents::ColorblindSafe => [
+                Color::from_rgb(0.9, 0.62, 0.0),
+                Color::from_rgb(0.0, 0.62, 0.45),
+                Color::from_rgb(0.34, 0.71, 0.91),
+            ],
+        }
+    }
+
+    /// 表示名
+    pub fn name(self) -> &'static str {
+        Self::variants()[self.to_index()]
+    }
+
+    /// ボタンで切り替えるときの次の組み合わせ
+    pub fn next(self) -> Self {
+        Self::from_index((self.to_index() + 1) % Self::variants().len())
+    }
+}
+
+/// エディター全体の配色
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub background: Color,
+    pub text: Color,
+    pub band_colors: [Color; NUM_BANDS],
+}
+
+impl Theme {
+    pub fn new(dark: bool, band_accents: BandAccents) -> Self {
+        let (background, text) = if dark {
+            (Color::from_rgb(0.1, 0.1, 0.11), Color::from_rgb(0.9, 0.9, 0.9))
+        } else {
+            (Color::from_rgb(0.98, 0.98, 0.98), Color::BLACK)
+        };
+
+        Self {
+            background,
+            text,
+            band_colors: band_accents.colors(),
+        }
+    }
+}
+
+/// ミュートやバイパスで出力に影響していないバンドの表示に使う不透明度
+const DIMMED_ALPHA: f32 = 0.35;
+
+/// 色を薄くする。出力に影響していないバンドの表示に使う
+pub fn dimmed(color: Color) -> Color {
+    Color {
+        a: color.a * DIMMED_ALPHA,
+        ..color
+    }
+}
+
+/// オン／オフを切り替えるボタンのスタイル。オンのときは `color` で塗りつぶす
+pub struct ToggleStyle {
+    pub active: bool,
+    pub color: Color,
+}
+
+impl button::StyleSheet for ToggleStyle {
+    fn active(&self) -> button::Style {
+        if self.active {
+            button::Style {
+                shadow_offset: Vector::default(),
+                background: Some(Background::Color(self.color)),
+                border_radius: 2.0,
+                border_width: 1.0,
+                border_color: self.color,
+                text_color: Color::BLACK,
+            }
+        } else {
+            button::Style {
+                shadow_offset: Vector::default(),
+                background: None,
+                border_radius: 2.0,
+                border_width: 1.0,
+                border_color: self.color,
+                text_color: self.color,
+            }
+        }
+    }
+}
+
+/// エディター全体を包むコンテナーのスタイル。中のウィジェットの文字色をテーマに合わせる
+pub struct RootStyle(pub Theme);
+
+impl container::StyleSheet for RootStyle {
+    fn style(&self) -> container::Style {
+        container::Style {
+            text_color: Some(self.0.text),
+            background: Some(Background::Color(self.0.background)),
+            ..container::Style::default()
+        }
+    }
+}
+
+/// ツールチップの吹き出しのスタイル。テーマに関係なく暗い背景に明るい文字で表示する
+pub struct TooltipStyle;
+
+impl container::StyleSheet for TooltipStyle {
+    fn style(&self) -> container::Style {
+        container::Style {
+            text_color: Some(Color::from_rgb(0.92, 0.92, 0.92)),
+            background: Some(Background::Color(Color::from_rgb(0.12, 0.12, 0.14))),
+            border_radius: 3.0,
+            border_width: 1.0,
+            border_color: Color::from_rgb(0.4, 0.4, 0.44),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! パラメーターのツールチップ。説明と、現在の値、デフォルト値を表示する。
+
+use nih_plug::prelude::Param;
+use nih_plug_iced::{tooltip, Element, Tooltip};
+
+use super::theme;
+
+const TEXT_SIZE: u16 = 13;
+
+pub const THRESHOLD: &str = "Level above which the band starts to be compressed.";
+pub const RATIO: &str =
+    "How strongly the level above the threshold is reduced (4 = 4 dB in, 1 dB out).";
+pub const ATTACK: &str = "How quickly the compressor reacts when the level rises.";
+pub const RELEASE: &str = "How quickly the gain recovers after the level falls.";
+pub const MAKEUP: &str = "Gain added after compression to make up for the reduced level.";
+pub const KNEE: &str =
+    "Width of the soft knee around the threshold. 0 dB switches abruptly into compression.";
+pub const RANGE: &str = "Maximum amount of gain reduction the band can apply.";
+pub const HOLD: &str = "How long the gain reduction is held before the release starts.";
+pub const DETECTOR: &str =
+    "Peak reacts to every transient, RMS follows the average level more like the ear does. \
+     Hilbert is as fast as Peak but does not ripple on low frequencies.";
+pub const MIX: &str = "Blend of compressed and uncompressed signal for parallel compression.";
+pub const DUCK: &str = "How far MIDI Ducking turns this band down while a note is held.";
+pub const SOLO: &str = "Listen to this band only. Several bands can be soloed at once.";
+pub const MUTE: &str = "Remove this band from the output.";
+pub const BYPASS: &str = "Pass this band through without compression. Switching fades over 20 ms.";
+pub const CROSSOVER: &str = "Split frequency between two neighbouring bands. The mid band is kept \
+     at least a third of an octave wide. Type values like 850 or 1.2k.";
+pub const CROSSOVER_Q: &str =
+    "0.71 gives a flat sum. Lower values overlap the bands, higher values separate them.";
+pub const PLUGIN_BYPASS: &str =
+    "Crossfade to the unprocessed input. The host's bypass button controls the same switch.";
+pub const AMOUNT: &str =
+    "Scales the ratio and range of all bands together. 0% turns the compression off.";
+pub const ATTACK_SCALE: &str =
+    "Multiplies the attack time of every band. Below 1x all bands react faster.";
+pub const RELEASE_SCALE: &str =
+    "Multiplies the release time of every band. Below 1x all bands recover faster.";
+pub const MORPH: &str =
+    "Position between the stored A (0%) and B (100%) snapshots. Needs both snapshots.";
+pub const INPUT_GAIN: &str = "Gain applied to the input before it is split into bands.";
+pub const OUTPUT_GAIN: &str = "Gain applied to the output after the bands are summed.";
+pub const OVERSAMPLING: &str =
+    "Run the compressors at a higher sample rate to reduce aliasing. Adds latency.";
+pub const PRECISION: &str =
+    "Split and compress the bands with 64-bit floats for extra numerical headroom.";
+pub const CHANNEL_LINK: &str = "Compress linked channels by the same amount, following the loudest \
+     one. Pairs links L/R and the surround pairs, All links every channel.";
+pub const EXCLUDE_LFE: &str = "Keep the LFE channel of 5.1 and 7.1 layouts unlinked in All mode.";
+pub const RMS_TIME: &str = "Integration time of the RMS meter. 300 ms behaves like a VU meter.";
+pub const PEAK_HOLD: &str = "How long the peak meters hold their highest value.";
+pub const GAIN_MATCH: &str =
+    "Match the output loudness to the input for fair comparisons with the bypassed signal.";
+pub const AUTO_MAKEUP: &str = "Slowly ride the output gain (at most 1 dB per second, up to 12 dB) \
+     so the short-term loudness matches the input or the loudness target.";
+pub const LOUDNESS_TARGET: &str = "Short-term loudness the Target mode of Auto Makeup aims for.";
+pub const K_WEIGHTED_METERS: &str = "Apply the BS.1770 K-weighting filter to the RMS meter.";
+pub const K_WEIGHTED_DETECTION: &str =
+    "Apply the K-weighting filter to the compressor detectors so they react like a listener.";
+pub const FAST_DETECTOR: &str =
+    "Use fast approximations for the compressor's dB math to lower the CPU load.";
+pub const OFFLINE_QUALITY: &str = "While bouncing offline, oversample at least 4x, use 64-bit \
+     precision and exact dB math regardless of the real-time settings.";
+pub const LEARN_OFFSET: &str =
+    "How far below the measured level of each band Learn places the threshold.";
+pub const MIDI_DUCKING: &str = "Turn each band down by its Duck amount while MIDI notes are held, \
+     scaled by velocity. Route a keyboard or drum pad to the plugin's MIDI input.";
+pub const DUCK_ATTACK: &str = "How fast MIDI Ducking reaches its depth when a note starts.";
+pub const DUCK_RELEASE: &str = "How fast the bands return after the last note is released.";
+
+/// `content` の上に、パラメーター名と説明、現在の値とデフォルト値を表示するツールチップを付ける
+pub fn with_tooltip<'a, Message: 'a>(
+    content: impl Into<Element<'a, Message>>,
+    param: &impl Param,
+    description: &str,
+) -> Element<'a, Message> {
+    let text = format!(
+        "{}\n{}\nValue: {} (default {})",
+        param.name(),
+        description,
+        param,
+        param.normalized_value_to_string(param.default_normalized_value(), true),
+    );
+
+    Tooltip::new(content, text, tooltip::Position::Top)
+        .size(TEXT_SIZE)
+        .gap(4)
+        .padding(6)
+        .style(theme::TooltipStyle)
+        .into()
+}
@@ -0,0 +1,399 @@
+//! バンドの静的な入出力特性を表示し、ドラッグでスレッショルドとレシオを変更するウィジェット。
+
+use nih_plug::prelude::{FloatParam, Param};
+use nih_plug_iced::renderer::Renderer as _;
+use nih_plug_iced::widgets::ParamMessage;
+use nih_plug_iced::{
+    event, layout, mouse, renderer, Background, Clipboard, Color, Element, Event, Layout, Length,
+    Point, Rectangle, Renderer, Shell, Size, Widget,
+};
+
+use crate::compression::{transfer_curve, CompressorSettings, DetectorMode};
+
+/// 表示する入力と出力のレベルの範囲 (dB)
+const MIN_DB: f32 = -60.0;
+const MAX_DB: f32 = 0.0;
+/// 目盛りの間隔 (dB)
+const GRID_INTERVAL_DB: f32 = 12.0;
+/// ニーのハンドルを掴める距離 (px)
+const HANDLE_GRAB_DISTANCE: f32 = 8.0;
+const HANDLE_SIZE: f32 = 8.0;
+const BORDER_WIDTH: f32 = 1.0;
+
+/// ドラッグしているもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragTarget {
+    /// ニー（スレッショルドの位置）
+    Threshold,
+    /// スレッショルドより上の傾き
+    Ratio,
+}
+
+/// ドラッグ状態。エディターがバンドごとに保持する
+#[derive(Debug, Default)]
+pub struct State {
+    drag_active: Option<DragTarget>,
+}
+
+/// 横軸を入力、縦軸を出力のレベルとして、メイクアップ込みの入出力特性を描く。
+///
+/// ニーのハンドルを左右にドラッグするとスレッショルドが、スレッショルドより上の部分をドラッグすると
+/// カーソルを通る傾きになるようにレシオが変わる。
+pub struct TransferCurve<'a> {
+    state: &'a mut State,
+    threshold: &'a FloatParam,
+    ratio: &'a FloatParam,
+    makeup_db: f32,
+    knee_db: f32,
+    range_db: f32,
+    color: Color,
+
+    width: Length,
+    height: Length,
+}
+
+impl<'a> TransferCurve<'a> {
+    pub fn new(
+        state: &'a mut State,
+        threshold: &'a FloatParam,
+        ratio: &'a FloatParam,
+        makeup_db: f32,
+        color: Color,
+    ) -> Self {
+        Self {
+            state,
+            threshold,
+            ratio,
+            makeup_db,
+            knee_db: 0.0,
+            range_db: f32::INFINITY,
+            color,
+
+            width: Length::Units(120),
+            height: Length::Units(120),
+        }
+    }
+
+    /// ソフトニーの幅 (dB)
+    pub fn knee(mut self, knee_db: f32) -> Self {
+        self.knee_db = knee_db;
+        self
+    }
+
+    /// ゲインリダクションの上限 (dB)
+    pub fn range(mut self, range_db: f32) -> Self {
+        self.range_db = range_db;
+        self
+    }
+
+    /// 一辺の長さ (px)
+    pub fn size(mut self, size: u16) -> Self {
+        self.width = Length::Units(size);
+        self.height = Length::Units(size);
+        self
+    }
+
+    /// 表示に使うコンプレッサーの設定。静的な特性だけを描くので、エンベロープや検出器の設定は使わない
+    fn settings(&self) -> CompressorSettings {
+        CompressorSettings {
+            threshold_db: self.threshold.value(),
+            ratio: self.ratio.value().max(1.0),
+            knee_db: self.knee_db,
+            range_db: self.range_db,
+            attack_coef: 0.0,
+            release_coef: 0.0,
+            hold_samples: 0,
+            detector: DetectorMode::Peak,
+            rms_coef: 0.0,
+            fast_math: false,
+            makeup_db: self.makeup_db,
+            mix: 1.0,
+        }
+    }
+
+    /// ニーのハンドルの位置
+    fn knee_position(&self, bounds: Rectangle) -> Point {
+        let threshold_db = self.threshold.value();
+        Point::new(
+            db_to_x(bounds, threshold_db),
+            db_to_y(bounds, threshold_db + self.makeup_db),
+        )
+    }
+
+    fn set_value(&self, shell: &mut Shell<'_, ParamMessage>, param: &FloatParam, plain: f32) {
+        let normalized_value = param.preview_normalized(plain);
+        if normalized_value != param.unmodulated_normalized_value() {
+            shell.publish(ParamMessage::SetParameterNormalized(
+                param.as_ptr(),
+                normalized_value,
+            ));
+        }
+    }
+
+    /// ドラッグ中のカーソル位置をパラメーターに反映する
+    fn drag_to(&self, shell: &mut Shell<'_, ParamMessage>, bounds: Rectangle, cursor: Point) {
+        match self.state.drag_active {
+            Some(DragTarget::Threshold) => {
+                self.set_value(shell, self.threshold, x_to_db(bounds, cursor.x));
+            }
+            Some(DragTarget::Ratio) => {
+                // カーソルの位置を通る直線の傾きの逆数がレシオになる。水平以下なら最大のレシオにする
+                let threshold_db = self.threshold.value();
+                let input_above = x_to_db(bounds, cursor.x) - threshold_db;
+                let output_above = y_to_db(bounds, cursor.y) - threshold_db - self.makeup_db;
+                if input_above > 0.0 {
+                    let ratio = if output_above > 0.0 {
+                        (input_above / output_above).max(1.0)
+                    } else {
+                        f32::INFINITY
+                    };
+                    self.set_value(shell, self.ratio, ratio);
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn drag_param(&self, target: DragTarget) -> &FloatParam {
+        match target {
+            DragTarget::Threshold => self.threshold,
+            DragTarget::Ratio => self.ratio,
+        }
+    }
+}
+
+fn db_to_x(bounds: Rectangle, db: f32) -> f32 {
+    bounds.x + (db - MIN_DB) / (MAX_DB - MIN_DB) * bounds.width
+}
+
+fn db_to_y(bounds: Rectangle, db: f32) -> f32 {
+    bounds.y + bounds.height - (db - MIN_DB) / (MAX_DB - MIN_DB) * bounds.height
+}
+
+fn x_to_db(bounds: Rectangle, x: f32) -> f32 {
+    MIN_DB + (x - bounds.x) / bounds.width * (MAX_DB - MIN_DB)
+}
+
+fn y_to_db(bounds: Rectangle, y: f32) -> f32 {
+    MIN_DB + (bounds.y + bounds.height - y) / bounds.height * (MAX_DB - MIN_DB)
+}
+
+fn fill_rect(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
+
+impl<'a> Widget<ParamMessage, Renderer> for TransferCurve<'a> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, ParamMessage>,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if !bounds.contains(cursor_position) {
+                    return event::Status::Ignored;
+                }
+
+                let knee = self.knee_position(bounds);
+                let target = if (cursor_position.x - knee.x).abs() <= HANDLE_GRAB_DISTANCE
+                    && (cursor_position.y - knee.y).abs() <= HANDLE_GRAB_DISTANCE
+                {
+                    DragTarget::Threshold
+                } else if cursor_position.x > knee.x {
+                    DragTarget::Ratio
+                } else {
+                    return event::Status::Ignored;
+                };
+
+                shell.publish(ParamMessage::BeginSetParameter(
+                    self.drag_param(target).as_ptr(),
+                ));
+                self.state.drag_active = Some(target);
+                self.drag_to(shell, bounds, cursor_position);
+
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                if let Some(target) = self.state.drag_active.take() {
+                    shell.publish(ParamMessage::EndSetParameter(
+                        self.drag_param(target).as_ptr(),
+                    ));
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if self.state.drag_active.is_some() {
+                    self.drag_to(shell, bounds, cursor_position);
+
+                    return event::Status::Captured;
+                }
+            }
+            _ => {}
+        }
+
+        event::Status::Ignored
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if self.state.drag_active.is_some() || layout.bounds().contains(cursor_position) {
+            mouse::Interaction::Grab
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::from_rgb(0.12, 0.12, 0.14)),
+        );
+
+        // 12 dB ごとのグリッドと、入力と出力が等しい対角線
+        let grid_color = Color::from_rgb(0.25, 0.25, 0.28);
+        let num_lines = ((MAX_DB - MIN_DB) / GRID_INTERVAL_DB) as usize;
+        for line in 1..num_lines {
+            let db = MIN_DB + line as f32 * GRID_INTERVAL_DB;
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: db_to_x(bounds, db),
+                    width: 1.0,
+                    ..bounds
+                },
+                grid_color,
+            );
+            fill_rect(
+                renderer,
+                Rectangle {
+                    y: db_to_y(bounds, db),
+                    height: 1.0,
+                    ..bounds
+                },
+                grid_color,
+            );
+        }
+
+        let settings = self.settings();
+        let columns = bounds.width.floor().max(1.0) as usize;
+        for column in 0..columns {
+            let x = bounds.x + column as f32 + 0.5;
+            let input_db = x_to_db(bounds, x);
+            let unity_y = db_to_y(bounds, input_db);
+            fill_rect(
+                renderer,
+                Rectangle {
+                    x: x - 0.5,
+                    y: unity_y,
+                    width: 1.0,
+                    height: 1.0,
+                },
+                grid_color,
+            );
+
+            let y = db_to_y(bounds, transfer_curve(&settings, input_db));
+            if y >= bounds.y && y <= bounds.y + bounds.height {
+                fill_rect(
+                    renderer,
+                    Rectangle {
+                        x: x - 0.5,
+                        y: y - 1.0,
+                        width: 1.0,
+                        height: 2.0,
+                    },
+                    self.color,
+                );
+            }
+        }
+
+        let knee = self.knee_position(bounds);
+        if bounds.contains(knee) {
+            let color = if self.state.drag_active == Some(DragTarget::Threshold) {
+                Color::WHITE
+            } else {
+                self.color
+            };
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: knee.x - HANDLE_SIZE / 2.0,
+                        y: knee.y - HANDLE_SIZE / 2.0,
+                        width: HANDLE_SIZE,
+                        height: HANDLE_SIZE,
+                    },
+                    border_radius: HANDLE_SIZE / 2.0,
+                    border_width: 1.0,
+                    border_color: Color::WHITE,
+                },
+                Background::Color(color),
+            );
+        }
+    }
+}
+
+impl<'a> TransferCurve<'a> {
+    /// エディターのメッセージ型に変換した [`Element`] を返す。`f` は [`ParamMessage`] を包む
+    /// バリアントで、エディターはそれを `handle_param_message()` に渡す
+    pub fn map<Message, F>(self, f: F) -> Element<'a, Message>
+    where
+        Message: 'static,
+        F: Fn(ParamMessage) -> Message + 'static,
+    {
+        Element::from(self).map(f)
+    }
+}
+
+impl<'a> From<TransferCurve<'a>> for Element<'a, ParamMessage> {
+    fn from(widget: TransferCurve<'a>) -> Self {
+        Element::new(widget)
+    }
+}
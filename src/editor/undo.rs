@@ -0,0 +1,144 @@
+//! エディターでのパラメーター操作を元に戻す／やり直すための履歴。
+//!
+//! ホストの Undo はパラメーターごとの変更しか扱わないことが多いので、リンクしたバンドのように同時に動いた
+//! パラメーターを 1 つの操作として記録する。
+
+use nih_plug::prelude::ParamPtr;
+use std::time::{Duration, Instant};
+
+/// 同じパラメーターへの操作がこの時間内に続いたときは 1 つの操作にまとめる。矢印キーやホイールの連続操作用
+const COALESCE_WINDOW: Duration = Duration::from_millis(500);
+/// 覚えておく操作の数
+const MAX_ENTRIES: usize = 100;
+
+/// 1 つのパラメーターの変更（正規化された値）
+#[derive(Debug, Clone, Copy)]
+struct ParamChange {
+    param: ParamPtr,
+    before: f32,
+    after: f32,
+}
+
+/// 1 回の操作で変わったパラメーター
+#[derive(Debug)]
+struct Entry {
+    changes: Vec<ParamChange>,
+    finished_at: Instant,
+}
+
+/// パラメーターのジェスチャーから作る Undo/Redo の履歴
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    /// 進行中の操作の変更。全てのジェスチャーが終わったら 1 つの操作として記録する
+    pending: Vec<ParamChange>,
+    /// 進行中のジェスチャーの数
+    open_gestures: usize,
+}
+
+impl UndoHistory {
+    /// ジェスチャーの開始。`value` は変更前の値
+    pub fn begin(&mut self, param: ParamPtr, value: f32) {
+        self.open_gestures += 1;
+        if !self.pending.iter().any(|change| change.param == param) {
+            self.pending.push(ParamChange {
+                param,
+                before: value,
+                after: value,
+            });
+        }
+    }
+
+    /// ジェスチャー中の値の変更
+    pub fn set(&mut self, param: ParamPtr, value: f32) {
+        if let Some(change) = self
+            .pending
+            .iter_mut()
+            .find(|change| change.param == param)
+        {
+            change.after = value;
+        }
+    }
+
+    /// ジェスチャーの終了。最後のジェスチャーが終わったら、値が変わったパラメーターを 1 つの操作として
+    /// 記録し、やり直しの履歴を捨てる
+    pub fn end(&mut self) {
+        self.open_gestures = self.open_gestures.saturating_sub(1);
+        if self.open_gestures > 0 {
+            return;
+        }
+
+        let changes: Vec<ParamChange> = std::mem::take(&mut self.pending)
+            .into_iter()
+            .filter(|change| change.before != change.after)
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        self.redo.clear();
+        // 直前と同じパラメーターの操作が続いていれば、直前の操作の変更後の値だけを更新する
+        if let Some(last) = self.undo.last_mut() {
+            let same_params = last.changes.len() == changes.len()
+                && last
+                    .changes
+                    .iter()
+                    .zip(&changes)
+                    .all(|(last, change)| last.param == change.param);
+            if same_params && now.duration_since(last.finished_at) < COALESCE_WINDOW {
+                for (last, change) in last.changes.iter_mut().zip(changes) {
+                    last.after = change.after;
+                }
+                last.finished_at = now;
+
+                return;
+            }
+        }
+
+        if self.undo.len() == MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+        self.undo.push(Entry {
+            changes,
+            finished_at: now,
+        });
+    }
+
+    /// 最後の操作を取り消す。戻す先のパラメーターと値を返す。ジェスチャーの途中では何もしない
+    pub fn undo(&mut self) -> Vec<(ParamPtr, f32)> {
+        if self.open_gestures > 0 {
+            return Vec::new();
+        }
+        let Some(entry) = self.undo.pop() else {
+            return Vec::new();
+        };
+
+        let values = entry
+            .changes
+            .iter()
+            .map(|change| (change.param, change.before))
+            .collect();
+        self.redo.push(entry);
+        values
+    }
+
+    /// 最後に取り消した操作をやり直す。設定するパラメーターと値を返す
+    pub fn redo(&mut self) -> Vec<(ParamPtr, f32)> {
+        if self.open_gestures > 0 {
+            return Vec::new();
+        }
+        let Some(entry) = self.redo.pop() else {
+            return Vec::new();
+        };
+
+        let values = entry
+            .changes
+            .iter()
+            .map(|change| (change.param, change.after))
+            .collect();
+        self.undo.push(entry);
+        values
+    }
+}
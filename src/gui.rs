@@ -0,0 +1,45 @@
+//! エディターのバックエンドの切り替え。
+//!
+//! `iced`（デフォルト）と `vizia` の feature の少なくとも一方を有効にする。両方有効なときは iced の
+//! エディターを使い、vizia のエディターはコンパイルするだけになる。プロセッサーとパラメーターは
+//! このモジュールだけを使い、どちらのバックエンドにも同じ共有データを渡す。
+
+use nih_plug::prelude::AsyncExecutor;
+use std::sync::{Arc, Mutex};
+
+use crate::analysis::{AnalysisOutput, ResetRequests, SpectrumOutput, ThresholdLearn};
+use crate::metering::GainReductionHistory;
+use crate::midi::MidiCcEvent;
+use crate::params::MultibandCompressorParams;
+use crate::processor::MultibandCompressor;
+use crate::sample_queue::SampleConsumer;
+
+#[cfg(not(any(feature = "iced", feature = "vizia")))]
+compile_error!("Enable either the `iced` or the `vizia` feature");
+
+#[cfg(feature = "iced")]
+pub(crate) use crate::editor::{create, default_state, EditorState};
+#[cfg(all(feature = "vizia", not(feature = "iced")))]
+pub(crate) use crate::vizia_editor::{create, default_state, EditorState};
+
+/// プロセッサーがエディターに渡す共有データ。エディターを開くたびに複製して使う
+#[derive(Clone)]
+pub(crate) struct EditorShared {
+    pub params: Arc<MultibandCompressorParams>,
+    pub async_executor: AsyncExecutor<MultibandCompressor>,
+    /// メーターと解析結果の読み出し側
+    pub analysis: Arc<Mutex<AnalysisOutput>>,
+    /// バックグラウンドで計算したスペクトルの読み出し側。vizia のエディターはまだ表示しない
+    #[cfg_attr(not(feature = "iced"), allow(dead_code))]
+    pub spectrum: Arc<Mutex<SpectrumOutput>>,
+    pub reset_requests: Arc<ResetRequests>,
+    /// オートスレッショルドの学習の状態。vizia のエディターはまだ学習に対応していない
+    #[cfg_attr(not(feature = "iced"), allow(dead_code))]
+    pub threshold_learn: Arc<ThresholdLearn>,
+    /// ゲインリダクションの履歴。vizia のエディターはまだ表示しない
+    #[cfg_attr(not(feature = "iced"), allow(dead_code))]
+    pub gain_reduction_history: Arc<GainReductionHistory>,
+    /// プロセッサーが受け取った CC の読み出し側。vizia のエディターはまだ MIDI ラーンに対応していない
+    #[cfg_attr(not(feature = "iced"), allow(dead_code))]
+    pub midi_cc_events: Arc<Mutex<SampleConsumer<MidiCcEvent>>>,
+}
@@ -1,29 +1,81 @@
 use nih_plug::prelude::*;
 
-mod biquad;
-mod compression;
+// DSP の部品は nih_plug に依存しない multiband_compressor_core にある。compression, crossover,
+// oversampling は、ベンチマーク (`benches/`) から使えるようにここからも公開する
+pub use multiband_compressor_core::{compression, crossover, oversampling};
+use multiband_compressor_core::{biquad, precision, simd};
+
+mod analysis;
+mod background;
+mod band;
+mod channel;
+mod denormals;
+mod ducking;
+#[cfg(feature = "iced")]
 mod editor;
+mod gui;
+mod loudness;
+mod metering;
+mod midi;
+mod morph;
 mod params;
+mod preset_file;
+mod presets;
 mod processor;
+mod sample_queue;
+mod spectrum;
+mod surround;
+mod triple_buffer;
+mod units;
+// iced も有効なときは使わないが、両方の feature でビルドしたときにも壊れていないことを確かめる
+#[cfg(feature = "vizia")]
+#[cfg_attr(feature = "iced", allow(dead_code))]
+mod vizia_editor;
 
 pub use params::MultibandCompressorParams;
+pub use preset_file::{param_map_values, PresetFileError};
 pub use processor::MultibandCompressor;
 
+// ホストはプラグインを CLAP の ID と VST3 のクラス ID で見分けて、セッションに保存する。以前は
+// nih-plug のゲインのサンプルの ID のままだったので、他のプラグインと衝突しないものに変えた。
+// nih_plug には古い ID を新しい ID に読み替えさせる手段 (CLAP の ID の互換性の宣言や VST3 の
+// モニカーの置き換え) がないため、古い ID で保存したセッションを開くときは `legacy_ids` フィーチャーを
+// 付けてビルドし、古い ID のまま読み込ませる。ステートの形式は ID によらないので、古い版のステートも
+// `filter_state()` でそのまま移行できる
+const CLAP_ID: &str = "com.kakeru3.multiband-compressor";
+const VST3_CLASS_ID: [u8; 16] = *b"Kakeru3MBandComp";
+/// テンプレートから引き継いでいた ID。`legacy_ids` フィーチャーでビルドしたときだけ使う
+const LEGACY_CLAP_ID: &str = "com.moist-plugins-gmbh.gain-gui-iced";
+const LEGACY_VST3_CLASS_ID: [u8; 16] = *b"CompGuiIcedAaAAa";
+
 impl ClapPlugin for MultibandCompressor {
-    const CLAP_ID: &'static str = "com.moist-plugins-gmbh.gain-gui-iced";
-    const CLAP_DESCRIPTION: Option<&'static str> = Some("A smoothed gain parameter example plugin");
+    const CLAP_ID: &'static str = if cfg!(feature = "legacy_ids") {
+        LEGACY_CLAP_ID
+    } else {
+        CLAP_ID
+    };
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("A three-band compressor");
     const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
     const CLAP_SUPPORT_URL: Option<&'static str> = None;
     const CLAP_FEATURES: &'static [ClapFeature] = &[
         ClapFeature::AudioEffect,
         ClapFeature::Stereo,
         ClapFeature::Mono,
+        ClapFeature::Surround,
         ClapFeature::Utility,
     ];
+
+    fn remote_controls(&self, context: &mut impl RemoteControlsContext) {
+        self.params.remote_controls(context);
+    }
 }
 
 impl Vst3Plugin for MultibandCompressor {
-    const VST3_CLASS_ID: [u8; 16] = *b"CompGuiIcedAaAAa";
+    const VST3_CLASS_ID: [u8; 16] = if cfg!(feature = "legacy_ids") {
+        LEGACY_VST3_CLASS_ID
+    } else {
+        VST3_CLASS_ID
+    };
     const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
         &[Vst3SubCategory::Fx, Vst3SubCategory::Tools];
 }
@@ -0,0 +1,598 @@
+use nih_plug::prelude::{util, Enum};
+
+use crate::biquad::Biquad;
+
+/// ゲーティングブロックの更新間隔 (ms)。400 ms ブロックを 75% オーバーラップさせるので 100 ms ごと
+const STEP_MS: f32 = 100.0;
+/// モーメンタリー (400 ms) に使うステップ数
+const MOMENTARY_STEPS: usize = 4;
+/// ショートターム (3 s) に使うステップ数
+const SHORT_TERM_STEPS: usize = 30;
+/// 絶対ゲート (LUFS)
+const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+/// 相対ゲート (LU)
+const RELATIVE_GATE_LU: f32 = -10.0;
+/// インテグレーテッド用ヒストグラムの上限 (LUFS) と分解能 (LU)
+const HISTOGRAM_MAX_LUFS: f32 = 10.0;
+const HISTOGRAM_RESOLUTION_LU: f32 = 0.1;
+const HISTOGRAM_BINS: usize =
+    ((HISTOGRAM_MAX_LUFS - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU) as usize;
+/// ゲインマッチのトリムの上限 (dB)
+const GAIN_MATCH_MAX_TRIM_DB: f32 = 24.0;
+/// これより小さい平均二乗値（約 -70 LUFS）は無音として扱い、トリムを変えない
+const GAIN_MATCH_SILENCE: f32 = 1e-7;
+/// オートメイクアップのゲインの上限 (dB)
+const AUTO_MAKEUP_MAX_GAIN_DB: f32 = 12.0;
+/// オートメイクアップのゲインを動かす最大の速さ (dB/s)。音量の変化が聴こえないように遅くする
+const AUTO_MAKEUP_RATE_DB_PER_S: f32 = 1.0;
+
+/// オートメイクアップで出力のラウドネスを何に揃えるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum AutoMakeupMode {
+    #[name = "Off"]
+    Off,
+    /// 入力のショートタームラウドネス
+    #[name = "Match Input"]
+    MatchInput,
+    /// Loudness Target のショートタームラウドネス
+    #[name = "Target"]
+    Target,
+}
+
+/// ITU-R BS.1770 の K 特性フィルター（ハイシェルフ + ハイパス）
+#[derive(Clone, Copy)]
+pub struct KWeightingFilter {
+    shelf: Biquad,
+    highpass: Biquad,
+}
+
+impl KWeightingFilter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut filter = Self {
+            shelf: Biquad::new(),
+            highpass: Biquad::new(),
+        };
+        filter.set_sample_rate(sample_rate);
+        filter
+    }
+
+    /// 48 kHz 用に規格で定められた係数を、任意のサンプルレート向けに設計し直す
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        // Stage 1: 頭部の音響効果を模したハイシェルフ
+        let gain_db = 3.999_843_8_f32;
+        let f0 = 1_681.974_5_f32;
+        let q = 0.707_175_24_f32;
+        let k = (std::f32::consts::PI * f0 / sample_rate).tan();
+        let vh = util::db_to_gain(gain_db);
+        let vb = vh.powf(0.499_666_78);
+        let a0 = 1.0 + k / q + k * k;
+        self.shelf.set_coefficients(
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+            2.0 * (k * k - 1.0) / a0,
+            (1.0 - k / q + k * k) / a0,
+        );
+
+        // Stage 2: RLB 重み付けのハイパス
+        let f0 = 38.135_47_f32;
+        let q = 0.500_327_04_f32;
+        let k = (std::f32::consts::PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        self.highpass.set_coefficients(
+            1.0,
+            -2.0,
+            1.0,
+            2.0 * (k * k - 1.0) / a0,
+            (1.0 - k / q + k * k) / a0,
+        );
+    }
+
+    pub fn reset(&mut self) {
+        self.shelf.reset();
+        self.highpass.reset();
+    }
+
+    pub fn process_sample(&mut self, input: f32) -> f32 {
+        self.highpass.process_sample(self.shelf.process_sample(input))
+    }
+}
+
+/// BS.1770 のラウドネスメーターのうち、オーディオスレッドで行う部分。
+///
+/// K 特性をかけた二乗和を 100 ms のステップごとにまとめるだけで、ゲーティングと積算は
+/// バックグラウンドスレッドの [`LoudnessIntegrator`] に任せる。
+pub struct LoudnessMeter {
+    filters: Vec<KWeightingFilter>,
+    /// チャンネルごとの二乗にかける重み
+    weights: Vec<f32>,
+
+    /// 100 ms ステップあたりのサンプル数
+    step_len: usize,
+    step_pos: usize,
+    /// 現在のステップで積算中の K 特性二乗和（全チャンネルの重み付き合計）
+    step_energy: f64,
+}
+
+impl LoudnessMeter {
+    /// フィルターをここで確保するので、`initialize()` から呼ぶこと。`weights` はチャンネルごとの重みで、
+    /// チャンネル数もこの長さで決まる
+    pub fn new(weights: &[f32], sample_rate: f32) -> Self {
+        Self {
+            filters: vec![KWeightingFilter::new(sample_rate); weights.len()],
+            weights: weights.to_vec(),
+
+            step_len: ((STEP_MS / 1000.0 * sample_rate).round() as usize).max(1),
+            step_pos: 0,
+            step_energy: 0.0,
+        }
+    }
+
+    /// フィルター状態と積算中のステップをクリアする
+    pub fn reset(&mut self) {
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+        self.step_pos = 0;
+        self.step_energy = 0.0;
+    }
+
+    /// 1 チャンネル分のサンプルを積算する。全チャンネルを渡したら [`Self::end_frame()`] を呼ぶこと
+    pub fn process_sample(&mut self, channel: usize, sample: f32) {
+        if let (Some(filter), Some(&weight)) =
+            (self.filters.get_mut(channel), self.weights.get(channel))
+        {
+            let weighted = filter.process_sample(sample) as f64;
+            self.step_energy += weight as f64 * weighted * weighted;
+        }
+    }
+
+    /// 1 サンプルフレーム分の処理を終える。100 ms のステップが終わったら、そのステップの平均二乗値を返す
+    pub fn end_frame(&mut self) -> Option<f64> {
+        self.step_pos += 1;
+        if self.step_pos < self.step_len {
+            return None;
+        }
+
+        let mean_square = self.step_energy / self.step_len as f64;
+        self.step_pos = 0;
+        self.step_energy = 0.0;
+        Some(mean_square)
+    }
+}
+
+/// 直近 3 秒分の 100 ms ステップの平均二乗値から、モーメンタリーとショートタームを求める
+#[derive(Clone, Copy)]
+pub struct LoudnessWindow {
+    /// ステップごとの平均二乗値（リングバッファ）
+    steps: [f64; SHORT_TERM_STEPS],
+    step_idx: usize,
+    steps_filled: usize,
+
+    /// 直近 400 ms の平均二乗値
+    momentary_energy: f64,
+    momentary: f32,
+    short_term: f32,
+}
+
+impl LoudnessWindow {
+    pub fn new() -> Self {
+        Self {
+            steps: [0.0; SHORT_TERM_STEPS],
+            step_idx: 0,
+            steps_filled: 0,
+
+            momentary_energy: 0.0,
+            momentary: util::MINUS_INFINITY_DB,
+            short_term: util::MINUS_INFINITY_DB,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 100 ms ステップ 1 つ分の平均二乗値を加え、ラウドネス値を更新する
+    pub fn push_step(&mut self, mean_square: f64) {
+        self.steps[self.step_idx] = mean_square;
+        self.step_idx = (self.step_idx + 1) % SHORT_TERM_STEPS;
+        self.steps_filled = (self.steps_filled + 1).min(SHORT_TERM_STEPS);
+
+        self.momentary_energy = self.mean_of_last_steps(MOMENTARY_STEPS);
+        self.momentary = energy_to_lufs(self.momentary_energy);
+        self.short_term = energy_to_lufs(self.mean_of_last_steps(SHORT_TERM_STEPS));
+    }
+
+    pub fn momentary(&self) -> f32 {
+        self.momentary
+    }
+
+    pub fn short_term(&self) -> f32 {
+        self.short_term
+    }
+
+    fn mean_of_last_steps(&self, count: usize) -> f64 {
+        let count = count.min(self.steps_filled);
+        if count == 0 {
+            return 0.0;
+        }
+
+        let sum: f64 = (1..=count)
+            .map(|offset| self.steps[(self.step_idx + SHORT_TERM_STEPS - offset) % SHORT_TERM_STEPS])
+            .sum();
+        sum / count as f64
+    }
+}
+
+impl Default for LoudnessWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`LoudnessMeter`] が 100 ms ごとに求めた平均二乗値から、モーメンタリー、ショートターム、
+/// ゲート付きインテグレーテッドを計算する。バックグラウンドスレッドで使う。
+///
+/// インテグレーテッドは全ブロックを保存する代わりに、0.1 LU 刻みのヒストグラムに
+/// ブロック数とエネルギーを積算することで、メモリを固定サイズに抑えている。
+pub struct LoudnessIntegrator {
+    window: LoudnessWindow,
+
+    histogram_counts: Vec<u32>,
+    histogram_energy: Vec<f64>,
+    integrated: f32,
+}
+
+impl LoudnessIntegrator {
+    pub fn new() -> Self {
+        Self {
+            window: LoudnessWindow::new(),
+
+            histogram_counts: vec![0; HISTOGRAM_BINS],
+            histogram_energy: vec![0.0; HISTOGRAM_BINS],
+            integrated: util::MINUS_INFINITY_DB,
+        }
+    }
+
+    /// 全ての測定値をクリアする
+    pub fn reset(&mut self) {
+        self.window.reset();
+        self.reset_integrated();
+    }
+
+    /// インテグレーテッドの積算だけをやり直す
+    pub fn reset_integrated(&mut self) {
+        self.histogram_counts.fill(0);
+        self.histogram_energy.fill(0.0);
+        self.integrated = util::MINUS_INFINITY_DB;
+    }
+
+    /// 100 ms ステップ 1 つ分の平均二乗値を加え、ラウドネス値を更新する
+    pub fn push_step(&mut self, mean_square: f64) {
+        let window = &mut self.window;
+        window.push_step(mean_square);
+
+        // 400 ms のゲーティングブロックが揃ってからインテグレーテッドに加える
+        if window.steps_filled >= MOMENTARY_STEPS && window.momentary > ABSOLUTE_GATE_LUFS {
+            let bin = (((window.momentary - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU)
+                as usize)
+                .min(HISTOGRAM_BINS - 1);
+            self.histogram_counts[bin] += 1;
+            self.histogram_energy[bin] += window.momentary_energy;
+            self.integrated = self.compute_integrated();
+        }
+    }
+
+    pub fn momentary(&self) -> f32 {
+        self.window.momentary()
+    }
+
+    pub fn short_term(&self) -> f32 {
+        self.window.short_term()
+    }
+
+    pub fn integrated(&self) -> f32 {
+        self.integrated
+    }
+
+    /// 絶対ゲートを通ったブロックから相対ゲートを求め、その上のブロックだけで平均する
+    fn compute_integrated(&self) -> f32 {
+        let (count, energy) = self.gated_sums(0);
+        if count == 0 {
+            return util::MINUS_INFINITY_DB;
+        }
+
+        let relative_gate = energy_to_lufs(energy / count as f64) + RELATIVE_GATE_LU;
+        let first_bin = ((relative_gate - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU)
+            .ceil()
+            .max(0.0) as usize;
+        let (count, energy) = self.gated_sums(first_bin);
+        if count == 0 {
+            return util::MINUS_INFINITY_DB;
+        }
+
+        energy_to_lufs(energy / count as f64)
+    }
+
+    fn gated_sums(&self, first_bin: usize) -> (u64, f64) {
+        self.histogram_counts
+            .iter()
+            .zip(self.histogram_energy.iter())
+            .skip(first_bin)
+            .fold((0, 0.0), |(count, energy), (bin_count, bin_energy)| {
+                (count + *bin_count as u64, energy + bin_energy)
+            })
+    }
+}
+
+impl Default for LoudnessIntegrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 処理前（ドライ）と処理後（ウェット）の K 特性ラウドネスを比べ、
+/// ウェットをドライと同じラウドネスに揃えるためのゲインを求める。
+///
+/// バイパスと聴き比べたときに「音が大きい方が良く聴こえる」錯覚を避けるためのもの。
+pub struct GainMatcher {
+    dry_filters: Vec<KWeightingFilter>,
+    wet_filters: Vec<KWeightingFilter>,
+    /// チャンネルごとの二乗にかける重み
+    weights: Vec<f32>,
+    /// 現在のサンプルフレームで積算中の二乗和（全チャンネルの重み付き合計）
+    frame_dry: f32,
+    frame_wet: f32,
+    /// 一次のローパスで平均した二乗和
+    dry_energy: f32,
+    wet_energy: f32,
+    coef: f32,
+    trim_db: f32,
+}
+
+impl GainMatcher {
+    /// フィルターをここで確保するので、`initialize()` から呼ぶこと。`weights` は [`LoudnessMeter::new()`]
+    /// と同じ
+    pub fn new(weights: &[f32], sample_rate: f32) -> Self {
+        Self {
+            dry_filters: vec![KWeightingFilter::new(sample_rate); weights.len()],
+            wet_filters: vec![KWeightingFilter::new(sample_rate); weights.len()],
+            weights: weights.to_vec(),
+            frame_dry: 0.0,
+            frame_wet: 0.0,
+            dry_energy: 0.0,
+            wet_energy: 0.0,
+            // ショートタームと同じ 3 秒の時定数
+            coef: (-1.0 / (SHORT_TERM_STEPS as f32 * STEP_MS / 1000.0 * sample_rate)).exp(),
+            trim_db: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for filter in self.dry_filters.iter_mut().chain(self.wet_filters.iter_mut()) {
+            filter.reset();
+        }
+        self.frame_dry = 0.0;
+        self.frame_wet = 0.0;
+        self.dry_energy = 0.0;
+        self.wet_energy = 0.0;
+        self.trim_db = 0.0;
+    }
+
+    /// 1 チャンネル分のドライとウェット（トリム前）のサンプルを積算する
+    pub fn process_sample(&mut self, channel: usize, dry: f32, wet: f32) {
+        if let (Some(dry_filter), Some(wet_filter), Some(&weight)) = (
+            self.dry_filters.get_mut(channel),
+            self.wet_filters.get_mut(channel),
+            self.weights.get(channel),
+        ) {
+            let dry = dry_filter.process_sample(dry);
+            let wet = wet_filter.process_sample(wet);
+            self.frame_dry += weight * dry * dry;
+            self.frame_wet += weight * wet * wet;
+        }
+    }
+
+    /// 1 サンプルフレーム分の処理を終える
+    pub fn end_frame(&mut self) {
+        self.dry_energy = self.dry_energy * self.coef + self.frame_dry * (1.0 - self.coef);
+        self.wet_energy = self.wet_energy * self.coef + self.frame_wet * (1.0 - self.coef);
+        self.frame_dry = 0.0;
+        self.frame_wet = 0.0;
+
+        // どちらかがほぼ無音の間は、直前のトリムをそのまま使う
+        if self.dry_energy > GAIN_MATCH_SILENCE && self.wet_energy > GAIN_MATCH_SILENCE {
+            self.trim_db = (10.0 * (self.dry_energy / self.wet_energy).log10())
+                .clamp(-GAIN_MATCH_MAX_TRIM_DB, GAIN_MATCH_MAX_TRIM_DB);
+        }
+    }
+
+    /// ウェットに掛けるトリム (dB)
+    pub fn trim_db(&self) -> f32 {
+        self.trim_db
+    }
+}
+
+/// ショートタームラウドネスを測り、出力のラウドネスが入力か目標値に揃うように、ゆっくりとゲインを動かす。
+///
+/// ゲインをかける前の信号を測るので、測定がゲインの変化に引きずられて発振することはない。ゲインは
+/// [`AUTO_MAKEUP_RATE_DB_PER_S`] より速くは動かさず、[`AUTO_MAKEUP_MAX_GAIN_DB`] の範囲に収める。
+pub struct AutoMakeup {
+    input: LoudnessMeter,
+    output: LoudnessMeter,
+    input_window: LoudnessWindow,
+    output_window: LoudnessWindow,
+    /// 1 サンプルフレームでゲインを動かせる最大量 (dB)
+    max_step_db: f32,
+    gain_db: f32,
+}
+
+impl AutoMakeup {
+    /// ラウドネスメーターをここで確保するので、`initialize()` から呼ぶこと。`weights` は
+    /// [`LoudnessMeter::new()`] と同じ
+    pub fn new(weights: &[f32], sample_rate: f32) -> Self {
+        Self {
+            input: LoudnessMeter::new(weights, sample_rate),
+            output: LoudnessMeter::new(weights, sample_rate),
+            input_window: LoudnessWindow::new(),
+            output_window: LoudnessWindow::new(),
+            max_step_db: AUTO_MAKEUP_RATE_DB_PER_S / sample_rate,
+            gain_db: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.input.reset();
+        self.output.reset();
+        self.input_window.reset();
+        self.output_window.reset();
+        self.gain_db = 0.0;
+    }
+
+    /// 1 チャンネル分の入力と、ゲインをかける前の出力のサンプルを積算する
+    pub fn process_sample(&mut self, channel: usize, input: f32, output: f32) {
+        self.input.process_sample(channel, input);
+        self.output.process_sample(channel, output);
+    }
+
+    /// 1 サンプルフレーム分の処理を終え、`mode` の目標に向けてゲインを動かす。`target_lufs` は
+    /// [`AutoMakeupMode::Target`] のときの目標値
+    pub fn end_frame(&mut self, mode: AutoMakeupMode, target_lufs: f32) {
+        if let Some(mean_square) = self.input.end_frame() {
+            self.input_window.push_step(mean_square);
+        }
+        if let Some(mean_square) = self.output.end_frame() {
+            self.output_window.push_step(mean_square);
+        }
+
+        let target_lufs = match mode {
+            AutoMakeupMode::Off => {
+                self.gain_db = 0.0;
+                return;
+            }
+            AutoMakeupMode::MatchInput => self.input_window.short_term(),
+            AutoMakeupMode::Target => target_lufs,
+        };
+        // 無音（ゲート以下）の間は、直前のゲインをそのまま使う
+        let output_lufs = self.output_window.short_term();
+        if output_lufs <= ABSOLUTE_GATE_LUFS || target_lufs <= ABSOLUTE_GATE_LUFS {
+            return;
+        }
+
+        let desired_db =
+            (target_lufs - output_lufs).clamp(-AUTO_MAKEUP_MAX_GAIN_DB, AUTO_MAKEUP_MAX_GAIN_DB);
+        self.gain_db += (desired_db - self.gain_db).clamp(-self.max_step_db, self.max_step_db);
+    }
+
+    /// 出力にかけるゲイン (dB)
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+}
+
+/// チャンネル合計の平均二乗値を LUFS に変換する
+fn energy_to_lufs(energy: f64) -> f32 {
+    if energy > 0.0 {
+        ((-0.691 + 10.0 * energy.log10()) as f32).max(util::MINUS_INFINITY_DB)
+    } else {
+        util::MINUS_INFINITY_DB
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use nih_plug::prelude::{AudioIOLayout, PortNames};
+
+    use super::*;
+    use crate::surround::{ChannelLayout, LAYOUT_5_1};
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn weights_5_1() -> Vec<f32> {
+        ChannelLayout::new(&AudioIOLayout {
+            main_input_channels: NonZeroU32::new(6),
+            main_output_channels: NonZeroU32::new(6),
+            names: PortNames {
+                layout: Some(LAYOUT_5_1),
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        })
+        .loudness_weights()
+    }
+
+    fn sine(frame: usize) -> f32 {
+        0.25 * (std::f32::consts::TAU * 1000.0 * frame as f32 / SAMPLE_RATE).sin()
+    }
+
+    /// 5.1 の `channel` だけに 1 kHz のサイン波を 1 秒流し、最後のステップの平均二乗値を返す
+    fn step_energy_5_1(channel: usize) -> f64 {
+        let mut meter = LoudnessMeter::new(&weights_5_1(), SAMPLE_RATE);
+        let mut last = 0.0;
+        for frame in 0..SAMPLE_RATE as usize {
+            for ch in 0..6 {
+                meter.process_sample(ch, if ch == channel { sine(frame) } else { 0.0 });
+            }
+            if let Some(mean_square) = meter.end_frame() {
+                last = mean_square;
+            }
+        }
+        last
+    }
+
+    #[test]
+    fn surround_channels_are_weighted() {
+        assert_eq!(weights_5_1(), [1.0, 1.0, 1.0, 0.0, 1.41, 1.41]);
+
+        // L, R, C は同じ重みで、LFE は測らず、Ls, Rs は約 +1.5 dB 大きく測る
+        let center = step_energy_5_1(2);
+        assert!(center > 0.0);
+        assert!((step_energy_5_1(0) - center).abs() < center * 1e-6);
+        assert_eq!(step_energy_5_1(3), 0.0);
+        for channel in [4, 5] {
+            assert!((step_energy_5_1(channel) / center - 1.41).abs() < 1e-3);
+        }
+
+        // ゲインマッチも同じ重みを使うので、C のドライを Ls に移したウェットは 1.41 の分だけ下げ、
+        // LFE の分は無視する
+        let mut matcher = GainMatcher::new(&weights_5_1(), SAMPLE_RATE);
+        for frame in 0..SAMPLE_RATE as usize * 10 {
+            for ch in 0..6 {
+                let dry = if ch == 2 { sine(frame) } else { 0.0 };
+                let wet = if ch == 3 || ch == 4 { sine(frame) } else { 0.0 };
+                matcher.process_sample(ch, dry, wet);
+            }
+            matcher.end_frame();
+        }
+        assert!((matcher.trim_db() - -10.0 * 1.41_f32.log10()).abs() < 0.05);
+    }
+
+    #[test]
+    fn integrator_gates_and_resets() {
+        // -23 LUFS の平均二乗値
+        let level = 10.0_f64.powf((-23.0 + 0.691) / 10.0);
+        let mut integrator = LoudnessIntegrator::new();
+        for _ in 0..50 {
+            integrator.push_step(level);
+        }
+        assert!((integrator.integrated() - -23.0).abs() < 1e-3);
+
+        // 無音のステップは絶対ゲートで除かれ、インテグレーテッドは無音に切り替わる間のブロックの分しか下がらない
+        for _ in 0..50 {
+            integrator.push_step(0.0);
+        }
+        assert!((integrator.integrated() - -23.0).abs() < 0.2);
+        assert_eq!(integrator.short_term(), util::MINUS_INFINITY_DB);
+
+        integrator.reset_integrated();
+        assert_eq!(integrator.integrated(), util::MINUS_INFINITY_DB);
+        // ショートタームの窓はそのままで、リセットの後のブロックだけを積算する
+        for _ in 0..50 {
+            integrator.push_step(level);
+        }
+        assert!((integrator.momentary() - -23.0).abs() < 1e-3);
+        assert!((integrator.integrated() - -23.0).abs() < 0.2);
+    }
+}
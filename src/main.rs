@@ -0,0 +1,11 @@
+//! DAW を使わずに動かすスタンドアロン版。`standalone` フィーチャーでビルドする。
+//!
+//! オーディオのバックエンド (JACK、ALSA、CoreAudio、WASAPI) と入出力のデバイスはコマンドライン引数で選ぶ。
+//! 使える引数は `--help` で表示される。GUI はプラグイン版と同じものを開く。
+
+use multiband_compressor::MultibandCompressor;
+use nih_plug::prelude::*;
+
+fn main() {
+    nih_export_standalone::<MultibandCompressor>();
+}
@@ -0,0 +1,669 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::Enum;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::oversampling::{Oversampler, OversamplingFactor, MAX_OVERSAMPLING_FACTOR};
+use crate::processor::NUM_BANDS;
+
+/// ゲインリダクション履歴の記録レート (Hz)
+pub const GAIN_REDUCTION_HISTORY_RATE_HZ: f32 = 100.0;
+/// ゲインリダクション履歴の長さ。100 Hz で 10 秒分
+pub const GAIN_REDUCTION_HISTORY_LEN: usize = 1000;
+
+/// ゴニオメーターに送る点の数と、出力を間引いて記録するレート (Hz)
+pub const GONIOMETER_POINTS: usize = 512;
+pub const GONIOMETER_RATE_HZ: f32 = 12_000.0;
+
+/// ピークメーターが完全な無音になった後、12dB減衰するのにかかる時間
+pub const PEAK_METER_DECAY_MS: f64 = 150.0;
+/// VU 風のメーターの時定数。約 300 ms で最終値の 99% に届く
+const VU_TIME_CONSTANT_MS: f32 = 65.0;
+/// PPM の立ち上がりの時定数と、無音になった後に 24 dB 下がるまでの時間
+const PPM_ATTACK_MS: f32 = 10.0;
+const PPM_DECAY_SECONDS: f32 = 2.8;
+
+/// ピークが上回ったら即座に追従し、それ以外は `decay_weight` で減衰させた新しいメーター値を返す
+pub fn decay_peak_meter(current_peak_meter: f32, peak_amplitude: f32, decay_weight: f32) -> f32 {
+    if peak_amplitude > current_peak_meter {
+        peak_amplitude
+    } else {
+        current_peak_meter * decay_weight + peak_amplitude * (1.0 - decay_weight)
+    }
+}
+
+/// 一次のローパスで二乗平均を積分する RMS メーター
+#[derive(Debug, Clone)]
+pub struct RmsMeter {
+    mean_square: f32,
+    coef: f32,
+}
+
+impl RmsMeter {
+    pub fn new() -> Self {
+        Self {
+            mean_square: 0.0,
+            coef: 0.0,
+        }
+    }
+
+    /// 積分時間（時定数）を設定する
+    pub fn set_integration_time(&mut self, time_ms: f32, sample_rate: f32) {
+        self.coef = (-1.0 / (time_ms.max(1.0) / 1000.0 * sample_rate)).exp();
+    }
+
+    /// 1 サンプル分の二乗値（複数チャンネルならその平均）を積分する
+    pub fn process(&mut self, square: f32) {
+        self.mean_square = self.mean_square * self.coef + square * (1.0 - self.coef);
+    }
+
+    /// 現在の RMS 値（振幅）
+    pub fn rms(&self) -> f32 {
+        self.mean_square.sqrt()
+    }
+
+    pub fn reset(&mut self) {
+        self.mean_square = 0.0;
+    }
+}
+
+impl Default for RmsMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 入力と出力の L/R メーターの応答特性。エディターの設定として保存する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum MeterBallistics {
+    /// 即座に立ち上がり、[`PEAK_METER_DECAY_MS`] で 12 dB 下がるデジタルピーク
+    #[name = "Peak"]
+    Peak,
+    /// 立ち上がりも戻りも約 300 ms の VU 風
+    #[name = "VU"]
+    Vu,
+    /// 約 10 ms で立ち上がり、2.8 秒で 24 dB 下がる PPM
+    #[name = "PPM"]
+    Ppm,
+}
+
+impl MeterBallistics {
+    /// 保存されているインデックスから読み出す。範囲外ならピークにする
+    pub fn load(index: &AtomicUsize) -> Self {
+        let index = index.load(Ordering::Relaxed);
+        if index < Self::variants().len() {
+            Self::from_index(index)
+        } else {
+            MeterBallistics::Peak
+        }
+    }
+
+    /// 表示名
+    pub fn name(self) -> &'static str {
+        Self::variants()[self.to_index()]
+    }
+
+    /// ボタンで切り替えるときの次の応答特性
+    pub fn next(self) -> Self {
+        Self::from_index((self.to_index() + 1) % Self::variants().len())
+    }
+}
+
+/// [`MeterBallistics`] の応答特性で、サンプルごとに振幅を追うメーター 1 チャンネル分
+#[derive(Debug, Clone)]
+pub struct BallisticsMeter {
+    attack_coef: f32,
+    release_coef: f32,
+    value: f32,
+}
+
+impl BallisticsMeter {
+    pub fn new() -> Self {
+        Self {
+            attack_coef: 0.0,
+            release_coef: 0.0,
+            value: 0.0,
+        }
+    }
+
+    pub fn set_ballistics(&mut self, ballistics: MeterBallistics, sample_rate: f32) {
+        let time_constant_coef = |time_ms: f32| (-1000.0 / (time_ms * sample_rate)).exp();
+        (self.attack_coef, self.release_coef) = match ballistics {
+            MeterBallistics::Peak => (
+                0.0,
+                0.25f32.powf((sample_rate * PEAK_METER_DECAY_MS as f32 / 1000.0).recip()),
+            ),
+            MeterBallistics::Vu => (
+                time_constant_coef(VU_TIME_CONSTANT_MS),
+                time_constant_coef(VU_TIME_CONSTANT_MS),
+            ),
+            // 振幅の指数的な減衰は dB では直線になる
+            MeterBallistics::Ppm => (
+                time_constant_coef(PPM_ATTACK_MS),
+                10f32.powf(-24.0 / 20.0 / (PPM_DECAY_SECONDS * sample_rate)),
+            ),
+        };
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        let amplitude = sample.abs();
+        let coef = if amplitude > self.value {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.value = amplitude + coef * (self.value - amplitude);
+    }
+
+    /// 現在のメーター値（振幅）
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+    }
+}
+
+impl Default for BallisticsMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ピークホールドの保持時間
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PeakHoldTime {
+    #[id = "1s"]
+    #[name = "1 s"]
+    Seconds1,
+    #[id = "3s"]
+    #[name = "3 s"]
+    Seconds3,
+    #[id = "10s"]
+    #[name = "10 s"]
+    Seconds10,
+    /// 手動でリセットするまで保持する
+    #[id = "infinite"]
+    #[name = "Infinite"]
+    Infinite,
+}
+
+impl PeakHoldTime {
+    /// 保持時間（秒）。無限なら `None`
+    pub fn seconds(self) -> Option<f32> {
+        match self {
+            PeakHoldTime::Seconds1 => Some(1.0),
+            PeakHoldTime::Seconds3 => Some(3.0),
+            PeakHoldTime::Seconds10 => Some(10.0),
+            PeakHoldTime::Infinite => None,
+        }
+    }
+}
+
+/// 減衰するメーター値とは別に、最大値を一定時間（または無限に）保持するピークホールド
+#[derive(Debug, Clone)]
+pub struct PeakHold {
+    value: f32,
+    /// 保持するサンプル数。`None` なら無限
+    hold_samples: Option<u64>,
+    /// 最後に `value` を更新してからのサンプル数
+    elapsed_samples: u64,
+}
+
+impl PeakHold {
+    pub fn new() -> Self {
+        Self {
+            value: 0.0,
+            hold_samples: None,
+            elapsed_samples: 0,
+        }
+    }
+
+    pub fn set_hold_time(&mut self, hold_time: PeakHoldTime, sample_rate: f32) {
+        self.hold_samples = hold_time
+            .seconds()
+            .map(|seconds| (seconds * sample_rate) as u64);
+    }
+
+    /// `num_samples` サンプル分のブロックのピーク（振幅）を取り込む
+    pub fn process(&mut self, peak_amplitude: f32, num_samples: usize) {
+        self.elapsed_samples += num_samples as u64;
+        let expired = self
+            .hold_samples
+            .is_some_and(|hold_samples| self.elapsed_samples > hold_samples);
+        if peak_amplitude >= self.value || expired {
+            self.value = peak_amplitude;
+            self.elapsed_samples = 0;
+        }
+    }
+
+    /// 保持しているピーク（振幅）
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+        self.elapsed_samples = 0;
+    }
+}
+
+impl Default for PeakHold {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// DSP 負荷の平滑化の時定数 (秒)
+const DSP_LOAD_SMOOTHING_S: f32 = 0.5;
+
+/// `process()` にかかった時間を、バッファの実時間に対する割合として平滑化する DSP 負荷メーター
+#[derive(Debug, Clone, Default)]
+pub struct DspLoadMeter {
+    load_percent: f32,
+}
+
+impl DspLoadMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `num_samples` サンプルのバッファの処理に `elapsed` かかったことを記録する
+    pub fn process(&mut self, elapsed: Duration, num_samples: usize, sample_rate: f32) {
+        if num_samples == 0 {
+            return;
+        }
+
+        let buffer_duration = num_samples as f32 / sample_rate;
+        let load_percent = elapsed.as_secs_f32() / buffer_duration * 100.0;
+        // バッファの長さによらず同じ時定数になるように、バッファごとの重みを決める
+        let weight = (-buffer_duration / DSP_LOAD_SMOOTHING_S).exp();
+        self.load_percent = self.load_percent * weight + load_percent * (1.0 - weight);
+    }
+
+    /// 平滑化した DSP 負荷 (%)。100% を超えると実時間に間に合っていない
+    pub fn load_percent(&self) -> f32 {
+        self.load_percent
+    }
+
+    pub fn reset(&mut self) {
+        self.load_percent = 0.0;
+    }
+}
+
+/// 0 dBFS を超えたサンプルを数えるクリップカウンター。
+///
+/// 一度でもクリップすると、[`Self::reset()`] されるまでクリップ表示を保持する。
+#[derive(Debug, Clone, Default)]
+pub struct ClipCounter {
+    clipped_samples: u64,
+}
+
+impl ClipCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        if sample.abs() > 1.0 {
+            self.clipped_samples += 1;
+        }
+    }
+
+    /// 最後にリセットしてからクリップしたサンプル数（全チャンネル合計）
+    pub fn clipped_samples(&self) -> u64 {
+        self.clipped_samples
+    }
+
+    pub fn reset(&mut self) {
+        self.clipped_samples = 0;
+    }
+}
+
+/// L/R の相関係数を短い窓で求めるコリレーションメーター。
+///
+/// +1 が完全なモノラル、0 が無相関、-1 が逆相。積と二乗を一次のローパスで平均して計算する。
+#[derive(Debug, Clone)]
+pub struct CorrelationMeter {
+    left_right: f32,
+    left_square: f32,
+    right_square: f32,
+    coef: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new() -> Self {
+        Self {
+            left_right: 0.0,
+            left_square: 0.0,
+            right_square: 0.0,
+            coef: 0.0,
+        }
+    }
+
+    /// 平均化の時定数を設定する
+    pub fn set_window(&mut self, time_ms: f32, sample_rate: f32) {
+        self.coef = (-1.0 / (time_ms.max(1.0) / 1000.0 * sample_rate)).exp();
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) {
+        let weight = 1.0 - self.coef;
+        self.left_right = self.left_right * self.coef + left * right * weight;
+        self.left_square = self.left_square * self.coef + left * left * weight;
+        self.right_square = self.right_square * self.coef + right * right * weight;
+    }
+
+    /// 現在の相関係数 (-1..1)。無音のときは 0 を返す
+    pub fn correlation(&self) -> f32 {
+        let energy = (self.left_square * self.right_square).sqrt();
+        if energy > 1e-10 {
+            (self.left_right / energy).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.left_right = 0.0;
+        self.left_square = 0.0;
+        self.right_square = 0.0;
+    }
+}
+
+impl Default for CorrelationMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 4x オーバーサンプリングでサンプル間ピークを検出するトゥルーピーク検出器（1 チャンネル分）
+#[derive(Clone)]
+pub struct TruePeakDetector {
+    upsampler: Oversampler,
+    scratch: [f32; MAX_OVERSAMPLING_FACTOR],
+}
+
+impl TruePeakDetector {
+    pub fn new() -> Self {
+        let mut upsampler = Oversampler::new();
+        upsampler.set_factor(OversamplingFactor::X4);
+
+        Self {
+            upsampler,
+            scratch: [0.0; MAX_OVERSAMPLING_FACTOR],
+        }
+    }
+
+    /// 1 サンプルを補間し、補間後の最大の絶対値を返す
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.upsampler.upsample(sample, &mut self.scratch);
+        self.scratch
+            .iter()
+            .take(self.upsampler.factor().factor())
+            .fold(0.0_f32, |peak, x| peak.max(x.abs()))
+    }
+
+    pub fn reset(&mut self) {
+        self.upsampler.reset();
+    }
+}
+
+impl Default for TruePeakDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// GUI と共有するバンドごとのゲインリダクション履歴（固定長のリングバッファ）
+pub struct GainReductionHistory {
+    values: Vec<[AtomicF32; NUM_BANDS]>,
+    /// 次に書き込む位置。書き込み後に進めるので、ここが一番古い値になる
+    write_pos: AtomicUsize,
+}
+
+impl Default for GainReductionHistory {
+    fn default() -> Self {
+        Self {
+            values: (0..GAIN_REDUCTION_HISTORY_LEN)
+                .map(|_| Default::default())
+                .collect(),
+            write_pos: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl GainReductionHistory {
+    /// 1 エントリ分のゲインリダクション (dB) を書き込む。オーディオスレッドから呼ぶ
+    pub fn push(&self, gain_reduction_db: [f32; NUM_BANDS]) {
+        let pos = self.write_pos.load(Ordering::Relaxed);
+        for (target, value) in self.values[pos].iter().zip(gain_reduction_db) {
+            target.store(value, Ordering::Relaxed);
+        }
+        self.write_pos
+            .store((pos + 1) % GAIN_REDUCTION_HISTORY_LEN, Ordering::Release);
+    }
+
+    /// 古いものから順に `f(index, gain_reduction_db)` を呼ぶ。GUI から呼ぶ
+    pub fn for_each(&self, mut f: impl FnMut(usize, [f32; NUM_BANDS])) {
+        let start = self.write_pos.load(Ordering::Acquire);
+        for index in 0..GAIN_REDUCTION_HISTORY_LEN {
+            let entry = &self.values[(start + index) % GAIN_REDUCTION_HISTORY_LEN];
+            f(
+                index,
+                [
+                    entry[0].load(Ordering::Relaxed),
+                    entry[1].load(Ordering::Relaxed),
+                    entry[2].load(Ordering::Relaxed),
+                ],
+            );
+        }
+    }
+}
+
+/// サンプルごとのゲインリダクションを間引いて [`GainReductionHistory`] に書き込む。
+/// 間引く区間の中で最も深かった値を記録するので、短いピークも履歴に残る。
+#[derive(Debug, Clone)]
+pub struct GainReductionRecorder {
+    interval: usize,
+    counter: usize,
+    deepest: [f32; NUM_BANDS],
+}
+
+impl GainReductionRecorder {
+    pub fn new() -> Self {
+        Self {
+            interval: 1,
+            counter: 0,
+            deepest: [0.0; NUM_BANDS],
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.interval = ((sample_rate / GAIN_REDUCTION_HISTORY_RATE_HZ).round() as usize).max(1);
+    }
+
+    /// 1 サンプルフレーム分のゲインリダクション (dB) を積算する
+    pub fn process(&mut self, gain_reduction_db: &[f32; NUM_BANDS], history: &GainReductionHistory) {
+        for (deepest, value) in self.deepest.iter_mut().zip(gain_reduction_db) {
+            *deepest = deepest.min(*value);
+        }
+
+        self.counter += 1;
+        if self.counter >= self.interval {
+            history.push(self.deepest);
+            self.counter = 0;
+            self.deepest = [0.0; NUM_BANDS];
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.counter = 0;
+        self.deepest = [0.0; NUM_BANDS];
+    }
+}
+
+impl Default for GainReductionRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 出力の L/R を間引いて、ゴニオメーターに表示する直近の [`GONIOMETER_POINTS`] 点を保持するリングバッファ
+#[derive(Debug, Clone)]
+pub struct GoniometerRecorder {
+    points: [[f32; 2]; GONIOMETER_POINTS],
+    /// 次に書き込む位置。ここが一番古い点になる
+    write_pos: usize,
+    interval: usize,
+    counter: usize,
+}
+
+impl GoniometerRecorder {
+    pub fn new() -> Self {
+        Self {
+            points: [[0.0; 2]; GONIOMETER_POINTS],
+            write_pos: 0,
+            interval: 1,
+            counter: 0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.interval = ((sample_rate / GONIOMETER_RATE_HZ).round() as usize).max(1);
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) {
+        self.counter += 1;
+        if self.counter >= self.interval {
+            self.counter = 0;
+            self.points[self.write_pos] = [left, right];
+            self.write_pos = (self.write_pos + 1) % GONIOMETER_POINTS;
+        }
+    }
+
+    /// 古い順に並べた点を `points` に書き込む
+    pub fn copy_to(&self, points: &mut [[f32; 2]; GONIOMETER_POINTS]) {
+        let (newer, older) = self.points.split_at(self.write_pos);
+        points[..older.len()].copy_from_slice(older);
+        points[older.len()..].copy_from_slice(newer);
+    }
+
+    pub fn reset(&mut self) {
+        self.points = [[0.0; 2]; GONIOMETER_POINTS];
+        self.write_pos = 0;
+        self.counter = 0;
+    }
+}
+
+impl Default for GoniometerRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ゲインリダクション統計の集計ブロックの長さ (ms)
+const GAIN_REDUCTION_STATS_BLOCK_MS: f32 = 100.0;
+/// 統計を取るローリングウィンドウのブロック数。100 ms × 100 で 10 秒分
+const GAIN_REDUCTION_STATS_BLOCKS: usize = 100;
+/// これより深いゲインリダクションを「コンプレッションがかかっている」とみなす (dB)
+pub const GAIN_REDUCTION_ACTIVE_DB: f32 = 1.0;
+
+/// 1 バンド分のゲインリダクション統計（直近のローリングウィンドウ）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GainReductionStats {
+    /// 平均ゲインリダクション (dB、0 以下)
+    pub average_db: f32,
+    /// 最も深かったゲインリダクション (dB、0 以下)
+    pub max_db: f32,
+    /// [`GAIN_REDUCTION_ACTIVE_DB`] より深くかかっていた時間の割合 (%)
+    pub active_percent: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct GainReductionBlock {
+    sum_db: f32,
+    max_db: f32,
+    active_samples: u32,
+    samples: u32,
+}
+
+/// バンドごとのゲインリダクション統計を、100 ms ブロックのリングバッファで集計する。
+///
+/// ブロックは `new()` で全て確保するので、`process()` はアロケーションしない。
+#[derive(Debug, Clone)]
+pub struct GainReductionStatsTracker {
+    block_len: u32,
+    current: [GainReductionBlock; NUM_BANDS],
+    blocks: Vec<[GainReductionBlock; NUM_BANDS]>,
+    block_idx: usize,
+}
+
+impl GainReductionStatsTracker {
+    pub fn new() -> Self {
+        Self {
+            block_len: 1,
+            current: Default::default(),
+            blocks: vec![Default::default(); GAIN_REDUCTION_STATS_BLOCKS],
+            block_idx: 0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.block_len =
+            ((GAIN_REDUCTION_STATS_BLOCK_MS / 1000.0 * sample_rate).round() as u32).max(1);
+    }
+
+    /// 1 サンプルフレーム分のゲインリダクション (dB) を積算する
+    pub fn process(&mut self, gain_reduction_db: &[f32; NUM_BANDS]) {
+        for (block, reduction) in self.current.iter_mut().zip(gain_reduction_db) {
+            block.sum_db += reduction;
+            block.max_db = block.max_db.min(*reduction);
+            if *reduction < -GAIN_REDUCTION_ACTIVE_DB {
+                block.active_samples += 1;
+            }
+            block.samples += 1;
+        }
+
+        if self.current[0].samples >= self.block_len {
+            self.blocks[self.block_idx] = self.current;
+            self.block_idx = (self.block_idx + 1) % GAIN_REDUCTION_STATS_BLOCKS;
+            self.current = Default::default();
+        }
+    }
+
+    /// ローリングウィンドウ全体の統計
+    pub fn stats(&self) -> [GainReductionStats; NUM_BANDS] {
+        let mut stats = [GainReductionStats::default(); NUM_BANDS];
+        for (band, stats) in stats.iter_mut().enumerate() {
+            let mut sum_db = 0.0;
+            let mut active_samples = 0;
+            let mut samples = 0;
+            for block in self.blocks.iter().map(|blocks| &blocks[band]) {
+                sum_db += block.sum_db;
+                stats.max_db = stats.max_db.min(block.max_db);
+                active_samples += block.active_samples;
+                samples += block.samples;
+            }
+
+            if samples > 0 {
+                stats.average_db = sum_db / samples as f32;
+                stats.active_percent = active_samples as f32 / samples as f32 * 100.0;
+            }
+        }
+
+        stats
+    }
+
+    pub fn reset(&mut self) {
+        self.current = Default::default();
+        self.blocks.fill(Default::default());
+        self.block_idx = 0;
+    }
+}
+
+impl Default for GainReductionStatsTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,28 @@
+//! MIDI CC でパラメーターを操作するための、プロセッサーとエディターの共有データ。
+//!
+//! プロセッサーは受け取った CC を [`MidiCcEvent`] としてキューに積むだけで、どのパラメーターを動かすかは
+//! エディターが決める。nih-plug ではプラグイン自身がパラメーターを変えられるのはエディターの
+//! `GuiContext` からだけなので、CC はエディターを開いている間だけ効く。
+
+use std::collections::BTreeMap;
+
+use crate::sample_queue::{sample_queue, SampleConsumer, SampleProducer};
+
+/// エディターに送る CC のキューの容量
+const MIDI_CC_QUEUE_CAPACITY: usize = 256;
+
+/// CC 番号と、それに割り当てたパラメーターの ID
+pub type MidiCcMappings = BTreeMap<u8, String>;
+
+/// 受け取った 1 つの CC
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MidiCcEvent {
+    pub cc: u8,
+    /// 0〜1 に正規化した値
+    pub value: f32,
+}
+
+/// CC をエディターに送るキューを作る。書き込み側はプロセッサー、読み出し側はエディターが持つ
+pub fn midi_cc_queue() -> (SampleProducer<MidiCcEvent>, SampleConsumer<MidiCcEvent>) {
+    sample_queue(MIDI_CC_QUEUE_CAPACITY)
+}
@@ -0,0 +1,192 @@
+//! A と B の 2 つのスナップショットの間のモーフ。
+//!
+//! スナップショットには連続的な値のパラメーターだけを保存する。オン/オフや選択肢は補間できないので
+//! 含めない。A と B の両方を保存しているあいだは、プロセッサーがそれらのパラメーターの代わりに、Morph の
+//! 位置で 2 つのスナップショットを補間した値を使う。
+
+use nih_plug::prelude::{FloatParam, Param};
+use serde::{Deserialize, Serialize};
+
+use crate::crossover;
+use crate::params::{BandParams, MultibandCompressorParams};
+use crate::processor::NUM_BANDS;
+
+/// 1 バンド分の連続的なパラメーターのプレーンな値
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BandSnapshot {
+    pub threshold: f32,
+    pub ratio: f32,
+    pub attack: f32,
+    pub release: f32,
+    pub makeup: f32,
+    pub knee: f32,
+    pub range: f32,
+    pub hold: f32,
+    pub mix: f32,
+}
+
+/// 全ての連続的なパラメーターのプレーンな値
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub bands: [BandSnapshot; NUM_BANDS],
+    pub xover_lo_mid: f32,
+    pub xover_mid_hi: f32,
+    pub xover_q: f32,
+    pub amount: f32,
+    pub attack_scale: f32,
+    pub release_scale: f32,
+    pub input_gain: f32,
+    pub output_gain: f32,
+}
+
+/// 保存した A と B のスナップショット
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct MorphSnapshots {
+    pub a: Option<Snapshot>,
+    pub b: Option<Snapshot>,
+}
+
+impl MorphSnapshots {
+    /// 両方を保存していればモーフに使う組を返す
+    pub fn pair(&self) -> Option<(Snapshot, Snapshot)> {
+        self.a.zip(self.b)
+    }
+}
+
+/// サンプルごとにスムージングする値
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothedValues {
+    pub threshold: [f32; NUM_BANDS],
+    pub ratio: [f32; NUM_BANDS],
+    pub knee: [f32; NUM_BANDS],
+    pub range: [f32; NUM_BANDS],
+    pub makeup: [f32; NUM_BANDS],
+    pub mix: [f32; NUM_BANDS],
+    pub amount: f32,
+    pub input_gain: f32,
+    pub output_gain: f32,
+}
+
+impl BandSnapshot {
+    fn capture(params: &BandParams) -> Self {
+        Self {
+            threshold: params.threshold.value(),
+            ratio: params.ratio.value(),
+            attack: params.attack.value(),
+            release: params.release.value(),
+            makeup: params.makeup.value(),
+            knee: params.knee.value(),
+            range: params.range.value(),
+            hold: params.hold.value(),
+            mix: params.mix.value(),
+        }
+    }
+
+    fn morph(params: &BandParams, a: &Self, b: &Self, t: f32) -> Self {
+        Self {
+            threshold: morph_value(&params.threshold, a.threshold, b.threshold, t),
+            ratio: morph_value(&params.ratio, a.ratio, b.ratio, t),
+            attack: morph_value(&params.attack, a.attack, b.attack, t),
+            release: morph_value(&params.release, a.release, b.release, t),
+            makeup: morph_value(&params.makeup, a.makeup, b.makeup, t),
+            knee: morph_value(&params.knee, a.knee, b.knee, t),
+            range: morph_value(&params.range, a.range, b.range, t),
+            hold: morph_value(&params.hold, a.hold, b.hold, t),
+            mix: morph_value(&params.mix, a.mix, b.mix, t),
+        }
+    }
+}
+
+impl Snapshot {
+    /// パラメーターの今の値
+    pub fn capture(params: &MultibandCompressorParams) -> Self {
+        Self {
+            bands: params.bands().map(BandSnapshot::capture),
+            xover_lo_mid: params.crossovers.lo_mid.value(),
+            xover_mid_hi: params.crossovers.mid_hi.value(),
+            xover_q: params.crossovers.q.value(),
+            amount: params.global.amount.value(),
+            attack_scale: params.global.attack_scale.value(),
+            release_scale: params.global.release_scale.value(),
+            input_gain: params.global.input_gain.value(),
+            output_gain: params.global.output_gain.value(),
+        }
+    }
+
+    /// `a` から `b` へ `t` (0..1) だけ進めた値。ノブの動きと揃うように、各パラメーターの正規化した値で
+    /// 補間する
+    pub fn morph(params: &MultibandCompressorParams, a: &Self, b: &Self, t: f32) -> Self {
+        let bands = params.bands();
+        let crossovers = &params.crossovers;
+        let global = &params.global;
+
+        Self {
+            bands: std::array::from_fn(|band| {
+                BandSnapshot::morph(bands[band], &a.bands[band], &b.bands[band], t)
+            }),
+            xover_lo_mid: morph_value(&crossovers.lo_mid, a.xover_lo_mid, b.xover_lo_mid, t),
+            xover_mid_hi: morph_value(&crossovers.mid_hi, a.xover_mid_hi, b.xover_mid_hi, t),
+            xover_q: morph_value(&crossovers.q, a.xover_q, b.xover_q, t),
+            amount: morph_value(&global.amount, a.amount, b.amount, t),
+            attack_scale: morph_value(&global.attack_scale, a.attack_scale, b.attack_scale, t),
+            release_scale: morph_value(&global.release_scale, a.release_scale, b.release_scale, t),
+            input_gain: morph_value(&global.input_gain, a.input_gain, b.input_gain, t),
+            output_gain: morph_value(&global.output_gain, a.output_gain, b.output_gain, t),
+        }
+    }
+
+    /// 実際に使うクロスオーバー周波数 (Low-Mid, Mid-High)
+    pub fn crossover_frequencies(&self) -> [f32; 2] {
+        crossover::limit_mid_band(self.xover_lo_mid, self.xover_mid_hi)
+    }
+}
+
+impl SmoothedValues {
+    /// パラメーターのスムーザーを 1 サンプル進めた値
+    pub fn next(params: &MultibandCompressorParams) -> Self {
+        let bands = params.bands();
+
+        Self {
+            threshold: bands.map(|band| band.threshold.smoothed.next()),
+            ratio: bands.map(|band| band.ratio.smoothed.next()),
+            knee: bands.map(|band| band.knee.smoothed.next()),
+            range: bands.map(|band| band.range.smoothed.next()),
+            makeup: bands.map(|band| band.makeup.smoothed.next()),
+            mix: bands.map(|band| band.mix.smoothed.next()),
+            amount: params.global.amount.smoothed.next(),
+            input_gain: params.global.input_gain.smoothed.next(),
+            output_gain: params.global.output_gain.smoothed.next(),
+        }
+    }
+
+    /// `a` から `b` へ `t` (0..1) だけ進めた値。レシオ以外は線形の範囲のパラメーターなので、プレーンな値で
+    /// 補間しても正規化した値で補間したのと同じになる
+    pub fn morph(params: &MultibandCompressorParams, a: &Snapshot, b: &Snapshot, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let bands = params.bands();
+
+        Self {
+            threshold: std::array::from_fn(|band| {
+                lerp(a.bands[band].threshold, b.bands[band].threshold)
+            }),
+            ratio: std::array::from_fn(|band| {
+                morph_value(&bands[band].ratio, a.bands[band].ratio, b.bands[band].ratio, t)
+            }),
+            knee: std::array::from_fn(|band| lerp(a.bands[band].knee, b.bands[band].knee)),
+            range: std::array::from_fn(|band| lerp(a.bands[band].range, b.bands[band].range)),
+            makeup: std::array::from_fn(|band| lerp(a.bands[band].makeup, b.bands[band].makeup)),
+            mix: std::array::from_fn(|band| lerp(a.bands[band].mix, b.bands[band].mix)),
+            amount: lerp(a.amount, b.amount),
+            input_gain: lerp(a.input_gain, b.input_gain),
+            output_gain: lerp(a.output_gain, b.output_gain),
+        }
+    }
+}
+
+/// `param` の正規化した値で `a` と `b` を補間したプレーンな値
+fn morph_value(param: &FloatParam, a: f32, b: f32, t: f32) -> f32 {
+    let a = param.preview_normalized(a);
+    let b = param.preview_normalized(b);
+
+    param.preview_plain(a + (b - a) * t)
+}
@@ -1,247 +1,809 @@
 use nih_plug::prelude::*;
-use nih_plug_iced::IcedState;
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, RwLock};
+
+use crate::biquad::BUTTERWORTH_Q;
+use crate::compression::DetectorMode;
+use crate::crossover;
+use crate::gui;
+use crate::loudness::AutoMakeupMode;
+use crate::metering::PeakHoldTime;
+use crate::midi::MidiCcMappings;
+use crate::morph::MorphSnapshots;
+use crate::oversampling::OversamplingFactor;
+use crate::precision::Precision;
+use crate::processor::NUM_BANDS;
+use crate::surround::ChannelLink;
+use crate::units;
+
+/// 保存するステートの形式のバージョン。パラメーターの ID や意味を変えたら 1 つ上げ、
+/// `migrate_state()` に古い形式からの変換を足す
+///
+/// - 0: `state-version` を保存する前のステート
+/// - 1: `state-version` を保存するようにした。パラメーターの ID と意味は 0 と同じ
+pub const STATE_VERSION: usize = 1;
+/// `state_version` を保存するキー。`#[persist]` の属性と同じにする
+const STATE_VERSION_KEY: &str = "state-version";
 
 #[derive(Params)]
 pub struct MultibandCompressorParams {
+    /// このステートを保存したときの [`STATE_VERSION`]
+    #[persist = "state-version"]
+    pub state_version: Arc<AtomicUsize>,
     #[persist = "editor-state"]
-    pub editor_state: Arc<IcedState>,
-
-    // Low band parameters
-    #[id = "threshold_low"]
-    pub threshold_low: FloatParam,
-    #[id = "ratio_low"]
-    pub ratio_low: FloatParam,
-    #[id = "attack_low"]
-    pub attack_low: FloatParam,
-    #[id = "release_low"]
-    pub release_low: FloatParam,
-    #[id = "makeup_low"]
-    pub makeup_low: FloatParam,
-
-    // Mid band parameters
-    #[id = "threshold_mid"]
-    pub threshold_mid: FloatParam,
-    #[id = "ratio_mid"]
-    pub ratio_mid: FloatParam,
-    #[id = "attack_mid"]
-    pub attack_mid: FloatParam,
-    #[id = "release_mid"]
-    pub release_mid: FloatParam,
-    #[id = "makeup_mid"]
-    pub makeup_mid: FloatParam,
-
-    // High band parameters
-    #[id = "threshold_high"]
-    pub threshold_high: FloatParam,
-    #[id = "ratio_high"]
-    pub ratio_high: FloatParam,
-    #[id = "attack_high"]
-    pub attack_high: FloatParam,
-    #[id = "release_high"]
-    pub release_high: FloatParam,
-    #[id = "makeup_high"]
-    pub makeup_high: FloatParam,
-
-    // Crossover frequencies
+    pub editor_state: Arc<gui::EditorState>,
+    /// エディターをダークテーマで表示するか
+    #[persist = "dark-theme"]
+    pub dark_theme: Arc<AtomicBool>,
+    /// バンドのアクセントカラー（`BandAccents` のインデックス）
+    #[persist = "band-accents"]
+    pub band_accents: Arc<AtomicUsize>,
+    /// GUI の拡大率（`UiScale` のインデックス）
+    #[persist = "ui-scale"]
+    pub ui_scale: Arc<AtomicUsize>,
+    /// 3 列ではなく、選んだ 1 バンドだけを大きく表示するか
+    #[persist = "focused-band-view"]
+    pub focused_band_view: Arc<AtomicBool>,
+    /// 1 バンド表示で表示しているバンドのインデックス
+    #[persist = "focused-band"]
+    pub focused_band: Arc<AtomicUsize>,
+    /// バンドのスレッショルド、レシオ、メイクアップを、差を保ったまま全バンドで一緒に動かすか
+    #[persist = "link-bands"]
+    pub link_bands: Arc<AtomicBool>,
+    /// 入力と出力のメーターの表示範囲（`MeterScale` のインデックス）
+    #[persist = "meter-scale"]
+    pub meter_scale: Arc<AtomicUsize>,
+    /// 入力と出力のメーターの応答特性（`MeterBallistics` のインデックス）
+    #[persist = "meter-ballistics"]
+    pub meter_ballistics: Arc<AtomicUsize>,
+    /// 各バンドの詳細設定（ニー、レンジ、ホールド、検出方法、ミックス）を開いているか
+    #[persist = "advanced-low"]
+    pub advanced_low: Arc<AtomicBool>,
+    #[persist = "advanced-mid"]
+    pub advanced_mid: Arc<AtomicBool>,
+    #[persist = "advanced-high"]
+    pub advanced_high: Arc<AtomicBool>,
+    /// MIDI ラーンで CC に割り当てたパラメーター
+    #[persist = "midi-cc-mappings"]
+    pub midi_cc_mappings: Arc<RwLock<MidiCcMappings>>,
+    /// Morph で補間する A と B のスナップショット
+    #[persist = "morph-snapshots"]
+    pub morph_snapshots: Arc<RwLock<MorphSnapshots>>,
+
+    /// バンドのパラメーター。ホストにはバンドごとのグループとして見え、ID には `_low` などが付く。
+    /// 3 つまとめて扱うときは `bands()` を使う
+    #[nested(group = "Low Band")]
+    pub low: BandParams,
+    #[nested(group = "Mid Band")]
+    pub mid: BandParams,
+    #[nested(group = "High Band")]
+    pub high: BandParams,
+
+    #[nested(group = "Crossovers")]
+    pub crossovers: CrossoverParams,
+
+    #[nested(group = "Global")]
+    pub global: GlobalParams,
+}
+
+/// バンドを分けるクロスオーバーのパラメーター
+#[derive(Params)]
+pub struct CrossoverParams {
     #[id = "xover_lo_mid"]
-    pub xover_lo_mid: FloatParam,
+    pub lo_mid: FloatParam,
     #[id = "xover_mid_hi"]
-    pub xover_mid_hi: FloatParam,
+    pub mid_hi: FloatParam,
+    #[id = "xover_q"]
+    pub q: FloatParam,
+}
+
+/// バンドに属さない、プラグイン全体のパラメーター
+#[derive(Params)]
+pub struct GlobalParams {
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+    #[id = "amount"]
+    pub amount: FloatParam,
+    #[id = "attack_scale"]
+    pub attack_scale: FloatParam,
+    #[id = "release_scale"]
+    pub release_scale: FloatParam,
+    #[id = "morph"]
+    pub morph: FloatParam,
+    #[id = "input_gain"]
+    pub input_gain: FloatParam,
+    #[id = "output_gain"]
+    pub output_gain: FloatParam,
+    #[id = "oversampling"]
+    pub oversampling: EnumParam<OversamplingFactor>,
+    #[id = "precision"]
+    pub precision: EnumParam<Precision>,
+    #[id = "channel_link"]
+    pub channel_link: EnumParam<ChannelLink>,
+    #[id = "exclude_lfe"]
+    pub exclude_lfe: BoolParam,
+    #[id = "rms_time"]
+    pub rms_time: FloatParam,
+    #[id = "peak_hold"]
+    pub peak_hold: EnumParam<PeakHoldTime>,
+    #[id = "gain_match"]
+    pub gain_match: BoolParam,
+    #[id = "auto_makeup"]
+    pub auto_makeup: EnumParam<AutoMakeupMode>,
+    #[id = "loudness_target"]
+    pub loudness_target: FloatParam,
+    #[id = "k_weighted_meters"]
+    pub k_weighted_meters: BoolParam,
+    #[id = "k_weighted_detection"]
+    pub k_weighted_detection: BoolParam,
+    #[id = "fast_detector"]
+    pub fast_detector: BoolParam,
+    #[id = "offline_quality"]
+    pub offline_quality: BoolParam,
+    #[id = "learn_offset"]
+    pub learn_offset: FloatParam,
+    #[id = "midi_ducking"]
+    pub midi_ducking: BoolParam,
+    #[id = "duck_attack"]
+    pub duck_attack: FloatParam,
+    #[id = "duck_release"]
+    pub duck_release: FloatParam,
 }
 
 impl Default for MultibandCompressorParams {
     fn default() -> Self {
         Self {
-            editor_state: IcedState::from_size(680, 500),
+            state_version: Arc::new(AtomicUsize::new(STATE_VERSION)),
+            editor_state: gui::default_state(),
+            dark_theme: Arc::new(AtomicBool::new(false)),
+            band_accents: Arc::new(AtomicUsize::new(0)),
+            // 100%
+            ui_scale: Arc::new(AtomicUsize::new(1)),
+            focused_band_view: Arc::new(AtomicBool::new(false)),
+            focused_band: Arc::new(AtomicUsize::new(0)),
+            link_bands: Arc::new(AtomicBool::new(false)),
+            meter_scale: Arc::new(AtomicUsize::new(0)),
+            meter_ballistics: Arc::new(AtomicUsize::new(0)),
+            advanced_low: Arc::new(AtomicBool::new(false)),
+            advanced_mid: Arc::new(AtomicBool::new(false)),
+            advanced_high: Arc::new(AtomicBool::new(false)),
+            midi_cc_mappings: Arc::new(RwLock::new(MidiCcMappings::new())),
+            morph_snapshots: Arc::new(RwLock::new(MorphSnapshots::default())),
 
-            // Low band
-            threshold_low: FloatParam::new(
-                "Threshold Low",
-                -12.0,
-                FloatRange::Linear {
-                    min: -60.0,
-                    max: 0.0,
+            low: BandParams::new(BAND_DEFAULTS[0]),
+            mid: BandParams::new(BAND_DEFAULTS[1]),
+            high: BandParams::new(BAND_DEFAULTS[2]),
+
+            crossovers: CrossoverParams::default(),
+            global: GlobalParams::default(),
+        }
+    }
+}
+
+impl Default for CrossoverParams {
+    fn default() -> Self {
+        Self {
+            // 可聴域全体を選べるようにし、周波数の比で動くように偏らせる。Mid-High が Low-Mid より低い
+            // ときは `frequencies()` で Mid-High を持ち上げる
+            lo_mid: FloatParam::new(
+                "Crossover Low-Mid",
+                200.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20_000.0,
+                    factor: FloatRange::skew_factor(-2.0),
                 },
             )
-            .with_unit(" dB")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_value_to_string(units::v2s_hz_then_khz())
+            .with_string_to_value(units::s2v_hz()),
+
+            mid_hi: FloatParam::new(
+                "Crossover Mid-High",
+                2000.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20_000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(units::v2s_hz_then_khz())
+            .with_string_to_value(units::s2v_hz()),
 
-            ratio_low: FloatParam::new(
-                "Ratio Low",
-                2.0,
+            // 0.707 で LR4 (フラットな合成特性)、低いほどバンドが緩やかに重なり、高いほど急峻に分離する
+            q: FloatParam::new(
+                "Crossover Q",
+                BUTTERWORTH_Q,
                 FloatRange::Linear {
-                    min: 1.0,
-                    max: 20.0,
+                    min: 0.5,
+                    max: 1.5,
                 },
             )
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
+        }
+    }
+}
+
+impl Default for GlobalParams {
+    fn default() -> Self {
+        Self {
+            // ホストのバイパスボタンに割り当てる。プロセッサーは処理を止めずにドライ信号へクロスフェードする
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
 
-            attack_low: FloatParam::new(
-                "Attack Low",
-                20.0,
+            // 全バンドのレシオとレンジをまとめて弱める。0% で圧縮しなくなる
+            amount: FloatParam::new(
+                "Amount",
+                100.0,
                 FloatRange::Linear {
-                    min: 0.1,
+                    min: 0.0,
                     max: 100.0,
                 },
             )
-            .with_unit(" ms")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
 
-            release_low: FloatParam::new(
-                "Release Low",
-                150.0,
-                FloatRange::Linear {
-                    min: 10.0,
-                    max: 1000.0,
+            // 全バンドのアタックとリリースの時間にかける倍率。中央がおよそ 1x になる
+            attack_scale: FloatParam::new(
+                "Attack Scale",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 4.0,
+                    factor: FloatRange::skew_factor(-1.2),
+                },
+            )
+            .with_unit("x")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            release_scale: FloatParam::new(
+                "Release Scale",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 4.0,
+                    factor: FloatRange::skew_factor(-1.2),
                 },
             )
-            .with_unit(" ms")
+            .with_unit("x")
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            makeup_low: FloatParam::new(
-                "Makeup Low",
+            // A と B のスナップショットを両方保存しているときの、A (0%) から B (100%) までの位置
+            morph: FloatParam::new(
+                "Morph",
                 0.0,
                 FloatRange::Linear {
                     min: 0.0,
-                    max: 24.0,
+                    max: 100.0,
                 },
             )
-            .with_unit(" dB")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
 
-            // Mid band
-            threshold_mid: FloatParam::new(
-                "Threshold Mid",
-                -10.0,
+            // バンド分割前の入力と、合成後の出力にかけるゲイン
+            input_gain: FloatParam::new(
+                "Input Trim",
+                0.0,
                 FloatRange::Linear {
-                    min: -60.0,
-                    max: 0.0,
+                    min: -24.0,
+                    max: 24.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(50.0))
             .with_unit(" dB")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
-
-            ratio_mid: FloatParam::new(
-                "Ratio Mid",
-                3.0,
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            output_gain: FloatParam::new(
+                "Output Gain",
+                0.0,
                 FloatRange::Linear {
-                    min: 1.0,
-                    max: 20.0,
+                    min: -24.0,
+                    max: 24.0,
                 },
             )
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
 
-            attack_mid: FloatParam::new(
-                "Attack Mid",
-                10.0,
+            oversampling: EnumParam::new("Oversampling", OversamplingFactor::Off),
+            // バンド分割と圧縮を倍精度で行う。高いサンプルレートでのマスタリング向けで、処理は少し重くなる
+            precision: EnumParam::new("Precision", Precision::Single),
+            // 各バンドの検出器のレベルをリンクしたチャンネルで揃え、同じだけ圧縮する。Exclude LFE なら
+            // サラウンドの LFE は All でもリンクしない
+            channel_link: EnumParam::new("Channel Link", ChannelLink::Off),
+            exclude_lfe: BoolParam::new("Exclude LFE", true),
+
+            // RMS メーターの積分時間（300 ms で VU メーター相当）
+            rms_time: FloatParam::new(
+                "RMS Time",
+                300.0,
                 FloatRange::Linear {
-                    min: 0.1,
-                    max: 100.0,
+                    min: 10.0,
+                    max: 3000.0,
                 },
             )
-            .with_unit(" ms")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_value_to_string(units::v2s_ms_then_s(0))
+            .with_string_to_value(units::s2v_ms()),
 
-            release_mid: FloatParam::new(
-                "Release Mid",
-                100.0,
+            // ピークメーターのホールド時間
+            peak_hold: EnumParam::new("Peak Hold", PeakHoldTime::Seconds3),
+
+            // 出力を入力と同じラウドネスに揃え、バイパスと公平に聴き比べられるようにする
+            gain_match: BoolParam::new("Gain Match", false),
+
+            // 出力のショートタームラウドネスを、入力か Loudness Target に揃えるようにゆっくりゲインを動かす
+            auto_makeup: EnumParam::new("Auto Makeup", AutoMakeupMode::Off),
+            loudness_target: FloatParam::new(
+                "Loudness Target",
+                -14.0,
                 FloatRange::Linear {
-                    min: 10.0,
-                    max: 1000.0,
+                    min: -36.0,
+                    max: -6.0,
                 },
             )
-            .with_unit(" ms")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_unit(" LUFS")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
 
-            makeup_mid: FloatParam::new(
-                "Makeup Mid",
-                0.0,
+            // BS.1770 の K 特性を RMS メーターやコンプレッサーの検出器の前段にかける。
+            // 聴感上のラウドネスに近い反応になるが、低域のバンドは反応が鈍くなる
+            k_weighted_meters: BoolParam::new("K-Weighted Meters", false),
+            k_weighted_detection: BoolParam::new("K-Weighted Detection", false),
+            // コンプレッサーの dB 変換を多項式の近似にして、処理を軽くする。誤差は 1e-4 dB 程度
+            fast_detector: BoolParam::new("Fast Detector", false),
+            // オフラインレンダリング中は、オーバーサンプリングを 4x 以上、演算精度を 64-bit にして、
+            // Fast Detector の近似も使わない
+            offline_quality: BoolParam::new("Offline Quality", true),
+
+            // オートスレッショルドの学習で、測ったレベルからどれだけ下にスレッショルドを置くか
+            learn_offset: FloatParam::new(
+                "Learn Offset",
+                6.0,
                 FloatRange::Linear {
                     min: 0.0,
                     max: 24.0,
                 },
             )
             .with_unit(" dB")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            // MIDI のノートを押している間、各バンドをそのバンドの Duck の量だけ下げる。
+            // 深さはベロシティに比例し、アタックとリリースで滑らかに下げて戻す
+            midi_ducking: BoolParam::new("MIDI Ducking", false),
+            duck_attack: FloatParam::new(
+                "Duck Attack",
+                5.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 100.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(units::v2s_ms_then_s(2))
+            .with_string_to_value(units::s2v_ms()),
+            duck_release: FloatParam::new(
+                "Duck Release",
+                200.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(units::v2s_ms_then_s(2))
+            .with_string_to_value(units::s2v_ms()),
+        }
+    }
+}
 
-            // High band
-            threshold_high: FloatParam::new(
-                "Threshold High",
-                -8.0,
+/// 1 バンド分のパラメーター。ID は `threshold_low` のように後ろにバンドの名前が付く（`Params` の実装を参照）
+pub struct BandParams {
+    /// ID の後ろに付けるバンドの名前
+    id_suffix: &'static str,
+
+    pub threshold: FloatParam,
+    pub ratio: FloatParam,
+    pub attack: FloatParam,
+    pub release: FloatParam,
+    pub makeup: FloatParam,
+    pub solo: BoolParam,
+    pub mute: BoolParam,
+    pub bypass: BoolParam,
+    pub knee: FloatParam,
+    pub range: FloatParam,
+    pub hold: FloatParam,
+    pub detector: EnumParam<DetectorMode>,
+    pub mix: FloatParam,
+    pub duck: FloatParam,
+}
+
+// `#[derive(Params)]` の `#[nested(id_prefix)]` では ID の前にしか付けられないので、手で実装して
+// バンドを分けていたころから変わらない `threshold_low` などの ID を保つ。ID を変えると、保存したセッションや
+// オートメーションが読み込めなくなる
+// SAFETY: the pointers come from fields of `self`, and the plugin keeps the params in an `Arc` so
+//         they never move
+unsafe impl Params for BandParams {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        let params = [
+            ("threshold", self.threshold.as_ptr()),
+            ("ratio", self.ratio.as_ptr()),
+            ("attack", self.attack.as_ptr()),
+            ("release", self.release.as_ptr()),
+            ("makeup", self.makeup.as_ptr()),
+            ("solo", self.solo.as_ptr()),
+            ("mute", self.mute.as_ptr()),
+            ("bypass", self.bypass.as_ptr()),
+            ("knee", self.knee.as_ptr()),
+            ("range", self.range.as_ptr()),
+            ("hold", self.hold.as_ptr()),
+            ("detector", self.detector.as_ptr()),
+            ("mix", self.mix.as_ptr()),
+            ("duck", self.duck.as_ptr()),
+        ];
+
+        params
+            .into_iter()
+            .map(|(id, ptr)| (format!("{id}_{}", self.id_suffix), ptr, String::new()))
+            .collect()
+    }
+}
+
+/// バンドごとに異なるデフォルト値
+#[derive(Clone, Copy)]
+struct BandDefaults {
+    /// パラメーター名の後ろに付ける名前
+    name: &'static str,
+    /// パラメーター ID の後ろに付ける名前
+    id_suffix: &'static str,
+    threshold_db: f32,
+    ratio: f32,
+    attack_ms: f32,
+    release_ms: f32,
+}
+
+/// 高いバンドほどスレッショルドを低めに、レシオを高めに、時定数を短くする
+const BAND_DEFAULTS: [BandDefaults; NUM_BANDS] = [
+    BandDefaults {
+        name: "Low",
+        id_suffix: "low",
+        threshold_db: -12.0,
+        ratio: 2.0,
+        attack_ms: 20.0,
+        release_ms: 150.0,
+    },
+    BandDefaults {
+        name: "Mid",
+        id_suffix: "mid",
+        threshold_db: -10.0,
+        ratio: 3.0,
+        attack_ms: 10.0,
+        release_ms: 100.0,
+    },
+    BandDefaults {
+        name: "High",
+        id_suffix: "high",
+        threshold_db: -8.0,
+        ratio: 4.0,
+        attack_ms: 5.0,
+        release_ms: 80.0,
+    },
+];
+
+impl BandParams {
+    fn new(defaults: BandDefaults) -> Self {
+        let name = defaults.name;
+
+        Self {
+            id_suffix: defaults.id_suffix,
+
+            threshold: FloatParam::new(
+                format!("Threshold {name}"),
+                defaults.threshold_db,
                 FloatRange::Linear {
                     min: -60.0,
                     max: 0.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(20.0))
             .with_unit(" dB")
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            ratio_high: FloatParam::new(
-                "Ratio High",
-                4.0,
-                FloatRange::Linear {
+            ratio: FloatParam::new(
+                format!("Ratio {name}"),
+                defaults.ratio,
+                // 中央がおよそ 6:1 になる
+                FloatRange::Skewed {
                     min: 1.0,
                     max: 20.0,
+                    factor: FloatRange::skew_factor(-1.0),
                 },
             )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
 
-            attack_high: FloatParam::new(
-                "Attack High",
-                5.0,
-                FloatRange::Linear {
+            attack: FloatParam::new(
+                format!("Attack {name}"),
+                defaults.attack_ms,
+                // 0.1〜10 ms にノブの半分を使う
+                FloatRange::Skewed {
                     min: 0.1,
                     max: 100.0,
+                    factor: FloatRange::skew_factor(-2.0),
                 },
             )
-            .with_unit(" ms")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_value_to_string(units::v2s_ms_then_s(2))
+            .with_string_to_value(units::s2v_ms()),
 
-            release_high: FloatParam::new(
-                "Release High",
-                80.0,
-                FloatRange::Linear {
+            release: FloatParam::new(
+                format!("Release {name}"),
+                defaults.release_ms,
+                // 中央がおよそ 70 ms になる
+                FloatRange::Skewed {
                     min: 10.0,
                     max: 1000.0,
+                    factor: FloatRange::skew_factor(-2.0),
                 },
             )
-            .with_unit(" ms")
-            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            .with_value_to_string(units::v2s_ms_then_s(2))
+            .with_string_to_value(units::s2v_ms()),
 
-            makeup_high: FloatParam::new(
-                "Makeup High",
+            makeup: FloatParam::new(
+                format!("Makeup {name}"),
                 0.0,
                 FloatRange::Linear {
                     min: 0.0,
                     max: 24.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(20.0))
             .with_unit(" dB")
             .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            solo: BoolParam::new(format!("Solo {name}"), false),
+            mute: BoolParam::new(format!("Mute {name}"), false),
+            bypass: BoolParam::new(format!("Bypass {name}"), false),
 
-            // Crossovers
-            xover_lo_mid: FloatParam::new(
-                "Crossover Low-Mid",
-                200.0,
+            knee: FloatParam::new(
+                format!("Knee {name}"),
+                0.0,
                 FloatRange::Linear {
-                    min: 40.0,
-                    max: 1000.0,
+                    min: 0.0,
+                    max: 24.0,
                 },
             )
-            .with_unit(" Hz")
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" dB")
             .with_value_to_string(formatters::v2s_f32_rounded(1)),
 
-            xover_mid_hi: FloatParam::new(
-                "Crossover Mid-High",
-                2000.0,
+            range: FloatParam::new(
+                format!("Range {name}"),
+                60.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 60.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            hold: FloatParam::new(
+                format!("Hold {name}"),
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 500.0,
+                },
+            )
+            .with_value_to_string(units::v2s_ms_then_s(1))
+            .with_string_to_value(units::s2v_ms()),
+
+            detector: EnumParam::new(format!("Detector {name}"), DetectorMode::Peak),
+
+            mix: FloatParam::new(
+                format!("Mix {name}"),
+                100.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            // MIDI Ducking でノートを押したときに、このバンドを下げる量
+            duck: FloatParam::new(
+                format!("Duck {name}"),
+                12.0,
                 FloatRange::Linear {
-                    min: 500.0,
-                    max: 8000.0,
+                    min: 0.0,
+                    max: 48.0,
                 },
             )
-            .with_unit(" Hz")
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" dB")
             .with_value_to_string(formatters::v2s_f32_rounded(1)),
         }
     }
 }
+
+impl MultibandCompressorParams {
+    /// Solo と Mute を反映した、各バンドが出力に含まれるかどうか。
+    /// どれかのバンドがソロになっているときは、ソロでないバンドをミュートする
+    pub fn audible_bands(&self) -> [bool; NUM_BANDS] {
+        let solo = self.bands().map(|band| band.solo.value());
+        let mute = self.bands().map(|band| band.mute.value());
+        let any_solo = solo.contains(&true);
+
+        std::array::from_fn(|band| !mute[band] && (solo[band] || !any_solo))
+    }
+
+    /// 各バンドのコンプレッサーをバイパスするかどうか
+    pub fn bypassed_bands(&self) -> [bool; NUM_BANDS] {
+        self.bands().map(|band| band.bypass.value())
+    }
+
+    /// low, mid, high の順に並べたバンドのパラメーター
+    pub fn bands(&self) -> [&BandParams; NUM_BANDS] {
+        [&self.low, &self.mid, &self.high]
+    }
+
+    /// CLAP のホストがハードウェアのコントローラーに割り当てるページ。バンドごとに 1 ページ、全体で
+    /// 1 ページを、どれも 8 個のノブに収まるように並べる
+    pub fn remote_controls(&self, context: &mut impl RemoteControlsContext) {
+        for (band, defaults) in self.bands().into_iter().zip(&BAND_DEFAULTS) {
+            let name = format!("{} Band", defaults.name);
+            context.add_section(&name, |section| {
+                section.add_page(&name, |page| {
+                    page.add_param(&band.threshold);
+                    page.add_param(&band.ratio);
+                    page.add_param(&band.attack);
+                    page.add_param(&band.release);
+                    page.add_param(&band.makeup);
+                    page.add_param(&band.knee);
+                    page.add_param(&band.range);
+                    page.add_param(&band.mix);
+                });
+            });
+        }
+
+        context.add_section("Global", |section| {
+            section.add_page("Global", |page| {
+                page.add_param(&self.global.amount);
+                page.add_param(&self.global.input_gain);
+                page.add_param(&self.global.output_gain);
+                page.add_param(&self.crossovers.lo_mid);
+                page.add_param(&self.crossovers.mid_hi);
+                page.add_param(&self.crossovers.q);
+                page.add_param(&self.global.gain_match);
+                page.add_param(&self.global.bypass);
+            });
+        });
+    }
+
+    /// 各バンドの詳細設定を開いているか
+    pub fn advanced_bands(&self) -> [&AtomicBool; NUM_BANDS] {
+        [&self.advanced_low, &self.advanced_mid, &self.advanced_high]
+    }
+
+    /// 古いバージョンで保存したステートを今の形式に変換する。バージョンのないステートは 0 として扱う
+    pub fn migrate_state(state: &mut PluginState) {
+        let version = state
+            .fields
+            .get(STATE_VERSION_KEY)
+            .and_then(|version| version.parse::<usize>().ok())
+            .unwrap_or(0);
+        if version > STATE_VERSION {
+            nih_warn!(
+                "The state was saved by a newer version (state version {version}, this version \
+                 reads up to {STATE_VERSION}), some parameters may not be restored"
+            );
+        }
+
+        Self::migrate_param_ids(&mut state.params, version);
+
+        state
+            .fields
+            .insert(STATE_VERSION_KEY.to_owned(), STATE_VERSION.to_string());
+    }
+
+    /// `version` の形式で保存したパラメーターの ID を今の ID に置き換える。ステートとプリセットファイルの
+    /// 両方で使う
+    pub fn migrate_param_ids<T>(params: &mut BTreeMap<String, T>, version: usize) {
+        rename_param_ids(params, version, RENAMED_PARAM_IDS);
+    }
+}
+
+impl CrossoverParams {
+    /// 実際に使うクロスオーバー周波数 (Low-Mid, Mid-High)。[`crossover::limit_mid_band()`] を参照
+    pub fn frequencies(&self) -> [f32; 2] {
+        crossover::limit_mid_band(self.lo_mid.value(), self.mid_hi.value())
+    }
+}
+
+/// ID を変えたパラメーターの (変えたときの [`STATE_VERSION`], 古い ID, 新しい ID)。ホストのオートメーションは
+/// ID で結び付くので、ID はできるだけ変えない。今のところ変えたものはない
+const RENAMED_PARAM_IDS: &[(usize, &str, &str)] = &[];
+
+/// `version` より後で ID を変えたパラメーターの値を、古い ID から新しい ID に移す
+fn rename_param_ids<T>(
+    params: &mut BTreeMap<String, T>,
+    version: usize,
+    renames: &[(usize, &str, &str)],
+) {
+    for &(renamed_in, old_id, new_id) in renames {
+        if version < renamed_in {
+            if let Some(value) = params.remove(old_id) {
+                params.insert(new_id.to_owned(), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `state-version` を保存する前の版のステート
+    fn version_0_state() -> PluginState {
+        PluginState {
+            version: String::from("0.1.0"),
+            params: BTreeMap::from([
+                (String::from("threshold_low"), ParamValue::F32(-20.0)),
+                (String::from("ratio_mid"), ParamValue::F32(4.0)),
+                (String::from("solo_high"), ParamValue::Bool(true)),
+                (String::from("detector_low"), ParamValue::I32(1)),
+                (String::from("xover_lo_mid"), ParamValue::F32(150.0)),
+            ]),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn version_0_state_keeps_ids_and_values() {
+        let mut state = version_0_state();
+        MultibandCompressorParams::migrate_state(&mut state);
+
+        // ID は変えていないので、値はそのまま残り、今のバージョンが付く
+        assert_eq!(state.params, version_0_state().params);
+        assert_eq!(
+            state.fields.get(STATE_VERSION_KEY),
+            Some(&STATE_VERSION.to_string())
+        );
+
+        // 古い ID が今のバンドのパラメーターにある
+        let ids: Vec<String> = BAND_DEFAULTS
+            .iter()
+            .flat_map(|&defaults| BandParams::new(defaults).param_map())
+            .map(|(id, _, _)| id)
+            .collect();
+        for id in ["threshold_low", "ratio_mid", "solo_high", "detector_low"] {
+            assert!(
+                ids.iter().any(|known| known == id),
+                "{id} is not a parameter"
+            );
+        }
+    }
+
+    #[test]
+    fn renames_ids_changed_after_the_saved_version() {
+        let renames = [(1, "gain", "input_gain"), (2, "depth", "amount")];
+        let mut params = BTreeMap::from([
+            (String::from("gain"), 3.0_f32),
+            (String::from("depth"), 50.0),
+        ]);
+
+        // バージョン 1 で保存したものは、2 で変えた ID だけを置き換える
+        rename_param_ids(&mut params, 1, &renames);
+        assert_eq!(
+            params,
+            BTreeMap::from([(String::from("gain"), 3.0), (String::from("amount"), 50.0)])
+        );
+
+        // 今のところ ID を変えたパラメーターはないので、プリセットファイルの値もそのまま
+        let mut params = BTreeMap::from([(String::from("threshold_low"), -20.0_f32)]);
+        MultibandCompressorParams::migrate_param_ids(&mut params, 0);
+        assert_eq!(
+            params,
+            BTreeMap::from([(String::from("threshold_low"), -20.0)])
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! パラメーターを JSON のプリセットファイルに書き出し、読み込む。
+//!
+//! DAW ごとのプリセット形式に頼らずに設定を共有するための形式で、次のようになる。
+//!
+//! ```json
+//! {
+//!   "format": "multiband_compressor-preset",
+//!   "state_version": 1,
+//!   "params": {
+//!     "threshold_low": -12.0,
+//!     "solo_low": 0.0,
+//!     "detector_low": 1.0,
+//!     "xover_lo_mid": 200.0
+//!   }
+//! }
+//! ```
+//!
+//! - `format` は常に `"multiband_compressor-preset"`
+//! - `state_version` は書き出したときの [`STATE_VERSION`]
+//! - `params` はパラメーター ID とプレーンな値。単位はエディターの表示と同じ (dB、ms、Hz、%) で、
+//!   オン/オフは 0 か 1、選択肢は 0 から始まる番号になる
+//!
+//! 読み込むときは古い `state_version` の ID を今の ID に置き換え、知らない ID を無視する。ファイルにない
+//! パラメーターはファクトリープリセットと同じ規則でデフォルト値に戻す。
+
+use nih_plug::prelude::{ParamPtr, Params};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+use crate::params::{MultibandCompressorParams, STATE_VERSION};
+use crate::presets;
+
+/// `format` に書く値
+const FORMAT: &str = "multiband_compressor-preset";
+
+#[derive(Serialize, Deserialize)]
+struct PresetFile {
+    format: String,
+    state_version: usize,
+    params: BTreeMap<String, f32>,
+}
+
+/// プリセットファイルを読み込めなかった理由
+#[derive(Debug)]
+pub enum PresetFileError {
+    /// ファイルを読めなかった
+    Io(io::Error),
+    /// JSON として読めないか、必要なフィールドがない
+    Json(serde_json::Error),
+    /// このプラグインのプリセットファイルではない
+    UnknownFormat(String),
+}
+
+impl fmt::Display for PresetFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetFileError::Io(error) => write!(f, "{error}"),
+            PresetFileError::Json(error) => write!(f, "not a valid preset file: {error}"),
+            PresetFileError::UnknownFormat(format) => {
+                write!(f, "unknown preset format \"{format}\"")
+            }
+        }
+    }
+}
+
+/// 全パラメーターの現在の値（モジュレーション前）を JSON にする
+pub fn to_json(params: &dyn Params) -> String {
+    let params = params
+        .param_map()
+        .into_iter()
+        .map(|(id, param, _)| {
+            // SAFETY: the pointer comes from `params`, which the caller keeps alive
+            let plain = unsafe { param.unmodulated_plain_value() };
+            (id, plain)
+        })
+        .collect();
+    let file = PresetFile {
+        format: FORMAT.to_owned(),
+        state_version: STATE_VERSION,
+        params,
+    };
+
+    // 文字列のキーと有限の数値しか持たないので、書き出しは失敗しない
+    serde_json::to_string_pretty(&file).unwrap_or_default()
+}
+
+/// JSON のプリセットを読み、設定するパラメーターと正規化された値を返す
+pub fn from_json(
+    params: &dyn Params,
+    json: &str,
+) -> Result<Vec<(ParamPtr, f32)>, PresetFileError> {
+    let mut file: PresetFile = serde_json::from_str(json).map_err(PresetFileError::Json)?;
+    if file.format != FORMAT {
+        return Err(PresetFileError::UnknownFormat(file.format));
+    }
+    if file.state_version > STATE_VERSION {
+        nih_plug::nih_warn!(
+            "The preset was saved by a newer version (state version {}), unknown parameters are \
+             ignored",
+            file.state_version
+        );
+    }
+
+    MultibandCompressorParams::migrate_param_ids(&mut file.params, file.state_version);
+
+    Ok(presets::normalized_values(params, |id| file.params.get(id).copied()))
+}
+
+/// JSON のプリセットを読み、全パラメーターの名前と設定する正規化された値を [`Params::param_map()`] の順に
+/// 返す。プリセットを読み込んでも変わらないパラメーターの値は `None`。プラグインの外から CLAP の
+/// パラメーターとして設定するためのもので、CLAP のパラメーターも同じ順に並ぶ
+pub fn param_map_values(json: &str) -> Result<Vec<(String, Option<f32>)>, PresetFileError> {
+    let params = MultibandCompressorParams::default();
+    let values = from_json(&params, json)?;
+    Ok(params
+        .param_map()
+        .into_iter()
+        .map(|(_, param, _)| {
+            let normalized = values
+                .iter()
+                .find(|(ptr, _)| *ptr == param)
+                .map(|&(_, normalized)| normalized);
+            // SAFETY: the pointer comes from `params`, which outlives this closure
+            let name = unsafe { param.name() }.to_owned();
+            (name, normalized)
+        })
+        .collect())
+}
+
+/// 全パラメーターの現在の値を `path` に書き出す
+pub fn write(params: &dyn Params, path: &Path) -> io::Result<()> {
+    fs::write(path, to_json(params))
+}
+
+/// `path` のプリセットファイルを読み、設定するパラメーターと正規化された値を返す
+pub fn read(params: &dyn Params, path: &Path) -> Result<Vec<(ParamPtr, f32)>, PresetFileError> {
+    let json = fs::read_to_string(path).map_err(PresetFileError::Io)?;
+    from_json(params, &json)
+}
+
+/// 書き出し先を選ぶダイアログを開く。キャンセルされたら `None`
+pub fn save_dialog() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("Preset", &["json"])
+        .set_file_name("preset.json")
+        .save_file()
+}
+
+/// 読み込むファイルを選ぶダイアログを開く。キャンセルされたら `None`
+pub fn open_dialog() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("Preset", &["json"])
+        .pick_file()
+}
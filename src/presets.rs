@@ -0,0 +1,302 @@
+//! 組み込みのファクトリープリセットと、Init と Randomize のコマンド。
+//!
+//! プリセットはパラメーター ID とプレーンな値の組で持つ。プリセットに含まれていない音に関わるパラメーターは
+//! デフォルト値に戻し、メーターの設定はそのまま残す。JSON ファイルのプリセットも同じ規則で読み込む。
+
+use nih_plug::prelude::{ParamPtr, Params};
+
+/// プリセットに含まれていなくてもデフォルト値に戻さないパラメーター（メーターと聴き比べの設定）
+const KEPT_PARAM_IDS: [&str; 4] = ["rms_time", "peak_hold", "gain_match", "k_weighted_meters"];
+
+/// パラメーター ID とプレーンな値の組で保存したプリセット
+pub struct FactoryPreset {
+    pub name: &'static str,
+    values: &'static [(&'static str, f32)],
+}
+
+impl FactoryPreset {
+    /// このプリセットを読み込むときに設定するパラメーターと正規化された値
+    pub fn normalized_values(&self, params: &dyn Params) -> Vec<(ParamPtr, f32)> {
+        normalized_values(params, |id| {
+            self.values
+                .iter()
+                .find(|(preset_id, _)| *preset_id == id)
+                .map(|&(_, plain)| plain)
+        })
+    }
+}
+
+/// `plain_value` が返すプレーンな値を正規化した、プリセットを読み込むときに設定する値。値のない
+/// パラメーターは、[`KEPT_PARAM_IDS`] のもの以外をデフォルト値に戻す
+pub fn normalized_values(
+    params: &dyn Params,
+    plain_value: impl Fn(&str) -> Option<f32>,
+) -> Vec<(ParamPtr, f32)> {
+    params
+        .param_map()
+        .into_iter()
+        .filter_map(|(id, param, _)| {
+            let plain = plain_value(&id);
+            if plain.is_none() && KEPT_PARAM_IDS.contains(&id.as_str()) {
+                return None;
+            }
+
+            // SAFETY: the pointer comes from `params`, which the caller keeps alive
+            let normalized = unsafe {
+                match plain {
+                    Some(plain) => param.preview_normalized(plain),
+                    None => param.default_normalized_value(),
+                }
+            };
+
+            Some((param, normalized))
+        })
+        .collect()
+}
+
+/// 全てのバンドを 1:1 にして、何もしない状態に戻すプリセット
+pub const INIT_PRESET: FactoryPreset = FactoryPreset {
+    name: "Init",
+    values: &[("ratio_low", 1.0), ("ratio_mid", 1.0), ("ratio_high", 1.0)],
+};
+
+/// Randomize で値を選ぶパラメーターと、その範囲 (ID、最小値、最大値、周波数や時間のように比で選ぶか)。
+/// バンドのパラメーターは `_low` などを除いた ID で、全バンドに使う。クロスオーバーは範囲が重ならないので、
+/// 順番が入れ替わることはない
+const RANDOM_RANGES: [(&str, f32, f32, bool); 8] = [
+    ("threshold", -40.0, -6.0, false),
+    ("ratio", 1.5, 6.0, true),
+    ("attack", 1.0, 50.0, true),
+    ("release", 30.0, 500.0, true),
+    ("makeup", 0.0, 6.0, false),
+    ("knee", 0.0, 12.0, false),
+    ("xover_lo_mid", 80.0, 400.0, true),
+    ("xover_mid_hi", 1500.0, 6000.0, true),
+];
+
+/// [`RANDOM_RANGES`] のパラメーターに、その範囲から選んだ値を設定する値。ほかのパラメーターは変えない
+pub fn random_values(params: &dyn Params, rng: &mut fastrand::Rng) -> Vec<(ParamPtr, f32)> {
+    params
+        .param_map()
+        .into_iter()
+        .filter_map(|(id, param, _)| {
+            let plain = random_plain(random_range(&id)?, rng);
+
+            // SAFETY: the pointer comes from `params`, which the caller keeps alive
+            Some((param, unsafe { param.preview_normalized(plain) }))
+        })
+        .collect()
+}
+
+/// `id` のパラメーターを Randomize で選ぶ範囲 (最小値、最大値、比で選ぶか)。[`RANDOM_RANGES`] にない
+/// パラメーターなら `None`。`duck_attack` や `attack_scale` のように名前が似ていても、バンドの接尾辞の
+/// ないものは含めない
+fn random_range(id: &str) -> Option<(f32, f32, bool)> {
+    RANDOM_RANGES
+        .iter()
+        .find(|(name, ..)| {
+            id == *name
+                || id
+                    .strip_prefix(name)
+                    .is_some_and(|suffix| matches!(suffix, "_low" | "_mid" | "_high"))
+        })
+        .map(|&(_, min, max, proportional)| (min, max, proportional))
+}
+
+/// `(min, max, proportional)` の範囲から選んだ値。比で選ぶものは対数の軸で一様に選ぶ
+fn random_plain((min, max, proportional): (f32, f32, bool), rng: &mut fastrand::Rng) -> f32 {
+    if proportional {
+        min * (max / min).powf(rng.f32())
+    } else {
+        min + (max - min) * rng.f32()
+    }
+}
+
+/// ファクトリープリセットの一覧。エディターのボタンはこの順に並ぶ
+pub const FACTORY_PRESETS: [FactoryPreset; 5] = [
+    // 全体を浅く、ゆっくりまとめる
+    FactoryPreset {
+        name: "Mastering Glue",
+        values: &[
+            ("threshold_low", -18.0),
+            ("threshold_mid", -16.0),
+            ("threshold_high", -14.0),
+            ("ratio_low", 1.5),
+            ("ratio_mid", 1.5),
+            ("ratio_high", 1.5),
+            ("attack_low", 30.0),
+            ("attack_mid", 20.0),
+            ("attack_high", 10.0),
+            ("release_low", 200.0),
+            ("release_mid", 150.0),
+            ("release_high", 100.0),
+            ("makeup_low", 1.0),
+            ("makeup_mid", 1.0),
+            ("makeup_high", 1.0),
+            ("knee_low", 6.0),
+            ("knee_mid", 6.0),
+            ("knee_high", 6.0),
+            // RMS
+            ("detector_low", 1.0),
+            ("detector_mid", 1.0),
+            ("detector_high", 1.0),
+            ("xover_lo_mid", 120.0),
+            ("xover_mid_hi", 4000.0),
+        ],
+    },
+    // アタックを残して強めに潰し、元の音と混ぜる
+    FactoryPreset {
+        name: "Drum Bus",
+        values: &[
+            ("threshold_low", -20.0),
+            ("threshold_mid", -18.0),
+            ("threshold_high", -16.0),
+            ("ratio_low", 4.0),
+            ("ratio_mid", 3.0),
+            ("ratio_high", 3.0),
+            ("attack_low", 10.0),
+            ("attack_mid", 5.0),
+            ("attack_high", 2.0),
+            ("release_low", 100.0),
+            ("release_mid", 80.0),
+            ("release_high", 60.0),
+            ("makeup_low", 3.0),
+            ("makeup_mid", 2.0),
+            ("makeup_high", 2.0),
+            ("knee_low", 3.0),
+            ("knee_mid", 3.0),
+            ("knee_high", 3.0),
+            ("mix_low", 70.0),
+            ("mix_mid", 70.0),
+            ("mix_high", 70.0),
+            ("xover_lo_mid", 150.0),
+            ("xover_mid_hi", 3000.0),
+        ],
+    },
+    // 中域を揃え、こもりと歯擦音を軽く抑える
+    FactoryPreset {
+        name: "Vocal",
+        values: &[
+            ("threshold_low", -24.0),
+            ("threshold_mid", -20.0),
+            ("threshold_high", -22.0),
+            ("ratio_low", 2.0),
+            ("ratio_mid", 3.0),
+            ("ratio_high", 2.5),
+            ("attack_low", 15.0),
+            ("attack_mid", 8.0),
+            ("attack_high", 3.0),
+            ("release_low", 150.0),
+            ("release_mid", 100.0),
+            ("release_high", 80.0),
+            ("makeup_mid", 2.0),
+            ("makeup_high", 1.0),
+            ("knee_low", 6.0),
+            ("knee_mid", 6.0),
+            ("knee_high", 3.0),
+            ("detector_mid", 1.0),
+            ("xover_lo_mid", 250.0),
+            ("xover_mid_hi", 5000.0),
+        ],
+    },
+    // 低域だけをしっかり押さえ、他のバンドはほとんど触らない
+    FactoryPreset {
+        name: "Bass Control",
+        values: &[
+            ("threshold_low", -24.0),
+            ("threshold_mid", -6.0),
+            ("threshold_high", -6.0),
+            ("ratio_low", 4.0),
+            ("ratio_mid", 1.5),
+            ("ratio_high", 1.5),
+            ("attack_low", 20.0),
+            ("release_low", 200.0),
+            ("hold_low", 20.0),
+            ("makeup_low", 2.0),
+            ("knee_low", 6.0),
+            ("detector_low", 1.0),
+            ("xover_lo_mid", 120.0),
+            ("xover_mid_hi", 2000.0),
+        ],
+    },
+    // 耳に痛い高域だけを素早く抑える
+    FactoryPreset {
+        name: "De-Harsh",
+        values: &[
+            ("threshold_low", -6.0),
+            ("threshold_mid", -6.0),
+            ("threshold_high", -30.0),
+            ("ratio_low", 1.0),
+            ("ratio_mid", 1.0),
+            ("ratio_high", 4.0),
+            ("attack_high", 1.0),
+            ("release_high", 60.0),
+            ("knee_high", 6.0),
+            ("range_high", 9.0),
+            ("xover_lo_mid", 300.0),
+            ("xover_mid_hi", 3000.0),
+        ],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::MultibandCompressorParams;
+
+    #[test]
+    fn randomizes_band_dynamics_and_crossovers_only() {
+        let params = MultibandCompressorParams::default();
+        let ids: Vec<String> = params
+            .param_map()
+            .into_iter()
+            .map(|(id, ..)| id)
+            .filter(|id| random_range(id).is_some())
+            .collect();
+
+        let randomized = |id: &str| ids.iter().any(|random| random == id);
+
+        for name in ["threshold", "ratio", "attack", "release", "makeup", "knee"] {
+            for band in ["low", "mid", "high"] {
+                let id = format!("{name}_{band}");
+                assert!(randomized(&id), "{id} is not randomized");
+            }
+        }
+        assert!(randomized("xover_lo_mid") && randomized("xover_mid_hi"));
+        // 名前が似ているダッキングの時間や、全体の時定数の倍率は変えない
+        for id in [
+            "duck_attack",
+            "duck_release",
+            "attack_scale",
+            "release_scale",
+        ] {
+            assert!(!randomized(id), "{id} is randomized");
+        }
+        assert_eq!(ids.len(), 6 * 3 + 2);
+    }
+
+    #[test]
+    fn random_values_stay_in_range() {
+        let params = MultibandCompressorParams::default();
+        let mut rng = fastrand::Rng::with_seed(1);
+        assert_eq!(random_values(&params, &mut rng).len(), 6 * 3 + 2);
+
+        for &(id, min, max, proportional) in &RANDOM_RANGES {
+            // 比で選ぶものは幾何平均、そうでないものは算術平均より下がおよそ半分になる
+            let middle = if proportional {
+                (min * max).sqrt()
+            } else {
+                (min + max) / 2.0
+            };
+            let mut below_middle = 0;
+            for _ in 0..1000 {
+                let plain = random_plain((min, max, proportional), &mut rng);
+                assert!((min..=max).contains(&plain), "{id}: {plain}");
+                if plain < middle {
+                    below_middle += 1;
+                }
+            }
+            assert!((400..600).contains(&below_middle), "{id}: {below_middle}");
+        }
+    }
+}
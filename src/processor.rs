@@ -1,107 +1,635 @@
-use atomic_float::AtomicF32;
 use nih_plug::prelude::*;
-use std::sync::Arc;
+use std::f32::consts::PI;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use crate::biquad::Biquad;
-use crate::compression::{CompressorSettings, SingleBandCompressor};
-use crate::editor;
+use crate::analysis::{
+    analysis_channel, spectrum_channel, AnalysisInput, AnalysisOutput, LoudnessReadings,
+    ResetRequests, SpectrumOutput, ThresholdLearn, METER_CHANNELS,
+};
+use crate::background::{
+    band_level_queue, loudness_queue, spectrum_queue, BackgroundTask, BandLevelSample,
+    BandLevelWorker, LoudnessWorker, SpectrumSample, SpectrumWorker,
+};
+use crate::band::BandProcessor;
+use crate::channel::{BandOutputs, ChannelBlock, ChannelOptions, FrameStats, FrameValues};
+use crate::compression::CompressorSettings;
+use crate::crossover::{band_lanes, Crossover};
+use crate::denormals::DenormalGuard;
+use crate::ducking::Ducker;
+use crate::gui::{self, EditorShared};
+use crate::loudness::{AutoMakeup, GainMatcher, KWeightingFilter, LoudnessMeter};
+use crate::metering::{
+    decay_peak_meter, BallisticsMeter, ClipCounter, CorrelationMeter, DspLoadMeter,
+    GainReductionHistory, GainReductionRecorder,
+    GainReductionStatsTracker, GoniometerRecorder, MeterBallistics, PeakHold, RmsMeter,
+    TruePeakDetector, PEAK_METER_DECAY_MS,
+};
+use crate::midi::{midi_cc_queue, MidiCcEvent};
+use crate::morph::{SmoothedValues, Snapshot};
+use crate::oversampling::{LatencyDelay, Oversampler, OversamplingFactor, MAX_OVERSAMPLING_FACTOR};
 use crate::params::MultibandCompressorParams;
+use crate::precision::{Crossover64, Precision};
+use crate::sample_queue::{SampleConsumer, SampleProducer};
+use crate::spectrum::HOP_SIZE;
+use crate::surround::{ChannelLayout, LAYOUT_5_1, LAYOUT_7_1, MAX_CHANNELS};
 
-/// ピークメーターが完全な無音になった後、12dB減衰するのにかかる時間
-const PEAK_METER_DECAY_MS: f64 = 150.0;
+/// バイパスを切り替えるときのクロスフェードの長さ
+const BYPASS_FADE_MS: f32 = 20.0;
+/// コリレーションメーターの平均化時間
+const CORRELATION_WINDOW_MS: f32 = 300.0;
+/// バンドのエネルギー分布の平均化時間
+const BAND_ENERGY_WINDOW_MS: f32 = 1000.0;
+/// コンプレッサーの RMS 検出の平均化時間
+const RMS_DETECTOR_WINDOW_MS: f32 = 10.0;
+/// オートスレッショルドの学習で入力を測る長さ
+const THRESHOLD_LEARN_SECONDS: f32 = 5.0;
+/// ブロック処理の 1 ブロックの最大サンプル数。パラメーターとクロスオーバーはこの間隔で評価する
+pub const MAX_BLOCK_SIZE: usize = 64;
+/// 出力の振幅の上限 (+24 dBFS)。極端な設定でもこれ以上大きな値をホストに渡さない
+const MAX_OUTPUT_AMPLITUDE: f32 = 16.0;
+/// オフラインレンダリングの高品質モードで使う最低限のオーバーサンプリング倍率
+const OFFLINE_OVERSAMPLING: OversamplingFactor = OversamplingFactor::X4;
+/// クロスオーバーの IIR フィルターが鳴り終わったとみなす、包絡線の時定数の数（約 -120 dB）
+const CROSSOVER_RING_TIME_CONSTANTS: f32 = 14.0;
+/// ゲインリダクションが戻ったとみなす、リリースの時定数の数（残り 5% 程度）
+const RELEASE_TAIL_TIME_CONSTANTS: f32 = 3.0;
+
+pub use crate::crossover::NUM_BANDS;
 
 pub struct MultibandCompressor {
     // GUIやホストと共有するパラーメーター
-    params: Arc<MultibandCompressorParams>,
+    pub(crate) params: Arc<MultibandCompressorParams>,
+    // このバッファで使う連続的なパラメーターの値と、モーフに使う A と B のスナップショット。
+    // A と B の両方が保存されていれば、パラメーターの値の代わりに Morph の位置で補間した値を使う
+    param_values: Snapshot,
+    morph_snapshots: Option<(Snapshot, Snapshot)>,
+    /// 内部のサンプルレートが変わったときだけ計算し直す RMS 検出の係数
+    rms_coef: f64,
 
     /// ピークメーターが減衰する速さ
     peak_meter_decay_weight: f32,
-    // GUIに表示するためのピークメーターの値
-    peak_meter: Arc<AtomicF32>,
+    // L/R のメーター（バンド分割前の入力と、合成後の出力）。応答特性はエディターの設定に従う
+    input_level_meters: [BallisticsMeter; METER_CHANNELS],
+    output_level_meters: [BallisticsMeter; METER_CHANNELS],
+    meter_ballistics: MeterBallistics,
+    // 減衰するメーターとは別に保持するピークホールド（入力、出力、トゥルーピーク）
+    input_peak_hold: PeakHold,
+    output_peak_hold: PeakHold,
+    output_true_peak_hold: PeakHold,
+    // per-channel 4x オーバーサンプリングのトゥルーピーク検出器
+    true_peak_detectors: Vec<TruePeakDetector>,
+    output_true_peak_meter: f32,
+    // 入力と出力のクリップカウンター。GUI のクリックでリセットする
+    input_clips: ClipCounter,
+    output_clips: ClipCounter,
+    // 出力の RMS メーター。ピークだけでは持続音のコンプレッション量が分からないため
+    output_rms: RmsMeter,
+    // per-channel K 特性フィルター（K-Weighted Meters が有効なときに RMS メーターの前段で使う）
+    meter_k_filters: Vec<KWeightingFilter>,
+    // 出力の L/R コリレーションメーター。アンリンクのマルチバンド圧縮でモノ互換性が崩れていないか確認するため
+    output_correlation: CorrelationMeter,
+    // ゴニオメーターに送る、間引いた出力の L/R
+    goniometer: GoniometerRecorder,
+    // 出力の BS.1770 ラウドネスメーター。オーディオスレッドは K 特性をかけて 100 ms ごとのステップに
+    // まとめるだけで、ゲーティングと積算はバックグラウンドタスクで行い、結果を `loudness_readings` で受け取る
+    loudness: LoudnessMeter,
+    loudness_steps: SampleProducer<f64>,
+    loudness_worker: Arc<Mutex<LoudnessWorker>>,
+    loudness_readings: Arc<LoudnessReadings>,
+    // バイパスとの比較用に、出力を入力と同じラウドネスに揃えるトリム
+    gain_matcher: GainMatcher,
+    // 出力のラウドネスを入力か目標値に揃える、ゆっくり動くメイクアップゲイン
+    auto_makeup: AutoMakeup,
+    // GUI のリセットボタンで立つフラグ。次の process() で対応するメーターをクリアする
+    reset_requests: Arc<ResetRequests>,
+    // クロスオーバー設定用のスペクトル解析。オーディオスレッドはサンプルをキューに積むだけで、
+    // FFT はバックグラウンドタスクで行う
+    spectrum_samples: SampleProducer<SpectrumSample>,
+    spectrum_worker: Arc<Mutex<SpectrumWorker>>,
+    spectrum_output: Arc<Mutex<SpectrumOutput>>,
+    // MIDI ラーンで割り当てたパラメーターを動かすために、受け取った CC をエディターに送るキュー
+    midi_cc_events: SampleProducer<MidiCcEvent>,
+    midi_cc_output: Arc<Mutex<SampleConsumer<MidiCcEvent>>>,
+    // MIDI Ducking で、ノートに合わせて各バンドを下げるエンベロープ
+    ducker: Ducker,
+    // 前回スペクトル解析のタスクを投げてから積んだサンプル数
+    samples_since_spectrum_task: usize,
+    // オートスレッショルドの学習。学習中は各バンドのレベルをキューに積み、集計はバックグラウンドタスクで行う
+    threshold_learn: Arc<ThresholdLearn>,
+    band_level_samples: SampleProducer<BandLevelSample>,
+    band_level_worker: Arc<Mutex<BandLevelWorker>>,
+    // 学習が終わるまでに、あと何サンプルフレーム測るか
+    learn_samples_remaining: usize,
+    // process() にかかった時間の、バッファの実時間に対する割合
+    dsp_load: DspLoadMeter,
+    // メーターと解析結果を GUI に送るトリプルバッファ。読み出し側はエディターに渡す
+    analysis_input: AnalysisInput,
+    analysis_output: Arc<Mutex<AnalysisOutput>>,
+    // ゲインリダクションの履歴（100 Hz に間引いたもの）
+    gain_reduction_recorder: GainReductionRecorder,
+    gain_reduction_history: Arc<GainReductionHistory>,
+    // バンドごとのゲインリダクション統計（直近 10 秒の平均、最大、かかっていた時間の割合）
+    gain_reduction_stats: GainReductionStatsTracker,
 
     // マルチバンド用拡張
     sample_rate: f32,
-    // per-channel crossover filters
-    filters: Vec<ChannelFilters>,
-    // per-channel compressors: [low, mid, high]
-    compressors: Vec<[SingleBandCompressor; 3]>,
+    // ホストがオフラインでレンダリングしているか。initialize() の BufferConfig から判断する
+    offline_render: bool,
+    // ホストと決めたレイアウトのチャンネルの並び。チャンネルのリンクのグループ分けに使う
+    channel_layout: ChannelLayout,
+    // crossover filters; coefficients are shared and per-channel states are stored contiguously
+    crossover: Crossover,
+    // 倍精度の経路で使う crossover filters
+    precision: Precision,
+    crossover64: Crossover64,
+    // 分割した後の各バンドのコンプレッサー、フェード、メーター（low, mid, high）
+    bands: [BandProcessor; NUM_BANDS],
     current_lo_mid: f32,
     current_mid_hi: f32,
-}
+    current_xover_q: f32,
+
+    // オーバーサンプリング
+    oversampling: OversamplingFactor,
+    // per-channel up/down samplers
+    oversamplers: Vec<Oversampler>,
+    // per-channel scratch buffers for the oversampled samples
+    oversampling_scratch: Vec<[f32; MAX_OVERSAMPLING_FACTOR]>,
+    // per-channel copies of the current block's input for the output stage after the bands are summed
+    block_inputs: Vec<[f32; MAX_BLOCK_SIZE]>,
+    // per-channel summed bands of the current block, before the output stage
+    block_outputs: Vec<[f32; MAX_BLOCK_SIZE]>,
+    // per-channel measurements of the current block when the channels are processed in parallel
+    #[cfg(feature = "parallel")]
+    channel_stats: Vec<[FrameStats; MAX_BLOCK_SIZE]>,
+
+    // バイパス。0 で処理した信号、1 でドライ信号になる混合比と、オーバーサンプリングの遅延に揃えたドライ信号
+    bypass_mix: Smoother<f32>,
+    dry_delays: Vec<LatencyDelay>,
 
-struct ChannelFilters {
-    low_lp: [Biquad; 2],
-    mid_hp: [Biquad; 2],
-    mid_lp: [Biquad; 2],
-    high_hp: [Biquad; 2],
+    // バンドごとの出力バス（マルチアウトのレイアウトが選ばれたときだけ使う）
+    band_outputs_enabled: bool,
+    // per-channel, per-band downsamplers so the band outputs get the same latency as the main output
+    band_downsamplers: Vec<[Oversampler; NUM_BANDS]>,
+    // per-channel, per-band scratch buffers for the oversampled band outputs
+    band_scratch: Vec<[[f32; MAX_OVERSAMPLING_FACTOR]; NUM_BANDS]>,
+    // per-channel, per-band outputs of the current block before they are copied to the aux buses
+    block_band_outputs: Vec<[[f32; MAX_BLOCK_SIZE]; NUM_BANDS]>,
+
+    // テストで、エディターが開いているときの経路を通すためのもの
+    #[cfg(test)]
+    force_editor_open: bool,
 }
 
-impl ChannelFilters {
-    fn new() -> Self {
-        Self {
-            low_lp: [Biquad::new(), Biquad::new()],
-            mid_hp: [Biquad::new(), Biquad::new()],
-            mid_lp: [Biquad::new(), Biquad::new()],
-            high_hp: [Biquad::new(), Biquad::new()],
+impl MultibandCompressor {
+    /// フィルターやコンプレッサーが実際に動作するサンプルレート
+    fn processing_rate(&self) -> f32 {
+        self.sample_rate * self.oversampling.factor() as f32
+    }
+
+    /// オフラインレンダリング中で、Offline Quality が有効かどうか。有効ならリアルタイムの負荷を気にせず、
+    /// オーバーサンプリングと演算精度を上げ、検出器の近似をやめる
+    fn offline_quality(&self) -> bool {
+        self.offline_render && self.params.global.offline_quality.value()
+    }
+
+    /// 実際に使うオーバーサンプリング倍率
+    fn oversampling_setting(&self) -> OversamplingFactor {
+        let factor = self.params.global.oversampling.value();
+        if self.offline_quality() && factor.factor() < OFFLINE_OVERSAMPLING.factor() {
+            OFFLINE_OVERSAMPLING
+        } else {
+            factor
         }
     }
-}
 
-impl MultibandCompressor {
+    /// 実際に使う演算精度
+    fn precision_setting(&self) -> Precision {
+        if self.offline_quality() {
+            Precision::Double
+        } else {
+            self.params.global.precision.value()
+        }
+    }
+
+    /// `process()` の 2) の、各チャンネルのバンド分割、圧縮、合成。`band_outputs` ならバンドごとの出力も
+    /// `block_band_outputs` に書き込む。`link_groups` は各チャンネルが属するリンクのグループ
+    fn process_channel_blocks(
+        &mut self,
+        frames: &[FrameValues],
+        stats: &mut [FrameStats],
+        processed_channels: usize,
+        band_outputs: bool,
+        link_groups: &[usize; MAX_CHANNELS],
+        options: &ChannelOptions,
+    ) {
+        let [low, mid, high] = self.bands.each_mut().map(BandProcessor::channels_mut);
+        // バンド出力がある場合のダウンサンプラーと、このブロックの書き込み先
+        let band_output_states = self
+            .band_downsamplers
+            .iter_mut()
+            .zip(self.band_scratch.iter_mut())
+            .zip(self.block_band_outputs.iter_mut())
+            .take(if band_outputs { usize::MAX } else { 0 })
+            .map(|((downsamplers, scratch), outputs)| {
+                Some(BandOutputs {
+                    downsamplers,
+                    scratch,
+                    outputs,
+                })
+            })
+            .chain(std::iter::repeat_with(|| None));
+        let mut channel_blocks = self
+            .block_inputs
+            .iter()
+            .zip(self.block_outputs.iter_mut())
+            .zip(self.oversamplers.iter_mut())
+            .zip(self.oversampling_scratch.iter_mut())
+            .zip(self.crossover.channels_mut())
+            .zip(self.crossover64.channels_mut())
+            .zip(low.zip(mid).zip(high))
+            .zip(band_output_states)
+            .take(processed_channels)
+            .map(
+                |(
+                    (
+                        (((((inputs, outputs), oversampler), scratch), crossover), crossover64),
+                        ((low, mid), high),
+                    ),
+                    band_outputs,
+                )| ChannelBlock {
+                    inputs,
+                    outputs,
+                    oversampler,
+                    scratch,
+                    crossover,
+                    crossover64,
+                    bands: [low, mid, high],
+                    band_outputs,
+                },
+            );
+        let linked = link_groups
+            .iter()
+            .take(processed_channels)
+            .enumerate()
+            .any(|(channel, &group)| group != channel);
+        // `parallel` フィーチャーを有効にしたビルドのオフラインレンダリングでは、チャンネルを並列に処理する
+        #[cfg(feature = "parallel")]
+        let parallel = self.offline_render && processed_channels > 1;
+        #[cfg(not(feature = "parallel"))]
+        let parallel = false;
+        if linked {
+            // リンクしたチャンネルはサンプルごとに検出器のレベルを揃えるので、全チャンネルを同時に借りる。
+            // 並列には処理しない
+            let mut channel_blocks: [Option<ChannelBlock>; MAX_CHANNELS] =
+                std::array::from_fn(|_| channel_blocks.next());
+            crate::channel::process_linked(
+                &mut channel_blocks,
+                link_groups,
+                frames,
+                stats,
+                options,
+            );
+        } else if parallel {
+            // オフラインではリアルタイムの制約がないので、スレッドプールとのやりとりでのメモリ確保を許す
+            #[cfg(feature = "parallel")]
+            util::permit_alloc(|| {
+                let mut channel_blocks: Vec<ChannelBlock> = channel_blocks.collect();
+                crate::channel::process_parallel(
+                    &mut channel_blocks,
+                    frames,
+                    stats,
+                    &mut self.channel_stats,
+                    options,
+                );
+            });
+        } else {
+            for mut channel_block in channel_blocks {
+                channel_block.process(frames, stats, options);
+            }
+        }
+    }
+
+    /// 入力が無音になってから、出力が鳴り終わってコンプレッサーが戻るまでのサンプル数。オーバーサンプリングの
+    /// レイテンシーと、最も低いクロスオーバーの鳴り終わり、最も長いホールドとリリースの和
+    fn tail_samples(&self) -> u32 {
+        // 2 次のセクションの包絡線は、時定数 Q / (π f) で減衰する
+        let crossover_ring = CROSSOVER_RING_TIME_CONSTANTS * self.current_xover_q
+            / (PI * self.current_lo_mid.max(1.0));
+        let values = &self.param_values;
+        let release = values
+            .bands
+            .iter()
+            .map(|band| {
+                (band.hold + RELEASE_TAIL_TIME_CONSTANTS * band.release * values.release_scale)
+                    / 1000.0
+            })
+            .fold(0.0, f32::max);
+
+        self.oversampling.latency_samples()
+            + ((crossover_ring + release) * self.sample_rate).ceil() as u32
+    }
+
+    /// 内部のサンプルレートに合わせて RMS 検出の係数を計算し直す
+    fn update_rms_coef(&mut self) {
+        let window_samples = f64::from(RMS_DETECTOR_WINDOW_MS * self.processing_rate()) / 1000.0;
+        self.rms_coef = (-1.0 / window_samples).exp();
+    }
+
+    // オーバーサンプリング倍率の更新。倍率が変わったらクロスオーバーを作り直してレイテンシーを報告する
+    fn update_oversampling(&mut self, context: &mut impl ProcessContext<Self>) {
+        let factor = self.oversampling_setting();
+        if factor == self.oversampling {
+            return;
+        }
+
+        self.oversampling = factor;
+        for oversampler in self
+            .oversamplers
+            .iter_mut()
+            .chain(self.band_downsamplers.iter_mut().flatten())
+        {
+            oversampler.set_factor(factor);
+        }
+        for delay in self.dry_delays.iter_mut() {
+            delay.set_factor(factor);
+        }
+
+        // 内部レートが変わったのでクロスオーバーと検出器の K 特性の係数を作り直す。エンベロープの係数は
+        // 次の process() でサンプルレートの変化を見て計算し直される
+        self.update_crossovers(true);
+        self.update_rms_coef();
+        let processing_rate = self.processing_rate();
+        for band in self.bands.iter_mut() {
+            band.set_processing_rate(processing_rate);
+        }
+
+        context.set_latency_samples(factor.latency_samples());
+    }
+
+    /// 演算精度の更新。切り替えた先の経路には前回使っていたときの状態が残っているので、クリアしてから使う
+    fn update_precision(&mut self) {
+        let precision = self.precision_setting();
+        if precision == self.precision {
+            return;
+        }
+
+        self.precision = precision;
+        match precision {
+            Precision::Single => self.crossover.reset(),
+            Precision::Double => self.crossover64.reset(),
+        }
+        for band in self.bands.iter_mut() {
+            band.reset_compressors(precision);
+        }
+    }
+
+    // 入力と出力の L/R メーターの応答特性の更新。`force` が true のときは変わっていなくても係数を再計算する
+    fn update_meter_ballistics(&mut self, force: bool) {
+        let ballistics = MeterBallistics::load(&self.params.meter_ballistics);
+        if ballistics == self.meter_ballistics && !force {
+            return;
+        }
+
+        self.meter_ballistics = ballistics;
+        for meter in self
+            .input_level_meters
+            .iter_mut()
+            .chain(self.output_level_meters.iter_mut())
+        {
+            meter.set_ballistics(ballistics, self.sample_rate);
+        }
+    }
+
+    /// このバッファで使う連続的なパラメーターの値を更新する。A と B の両方を保存していれば Morph の位置で
+    /// 補間する
+    fn update_param_values(&mut self) {
+        // エディターが書き込み中でロックできなければ、前回のスナップショットを使い続ける
+        if let Ok(snapshots) = self.params.morph_snapshots.try_read() {
+            self.morph_snapshots = snapshots.pair();
+        }
+
+        self.param_values = match &self.morph_snapshots {
+            Some((a, b)) => {
+                let t = self.params.global.morph.value() / 100.0;
+                Snapshot::morph(&self.params, a, b, t)
+            }
+            None => Snapshot::capture(&self.params),
+        };
+    }
+
+    /// ホストからの MIDI イベントを処理する。CC はエディターが割り当てたパラメーターに反映するので、
+    /// ここではキューに積むだけ。エディターを閉じていてキューが一杯なら捨てる。ノートは MIDI Ducking が
+    /// 無効でも追い、有効にした時点で押しているノートで下げる
+    fn handle_event(&mut self, event: PluginNoteEvent<Self>) {
+        match event {
+            NoteEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } => self.ducker.note_on(channel, note, velocity),
+            NoteEvent::NoteOff { channel, note, .. } => self.ducker.note_off(channel, note),
+            NoteEvent::MidiCC { cc, value, .. } => {
+                self.midi_cc_events.push(MidiCcEvent { cc, value });
+            }
+            _ => (),
+        }
+    }
+
+    /// ブロックの先頭で、パラメーターの値から各バンドのコンプレッサーの設定とクロスオーバーを更新し、
+    /// このブロックのコンプレッサーの設定を返す。値が変わっていなければ前回の係数をそのまま使う
+    fn update_block_params(&mut self) -> [CompressorSettings; NUM_BANDS] {
+        self.update_param_values();
+        let values = self.param_values;
+
+        // initialize() で受け取ったサンプルレートを用いて per-sample coef を計算（オーバーサンプリング時は内部レートで）
+        let sample_rate = self.processing_rate();
+        let fast_detector = self.params.global.fast_detector.value() && !self.offline_quality();
+        for ((band, params), band_values) in self
+            .bands
+            .iter_mut()
+            .zip(self.params.bands())
+            .zip(&values.bands)
+        {
+            band.update_settings(
+                params,
+                band_values,
+                &values,
+                sample_rate,
+                self.rms_coef,
+                fast_detector,
+            );
+        }
+
+        // クロスオーバー周波数の更新（頻繁な再初期化を避ける）
+        self.update_crossovers(false);
+
+        self.bands.each_ref().map(|band| *band.settings())
+    }
+
+    /// バイパスの混合比の目標値
+    fn bypass_target(&self) -> f32 {
+        if self.params.global.bypass.value() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// 各バンドの音量とコンプレッサーをかける割合の目標値
+    fn band_fade_targets(&self) -> [(f32, f32); NUM_BANDS] {
+        let audible_bands = self.params.audible_bands();
+        let bypassed_bands = self.params.bypassed_bands();
+
+        std::array::from_fn(|band| {
+            (
+                if audible_bands[band] { 1.0 } else { 0.0 },
+                if bypassed_bands[band] { 0.0 } else { 1.0 },
+            )
+        })
+    }
+
     // クロスオーバー更新（低域ローパスと高域ハイパス）
-    fn update_crossovers(&mut self) {
-        let lo_mid = self.params.xover_lo_mid.value();
-        let mid_hi = self.params.xover_mid_hi.value();
+    // `force` が true のときはパラメーターが動いていなくても係数を再計算する（サンプルレート変更時など）
+    fn update_crossovers(&mut self, force: bool) {
+        let [lo_mid, mid_hi] = self.param_values.crossover_frequencies();
+        let q = self.param_values.xover_q;
 
-        let mut needs_update = false;
+        let mut needs_update = force;
 
-        if (lo_mid - self.current_lo_mid).abs() > 0.5 {
+        if force || (lo_mid - self.current_lo_mid).abs() > 0.5 {
             self.current_lo_mid = lo_mid;
             needs_update = true;
         }
 
-        if (mid_hi - self.current_mid_hi).abs() > 0.5 {
+        if force || (mid_hi - self.current_mid_hi).abs() > 0.5 {
             self.current_mid_hi = mid_hi;
             needs_update = true;
         }
 
-        if needs_update {
-            let nyquist = self.sample_rate * 0.5;
-            let low_freq = self.current_lo_mid.clamp(10.0, nyquist * 0.8);
-            let high_freq = self.current_mid_hi.clamp(low_freq + 10.0, nyquist * 0.99);
+        if force || (q - self.current_xover_q).abs() > 0.005 {
+            self.current_xover_q = q;
+            needs_update = true;
+        }
 
-            for filters in self.filters.iter_mut() {
-                for lp in filters.low_lp.iter_mut() {
-                    lp.set_lowpass(low_freq, self.sample_rate);
-                }
-                for hp in filters.mid_hp.iter_mut() {
-                    hp.set_highpass(low_freq, self.sample_rate);
-                }
-                for lp in filters.mid_lp.iter_mut() {
-                    lp.set_lowpass(high_freq, self.sample_rate);
-                }
-                for hp in filters.high_hp.iter_mut() {
-                    hp.set_highpass(high_freq, self.sample_rate);
-                }
-            }
+        if needs_update {
+            let sample_rate = self.processing_rate();
+            self.crossover.set_frequencies(
+                self.current_lo_mid,
+                self.current_mid_hi,
+                self.current_xover_q,
+                sample_rate,
+            );
+            self.crossover64.set_frequencies(
+                self.current_lo_mid,
+                self.current_mid_hi,
+                self.current_xover_q,
+                sample_rate,
+            );
         }
     }
 }
 
 impl Default for MultibandCompressor {
     fn default() -> Self {
+        let (analysis_input, analysis_output) = analysis_channel();
+        let (spectrum_input, spectrum_output) = spectrum_channel();
+        let (spectrum_samples, spectrum_consumer) = spectrum_queue();
+        let (midi_cc_events, midi_cc_output) = midi_cc_queue();
+        let (band_level_samples, band_level_consumer) = band_level_queue();
+        let (loudness_steps, loudness_consumer) = loudness_queue();
+        let loudness_readings = Arc::new(LoudnessReadings::default());
+        let threshold_learn = Arc::new(ThresholdLearn::default());
+
+        let params = Arc::new(MultibandCompressorParams::default());
+        let param_values = Snapshot::capture(&params);
+
         // Initialize with empty filter/compressor vectors; actual sizes are set in `initialize`
         Self {
-            params: Arc::new(MultibandCompressorParams::default()),
+            params,
+            param_values,
+            morph_snapshots: None,
+            rms_coef: 0.0,
 
             peak_meter_decay_weight: 1.0,
-            peak_meter: Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
+            input_level_meters: Default::default(),
+            output_level_meters: Default::default(),
+            meter_ballistics: MeterBallistics::Peak,
+            input_peak_hold: PeakHold::new(),
+            output_peak_hold: PeakHold::new(),
+            output_true_peak_hold: PeakHold::new(),
+            true_peak_detectors: Vec::new(),
+            output_true_peak_meter: 0.0,
+            input_clips: ClipCounter::new(),
+            output_clips: ClipCounter::new(),
+            output_rms: RmsMeter::new(),
+            meter_k_filters: Vec::new(),
+            output_correlation: CorrelationMeter::new(),
+            goniometer: GoniometerRecorder::new(),
+            loudness: LoudnessMeter::new(&[], 44100.0),
+            loudness_steps,
+            loudness_worker: Arc::new(Mutex::new(LoudnessWorker::new(
+                loudness_consumer,
+                loudness_readings.clone(),
+            ))),
+            loudness_readings,
+            gain_matcher: GainMatcher::new(&[], 44100.0),
+            auto_makeup: AutoMakeup::new(&[], 44100.0),
+            reset_requests: Arc::new(ResetRequests::default()),
+            spectrum_samples,
+            spectrum_worker: Arc::new(Mutex::new(SpectrumWorker::new(
+                spectrum_consumer,
+                spectrum_input,
+            ))),
+            spectrum_output: Arc::new(Mutex::new(spectrum_output)),
+            midi_cc_events,
+            midi_cc_output: Arc::new(Mutex::new(midi_cc_output)),
+            ducker: Ducker::default(),
+            samples_since_spectrum_task: 0,
+            band_level_worker: Arc::new(Mutex::new(BandLevelWorker::new(
+                band_level_consumer,
+                threshold_learn.clone(),
+            ))),
+            threshold_learn,
+            band_level_samples,
+            learn_samples_remaining: 0,
+            dsp_load: DspLoadMeter::new(),
+            analysis_input,
+            analysis_output: Arc::new(Mutex::new(analysis_output)),
+            gain_reduction_recorder: GainReductionRecorder::new(),
+            gain_reduction_history: Arc::new(GainReductionHistory::default()),
+            gain_reduction_stats: GainReductionStatsTracker::new(),
 
             sample_rate: 44100.0,
-            filters: Vec::new(),
-            compressors: Vec::new(),
+            offline_render: false,
+            channel_layout: ChannelLayout::default(),
+            crossover: Crossover::new(0),
+            precision: Precision::Single,
+            crossover64: Crossover64::new(0),
+            bands: Default::default(),
             current_lo_mid: 0.0,
             current_mid_hi: 0.0,
+            current_xover_q: 0.0,
+
+            oversampling: OversamplingFactor::Off,
+            oversamplers: Vec::new(),
+            oversampling_scratch: Vec::new(),
+            block_inputs: Vec::new(),
+            block_outputs: Vec::new(),
+            #[cfg(feature = "parallel")]
+            channel_stats: Vec::new(),
+            bypass_mix: Smoother::new(SmoothingStyle::Linear(BYPASS_FADE_MS)),
+            dry_delays: Vec::new(),
+
+            band_outputs_enabled: false,
+            band_downsamplers: Vec::new(),
+            band_scratch: Vec::new(),
+            block_band_outputs: Vec::new(),
+
+            #[cfg(test)]
+            force_editor_open: false,
         }
     }
 }
@@ -125,50 +653,199 @@ impl Plugin for MultibandCompressor {
             main_output_channels: NonZeroU32::new(1),
             ..AudioIOLayout::const_default()
         },
+        // メイン出力に加えて、各バンドのコンプレッション後の信号を個別のステレオバスに出力する
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            aux_output_ports: &[new_nonzero_u32(2); NUM_BANDS],
+            names: PortNames {
+                layout: Some("Stereo + Band Outputs"),
+                aux_outputs: &["Low Band", "Mid Band", "High Band"],
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        // サラウンドのレイアウト。チャンネルの並びは `crate::surround` を参照
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(6),
+            main_output_channels: NonZeroU32::new(6),
+            names: PortNames {
+                layout: Some(LAYOUT_5_1),
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(8),
+            main_output_channels: NonZeroU32::new(8),
+            names: PortNames {
+                layout: Some(LAYOUT_7_1),
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        // 並びを決めないマルチチャンネルのレイアウト。LFE はなく、Pairs では隣り合うチャンネルを組にする
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(4),
+            main_output_channels: NonZeroU32::new(4),
+            names: PortNames {
+                layout: Some("4 Channels"),
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(MAX_CHANNELS as u32),
+            main_output_channels: NonZeroU32::new(MAX_CHANNELS as u32),
+            names: PortNames {
+                layout: Some("16 Channels"),
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
     ];
 
+    // MidiCCs はノートのイベントも含む。ノートは MIDI Ducking で使う
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    // ホストはパラメーターが変わるサンプルでバッファを分割して process() を呼ぶ。スムージングしない
+    // パラメーターは分割したバッファの先頭で、スムージングするものはサンプルごとに読むので、
+    // オートメーションはサンプル単位で反映される
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = BackgroundTask;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
-    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
-        editor::create(
-            self.params.clone(),
-            self.peak_meter.clone(),
-            self.params.editor_state.clone(),
-        )
+    fn filter_state(state: &mut PluginState) {
+        MultibandCompressorParams::migrate_state(state);
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let spectrum_worker = self.spectrum_worker.clone();
+        let band_level_worker = self.band_level_worker.clone();
+        let loudness_worker = self.loudness_worker.clone();
+        Box::new(move |task| match task {
+            BackgroundTask::AnalyzeSpectrum { .. } | BackgroundTask::ResetSpectrum => {
+                if let Ok(mut worker) = spectrum_worker.lock() {
+                    worker.run(task);
+                }
+            }
+            BackgroundTask::StartThresholdLearn | BackgroundTask::AnalyzeBandLevels { .. } => {
+                if let Ok(mut worker) = band_level_worker.lock() {
+                    worker.run(task);
+                }
+            }
+            BackgroundTask::AnalyzeLoudness | BackgroundTask::ResetIntegratedLoudness => {
+                if let Ok(mut worker) = loudness_worker.lock() {
+                    worker.run(task);
+                }
+            }
+        })
+    }
+
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        gui::create(EditorShared {
+            params: self.params.clone(),
+            async_executor,
+            analysis: self.analysis_output.clone(),
+            spectrum: self.spectrum_output.clone(),
+            midi_cc_events: self.midi_cc_output.clone(),
+            reset_requests: self.reset_requests.clone(),
+            threshold_learn: self.threshold_learn.clone(),
+            gain_reduction_history: self.gain_reduction_history.clone(),
+        })
     }
 
     fn initialize(
         &mut self,
-        _audio_io_layout: &AudioIOLayout,
+        audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
         // サンプルレートを保持
-        self.sample_rate = buffer_config.sample_rate as f32;
-
-        // チャンネル数に合わせて filters/compressors を (再)構築
-        // BufferConfig から直接チャンネル数が得られない場合があるため、とりあえずステレオを仮定して作る。
-        // 実際のホストに合わせて必要なら後で動的に再構築してください。
-        let ch = 2usize;
-        self.current_lo_mid = 0.0;
-        self.current_mid_hi = 0.0;
-        self.filters.clear();
-        self.compressors.clear();
+        self.sample_rate = buffer_config.sample_rate;
+        self.offline_render = buffer_config.process_mode == ProcessMode::Offline;
+
+        // ホストと決めたレイアウトのメイン出力のチャンネル数に合わせてクロスオーバーとバンドの状態を (再)構築する。
+        // レイアウトやサンプルレートが変わるとホストは initialize() を呼び直すので、状態は全て作り直し、
+        // 古いレイアウトのチャンネル数や古いサンプルレートで計算した係数、エンベロープは残らない。
+        // process() では、ここで確保したチャンネルだけを処理する
+        self.channel_layout = ChannelLayout::new(audio_io_layout);
+        let ch = self.channel_layout.num_channels();
+        self.oversampling = self.oversampling_setting();
+        self.crossover = Crossover::new(ch);
+        self.precision = self.precision_setting();
+        self.crossover64 = Crossover64::new(ch);
+        self.meter_k_filters.clear();
+        self.oversamplers.clear();
+        self.oversampling_scratch.clear();
+        self.block_inputs.clear();
+        self.block_outputs.clear();
+        #[cfg(feature = "parallel")]
+        self.channel_stats.clear();
+        self.dry_delays.clear();
+        self.band_outputs_enabled = audio_io_layout.aux_output_ports.len() == NUM_BANDS;
+        self.band_downsamplers.clear();
+        self.band_scratch.clear();
+        self.block_band_outputs.clear();
+        self.true_peak_detectors.clear();
         for _ in 0..ch {
-            self.filters.push(ChannelFilters::new());
-            self.compressors
-                .push([SingleBandCompressor::new(), SingleBandCompressor::new(), SingleBandCompressor::new()]);
+            self.meter_k_filters
+                .push(KWeightingFilter::new(self.sample_rate));
+
+            let mut oversampler = Oversampler::new();
+            oversampler.set_factor(self.oversampling);
+            self.oversamplers.push(oversampler);
+            self.oversampling_scratch.push([0.0; MAX_OVERSAMPLING_FACTOR]);
+            self.block_inputs.push([0.0; MAX_BLOCK_SIZE]);
+            self.block_outputs.push([0.0; MAX_BLOCK_SIZE]);
+            #[cfg(feature = "parallel")]
+            self.channel_stats
+                .push([FrameStats::default(); MAX_BLOCK_SIZE]);
+            let mut dry_delay = LatencyDelay::new();
+            dry_delay.set_factor(self.oversampling);
+            self.dry_delays.push(dry_delay);
+            self.true_peak_detectors.push(TruePeakDetector::new());
+
+            if self.band_outputs_enabled {
+                let mut downsamplers: [Oversampler; NUM_BANDS] = Default::default();
+                for downsampler in downsamplers.iter_mut() {
+                    downsampler.set_factor(self.oversampling);
+                }
+                self.band_downsamplers.push(downsamplers);
+                self.band_scratch
+                    .push([[0.0; MAX_OVERSAMPLING_FACTOR]; NUM_BANDS]);
+                self.block_band_outputs
+                    .push([[0.0; MAX_BLOCK_SIZE]; NUM_BANDS]);
+            }
         }
+        context.set_latency_samples(self.oversampling.latency_samples());
 
-        // 初期クロスオーバー設定（後述の inherent impl にて実装）
-        self.update_crossovers();
+        // K 特性フィルターとゲーティング用のヒストグラムはここで確保する
+        let loudness_weights = self.channel_layout.loudness_weights();
+        self.loudness = LoudnessMeter::new(&loudness_weights, self.sample_rate);
+        self.gain_matcher = GainMatcher::new(&loudness_weights, self.sample_rate);
+        self.auto_makeup = AutoMakeup::new(&loudness_weights, self.sample_rate);
+        self.gain_reduction_recorder.set_sample_rate(self.sample_rate);
+        self.update_meter_ballistics(true);
+        self.gain_reduction_stats.set_sample_rate(self.sample_rate);
+        self.output_correlation
+            .set_window(CORRELATION_WINDOW_MS, self.sample_rate);
+        self.goniometer.set_sample_rate(self.sample_rate);
+        let processing_rate = self.processing_rate();
+        for band in self.bands.iter_mut() {
+            band.initialize(ch, processing_rate);
+            band.energy
+                .set_integration_time(BAND_ENERGY_WINDOW_MS, self.sample_rate);
+        }
+
+        // 新しいサンプルレートでクロスオーバーを設計し直す
+        self.update_param_values();
+        self.update_crossovers(true);
+        self.update_rms_coef();
 
         // ピークメーターの減衰スピードを、サンプルレートに合わせて設定
         self.peak_meter_decay_weight = 0.25f64
@@ -178,136 +855,672 @@ impl Plugin for MultibandCompressor {
         true
     }
 
+    fn reset(&mut self) {
+        // 係数はそのままで、フィルターやエンベロープ、遅延線、メーターに溜まった状態だけをクリアする。
+        // ホストが再生位置を変えたときなどに呼ぶので、前の位置のエンベロープが最初の音にかからないようにする
+        self.crossover.reset();
+        self.crossover64.reset();
+        for oversampler in self
+            .oversamplers
+            .iter_mut()
+            .chain(self.band_downsamplers.iter_mut().flatten())
+        {
+            oversampler.reset();
+        }
+        for scratch in self.oversampling_scratch.iter_mut() {
+            *scratch = [0.0; MAX_OVERSAMPLING_FACTOR];
+        }
+        for scratch in self.band_scratch.iter_mut() {
+            *scratch = [[0.0; MAX_OVERSAMPLING_FACTOR]; NUM_BANDS];
+        }
+        for delay in self.dry_delays.iter_mut() {
+            delay.reset();
+        }
+        self.bypass_mix.reset(self.bypass_target());
+        let band_fade_targets = self.band_fade_targets();
+        for (band, fade_target) in self.bands.iter_mut().zip(band_fade_targets) {
+            band.reset(fade_target);
+        }
+        for filter in self.meter_k_filters.iter_mut() {
+            filter.reset();
+        }
+        for detector in self.true_peak_detectors.iter_mut() {
+            detector.reset();
+        }
+        self.output_true_peak_meter = 0.0;
+        for hold in [
+            &mut self.input_peak_hold,
+            &mut self.output_peak_hold,
+            &mut self.output_true_peak_hold,
+        ] {
+            hold.reset();
+        }
+        self.output_rms.reset();
+        for meter in self
+            .input_level_meters
+            .iter_mut()
+            .chain(self.output_level_meters.iter_mut())
+        {
+            meter.reset();
+        }
+        self.output_correlation.reset();
+        self.goniometer.reset();
+        self.loudness.reset();
+        self.gain_matcher.reset();
+        self.auto_makeup.reset();
+        // 解析中ならバックグラウンドスレッドを待たずに、次に GUI を開いたときのリセットに任せる
+        if let Ok(mut worker) = self.spectrum_worker.try_lock() {
+            worker.run(BackgroundTask::ResetSpectrum);
+        }
+        if let Ok(mut worker) = self.loudness_worker.try_lock() {
+            worker.reset();
+        }
+        self.samples_since_spectrum_task = 0;
+        self.gain_reduction_recorder.reset();
+        self.gain_reduction_stats.reset();
+        self.dsp_load.reset();
+        // 再生位置が飛ぶとノートオフが届かないことがあるので、押したままのノートも離す
+        self.ducker.reset();
+    }
+
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
+        aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        // Low band parameters
-        let threshold_low = self.params.threshold_low.value();
-        let ratio_low = self.params.ratio_low.value().max(1.0);
-        let attack_low = (self.params.attack_low.value() / 1000.0).max(0.0001);
-        let release_low = (self.params.release_low.value() / 1000.0).max(0.0001);
-        let makeup_low = self.params.makeup_low.value();
-
-        // Mid band parameters
-        let threshold_mid = self.params.threshold_mid.value();
-        let ratio_mid = self.params.ratio_mid.value().max(1.0);
-        let attack_mid = (self.params.attack_mid.value() / 1000.0).max(0.0001);
-        let release_mid = (self.params.release_mid.value() / 1000.0).max(0.0001);
-        let makeup_mid = self.params.makeup_mid.value();
-
-        // High band parameters
-        let threshold_high = self.params.threshold_high.value();
-        let ratio_high = self.params.ratio_high.value().max(1.0);
-        let attack_high = (self.params.attack_high.value() / 1000.0).max(0.0001);
-        let release_high = (self.params.release_high.value() / 1000.0).max(0.0001);
-        let makeup_high = self.params.makeup_high.value();
-
-        // サンプルレートを用いて per-sample coef を計算
-        let sample_rate = context.transport().sample_rate as f32;
-        let attack_coef_low = (-1.0_f32 / (attack_low * sample_rate)).exp();
-        let release_coef_low = (-1.0_f32 / (release_low * sample_rate)).exp();
-        let attack_coef_mid = (-1.0_f32 / (attack_mid * sample_rate)).exp();
-        let release_coef_mid = (-1.0_f32 / (release_mid * sample_rate)).exp();
-        let attack_coef_high = (-1.0_f32 / (attack_high * sample_rate)).exp();
-        let release_coef_high = (-1.0_f32 / (release_high * sample_rate)).exp();
-
-        let low_settings = CompressorSettings {
-            threshold_db: threshold_low,
-            ratio: ratio_low,
-            attack_coef: attack_coef_low,
-            release_coef: release_coef_low,
-            makeup_db: makeup_low,
-        };
+        let process_start = Instant::now();
+        // 無音でフィルターやエンベロープの状態が非正規化数になっても遅くならないようにする
+        let _denormals = DenormalGuard::enable();
 
-        let mid_settings = CompressorSettings {
-            threshold_db: threshold_mid,
-            ratio: ratio_mid,
-            attack_coef: attack_coef_mid,
-            release_coef: release_coef_mid,
-            makeup_db: makeup_mid,
-        };
+        // ノートのイベントはダッキングに合わせてサンプル単位で読むので、ブロックのサンプルフレームごとの
+        // ループで `timing` が来たものから処理する
+        let mut next_event = context.next_event();
 
-        let high_settings = CompressorSettings {
-            threshold_db: threshold_high,
-            ratio: ratio_high,
-            attack_coef: attack_coef_high,
-            release_coef: release_coef_high,
-            makeup_db: makeup_high,
-        };
+        self.update_oversampling(context);
+        self.update_precision();
+        self.update_meter_ballistics(false);
 
-        // クロスオーバー周波数の更新（頻繁な再初期化を避ける）
-        self.update_crossovers();
-
-        let mut peak_amplitude = 0.0_f32;
-
-        for mut channel_samples in buffer.iter_samples() {
-            let channel_count = channel_samples.len();
-            for ch_idx in 0..channel_count {
-                let sample = channel_samples
-                    .get_mut(ch_idx)
-                    .expect("channel index out of range");
-                let input = *sample;
-
-                // 1) バンド分割
-                let (low, mid, high) = if let Some(filters) = self.filters.get_mut(ch_idx) {
-                    let mut low = input;
-                    for biquad in filters.low_lp.iter_mut() {
-                        low = biquad.process_sample(low);
-                    }
+        if self.threshold_learn.start.swap(false, Ordering::Relaxed) {
+            self.learn_samples_remaining = (THRESHOLD_LEARN_SECONDS * self.sample_rate) as usize;
+            self.threshold_learn.active.store(true, Ordering::Relaxed);
+            context.execute_background(BackgroundTask::StartThresholdLearn);
+        }
+        let threshold_learning = self.learn_samples_remaining > 0;
 
-                    let mut high = input;
-                    for biquad in filters.high_hp.iter_mut() {
-                        high = biquad.process_sample(high);
+        if self.reset_requests.loudness.swap(false, Ordering::Relaxed) {
+            context.execute_background(BackgroundTask::ResetIntegratedLoudness);
+        }
+        if self.reset_requests.clip_indicators.swap(false, Ordering::Relaxed) {
+            self.input_clips.reset();
+            self.output_clips.reset();
+        }
+        if self.reset_requests.peak_holds.swap(false, Ordering::Relaxed) {
+            self.input_peak_hold.reset();
+            self.output_peak_hold.reset();
+            self.output_true_peak_hold.reset();
+            for band in self.bands.iter_mut() {
+                band.gain_reduction_hold.reset();
+            }
+        }
+        let peak_hold_time = self.params.global.peak_hold.value();
+        for hold in [
+            &mut self.input_peak_hold,
+            &mut self.output_peak_hold,
+            &mut self.output_true_peak_hold,
+        ]
+        .into_iter()
+        .chain(self.bands.iter_mut().map(|band| &mut band.gain_reduction_hold))
+        {
+            hold.set_hold_time(peak_hold_time, self.sample_rate);
+        }
+
+        // このバッファ内の L/R のピーク
+        let mut input_peak_amplitude = [0.0_f32; METER_CHANNELS];
+        let mut output_peak_amplitude = [0.0_f32; METER_CHANNELS];
+        let mut output_true_peak = 0.0_f32;
+        self.output_rms
+            .set_integration_time(self.params.global.rms_time.value(), self.sample_rate);
+        // このバッファ内の各バンドの入力ピーク（全チャンネル）
+        let mut band_input_peak_amplitude = [0.0_f32; NUM_BANDS];
+        // このバッファ内で最も深かったゲインリダクション（全チャンネル）
+        let mut band_gain_reduction = [0.0_f32; NUM_BANDS];
+        let band_outputs = self.band_outputs_enabled && aux.outputs.len() >= NUM_BANDS;
+        // このバッファでラウドネスの 100 ms ステップが終わったか
+        let mut loudness_stepped = false;
+        // モノラルのときはコリレーションを計算しない（常に 1）
+        let stereo_output = buffer.channels() >= 2;
+        let gain_match = self.params.global.gain_match.value();
+        let auto_makeup = self.params.global.auto_makeup.value();
+        let loudness_target = self.params.global.loudness_target.value();
+        let k_weighted_meters = self.params.global.k_weighted_meters.value();
+        self.bypass_mix.set_target(self.sample_rate, self.bypass_target());
+        let k_weighted_detection = self.params.global.k_weighted_detection.value();
+        let link_groups = self.channel_layout.link_groups(
+            self.params.global.channel_link.value(),
+            self.params.global.exclude_lfe.value(),
+        );
+        let band_fade_targets = self.band_fade_targets();
+        for (band, fade_target) in self.bands.iter_mut().zip(band_fade_targets) {
+            band.set_fade_target(self.sample_rate, fade_target);
+        }
+        let midi_ducking = self.params.global.midi_ducking.value();
+        self.ducker.set_times(
+            self.params.global.duck_attack.value(),
+            self.params.global.duck_release.value(),
+            self.sample_rate,
+        );
+        // スペクトルは GUI が開いているときだけ計算する
+        let editor_open = self.params.editor_state.is_open();
+        #[cfg(test)]
+        let editor_open = editor_open || self.force_editor_open;
+
+        let num_samples = buffer.samples();
+        let channel_count = buffer.channels();
+        // 状態を確保したチャンネルだけを処理し、それ以外のチャンネルはそのまま通す
+        let processed_channels = channel_count.min(self.crossover.num_channels());
+        let oversampling_factor = self.oversampling.factor();
+        let precision = self.precision;
+
+        for (block_start, mut channels) in buffer.iter_blocks(MAX_BLOCK_SIZE) {
+            let block_len = channels.samples();
+            let block_range = block_start..block_start + block_len;
+
+            // 0) パラメーターの値とコンプレッサーの設定、クロスオーバーはブロックの先頭でまとめて評価し、
+            // サンプルごとのループでは読まない
+            let band_settings = self.update_block_params();
+
+            // 1) このブロックのサンプルフレームごとの設定をまとめて計算する。自動化で段差ができないように、
+            // ゲインリダクションと音量に関わるパラメーターはスムージングした値をサンプルごとに使う。モーフ中は Morph の
+            // スムージングした位置で A と B を補間する。MIDI Ducking のときは、ノートで動かしたエンベロープの分だけ
+            // 各バンドの音量を下げる
+            let mut block = [FrameValues::new(band_settings); MAX_BLOCK_SIZE];
+            for (i, frame) in block.iter_mut().take(block_len).enumerate() {
+                while let Some(event) =
+                    next_event.take_if(|event| event.timing() as usize <= block_start + i)
+                {
+                    self.handle_event(event);
+                    next_event = context.next_event();
+                }
+                let morph = self.params.global.morph.smoothed.next() / 100.0;
+                let smoothed = match &self.morph_snapshots {
+                    Some((a, b)) => SmoothedValues::morph(&self.params, a, b, morph),
+                    None => SmoothedValues::next(&self.params),
+                };
+                let duck_depth = self.params.bands().map(|band| band.duck.smoothed.next());
+                let ducking = self.ducker.next_envelope();
+                let mut band_gains = [0.0; NUM_BANDS];
+                let mut band_compression = [0.0; NUM_BANDS];
+                for (index, band) in self.bands.iter_mut().enumerate() {
+                    frame.band_settings[index] = band.frame_settings(index, &smoothed);
+                    (band_gains[index], band_compression[index]) = band.next_fade();
+                    // ダッキングしていないときは音量に触れず、出力を変えない
+                    if midi_ducking && ducking > 0.0 {
+                        frame.duck_db[index] = duck_depth[index] * ducking;
+                        band_gains[index] *= util::db_to_gain(-frame.duck_db[index]);
                     }
+                }
+                frame.input_gain = util::db_to_gain(smoothed.input_gain);
+                frame.output_gain = util::db_to_gain(smoothed.output_gain);
+                frame.bypass_mix = self.bypass_mix.next();
+                frame.band_gains = band_lanes(band_gains);
+                frame.band_compression = band_lanes(band_compression);
+            }
 
-                    let mut mid = input;
-                    for biquad in filters.mid_hp.iter_mut() {
-                        mid = biquad.process_sample(mid);
+            // 2) チャンネルごとにブロック全体をバンド分割、圧縮、合成する。ホストからの入力は出力段で使うために
+            // 取っておき、出力ゲインなどをかける前の出力は `block_outputs` に書き込む。チャンネルの状態はここで
+            // 一度だけ借り、サンプルごとのループではフレームの値と一緒にイテレーターで進めて、添字で引かない
+            let mut stats = [FrameStats::default(); MAX_BLOCK_SIZE];
+            let frames = &block[..block_len];
+            for (ch_idx, inputs) in self
+                .block_inputs
+                .iter_mut()
+                .take(processed_channels)
+                .enumerate()
+            {
+                let Some(samples) = channels.get(ch_idx) else {
+                    continue;
+                };
+                let meter_channel = ch_idx.min(METER_CHANNELS - 1);
+                let input_peak = &mut input_peak_amplitude[meter_channel];
+                let input_level_meter = &mut self.input_level_meters[meter_channel];
+                for ((&sample, stored_input), frame_stats) in
+                    samples.iter().zip(inputs.iter_mut()).zip(stats.iter_mut())
+                {
+                    // ホストから NaN や無限大が来ても、フィルターやエンベロープに入れない
+                    let input = if sample.is_finite() { sample } else { 0.0 };
+                    *stored_input = input;
+                    *input_peak = input_peak.max(input.abs());
+                    if editor_open {
+                        input_level_meter.process(input);
                     }
-                    for biquad in filters.mid_lp.iter_mut() {
-                        mid = biquad.process_sample(mid);
+                    self.input_clips.process(input);
+                    frame_stats.input_sum += input;
+                }
+            }
+
+            let options = ChannelOptions {
+                oversampling_factor,
+                precision,
+                k_weighted_detection,
+            };
+            self.process_channel_blocks(
+                frames,
+                &mut stats,
+                processed_channels,
+                band_outputs,
+                &link_groups,
+                &options,
+            );
+
+            if band_outputs {
+                for (ch_idx, band_blocks) in self
+                    .block_band_outputs
+                    .iter()
+                    .take(processed_channels)
+                    .enumerate()
+                {
+                    for (output, band_block) in aux.outputs.iter_mut().zip(band_blocks) {
+                        let Some(aux_samples) = output
+                            .as_slice()
+                            .get_mut(ch_idx)
+                            .and_then(|channel| channel.get_mut(block_range.clone()))
+                        else {
+                            continue;
+                        };
+                        for (aux_sample, &band_sample) in aux_samples.iter_mut().zip(band_block) {
+                            *aux_sample = band_sample;
+                        }
                     }
+                }
+            }
 
-                    (low, mid, high)
+            // 3) サンプルフレームごとに全チャンネルの出力段を処理する。ゲインマッチとオートメイクアップは
+            // 前のフレームまでの全チャンネルの測定を使うので、ここだけはフレーム単位で進める
+            for (i, (frame, frame_stats)) in block.iter().zip(&stats).take(block_len).enumerate() {
+                let mut output_square_sum = 0.0_f32;
+                let mut output_sum = 0.0_f32;
+                // コリレーションメーター用の L/R 出力
+                let mut output_left_right = [0.0_f32; 2];
+                // トリムは常に測定しておき、Gain Match が有効なときだけ適用する
+                let gain_match_gain = if gain_match {
+                    util::db_to_gain(self.gain_matcher.trim_db())
                 } else {
-                    (input, 0.0, 0.0)
+                    1.0
                 };
+                let auto_makeup_gain = util::db_to_gain(self.auto_makeup.gain_db());
+                let channel_states = self
+                    .block_inputs
+                    .iter()
+                    .zip(self.block_outputs.iter())
+                    .zip(self.dry_delays.iter_mut())
+                    .zip(self.meter_k_filters.iter_mut())
+                    .zip(self.true_peak_detectors.iter_mut())
+                    .take(processed_channels)
+                    .enumerate();
+                for (
+                    ch_idx,
+                    ((((inputs, outputs), dry_delay), meter_k_filter), true_peak_detector),
+                ) in channel_states
+                {
+                    let (Some(sample), Some(&input), Some(&processed)) = (
+                        channels
+                            .get_mut(ch_idx)
+                            .and_then(|channel| channel.get_mut(i)),
+                        inputs.get(i),
+                        outputs.get(i),
+                    ) else {
+                        continue;
+                    };
+                    let meter_channel = ch_idx.min(METER_CHANNELS - 1);
 
-                // 2) 各バンドへのコンプレッサー適用
-                let (low_out, mid_out, high_out) =
-                    if let Some(bands) = self.compressors.get_mut(ch_idx) {
-                        let low_out = bands[0].process_sample(low, &low_settings);
-                        let mid_out = bands[1].process_sample(mid, &mid_settings);
-                        let high_out = bands[2].process_sample(high, &high_settings);
-                        (low_out, mid_out, high_out)
+                    self.gain_matcher.process_sample(ch_idx, input, processed);
+                    // オートメイクアップはゲインマッチの後の信号を測るので、両方有効でも二重に補正しない
+                    let out = processed * gain_match_gain;
+                    self.auto_makeup.process_sample(ch_idx, input, out);
+                    // 出力ゲインはゲインマッチとオートメイクアップの後にかけ、それらで打ち消されないようにする
+                    let out = out * auto_makeup_gain * frame.output_gain;
+                    // バイパス中も処理は続け、切り替えのときは遅延を揃えたドライ信号とクロスフェードする
+                    let dry = dry_delay.process(input);
+                    let out = limit_output(out + frame.bypass_mix * (dry - out));
+                    *sample = out;
+
+                    output_peak_amplitude[meter_channel] =
+                        output_peak_amplitude[meter_channel].max(out.abs());
+                    if editor_open {
+                        self.output_level_meters[meter_channel].process(out);
+                    }
+                    self.output_clips.process(out);
+                    let metered = if k_weighted_meters {
+                        meter_k_filter.process_sample(out)
                     } else {
-                        (low, mid, high)
+                        out
                     };
+                    output_square_sum += metered * metered;
+                    output_sum += out;
+                    if let Some(output) = output_left_right.get_mut(ch_idx) {
+                        *output = out;
+                    }
+                    output_true_peak = output_true_peak.max(true_peak_detector.process(out));
+                    self.loudness.process_sample(ch_idx, out);
+                }
+                if let Some(mean_square) = self.loudness.end_frame() {
+                    // キューが一杯ならバックグラウンドスレッドが止まっているので、ステップを捨てて構わない
+                    self.loudness_steps.push(mean_square);
+                    loudness_stepped = true;
+                }
+                self.gain_matcher.end_frame();
+                self.auto_makeup.end_frame(auto_makeup, loudness_target);
+
+                let mut frame_gain_reduction = frame_stats.gain_reduction;
+                for (reduction, duck_db) in frame_gain_reduction.iter_mut().zip(frame.duck_db) {
+                    *reduction -= duck_db;
+                }
+                for (reduction, frame_reduction) in
+                    band_gain_reduction.iter_mut().zip(frame_gain_reduction)
+                {
+                    *reduction = reduction.min(frame_reduction);
+                }
+                for (peak, frame_peak) in band_input_peak_amplitude
+                    .iter_mut()
+                    .zip(frame_stats.band_peak)
+                {
+                    *peak = peak.max(frame_peak);
+                }
+                self.gain_reduction_stats.process(&frame_gain_reduction);
+                if editor_open {
+                    self.gain_reduction_recorder
+                        .process(&frame_gain_reduction, &self.gain_reduction_history);
+                }
 
-                let out = low_out + mid_out + high_out;
-                *sample = out;
+                if processed_channels > 0 {
+                    if self.learn_samples_remaining > 0 {
+                        self.learn_samples_remaining -= 1;
+                        // キューが一杯なら集計が追いついていないので、サンプルを捨てて構わない
+                        let oversampled_samples = (processed_channels * oversampling_factor) as f32;
+                        self.band_level_samples.push(BandLevelSample {
+                            mean_square: frame_stats
+                                .band_energy
+                                .map(|energy| energy / oversampled_samples),
+                            peak: frame_stats.band_peak,
+                        });
+                    }
+
+                    self.output_rms
+                        .process(output_square_sum / processed_channels as f32);
+                    if stereo_output {
+                        self.output_correlation
+                            .process(output_left_right[0], output_left_right[1]);
+                    }
 
-                peak_amplitude = peak_amplitude.max(out.abs());
+                    if editor_open {
+                        // 割合だけを使うので、チャンネル数やオーバーサンプリング倍率で割る必要はない
+                        for (band, energy) in self.bands.iter_mut().zip(frame_stats.band_energy) {
+                            band.energy.process(energy);
+                        }
+                        let [left, right] = output_left_right;
+                        if stereo_output {
+                            self.goniometer.process(left, right);
+                        } else {
+                            self.goniometer.process(left, left);
+                        }
+                        // キューが一杯なら解析が追いついていないので、サンプルを捨てて構わない
+                        self.spectrum_samples.push(SpectrumSample {
+                            input: frame_stats.input_sum / processed_channels as f32,
+                            output: output_sum / processed_channels as f32,
+                        });
+                    }
+                }
             }
         }
+        // バッファの長さを超えた `timing` のイベントも捨てない
+        while let Some(event) = next_event {
+            self.handle_event(event);
+            next_event = context.next_event();
+        }
+
+        // モノラルでは R にも L と同じ値を表示する
+        let mono = channel_count == 1;
+        if mono {
+            input_peak_amplitude[1] = input_peak_amplitude[0];
+            output_peak_amplitude[1] = output_peak_amplitude[0];
+        }
+
+        // ピークホールドは L/R 共通
+        self.input_peak_hold
+            .process(input_peak_amplitude[0].max(input_peak_amplitude[1]), num_samples);
+        self.output_peak_hold
+            .process(output_peak_amplitude[0].max(output_peak_amplitude[1]), num_samples);
+        self.output_true_peak_hold.process(output_true_peak, num_samples);
+        for (band, reduction) in self.bands.iter_mut().zip(band_gain_reduction) {
+            band.gain_reduction_hold.process(-reduction, num_samples);
+        }
+
+        // GUI が開いているときだけ、メーターと解析結果を 1 フレームにまとめて送る
+        if editor_open {
+            self.output_true_peak_meter = decay_peak_meter(
+                self.output_true_peak_meter,
+                output_true_peak,
+                self.peak_meter_decay_weight,
+            );
 
-        // GUI のピークメーター更新
-        if self.params.editor_state.is_open() {
-            let current_peak_meter = self.peak_meter.load(std::sync::atomic::Ordering::Relaxed);
-            let new_peak_meter = if peak_amplitude > current_peak_meter {
-                peak_amplitude
+            for (band, peak) in self.bands.iter_mut().zip(band_input_peak_amplitude) {
+                band.input_peak_meter =
+                    decay_peak_meter(band.input_peak_meter, peak, self.peak_meter_decay_weight);
+            }
+
+            let frame = self.analysis_input.input_buffer();
+            for (peak, meters) in [
+                (&mut frame.input_peak, &self.input_level_meters),
+                (&mut frame.output_peak, &self.output_level_meters),
+            ] {
+                *peak = meters.each_ref().map(BallisticsMeter::value);
+                if mono {
+                    peak[1] = peak[0];
+                }
+            }
+            frame.output_true_peak = self.output_true_peak_meter;
+            frame.input_peak_hold = self.input_peak_hold.value();
+            frame.output_peak_hold = self.output_peak_hold.value();
+            frame.output_true_peak_hold = self.output_true_peak_hold.value();
+            frame.output_rms = self.output_rms.rms();
+            frame.input_clipped_samples = self.input_clips.clipped_samples();
+            frame.output_clipped_samples = self.output_clips.clipped_samples();
+            frame.output_correlation = if stereo_output {
+                self.output_correlation.correlation()
             } else {
-                current_peak_meter * self.peak_meter_decay_weight
-                    + peak_amplitude * (1.0 - self.peak_meter_decay_weight)
+                1.0
             };
+            self.goniometer.copy_to(&mut frame.output_goniometer);
+            frame.band_input_peak = self.bands.each_ref().map(|band| band.input_peak_meter);
+            let band_energy = self.bands.each_ref().map(|band| band.energy.rms().powi(2));
+            let total_energy: f32 = band_energy.iter().sum();
+            frame.band_energy_percent = if total_energy > 1e-12 {
+                band_energy.map(|energy| energy / total_energy * 100.0)
+            } else {
+                [0.0; NUM_BANDS]
+            };
+            frame.gain_reduction_db = band_gain_reduction;
+            frame.gain_reduction_hold_db = self
+                .bands
+                .each_ref()
+                .map(|band| -band.gain_reduction_hold.value());
+            frame.gain_reduction_stats = self.gain_reduction_stats.stats();
+            frame.gain_match_trim_db = if gain_match {
+                self.gain_matcher.trim_db()
+            } else {
+                0.0
+            };
+            frame.auto_makeup_db = self.auto_makeup.gain_db();
+            let loudness = &self.loudness_readings;
+            frame.momentary_lufs = loudness.momentary.load(Ordering::Relaxed);
+            frame.short_term_lufs = loudness.short_term.load(Ordering::Relaxed);
+            frame.integrated_lufs = loudness.integrated.load(Ordering::Relaxed);
+            frame.sample_rate = self.sample_rate;
+            // 今回の処理時間はまだ測り終えていないので、前回までの値を送る
+            frame.dsp_load_percent = self.dsp_load.load_percent();
+            self.analysis_input.publish();
+
+            // FFT のホップサイズ分のサンプルが溜まるごとに、バックグラウンドで解析させる
+            self.samples_since_spectrum_task += num_samples;
+            if self.samples_since_spectrum_task >= HOP_SIZE {
+                self.samples_since_spectrum_task = 0;
+                context.execute_background(BackgroundTask::AnalyzeSpectrum {
+                    sample_rate: self.sample_rate,
+                });
+            }
+        }
 
-            self.peak_meter
-                .store(new_peak_meter, std::sync::atomic::Ordering::Relaxed);
+        // インテグレーテッドは GUI を閉じていても積算するので、ステップが終わるごとに積算させる
+        if loudness_stepped {
+            context.execute_background(BackgroundTask::AnalyzeLoudness);
         }
 
-        ProcessStatus::Normal
+        // 学習中はバッファごとに集計させ、測り終えたら結果を GUI に渡させる
+        if threshold_learning {
+            context.execute_background(BackgroundTask::AnalyzeBandLevels {
+                finish: self.learn_samples_remaining == 0,
+            });
+        }
+
+        self.dsp_load
+            .process(process_start.elapsed(), num_samples, self.sample_rate);
+
+        // オートスレッショルドの学習は測ったサンプル数で終わるので、無音になっても処理を続けてもらう
+        if self.learn_samples_remaining > 0 {
+            ProcessStatus::KeepAlive
+        } else {
+            ProcessStatus::Tail(self.tail_samples())
+        }
+    }
+}
+
+/// ホストに渡す出力のサンプル。NaN と無限大は 0 にして、[`MAX_OUTPUT_AMPLITUDE`] で制限する
+fn limit_output(sample: f32) -> f32 {
+    if sample.is_finite() {
+        sample.clamp(-MAX_OUTPUT_AMPLITUDE, MAX_OUTPUT_AMPLITUDE)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ホストの代わりに `initialize()` と `process()` に渡すコンテキスト。バックグラウンドタスクは捨てる
+    struct TestContext;
+
+    impl InitContext<MultibandCompressor> for TestContext {
+        fn plugin_api(&self) -> PluginApi {
+            PluginApi::Clap
+        }
+
+        fn execute(&self, _task: BackgroundTask) {}
+
+        fn set_latency_samples(&self, _samples: u32) {}
+
+        fn set_current_voice_capacity(&self, _capacity: u32) {}
+    }
+
+    impl ProcessContext<MultibandCompressor> for TestContext {
+        fn plugin_api(&self) -> PluginApi {
+            PluginApi::Clap
+        }
+
+        fn execute_background(&self, _task: BackgroundTask) {}
+
+        fn execute_gui(&self, _task: BackgroundTask) {}
+
+        // nih_plug の `Transport` はクレートの外から作れないので、返せるものがない
+        fn transport(&self) -> &Transport {
+            panic!("process() does not read the transport")
+        }
+
+        fn next_event(&mut self) -> Option<PluginNoteEvent<MultibandCompressor>> {
+            None
+        }
+
+        fn send_event(&mut self, _event: PluginNoteEvent<MultibandCompressor>) {}
+
+        fn set_latency_samples(&self, _samples: u32) {}
+
+        fn set_current_voice_capacity(&self, _capacity: u32) {}
+    }
+
+    /// `channels` の先頭 `num_samples` サンプルを指す [`Buffer`]
+    fn test_buffer(channels: &mut [Vec<f32>], num_samples: usize) -> Buffer<'_> {
+        let mut buffer = Buffer::default();
+        // SAFETY: どのスライスも `num_samples` サンプルの長さがあり、`buffer` より長く生きる
+        unsafe {
+            buffer.set_slices(num_samples, |slices| {
+                slices.extend(channels.iter_mut().map(|channel| &mut channel[..num_samples]))
+            });
+        }
+        buffer
+    }
+
+    /// 全てのレイアウトと、リアルタイム（32-bit、オーバーサンプリングなし）とオフライン（64-bit、4x）の
+    /// 両方の経路で、`process()` がメモリを確保しないことを確かめる。確保すると `assert_no_alloc` が
+    /// プロセスを止める。確保を見つけるのは nih_plug の `assert_process_allocs` フィーチャーが組み込む
+    /// allocator で、これはデバッグビルドでしか有効にならない。リリースビルドのテストでは何も確かめない
+    #[test]
+    fn process_does_not_allocate() {
+        const MAX_BUFFER_SIZE: usize = 512;
+
+        for layout in MultibandCompressor::AUDIO_IO_LAYOUTS {
+            for process_mode in [ProcessMode::Realtime, ProcessMode::Offline] {
+                // エディターが開いているときは、スペクトルやメーターの送信も通す
+                for editor_open in [false, true] {
+                    let mut plugin = MultibandCompressor {
+                        force_editor_open: editor_open,
+                        ..Default::default()
+                    };
+                    let buffer_config = BufferConfig {
+                        sample_rate: 48000.0,
+                        min_buffer_size: None,
+                        max_buffer_size: MAX_BUFFER_SIZE as u32,
+                        process_mode,
+                    };
+                    assert!(plugin.initialize(layout, &buffer_config, &mut TestContext));
+                    plugin.reset();
+
+                    let num_channels = layout.main_output_channels.map_or(0, NonZeroU32::get);
+                    let mut main = vec![vec![0.0; MAX_BUFFER_SIZE]; num_channels as usize];
+                    let mut aux_outputs: Vec<Vec<Vec<f32>>> = layout
+                        .aux_output_ports
+                        .iter()
+                        .map(|channels| vec![vec![0.0; MAX_BUFFER_SIZE]; channels.get() as usize])
+                        .collect();
+
+                    // ブロックの長さで割り切れない長さや 1 サンプルのバッファも混ぜる
+                    let mut phase = 0.0_f32;
+                    for num_samples in [MAX_BUFFER_SIZE, 100, 1, MAX_BUFFER_SIZE, 64] {
+                        for channel in main.iter_mut() {
+                            for (n, sample) in channel.iter_mut().enumerate() {
+                                *sample = (phase + n as f32 * 0.05).sin() * 0.9;
+                            }
+                        }
+                        phase += num_samples as f32 * 0.05;
+
+                        let mut buffer = test_buffer(&mut main, num_samples);
+                        let mut aux_buffers: Vec<Buffer> = aux_outputs
+                            .iter_mut()
+                            .map(|channels| test_buffer(channels, num_samples))
+                            .collect();
+                        let mut aux = AuxiliaryBuffers {
+                            inputs: &mut [],
+                            outputs: &mut aux_buffers,
+                        };
+                        let status = assert_no_alloc::assert_no_alloc(|| {
+                            plugin.process(&mut buffer, &mut aux, &mut TestContext)
+                        });
+                        assert!(matches!(status, ProcessStatus::Tail(_)));
+                    }
+
+                    assert!(main.iter().flatten().all(|sample| sample.is_finite()));
+                }
+            }
+        }
     }
 }
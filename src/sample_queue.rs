@@ -0,0 +1,85 @@
+//! オーディオスレッドからバックグラウンドスレッドへサンプルを送るための、ロックフリーの SPSC キュー。
+//!
+//! 容量は作成時に固定する。キューが一杯のときは書き込み側が新しい値を捨てるので、
+//! オーディオスレッドが待たされたりアロケーションしたりすることはない。
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Shared<T> {
+    slots: Box<[UnsafeCell<T>]>,
+    /// 次に書き込む位置（書き込み側だけが進める）
+    head: AtomicUsize,
+    /// 次に読み出す位置（読み出し側だけが進める）
+    tail: AtomicUsize,
+}
+
+// SAFETY: `head` と `tail` の間のスロットは読み出し側、それ以外は書き込み側だけがアクセスする。
+// 位置の更新は Release で公開し、相手側は Acquire で読む。
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// `capacity` 個まで値を溜められるキューを作り、書き込み側と読み出し側に分けて返す
+pub fn sample_queue<T: Copy + Default>(capacity: usize) -> (SampleProducer<T>, SampleConsumer<T>) {
+    // 満杯と空を区別するために 1 スロット余分に確保する
+    let shared = Arc::new(Shared {
+        slots: (0..capacity + 1)
+            .map(|_| UnsafeCell::new(T::default()))
+            .collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+
+    (
+        SampleProducer {
+            shared: shared.clone(),
+        },
+        SampleConsumer { shared },
+    )
+}
+
+/// キューの書き込み側。オーディオスレッドが所有する
+pub struct SampleProducer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Copy> SampleProducer<T> {
+    /// 値を追加する。キューが一杯なら値を捨てて `false` を返す
+    pub fn push(&mut self, value: T) -> bool {
+        let len = self.shared.slots.len();
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let next = (head + 1) % len;
+        if next == self.shared.tail.load(Ordering::Acquire) {
+            return false;
+        }
+
+        // SAFETY: `head` のスロットは読み出し側からは見えていない
+        unsafe { *self.shared.slots[head].get() = value };
+        self.shared.head.store(next, Ordering::Release);
+
+        true
+    }
+}
+
+/// キューの読み出し側。バックグラウンドスレッドが所有する
+pub struct SampleConsumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Copy> SampleConsumer<T> {
+    /// 一番古い値を取り出す
+    pub fn pop(&mut self) -> Option<T> {
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        if tail == self.shared.head.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // SAFETY: `tail` のスロットは書き込み側が公開済みで、`tail` を進めるまで上書きされない
+        let value = unsafe { *self.shared.slots[tail].get() };
+        self.shared
+            .tail
+            .store((tail + 1) % self.shared.slots.len(), Ordering::Release);
+
+        Some(value)
+    }
+}
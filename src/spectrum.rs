@@ -0,0 +1,163 @@
+use nih_plug::prelude::util;
+
+/// FFT のサイズ（2 のべき乗）
+pub const FFT_SIZE: usize = 2048;
+/// GUI に渡すビンの数（DC からナイキストまで）
+pub const SPECTRUM_BINS: usize = FFT_SIZE / 2 + 1;
+/// 解析の間隔。75% オーバーラップ
+pub const HOP_SIZE: usize = FFT_SIZE / 4;
+/// 新しいフレームの方が小さかったときに、1 フレームあたり何 dB 下がるか
+const FALL_DB_PER_FRAME: f32 = 1.5;
+
+/// バックグラウンドスレッド上でスペクトルを計算するアナライザー。
+///
+/// 必要なバッファは全て `new()` で確保するので、`push()` はアロケーションしない。
+pub struct SpectrumAnalyzer {
+    /// 直近 `FFT_SIZE` サンプルのリングバッファ
+    history: Vec<f32>,
+    history_pos: usize,
+    samples_until_analysis: usize,
+
+    window: Vec<f32>,
+    fft: Fft,
+    re: Vec<f32>,
+    im: Vec<f32>,
+
+    smoothed_db: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new() -> Self {
+        // Hann window, 振幅を 0 dBFS のサインが 0 dB になるように正規化する
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|n| {
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / FFT_SIZE as f32).cos()
+            })
+            .collect();
+        let window_sum: f32 = window.iter().sum();
+        let window = window.iter().map(|w| w * 2.0 / window_sum).collect();
+
+        Self {
+            history: vec![0.0; FFT_SIZE],
+            history_pos: 0,
+            samples_until_analysis: HOP_SIZE,
+
+            window,
+            fft: Fft::new(FFT_SIZE),
+            re: vec![0.0; FFT_SIZE],
+            im: vec![0.0; FFT_SIZE],
+
+            smoothed_db: vec![util::MINUS_INFINITY_DB; SPECTRUM_BINS],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.history_pos = 0;
+        self.samples_until_analysis = HOP_SIZE;
+        self.smoothed_db.fill(util::MINUS_INFINITY_DB);
+    }
+
+    /// 1 サンプル（全チャンネルの平均）を追加する。`HOP_SIZE` ごとに FFT を行う
+    pub fn push(&mut self, sample: f32) {
+        self.history[self.history_pos] = sample;
+        self.history_pos = (self.history_pos + 1) % FFT_SIZE;
+
+        self.samples_until_analysis -= 1;
+        if self.samples_until_analysis == 0 {
+            self.samples_until_analysis = HOP_SIZE;
+            self.analyze();
+        }
+    }
+
+    /// スムージング済みのスペクトル (dB)。長さは [`SPECTRUM_BINS`]
+    pub fn magnitudes_db(&self) -> &[f32] {
+        &self.smoothed_db
+    }
+
+    fn analyze(&mut self) {
+        // 一番古いサンプルから順に並べて窓をかける
+        for (n, (re, im)) in self.re.iter_mut().zip(self.im.iter_mut()).enumerate() {
+            let sample = self.history[(self.history_pos + n) % FFT_SIZE];
+            *re = sample * self.window[n];
+            *im = 0.0;
+        }
+
+        self.fft.process(&mut self.re, &mut self.im);
+
+        for (bin, smoothed) in self.smoothed_db.iter_mut().enumerate() {
+            let magnitude = (self.re[bin] * self.re[bin] + self.im[bin] * self.im[bin]).sqrt();
+            let magnitude_db = util::gain_to_db(magnitude);
+            // 立ち上がりは即座に、減衰はゆっくり
+            *smoothed = magnitude_db.max(*smoothed - FALL_DB_PER_FRAME);
+        }
+    }
+}
+
+impl Default for SpectrumAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 固定サイズの radix-2 FFT。回転因子とビット反転テーブルは事前に計算しておく
+struct Fft {
+    cos_table: Vec<f32>,
+    sin_table: Vec<f32>,
+    bit_reverse: Vec<usize>,
+}
+
+impl Fft {
+    fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two());
+
+        let bits = size.trailing_zeros();
+        Self {
+            cos_table: (0..size / 2)
+                .map(|k| (2.0 * std::f32::consts::PI * k as f32 / size as f32).cos())
+                .collect(),
+            sin_table: (0..size / 2)
+                .map(|k| -(2.0 * std::f32::consts::PI * k as f32 / size as f32).sin())
+                .collect(),
+            bit_reverse: (0..size)
+                .map(|i| i.reverse_bits() >> (usize::BITS - bits))
+                .collect(),
+        }
+    }
+
+    /// インプレースの前向き FFT
+    fn process(&self, re: &mut [f32], im: &mut [f32]) {
+        let size = re.len();
+
+        for i in 0..size {
+            let j = self.bit_reverse[i];
+            if j > i {
+                re.swap(i, j);
+                im.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= size {
+            let half = len / 2;
+            let table_step = size / len;
+            for start in (0..size).step_by(len) {
+                for k in 0..half {
+                    let (w_re, w_im) = (
+                        self.cos_table[k * table_step],
+                        self.sin_table[k * table_step],
+                    );
+                    let a = start + k;
+                    let b = a + half;
+                    let t_re = re[b] * w_re - im[b] * w_im;
+                    let t_im = re[b] * w_im + im[b] * w_re;
+                    re[b] = re[a] - t_re;
+                    im[b] = im[a] - t_im;
+                    re[a] += t_re;
+                    im[a] += t_im;
+                }
+            }
+            len *= 2;
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! サラウンドとマルチチャンネルのレイアウトと、チャンネルのリンク。
+//!
+//! チャンネルごとの状態は、ホストと決めたレイアウトのチャンネル数だけ `initialize()` で確保する。
+//! リンクしたチャンネルは各バンドの検出器のレベルを最も大きいチャンネルに揃えて同じだけ圧縮し、
+//! 片側だけが大きいときに定位が揺れないようにする。LFE はリンクから外せる。
+
+use nih_plug::prelude::{AudioIOLayout, Enum};
+
+/// 処理できる最大のチャンネル数。`AUDIO_IO_LAYOUTS` の最大のチャンネル数以上にする
+pub const MAX_CHANNELS: usize = 16;
+
+/// サラウンドのレイアウトの名前。チャンネルは L, R, C, LFE, Ls, Rs の順に並び、7.1 では後ろに
+/// Lrs, Rrs が続く
+pub const LAYOUT_5_1: &str = "5.1";
+pub const LAYOUT_7_1: &str = "7.1";
+/// サラウンドのレイアウトのセンターと LFE のチャンネル。どちらもペアにしない
+const SURROUND_CENTER_CHANNEL: usize = 2;
+const SURROUND_LFE_CHANNEL: usize = 3;
+/// サラウンドのレイアウトで、Ls, Rs 以降のチャンネルが始まる番号
+const SURROUND_FIRST_REAR_CHANNEL: usize = 4;
+/// BS.1770 のラウドネスで、Ls, Rs と 7.1 の後ろのペアにかける重み
+const SURROUND_LOUDNESS_WEIGHT: f32 = 1.41;
+
+/// チャンネルをリンクする範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ChannelLink {
+    /// チャンネルごとに独立して圧縮する
+    #[name = "Off"]
+    Off,
+    /// L/R、Ls/Rs のように左右の対になるチャンネルを 2 つずつリンクする。サラウンドでないレイアウトでは
+    /// 隣り合うチャンネルを組にする
+    #[name = "Pairs"]
+    Pairs,
+    /// 全チャンネルをリンクする
+    #[name = "All"]
+    All,
+}
+
+/// ホストと決めたレイアウトのチャンネルの並び
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelLayout {
+    num_channels: usize,
+    /// [`LAYOUT_5_1`] か [`LAYOUT_7_1`] か
+    surround: bool,
+}
+
+impl ChannelLayout {
+    pub fn new(audio_io_layout: &AudioIOLayout) -> Self {
+        Self {
+            num_channels: audio_io_layout
+                .main_output_channels
+                .map_or(0, |channels| channels.get() as usize),
+            surround: matches!(
+                audio_io_layout.names.layout,
+                Some(LAYOUT_5_1 | LAYOUT_7_1)
+            ),
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    /// LFE のチャンネル。サラウンドのレイアウトでなければ `None`
+    pub fn lfe_channel(&self) -> Option<usize> {
+        self.surround.then_some(SURROUND_LFE_CHANNEL)
+    }
+
+    /// BS.1770 のラウドネスを測るときの、各チャンネルの二乗にかける重み。LFE は含めず、サラウンドの
+    /// チャンネルは 1.41 にする。`initialize()` から呼ぶこと
+    pub fn loudness_weights(&self) -> Vec<f32> {
+        (0..self.num_channels)
+            .map(|channel| match channel {
+                _ if !self.surround => 1.0,
+                SURROUND_LFE_CHANNEL => 0.0,
+                channel if channel >= SURROUND_FIRST_REAR_CHANNEL => SURROUND_LOUDNESS_WEIGHT,
+                _ => 1.0,
+            })
+            .collect()
+    }
+
+    /// `link` でリンクするときの、各チャンネルが属するグループ。グループはそのグループの最初のチャンネルの
+    /// 番号で表し、同じグループのチャンネルを一緒に圧縮する。`exclude_lfe` なら LFE は常に単独にする
+    pub fn link_groups(&self, link: ChannelLink, exclude_lfe: bool) -> [usize; MAX_CHANNELS] {
+        let lfe = self.lfe_channel();
+        std::array::from_fn(|channel| match link {
+            ChannelLink::Off => channel,
+            ChannelLink::All if exclude_lfe && lfe == Some(channel) => channel,
+            ChannelLink::All => 0,
+            ChannelLink::Pairs
+                if self.surround
+                    && (channel == SURROUND_CENTER_CHANNEL || channel == SURROUND_LFE_CHANNEL) =>
+            {
+                channel
+            }
+            // サラウンドでも、センターと LFE の後ろは偶数番目から 2 つずつ組になる
+            ChannelLink::Pairs => channel & !1,
+        })
+    }
+}
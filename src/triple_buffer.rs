@@ -0,0 +1,167 @@
+//! オーディオスレッドから GUI へ解析データを渡すための、ロックフリーのトリプルバッファ。
+//!
+//! 書き込み側と読み出し側がそれぞれ 1 つずつスロットを専有し、残りの 1 つ（バック）を
+//! アトミックに交換する。書き込み側は待たされることもアロケーションすることもないので、
+//! `process()` から安全に使える。読み出し側は常に最後に公開されたフレームを受け取る。
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// バックスロットのインデックスに立てる、未読の新しいフレームがあることを示すビット
+const DIRTY_BIT: u8 = 0b100;
+const INDEX_MASK: u8 = 0b011;
+
+struct Shared<T> {
+    slots: [UnsafeCell<T>; 3],
+    /// バックスロットのインデックスと [`DIRTY_BIT`]
+    back: AtomicU8,
+}
+
+// SAFETY: 各スロットには常に書き込み側か読み出し側のどちらか一方しかアクセスしない。
+// スロットの所有権の受け渡しは `back` の swap (AcqRel) で同期される。
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// `initial` で初期化したトリプルバッファを作り、書き込み側と読み出し側に分けて返す
+pub fn triple_buffer<T: Clone>(initial: &T) -> (TripleBufferInput<T>, TripleBufferOutput<T>) {
+    let shared = Arc::new(Shared {
+        slots: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+        ],
+        back: AtomicU8::new(1),
+    });
+
+    (
+        TripleBufferInput {
+            shared: shared.clone(),
+            write_idx: 0,
+        },
+        TripleBufferOutput {
+            shared,
+            read_idx: 2,
+        },
+    )
+}
+
+/// トリプルバッファの書き込み側。オーディオスレッドが所有する
+pub struct TripleBufferInput<T> {
+    shared: Arc<Shared<T>>,
+    write_idx: u8,
+}
+
+impl<T> TripleBufferInput<T> {
+    /// 次に公開するフレームを書き込むためのスロット。
+    ///
+    /// 中身は 2 フレーム前のデータなので、公開する前に全てのフィールドを書き直すこと。
+    pub fn input_buffer(&mut self) -> &mut T {
+        // SAFETY: `write_idx` のスロットは publish() するまで書き込み側だけのもの
+        unsafe { &mut *self.shared.slots[self.write_idx as usize].get() }
+    }
+
+    /// 書き込んだフレームを読み出し側に公開する
+    pub fn publish(&mut self) {
+        let previous_back = self
+            .shared
+            .back
+            .swap(self.write_idx | DIRTY_BIT, Ordering::AcqRel);
+        self.write_idx = previous_back & INDEX_MASK;
+    }
+}
+
+/// トリプルバッファの読み出し側。GUI が所有する
+pub struct TripleBufferOutput<T> {
+    shared: Arc<Shared<T>>,
+    read_idx: u8,
+}
+
+impl<T> TripleBufferOutput<T> {
+    /// 新しいフレームがあれば受け取ってから、最新のフレームを返す
+    pub fn read(&mut self) -> &T {
+        self.update();
+        self.latest()
+    }
+
+    /// 新しいフレームがあれば受け取る。受け取ったときは `true` を返す
+    pub fn update(&mut self) -> bool {
+        if self.shared.back.load(Ordering::Relaxed) & DIRTY_BIT == 0 {
+            return false;
+        }
+
+        let previous_back = self.shared.back.swap(self.read_idx, Ordering::AcqRel);
+        self.read_idx = previous_back & INDEX_MASK;
+        true
+    }
+
+    /// 最後に受け取ったフレーム
+    pub fn latest(&self) -> &T {
+        // SAFETY: `read_idx` のスロットは次に swap するまで読み出し側だけのもの
+        unsafe { &*self.shared.slots[self.read_idx as usize].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn reader_sees_latest_publish_and_clears_dirty_bit() {
+        let (mut input, mut output) = triple_buffer(&0);
+        assert_eq!(*output.read(), 0);
+        assert!(!output.update());
+
+        // 読む前に何度公開しても、読み出し側は最後のフレームだけを受け取る
+        for frame in 1..=3 {
+            *input.input_buffer() = frame;
+            input.publish();
+        }
+        assert!(output.update());
+        assert_eq!(*output.latest(), 3);
+        assert_eq!(input.shared.back.load(Ordering::Relaxed) & DIRTY_BIT, 0);
+        assert!(!output.update());
+        assert_eq!(*output.read(), 3);
+    }
+
+    #[test]
+    fn writer_and_reader_never_share_a_slot() {
+        let (mut input, mut output) = triple_buffer(&0);
+        for frame in 1..=10 {
+            // 3 つのスロットは常に書き込み側、バック、読み出し側に 1 つずつ
+            let back = input.shared.back.load(Ordering::Relaxed) & INDEX_MASK;
+            let mut indices = [input.write_idx, back, output.read_idx];
+            indices.sort_unstable();
+            assert_eq!(indices, [0, 1, 2]);
+
+            *input.input_buffer() = frame;
+            input.publish();
+            if frame % 3 == 0 {
+                assert_eq!(*output.read(), frame);
+            }
+        }
+    }
+
+    #[test]
+    fn reader_never_sees_a_partial_write() {
+        const FRAMES: u64 = if cfg!(miri) { 200 } else { 100_000 };
+
+        let (mut input, mut output) = triple_buffer(&[0_u64; 16]);
+        let writer = thread::spawn(move || {
+            for frame in 1..=FRAMES {
+                *input.input_buffer() = [frame; 16];
+                input.publish();
+            }
+        });
+
+        // 受け取ったフレームは全て書き終わったもので、古いフレームに戻らない
+        let mut last = 0;
+        while last < FRAMES {
+            let frame = *output.read();
+            assert!(frame.iter().all(|&value| value == frame[0]), "{frame:?}");
+            assert!(frame[0] >= last);
+            last = frame[0];
+        }
+        writer.join().unwrap();
+    }
+}
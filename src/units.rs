@@ -0,0 +1,116 @@
+//! 周波数と時間のパラメーターの表示と、単位付きの入力の解釈。
+//!
+//! nih-plug の標準のフォーマッターは単位の表記揺れ（"120ms" や "0.85k" など）を受け付けないので、
+//! テキスト入力やホストの値の入力で使えるように、単位を見て値を換算する。
+
+use std::sync::Arc;
+
+/// パラメーターの値を表示する文字列にする関数
+pub type ValueToString = Arc<dyn Fn(f32) -> String + Send + Sync>;
+/// 入力された文字列をパラメーターの値にする関数
+pub type StringToValue = Arc<dyn Fn(&str) -> Option<f32> + Send + Sync>;
+
+/// 1 kHz 未満は Hz、それ以上は小数 2 桁の kHz で表示する（"850 Hz", "1.20 kHz"）
+pub fn v2s_hz_then_khz() -> ValueToString {
+    Arc::new(|value| {
+        // 999.6 Hz が "1000 Hz" にならないよう、丸めた後の値で単位を選ぶ
+        if value < 999.5 {
+            format!("{value:.0} Hz")
+        } else {
+            format!("{:.2} kHz", value / 1000.0)
+        }
+    })
+}
+
+/// "850", "850 Hz", "0.85k", "1.2 kHz" のような入力を Hz に換算する
+pub fn s2v_hz() -> StringToValue {
+    Arc::new(|string| {
+        let (value, unit) = split_unit(string)?;
+        match unit.as_str() {
+            "" | "hz" => Some(value),
+            "k" | "khz" => Some(value * 1000.0),
+            _ => None,
+        }
+    })
+}
+
+/// 1 秒未満は `digits` 桁の ms、それ以上は小数 2 桁の s で表示する（"120.00 ms", "1.50 s"）
+pub fn v2s_ms_then_s(digits: usize) -> ValueToString {
+    // 999.996 ms が "1000.00 ms" にならないよう、丸めた後の値で単位を選ぶ
+    let rounding = 0.5 / 10.0_f32.powi(digits as i32);
+    Arc::new(move |value| {
+        if value < 1000.0 - rounding {
+            format!("{value:.digits$} ms")
+        } else {
+            format!("{:.2} s", value / 1000.0)
+        }
+    })
+}
+
+/// "120", "120ms", "120 ms", "0.12 s" のような入力を ms に換算する
+pub fn s2v_ms() -> StringToValue {
+    Arc::new(|string| {
+        let (value, unit) = split_unit(string)?;
+        match unit.as_str() {
+            "" | "ms" => Some(value),
+            "s" | "sec" => Some(value * 1000.0),
+            _ => None,
+        }
+    })
+}
+
+/// 先頭の数値と、その後ろの小文字にした単位に分ける
+fn split_unit(string: &str) -> Option<(f32, String)> {
+    let string = string.trim();
+    let end = string
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+        .unwrap_or(string.len());
+    let value = string[..end].parse().ok()?;
+
+    Some((value, string[end..].trim().to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values_with_units() {
+        assert_eq!(split_unit(" 1.2 kHz "), Some((1.2, String::from("khz"))));
+        assert_eq!(split_unit("120ms"), Some((120.0, String::from("ms"))));
+        assert_eq!(split_unit("-3"), Some((-3.0, String::new())));
+        assert_eq!(split_unit(""), None);
+        assert_eq!(split_unit("kHz"), None);
+
+        let hz = s2v_hz();
+        assert_eq!(hz("850"), Some(850.0));
+        assert_eq!(hz("850 Hz"), Some(850.0));
+        assert_eq!(hz("0.85k"), Some(850.0));
+        assert_eq!(hz("1.2 kHz"), Some(1200.0));
+        assert_eq!(hz("1.2 ms"), None);
+        assert_eq!(hz(""), None);
+
+        let ms = s2v_ms();
+        assert_eq!(ms("120"), Some(120.0));
+        assert_eq!(ms("120ms"), Some(120.0));
+        assert_eq!(ms("0.12 s"), Some(120.0));
+        assert_eq!(ms("0.12 sec"), Some(120.0));
+        assert_eq!(ms("120 Hz"), None);
+        assert_eq!(ms(" "), None);
+    }
+
+    #[test]
+    fn picks_the_unit_after_rounding() {
+        let hz = v2s_hz_then_khz();
+        assert_eq!(hz(850.0), "850 Hz");
+        assert_eq!(hz(999.6), "1.00 kHz");
+        assert_eq!(hz(1200.0), "1.20 kHz");
+
+        let ms = v2s_ms_then_s(2);
+        assert_eq!(ms(120.0), "120.00 ms");
+        assert_eq!(ms(999.99), "999.99 ms");
+        assert_eq!(ms(999.996), "1.00 s");
+        assert_eq!(ms(1500.0), "1.50 s");
+        assert_eq!(v2s_ms_then_s(1)(999.96), "1.00 s");
+    }
+}
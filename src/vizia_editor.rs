@@ -0,0 +1,238 @@
+//! vizia で作ったエディター。`iced` の feature を無効にして `vizia` の feature を有効にしたときに、
+//! iced のエディターの代わりに使う。
+//!
+//! iced のエディターと同じ共有データを受け取る。今のところ入出力のメーター、バンドごとのゲインリダクション、
+//! プリセットのボタン、全パラメーターの汎用 UI を表示する。対応していない機能は README に挙げている。
+
+use nih_plug::prelude::{nih_error, util, Editor, ParamPtr};
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::widgets::*;
+use nih_plug_vizia::{assets, create_vizia_editor, ViziaState, ViziaTheming};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::analysis::{AnalysisFrame, AnalysisOutput, ResetRequests};
+use crate::background::BackgroundTask;
+use crate::gui::EditorShared;
+use crate::params::MultibandCompressorParams;
+use crate::preset_file;
+use crate::presets::{self, FACTORY_PRESETS, INIT_PRESET};
+use crate::processor::NUM_BANDS;
+
+/// バンドの表示名
+const BAND_NAMES: [&str; NUM_BANDS] = ["Low", "Mid", "High"];
+
+/// パラメーターに保存するエディターの状態
+pub(crate) type EditorState = ViziaState;
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (680, 500))
+}
+
+#[derive(Lens)]
+struct Data {
+    params: Arc<MultibandCompressorParams>,
+    analysis: Arc<Mutex<AnalysisOutput>>,
+    reset_requests: Arc<ResetRequests>,
+}
+
+enum AppEvent {
+    /// ピークホールドをリセットする
+    ResetPeakHolds,
+    /// `FACTORY_PRESETS` のプリセットを読み込む
+    LoadPreset(usize),
+    /// 全パラメーターを何もしない状態に戻す
+    InitParams,
+    /// 主なパラメーターを音楽的な範囲のランダムな値にする
+    RandomizeParams,
+    /// 全パラメーターを JSON のプリセットファイルに書き出す
+    ExportPresetFile,
+    /// JSON のプリセットファイルを読み込む
+    ImportPresetFile,
+}
+
+/// 複数のパラメーターを 1 回のジェスチャーでまとめて設定する
+fn set_params(cx: &mut EventContext, values: &[(ParamPtr, f32)]) {
+    for &(param, _) in values {
+        cx.emit(RawParamEvent::BeginSetParameter(param));
+    }
+    for &(param, value) in values {
+        cx.emit(RawParamEvent::SetParameterNormalized(param, value));
+    }
+    for &(param, _) in values {
+        cx.emit(RawParamEvent::EndSetParameter(param));
+    }
+}
+
+impl Model for Data {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|app_event, _| match app_event {
+            AppEvent::ResetPeakHolds => self
+                .reset_requests
+                .peak_holds
+                .store(true, Ordering::Relaxed),
+            AppEvent::LoadPreset(index) => {
+                let values = FACTORY_PRESETS[*index].normalized_values(self.params.as_ref());
+                set_params(cx, &values);
+            }
+            AppEvent::InitParams => {
+                let values = INIT_PRESET.normalized_values(self.params.as_ref());
+                set_params(cx, &values);
+            }
+            AppEvent::RandomizeParams => {
+                let values =
+                    presets::random_values(self.params.as_ref(), &mut fastrand::Rng::new());
+                set_params(cx, &values);
+            }
+            AppEvent::ExportPresetFile => {
+                let Some(path) = preset_file::save_dialog() else {
+                    return;
+                };
+                if let Err(error) = preset_file::write(self.params.as_ref(), &path) {
+                    nih_error!("Could not write '{}': {}", path.display(), error);
+                }
+            }
+            AppEvent::ImportPresetFile => {
+                let Some(path) = preset_file::open_dialog() else {
+                    return;
+                };
+                match preset_file::read(self.params.as_ref(), &path) {
+                    Ok(values) => set_params(cx, &values),
+                    Err(error) => nih_error!("Could not load '{}': {}", path.display(), error),
+                }
+            }
+        });
+    }
+}
+
+/// 最新の解析結果。新しいフレームがなければ前回のものがそのまま返る
+fn latest_frame(analysis: &Arc<Mutex<AnalysisOutput>>) -> AnalysisFrame {
+    analysis
+        .lock()
+        .map(|mut output| output.read().clone())
+        .unwrap_or_default()
+}
+
+/// ラベル付きのピークメーター。`peak` で解析結果から表示する値（振幅）を選ぶ
+fn peak_meter(cx: &mut Context, label: &str, peak: fn(&AnalysisFrame) -> f32) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, label);
+        PeakMeter::new(
+            cx,
+            Data::analysis.map(move |analysis| util::gain_to_db(peak(&latest_frame(analysis)))),
+            Some(Duration::from_millis(600)),
+        );
+    })
+    .height(Auto)
+    .row_between(Pixels(4.0));
+}
+
+pub(crate) fn create(shared: EditorShared) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(
+        shared.params.editor_state.clone(),
+        ViziaTheming::Custom,
+        move |cx, _| {
+            assets::register_noto_sans_light(cx);
+
+            // 前回 GUI を閉じたときの古いスペクトルが残らないように、解析の状態を捨てておく
+            shared
+                .async_executor
+                .execute_background(BackgroundTask::ResetSpectrum);
+
+            Data {
+                params: shared.params.clone(),
+                analysis: shared.analysis.clone(),
+                reset_requests: shared.reset_requests.clone(),
+            }
+            .build(cx);
+
+            ResizeHandle::new(cx);
+
+            VStack::new(cx, |cx| {
+                Label::new(cx, "Multiband Compressor")
+                    .font_size(24.0)
+                    .height(Pixels(40.0))
+                    .child_top(Stretch(1.0))
+                    .child_bottom(Pixels(0.0));
+
+                HStack::new(cx, |cx| {
+                    peak_meter(cx, "Input L", |frame| frame.input_peak[0]);
+                    peak_meter(cx, "Input R", |frame| frame.input_peak[1]);
+                    peak_meter(cx, "Output L", |frame| frame.output_peak[0]);
+                    peak_meter(cx, "Output R", |frame| frame.output_peak[1]);
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(AppEvent::ResetPeakHolds),
+                        |cx| Label::new(cx, "Reset Peaks"),
+                    );
+                })
+                .height(Auto)
+                .col_between(Pixels(20.0));
+
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Presets");
+                    for (index, preset) in FACTORY_PRESETS.iter().enumerate() {
+                        Button::new(
+                            cx,
+                            move |cx| cx.emit(AppEvent::LoadPreset(index)),
+                            |cx| Label::new(cx, preset.name),
+                        );
+                    }
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(AppEvent::InitParams),
+                        |cx| Label::new(cx, INIT_PRESET.name),
+                    );
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(AppEvent::RandomizeParams),
+                        |cx| Label::new(cx, "Randomize"),
+                    );
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(AppEvent::ExportPresetFile),
+                        |cx| Label::new(cx, "Export..."),
+                    );
+                    Button::new(
+                        cx,
+                        |cx| cx.emit(AppEvent::ImportPresetFile),
+                        |cx| Label::new(cx, "Import..."),
+                    );
+                })
+                .height(Auto)
+                .col_between(Pixels(10.0));
+
+                HStack::new(cx, |cx| {
+                    for (band, name) in BAND_NAMES.into_iter().enumerate() {
+                        Label::new(
+                            cx,
+                            Data::analysis.map(move |analysis| {
+                                let frame = latest_frame(analysis);
+                                format!(
+                                    "{} GR {:.1} dB (max {:.1})",
+                                    name,
+                                    frame.gain_reduction_db[band],
+                                    frame.gain_reduction_hold_db[band]
+                                )
+                            }),
+                        );
+                    }
+                })
+                .height(Auto)
+                .col_between(Pixels(20.0));
+
+                ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                    GenericUi::new(cx, Data::params)
+                        .width(Percentage(100.0))
+                        .height(Auto)
+                        .child_top(Pixels(5.0));
+                })
+                .width(Percentage(100.0));
+            })
+            .row_between(Pixels(10.0))
+            .child_left(Stretch(1.0))
+            .child_right(Stretch(1.0));
+        },
+    )
+}